@@ -1,19 +1,45 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use hic_resolution_rs::coverage::Coverage;
-use hic_resolution_rs::utils::Pair;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use hickit::coverage::Coverage;
+use hickit::straw::{add_bin_counts, ContactRecord};
+use hickit::utils::Pair;
+
+/// Synthetic long-format merged_nodups text (hg19 chromosome names).
+fn merged_nodups_text(lines: u32) -> Vec<u8> {
+    let mut text = String::new();
+    for i in 0..lines {
+        let (c1, c2) = ((i % 22) + 1, ((i * 7) % 22) + 1);
+        let (p1, p2) = ((i * 1237) % 100_000_000, (i * 7919) % 100_000_000);
+        text.push_str(&format!(
+            "0 chr{} {} {} 16 chr{} {} {} 60 100M SEQ 60 100M SEQ read{} read{}\n",
+            c1, p1, i, c2, p2, i + 3, i, i
+        ));
+    }
+    text.into_bytes()
+}
+
+fn benchmark_parse_merged_nodups(c: &mut Criterion) {
+    let text = merged_nodups_text(200_000);
+    c.bench_function("parse_merged_nodups_200k", |b| {
+        b.iter(|| {
+            let iter = hickit::parser::open_file_uncompressed(black_box(&text[..]), None).unwrap();
+            black_box(iter.filter(|p| p.is_ok()).count())
+        })
+    });
+}
 
 fn benchmark_coverage_build(c: &mut Criterion) {
     c.bench_function("coverage_build_1M_pairs", |b| {
         b.iter(|| {
-            let coverage = Coverage::new(50);
+            let mut coverage = Coverage::new(50, None);
 
             // Simulate 1M pairs
             for i in 0..1_000_000 {
                 let pair = Pair {
-                    chr1: ((i % 22) + 1) as u8,
+                    chr1: (i % 22) + 1,
                     pos1: (i * 1000) % 100_000_000,
-                    chr2: ((i % 22) + 1) as u8,
+                    chr2: (i % 22) + 1,
                     pos2: ((i * 1000) + 500) % 100_000_000,
+                    weight: 1,
                 };
                 coverage.add_pair(&pair);
             }
@@ -23,22 +49,72 @@ fn benchmark_coverage_build(c: &mut Criterion) {
     });
 }
 
+/// The 1M pairs of `coverage_build_1M_pairs`, materialized for chunked adds.
+fn synthetic_pairs() -> Vec<Pair> {
+    (0..1_000_000)
+        .map(|i| Pair {
+            chr1: (i % 22) + 1,
+            pos1: (i * 1000) % 100_000_000,
+            chr2: (i % 22) + 1,
+            pos2: ((i * 1000) + 500) % 100_000_000,
+            weight: 1,
+        })
+        .collect()
+}
+
+fn benchmark_coverage_layout(c: &mut Criterion) {
+    let pairs = synthetic_pairs();
+    let lengths = hickit::utils::get_default_genome_lengths();
+    // Allocating the hg19 bins (~236 MB at 50 bp) is setup, not measured
+    let flat = || Coverage::from_lengths(50, lengths.clone());
+    let nested = || -> Vec<Vec<u32>> { lengths.iter().map(|&len| vec![0u32; (len / 50) as usize + 1]).collect() };
+
+    c.bench_function("coverage_add_pair_1M_flat", |b| {
+        b.iter_batched_ref(flat, |coverage| {
+            for p in black_box(&pairs) {
+                coverage.add_pair(p);
+            }
+        }, BatchSize::LargeInput)
+    });
+
+    // Reference: the former one-Vec-per-chromosome layout with the same adds
+    c.bench_function("coverage_add_pair_1M_nested_vec", |b| {
+        b.iter_batched_ref(nested, |bins| {
+            for p in black_box(&pairs) {
+                for (chr, pos) in [(p.chr1, p.pos1), (p.chr2, p.pos2)] {
+                    let ci = (chr as usize).saturating_sub(1);
+                    if ci < bins.len() && pos < lengths[ci] {
+                        let v = &mut bins[ci][(pos / 50) as usize];
+                        *v = v.saturating_add(1);
+                    }
+                }
+            }
+        }, BatchSize::LargeInput)
+    });
+
+    // Chunked parallel aggregation, as `resolution` does
+    c.bench_function("coverage_add_pairs_1M_chunked", |b| {
+        b.iter_batched_ref(flat, |coverage| coverage.add_pairs(black_box(&pairs), 128_000), BatchSize::LargeInput)
+    });
+}
+
 fn benchmark_resolution_search(c: &mut Criterion) {
     // Pre-build coverage with some data
-    let coverage = Coverage::new(50);
+    let mut coverage = Coverage::new(50, None);
     for i in 0..100_000 {
         let pair = Pair {
-            chr1: ((i % 22) + 1) as u8,
+            chr1: (i % 22) + 1,
             pos1: (i * 1000) % 100_000_000,
-            chr2: ((i % 22) + 1) as u8,
+            chr2: (i % 22) + 1,
             pos2: ((i * 1000) + 500) % 100_000_000,
+            weight: 1,
         };
         coverage.add_pair(&pair);
     }
 
     c.bench_function("resolution_search", |b| {
         b.iter(|| {
-            hic_resolution_rs::resolution::find_resolution(
+            hickit::resolution::find_resolution(
                 black_box(&coverage),
                 black_box(0.8),
                 black_box(1000),
@@ -48,9 +124,52 @@ fn benchmark_resolution_search(c: &mut Criterion) {
     });
 }
 
+fn benchmark_effres_bin_counts(c: &mut Criterion) {
+    // A 250 Mb chromosome at 1 kb: 250k bins, 2M records in 1000-record blocks
+    let nbins = 250_000usize;
+    let blocks: Vec<Vec<ContactRecord>> = (0..2000u32)
+        .map(|b| {
+            (0..1000u32)
+                .map(|i| {
+                    let x = (b * 1000 + i).wrapping_mul(2_654_435_761) % nbins as u32;
+                    let y = (x + (i * 37) % 2000).min(nbins as u32 - 1);
+                    ContactRecord { bin_x: x as i32, bin_y: y as i32, counts: 1.0 + (i % 5) as f32 }
+                })
+                .collect()
+        })
+        .collect();
+
+    c.bench_function("effres_bin_counts_2M_dense", |b| {
+        b.iter(|| {
+            let mut counts = Vec::new();
+            for records in black_box(&blocks) {
+                add_bin_counts(&mut counts, records, nbins);
+            }
+            black_box(counts)
+        })
+    });
+
+    // Reference: the former sparse per-bin map
+    c.bench_function("effres_bin_counts_2M_hashmap", |b| {
+        b.iter(|| {
+            let mut counts: std::collections::HashMap<i32, f64> = std::collections::HashMap::new();
+            for records in black_box(&blocks) {
+                for rec in records {
+                    *counts.entry(rec.bin_x).or_insert(0.0) += rec.counts as f64;
+                    *counts.entry(rec.bin_y).or_insert(0.0) += rec.counts as f64;
+                }
+            }
+            black_box(counts)
+        })
+    });
+}
+
 criterion_group!(
     benches,
+    benchmark_effres_bin_counts,
     benchmark_coverage_build,
-    benchmark_resolution_search
+    benchmark_coverage_layout,
+    benchmark_resolution_search,
+    benchmark_parse_merged_nodups
 );
 criterion_main!(benches);
@@ -0,0 +1,117 @@
+//! Minimal BGZF (blocked gzip) writer, as used by samtools/htslib.
+//!
+//! Data is cut into blocks of at most [`BLOCK_DATA`] bytes, each written as
+//! its own gzip member with the `BC` extra field holding the compressed
+//! member size, and the stream ends with the standard empty EOF block. Any
+//! multi-member gzip reader (e.g. `MultiGzDecoder`) reads it back unchanged.
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+use std::io::{self, Write};
+
+/// Uncompressed bytes per block; leaves room for incompressible data to
+/// stay under the 64 KiB member limit.
+pub const BLOCK_DATA: usize = 0xff00;
+
+/// The empty block closing every BGZF stream.
+pub const EOF_BLOCK: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0, 0, 0, 0, 0, 0xff, 0x06, 0, b'B', b'C', 0x02, 0, 0x1b, 0, 0x03, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+];
+
+pub struct BgzfWriter<W: Write> {
+    inner: Option<W>,
+    buf: Vec<u8>,
+    level: Compression,
+}
+
+impl<W: Write> BgzfWriter<W> {
+    pub fn new(inner: W, level: Compression) -> Self {
+        BgzfWriter { inner: Some(inner), buf: Vec::with_capacity(BLOCK_DATA), level }
+    }
+
+    /// Compress and write the buffered bytes as one block.
+    fn write_block(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let mut enc = DeflateEncoder::new(Vec::with_capacity(self.buf.len()), self.level);
+        enc.write_all(&self.buf)?;
+        let cdata = enc.finish()?;
+        // Header (18 bytes) + deflate data + CRC32 and ISIZE (8 bytes)
+        let bsize = 18 + cdata.len() + 8;
+        if bsize > 1 << 16 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("BGZF block of {} bytes exceeds 64 KiB", bsize)));
+        }
+        let mut crc = Crc::new();
+        crc.update(&self.buf);
+        let w = self.inner.as_mut().expect("writer not finished");
+        w.write_all(&[0x1f, 0x8b, 0x08, 0x04, 0, 0, 0, 0, 0, 0xff, 0x06, 0, b'B', b'C', 0x02, 0])?;
+        w.write_all(&((bsize - 1) as u16).to_le_bytes())?;
+        w.write_all(&cdata)?;
+        w.write_all(&crc.sum().to_le_bytes())?;
+        w.write_all(&(self.buf.len() as u32).to_le_bytes())?;
+        self.buf.clear();
+        Ok(())
+    }
+
+    /// Write the last block and the EOF marker, returning the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_block()?;
+        let mut w = self.inner.take().expect("writer not finished");
+        w.write_all(&EOF_BLOCK)?;
+        Ok(w)
+    }
+}
+
+impl<W: Write> Write for BgzfWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let n = data.len().min(BLOCK_DATA - self.buf.len());
+        self.buf.extend_from_slice(&data[..n]);
+        if self.buf.len() == BLOCK_DATA {
+            self.write_block()?;
+        }
+        Ok(n)
+    }
+
+    /// Flushing ends the current block early, as in htslib.
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_block()?;
+        self.inner.as_mut().expect("writer not finished").flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::MultiGzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn blocks_carry_their_size_and_read_back_as_gzip() {
+        // Three blocks of data: two full, one partial
+        let data: Vec<u8> = (0..2 * BLOCK_DATA + 1000).map(|i| (i * 31 % 251) as u8).collect();
+        let mut w = BgzfWriter::new(Vec::new(), Compression::default());
+        w.write_all(&data).unwrap();
+        let out = w.finish().unwrap();
+
+        assert!(out.ends_with(&EOF_BLOCK));
+        let mut pos = 0;
+        let mut blocks = 0;
+        while pos < out.len() {
+            assert_eq!(&out[pos..pos + 4], &[0x1f, 0x8b, 0x08, 0x04]);
+            assert_eq!(&out[pos + 12..pos + 14], b"BC");
+            pos += u16::from_le_bytes([out[pos + 16], out[pos + 17]]) as usize + 1;
+            blocks += 1;
+        }
+        assert_eq!((pos, blocks), (out.len(), 4));
+
+        let mut back = Vec::new();
+        MultiGzDecoder::new(&out[..]).read_to_end(&mut back).unwrap();
+        assert_eq!(back, data);
+    }
+
+    #[test]
+    fn empty_stream_is_just_the_eof_block() {
+        let out = BgzfWriter::new(Vec::new(), Compression::default()).finish().unwrap();
+        assert_eq!(out, EOF_BLOCK);
+    }
+}
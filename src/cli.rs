@@ -1,22 +1,29 @@
 use anyhow::Result;
 use clap::{Args, Parser, Subcommand};
-use indicatif::{ProgressBar, ProgressStyle};
 use std::fs::File;
-use std::io::stdin;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
-use crate::{coverage, parser, resolution, straw, utils};
+use crate::{convert, coverage, parser, pipeline, resolution, straw, utils};
 use crate::filter;
-use rayon::prelude::*;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None, arg_required_else_help = true)]
 #[command(name = "hickit")]
 #[command(about = "Fast Hi-C toolkit: resolution + filters + .hic utils")]
 pub struct Cli {
-    /// Subcommands: resolution, straw, filter
+    /// Subcommands: resolution, straw, filter, compare, convert
     #[command(subcommand)]
     pub cmd: Commands,
+
+    /// Echo the first input lines and parsed pairs to stderr
+    #[arg(short, long, global = true)]
+    pub verbose: bool,
+
+    /// Mapping quality both ends of a pair need (merged_nodups, validPairs,
+    /// and `filter --require-unique`); e.g. 30 for Hi-C QC
+    #[arg(long, global = true, value_name = "MAPQ", default_value_t = parser::DEFAULT_MIN_MAPQ)]
+    pub min_mapq: u32,
 }
 
 #[derive(Subcommand)]
@@ -27,64 +34,473 @@ pub enum Commands {
     Straw(StrawCli),
     /// Filter merged_nodups(.gz) by genomic region
     Filter(FilterCli),
+    /// Compare the resolution from pairs with the effective resolution of a .hic of the same data
+    Compare(CompareCli),
+    /// Convert merged_nodups(.gz) to 4DN .pairs
+    Convert(ConvertCli),
 }
 
 #[derive(Args, Debug)]
 pub struct StrawCli {
     #[command(subcommand)]
     pub cmd: StrawCmd,
+
+    /// Number of threads decoding blocks (0 = auto)
+    #[arg(long, global = true, default_value_t = 0)]
+    pub threads: usize,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum StrawCmd {
     /// Dump genome-wide observed counts at resolution to a slice file (.slc.gz)
     Dump {
-        /// observed/oe/expected (only observed supported)
+        /// observed, oe (observed / expected), or expected (distance-decay TSV instead of a slice)
         matrix_type: String,
-        /// NONE/VC/VC_SQRT/KR (only NONE supported)
+        /// NONE/VC/VC_SQRT/KR/SCALE (see `straw list` for the types a file stores)
         norm: String,
         /// Input Hi-C file (.hic)
         input: PathBuf,
-        /// Units (BP only supported)
+        /// Units: BP, or FRAG for fragment-resolution matrices (bins are fragment numbers)
+        unit: String,
+        /// Bin size / resolution (bp, or fragments for FRAG). A comma-separated
+        /// list (e.g. 5000,10000,25000) dumps each genome-wide in one pass, to
+        /// OUTPUT.<binsize>.slc.gz (out.slc.gz gives out.<binsize>.slc.gz)
+        #[arg(value_name = "BINSIZE[,BINSIZE...]")]
+        binsize: BinSizes,
+        /// Output file path (.slc.gz), optionally preceded by two regions
+        /// CHR:START-END to dump only the contacts between them (as `dump-region`).
+        /// Two chromosome names instead print straw's `pos_x pos_y counts` text
+        /// for that pair (to stdout or -o); `ALL OUTPUT` writes the genome-wide
+        /// ALL matrix as `chrom1 pos1 chrom2 pos2 counts` text
+        #[arg(value_name = "[REGION1 REGION2] OUTPUT | CHR1 CHR2 | ALL OUTPUT", num_args = 1..=3, required = true)]
+        targets: Vec<String>,
+        /// Output file for the chromosome-pair text dump (default: stdout)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+        /// Sort records by bin within each chromosome pair
+        #[arg(long)]
+        sorted: bool,
+        /// With --sorted: sort genome-wide by (chrom1, bin1, chrom2, bin2), spilling to disk
+        #[arg(long, requires = "sorted")]
+        global: bool,
+        /// Directory for temporary sort runs (default: system temp dir)
+        #[arg(long, value_name = "DIR")]
+        tmpdir: Option<PathBuf>,
+        /// Records held in memory per sort run before spilling (16 bytes each)
+        #[arg(long, value_name = "RECORDS", default_value_t = crate::extsort::DEFAULT_RUN_RECORDS)]
+        sort_run_records: usize,
+        /// Only dump pairs among these chromosomes: chr1,chr2,... or a file
+        /// with one name per line
+        #[arg(long, value_name = "LIST|FILE")]
+        chroms: Option<String>,
+        /// Leave out these chromosomes (chr1,chr2,... or a file) and their pairs
+        #[arg(long, value_name = "LIST|FILE")]
+        exclude_chroms: Option<String>,
+        /// Emit intra-chromosomal records upper-triangular (swap so binX <= binY)
+        #[arg(long)]
+        canonicalize: bool,
+        /// Output layout: slice file, juicer short text for `juicer_tools pre`, or BEDPE
+        #[arg(long, value_enum, default_value_t = straw::DumpFormat::Slc)]
+        format: straw::DumpFormat,
+        /// Container of slice output: none (raw records), gzip, or bgzf blocks
+        #[arg(long, value_enum, default_value_t = straw::SliceCompression::Gzip)]
+        compression: straw::SliceCompression,
+        /// Slice key numbering and header order: name (chr1, chr10, chr2), index (.hic header order) or natural (chr1, chr2, chr10)
+        #[arg(long, value_enum, default_value_t = straw::KeyOrder::Index)]
+        key_order: straw::KeyOrder,
+        /// Retries of a block read failing with a transient IO error (EIO, timeout), e.g. on NFS
+        #[arg(long, value_name = "N", default_value_t = straw::DEFAULT_IO_RETRIES)]
+        io_retries: u32,
+        /// Skip records whose written value (after normalization / O/E) is below this (default: keep all above 0)
+        #[arg(long, value_name = "COUNT")]
+        min_count: Option<f32>,
+        /// Only dump intra-chromosomal pairs (chr1 == chr2)
+        #[arg(long, conflicts_with = "inter_only")]
+        intra_only: bool,
+        /// Only dump inter-chromosomal pairs (chr1 != chr2)
+        #[arg(long)]
+        inter_only: bool,
+        /// No progress bar on stderr during a genome-wide dump
+        #[arg(short, long)]
+        quiet: bool,
+        /// With ALL: write the ALL matrix's genome-wide bins (`bin_x bin_y counts`)
+        /// instead of chromosome positions
+        #[arg(long)]
+        genome_bins: bool,
+    },
+    /// Dump every stored resolution genome-wide, to PREFIX.<binsize>.slc.gz each
+    DumpAllResolutions {
+        /// observed or oe (observed / expected)
+        matrix_type: String,
+        /// NONE/VC/VC_SQRT/KR/SCALE (see `straw list` for the types a file stores)
+        norm: String,
+        /// Input Hi-C file (.hic)
+        input: PathBuf,
+        /// Units: BP, or FRAG for the fragment resolutions
+        unit: String,
+        /// Output prefix; out.slc.gz gives out.<binsize>.slc.gz
+        prefix: PathBuf,
+        /// Sort records by bin within each chromosome pair
+        #[arg(long)]
+        sorted: bool,
+        /// Only dump pairs among these chromosomes: chr1,chr2,... or a file
+        /// with one name per line
+        #[arg(long, value_name = "LIST|FILE")]
+        chroms: Option<String>,
+        /// Leave out these chromosomes (chr1,chr2,... or a file) and their pairs
+        #[arg(long, value_name = "LIST|FILE")]
+        exclude_chroms: Option<String>,
+        /// Emit intra-chromosomal records upper-triangular (swap so binX <= binY)
+        #[arg(long)]
+        canonicalize: bool,
+        /// Output layout: slice file, juicer short text for `juicer_tools pre`, or BEDPE
+        #[arg(long, value_enum, default_value_t = straw::DumpFormat::Slc)]
+        format: straw::DumpFormat,
+        /// Container of slice output: none (raw records), gzip, or bgzf blocks
+        #[arg(long, value_enum, default_value_t = straw::SliceCompression::Gzip)]
+        compression: straw::SliceCompression,
+        /// Slice key numbering and header order: name (chr1, chr10, chr2), index (.hic header order) or natural (chr1, chr2, chr10)
+        #[arg(long, value_enum, default_value_t = straw::KeyOrder::Index)]
+        key_order: straw::KeyOrder,
+        /// Retries of a block read failing with a transient IO error (EIO, timeout), e.g. on NFS
+        #[arg(long, value_name = "N", default_value_t = straw::DEFAULT_IO_RETRIES)]
+        io_retries: u32,
+        /// Skip records whose written value (after normalization / O/E) is below this (default: keep all above 0)
+        #[arg(long, value_name = "COUNT")]
+        min_count: Option<f32>,
+        /// Only dump intra-chromosomal pairs (chr1 == chr2)
+        #[arg(long, conflicts_with = "inter_only")]
+        intra_only: bool,
+        /// Only dump inter-chromosomal pairs (chr1 != chr2)
+        #[arg(long)]
+        inter_only: bool,
+        /// No progress bars on stderr
+        #[arg(short, long)]
+        quiet: bool,
+    },
+    /// Dump the contacts between two regions to a slice file (.slc.gz)
+    DumpRegion {
+        /// observed or oe (observed / expected)
+        matrix_type: String,
+        /// NONE/VC/VC_SQRT/KR/SCALE (see `straw list` for the types a file stores)
+        norm: String,
+        /// Input Hi-C file (.hic)
+        input: PathBuf,
+        /// Units (BP only: regions are given in bp)
         unit: String,
         /// Bin size / resolution in bp
         binsize: i32,
+        /// First region, CHR:START-END (inclusive, commas allowed)
+        region1: String,
+        /// Second region, CHR:START-END
+        region2: String,
         /// Output file path (.slc.gz)
         output: PathBuf,
+        /// Sort records by (binX, binY)
+        #[arg(long)]
+        sorted: bool,
+        /// Emit intra-chromosomal records upper-triangular (swap so binX <= binY)
+        #[arg(long)]
+        canonicalize: bool,
+        /// Output layout: slice file, juicer short text for `juicer_tools pre`, or BEDPE
+        #[arg(long, value_enum, default_value_t = straw::DumpFormat::Slc)]
+        format: straw::DumpFormat,
+        /// Slice key numbering and header order: name (chr1, chr10, chr2), index (.hic header order) or natural (chr1, chr2, chr10)
+        #[arg(long, value_enum, default_value_t = straw::KeyOrder::Index)]
+        key_order: straw::KeyOrder,
+        /// Retries of a block read failing with a transient IO error (EIO, timeout), e.g. on NFS
+        #[arg(long, value_name = "N", default_value_t = straw::DEFAULT_IO_RETRIES)]
+        io_retries: u32,
+    },
+    /// Write one chromosome's contact matrix as a dense N×N table (TSV, or NumPy .npy)
+    Matrix {
+        /// Input Hi-C file (.hic)
+        input: PathBuf,
+        /// Chromosome (matched like effres: chr1, 1, CHR1)
+        chrom: String,
+        /// Bin size / resolution in bp
+        binsize: i32,
+        /// Output file: .npy for NumPy, otherwise tab-separated rows (.gz compresses)
+        output: PathBuf,
+        /// Normalization: NONE/VC/VC_SQRT/KR/SCALE
+        #[arg(long, default_value = "NONE")]
+        norm: String,
+        /// Refuse matrices larger than this, e.g. 8G or 512M (default: 80% of available RAM)
+        #[arg(long, value_name = "SIZE")]
+        max_memory: Option<String>,
+        /// Retries of a block read failing with a transient IO error (EIO, timeout), e.g. on NFS
+        #[arg(long, value_name = "N", default_value_t = straw::DEFAULT_IO_RETRIES)]
+        io_retries: u32,
+    },
+    /// Virtual 4C: contacts of one viewpoint bin across the genome, as
+    /// partner_chrom, partner_start, count
+    Virtual4c {
+        /// observed or oe (observed / expected)
+        matrix_type: String,
+        /// NONE/VC/VC_SQRT/KR/SCALE (see `straw list` for the types a file stores)
+        norm: String,
+        /// Input Hi-C file (.hic)
+        input: PathBuf,
+        /// Bin size / resolution in bp
+        binsize: i32,
+        /// Viewpoint CHR:POS (commas allowed); its bin at BINSIZE is profiled
+        viewpoint: String,
+        /// Output file (.gz compresses); stdout when omitted
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// Only write these partner chromosomes: chr1,chr2,... or a file with
+        /// one name per line
+        #[arg(long, value_name = "LIST|FILE")]
+        chroms: Option<String>,
+        /// Retries of a block read failing with a transient IO error (EIO, timeout), e.g. on NFS
+        #[arg(long, value_name = "N", default_value_t = straw::DEFAULT_IO_RETRIES)]
+        io_retries: u32,
+    },
+    /// Write the stored expected contact decay as distance_bp/expected rows
+    Expected {
+        /// Input Hi-C file (.hic)
+        input: PathBuf,
+        /// Units: only BP (distances are bp)
+        unit: String,
+        /// Bin size / resolution in bp
+        binsize: i32,
+        /// Expected vector: NONE (observed) or a normalization such as KR
+        #[arg(long, default_value = "NONE")]
+        norm: String,
+        /// Divide by this chromosome's scale factor and stop at its length
+        #[arg(long, value_name = "CHROM")]
+        chrom: Option<String>,
+        /// Output file (.gz compresses); stdout when omitted
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Print each chromosome's observed contacts (intra, optionally trans) and contacts per Mb
+    Totals {
+        /// Input Hi-C file (.hic)
+        input: PathBuf,
+        /// Resolution in bp (default: the coarsest stored)
+        #[arg(long, value_name = "BP")]
+        res: Option<i32>,
+        /// Also sum each chromosome's inter-chromosomal matrices
+        #[arg(long)]
+        include_trans: bool,
+        /// List the most contacts per Mb first instead of header order
+        #[arg(long)]
+        sort: bool,
+        /// Output file (.gz compresses); stdout when omitted
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Write stored normalization vectors as bedGraph (chrom, start, end, value)
+    Normvec {
+        /// Input Hi-C file (.hic)
+        input: PathBuf,
+        /// Normalization type: VC/VC_SQRT/KR/SCALE (see `straw list`)
+        norm: String,
+        /// Units: only BP (bedGraph positions are bp)
+        unit: String,
+        /// Bin size / resolution in bp
+        binsize: i32,
+        /// Chromosomes (chr1,chr2,... matched like effres); every chromosome with a vector when omitted
+        chroms: Option<String>,
+        /// Output file (.gz compresses); stdout when omitted
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// Leave out NaN factors instead of writing `nan`
+        #[arg(long)]
+        skip_nan: bool,
     },
     /// List chromosomes in a .hic file
     List {
         /// Input Hi-C file (.hic)
         input: PathBuf,
+        /// Output layout: commented text block, TSV rows, or one JSON object
+        #[arg(long, value_enum, default_value_t = straw::ListFormat::Text)]
+        format: straw::ListFormat,
+    },
+    /// Print the .hic header as JSON for pipelines
+    ///
+    /// Fields (stable; new ones may be added): version, genomeId,
+    /// attributes (object of header key/value pairs), chromosomes (array of
+    /// {name, index, length}, index 0 being the "All" pseudo-chromosome),
+    /// bpResolutions, fragResolutions (header order), nviPosition and
+    /// nviLength (normalized-vector index; null before version 9).
+    Metadata {
+        /// Input Hi-C file (.hic)
+        input: PathBuf,
+        /// Output file; stdout when omitted
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// Print JSON (the only format; accepted for clarity in scripts)
+        #[arg(long)]
+        json: bool,
+    },
+    /// Read slice files (.slc.gz) written by `dump`
+    Slice {
+        #[command(subcommand)]
+        cmd: SliceCmd,
+    },
+    /// Check the block index of every matrix (positions, sizes, duplicates) without decoding blocks
+    ValidateIndex {
+        /// Input Hi-C file (.hic)
+        input: PathBuf,
+    },
+    /// Check a .hic for truncation or corruption: the index, then every block decoded
+    Verify {
+        /// Input Hi-C file (.hic)
+        input: PathBuf,
+        /// Only check that the index and block offsets lie inside the file (as validate-index)
+        #[arg(long)]
+        fast: bool,
+        /// Problems printed in full; the rest are only counted
+        #[arg(long, value_name = "N", default_value_t = 20)]
+        max_problems: usize,
     },
     /// Estimate effective resolution / coverage
     Effres {
         /// Input Hi-C file (.hic)
         input: PathBuf,
-        /// Chromosome name, e.g. 1 / chr1 / X. Omit to summarize across all chromosomes.
-        chromosome: Option<String>,
+        /// Chromosome names, e.g. 1 / chr1 / X; several give a table each and a
+        /// summary. Omit to summarize across all chromosomes.
+        chromosome: Vec<String>,
+        /// Chromosomes to report, as with the positional names
+        #[arg(long, value_name = "CHR,...", value_delimiter = ',')]
+        chromosomes: Vec<String>,
+        /// Report the effective resolution of every chromosome (>= 2.5 Mb) as a table
+        #[arg(long, conflicts_with_all = ["chromosome", "chromosomes"])]
+        per_chrom: bool,
+        /// Print the all-chromosome coverage as a chromosome x resolution grid
+        /// instead of min/mean/max
+        #[arg(long, conflicts_with_all = ["chromosome", "chromosomes", "per_chrom"])]
+        matrix: bool,
         /// Minimum contacts per bin to count as covered
         #[arg(long, default_value_t = 1000)]
         thr: i32,
         /// Coverage fraction threshold (0–1)
         #[arg(long, default_value_t = 0.8)]
         pct: f64,
+        /// How zero-contact bins enter the coverage denominator
+        /// (uncovered = all bins, as in `resolution`; excluded = only bins with contacts)
+        #[arg(long, value_enum, default_value_t = coverage::ZeroBins::Uncovered)]
+        count_zero_bins_as: coverage::ZeroBins,
+        /// Matrix unit: BP, or FRAG to scan the fragment resolutions
+        #[arg(long, default_value = "BP")]
+        unit: String,
+        /// Without a chromosome, leave out chromosomes shorter than this (bp)
+        #[arg(long, value_name = "BP", default_value_t = straw::EFFRES_MIN_CHROM_LEN, conflicts_with_all = ["chromosome", "chromosomes"])]
+        min_length: i64,
+        /// Without a chromosome, use only these chromosomes, whatever their length
+        #[arg(long, value_name = "CHR,...", value_delimiter = ',', conflicts_with_all = ["chromosome", "chromosomes"])]
+        include: Vec<String>,
+        /// Without a chromosome, always leave out these chromosomes
+        #[arg(long, value_name = "CHR,...", value_delimiter = ',', conflicts_with_all = ["chromosome", "chromosomes"])]
+        exclude: Vec<String>,
+        /// Also count each chromosome's inter-chromosomal contacts in its bins
+        /// (Juicer's map resolution; raises coverage over the intra-only default)
+        #[arg(long)]
+        include_trans: bool,
+        /// Scan these resolutions instead of the stored ones; one the file does
+        /// not store is summed from the finest stored resolution dividing it
+        #[arg(long, value_name = "RES,...", value_delimiter = ',')]
+        resolutions: Vec<i32>,
+        /// full: also report contacts and bin tallies, and across chromosomes the
+        /// median coverage, passing chromosomes and per-bin count quartiles
+        #[arg(long, value_enum, default_value_t = straw::EffresStats::Basic, conflicts_with_all = ["per_chrom", "matrix"])]
+        stats: straw::EffresStats,
+        /// Output file; stdout when omitted
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// Output layout: text (commented header), tsv (table only) or json
+        #[arg(long, value_enum, default_value_t = straw::EffresFormat::Text)]
+        format: straw::EffresFormat,
     },
 }
 
+#[derive(Subcommand, Debug)]
+pub enum SliceCmd {
+    /// Write the records as text: chrom1 bin1 chrom2 bin2 counts
+    #[command(visible_alias = "dump")]
+    Decode {
+        /// Slice file (.slc.gz, or an uncompressed slice)
+        input: PathBuf,
+        /// Output file (.gz compresses); stdout when omitted
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// Write bin starts in bp (bin x resolution) instead of bin numbers
+        #[arg(long)]
+        coords: bool,
+    },
+    /// Write the records as sparse BEDPE: chrom1 start1 end1 chrom2 start2 end2 count
+    #[command(visible_alias = "to-bedpe")]
+    Bedpe {
+        /// Slice file (.slc.gz, or an uncompressed slice)
+        input: PathBuf,
+        /// Output file (.gz compresses); stdout when omitted
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// Chromosome sizes (name<TAB>length) to end each chromosome's last bin
+        /// at its length; without it bins end at start + binsize
+        #[arg(short = 'c', long, value_name = "FILE")]
+        chrom_size: Option<PathBuf>,
+    },
+    /// Print the header (binsize, chromosome key table) and the record count
+    Info {
+        /// Slice file (.slc.gz, or an uncompressed slice)
+        input: PathBuf,
+    },
+    /// Check a slice file and summarize it: binsize, records, counts, intra/inter, per chromosome
+    Stats {
+        /// Slice file (.slc.gz, or an uncompressed slice)
+        input: PathBuf,
+    },
+}
+
+/// A comma-separated list of bin sizes, parsed from one positional argument
+/// so that the arguments after it stay separate.
+#[derive(Clone, Debug)]
+pub struct BinSizes(pub Vec<i32>);
+
+impl std::str::FromStr for BinSizes {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        s.split(',')
+            .map(|b| b.trim().parse::<i32>().map_err(|e| format!("invalid bin size '{}': {}", b, e)))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map(BinSizes)
+    }
+}
+
 #[derive(Args, Debug)]
 pub struct ResolutionCli {
-    /// Path to merged_nodups or .pairs file (can be .gz)
+    /// Path to merged_nodups or .pairs file (can be .gz or .zst)
     #[arg(value_name = "INPUT")] 
     pub nodups: Option<PathBuf>,
 
+    /// Input layout (auto detects .pairs, with or without a header, and HiC-Pro validPairs, else merged_nodups)
+    #[arg(long, value_enum, default_value_t = parser::InputFormat::Auto)]
+    pub format: parser::InputFormat,
+
     /// Path to chromosome sizes file (if input has no header)
     #[arg(short, long, value_name = "CHROM_SIZE")]
     pub chrom_size: Option<PathBuf>,
 
-    /// Total genome size in base pairs (unused; kept for compatibility)
-    #[arg(long, default_value_t = 1_000_000_000)]
-    pub genome_size: u64,
+    /// FASTA index (samtools faidx) to take the chromosome sizes from instead, in index order
+    #[arg(long, value_name = "FAI", conflicts_with = "chrom_size")]
+    pub fai: Option<PathBuf>,
+
+    /// Genome size in bp the chromosome sizes should sum to; a difference
+    /// over 5% is reported on stderr. The bins always follow the chromosome sizes
+    #[arg(long, value_name = "BP")]
+    pub genome_size: Option<u64>,
+
+    /// Fail when one chromosome missing from the chromosome sizes names more than --strict-fraction of the lines
+    #[arg(long, conflicts_with = "load_coverage")]
+    pub strict: bool,
+
+    /// Share of the input lines one unknown chromosome may name under --strict
+    #[arg(long, value_name = "FRACTION", default_value_t = 0.001, requires = "strict")]
+    pub strict_fraction: f64,
 
     /// Minimum bin size (base pairs)
     #[arg(long, default_value_t = 50)]
@@ -102,6 +518,10 @@ pub struct ResolutionCli {
     #[arg(long, default_value_t = 1000)]
     pub step_size: u32,
 
+    /// Largest bin size of the stepped coarse search; past it the bin size doubles up to the longest chromosome
+    #[arg(long, value_name = "BP", default_value_t = resolution::DEFAULT_MAX_BIN_SIZE)]
+    pub max_bin_size: u32,
+
     /// Number of threads to use (0 = auto)
     #[arg(short, long, default_value_t = 4)]
     pub threads: usize,
@@ -113,266 +533,469 @@ pub struct ResolutionCli {
     /// Per-worker subchunk size in number of pairs
     #[arg(long, value_name = "PAIRS", default_value_t = 128_000)]
     pub subchunk_pairs: usize,
+
+    /// Derive --chunk-pairs and --subchunk-pairs from this many GB for the pair buffers and --threads
+    #[arg(long, value_name = "GB", conflicts_with_all = ["chunk_pairs", "subchunk_pairs"])]
+    pub mem_budget_gb: Option<f64>,
+
+    /// Report the N most covered bins (rDNA, collapsed repeats, ...); 0 = off
+    #[arg(long, visible_alias = "busiest-bins", value_name = "N", default_value_t = 0)]
+    pub hotspots: usize,
+
+    /// Bin size for the hotspot report in bp (default: --bin-width)
+    #[arg(long, value_name = "BP")]
+    pub hotspot_bin_size: Option<u32>,
+
+    /// Upper bound for the dense coverage bins, e.g. 16G or 512M (default: 80% of available RAM)
+    #[arg(long, value_name = "SIZE")]
+    pub max_memory: Option<String>,
+
+    /// Also write the result and its inputs as JSON to this file
+    #[arg(long, value_name = "PATH")]
+    pub json: Option<PathBuf>,
+
+    /// Only count pair ends inside CHR:START-END and search over the region's bins
+    #[arg(long, value_name = "CHR:START-END")]
+    pub region: Option<String>,
+
+    /// Only search over these chromosomes: chr1,chr2,... or a file with one name per line
+    #[arg(long, value_name = "LIST|FILE", conflicts_with = "region")]
+    pub include_chrom: Option<String>,
+
+    /// Leave these chromosomes (chr1,chr2,... or a file) out of the coverage and the genome size, e.g. chrM
+    #[arg(long, value_name = "LIST|FILE", conflicts_with = "region")]
+    pub exclude_chrom: Option<String>,
+
+    /// Pairs counted: all, cis (intra-chromosomal) or trans (inter-chromosomal)
+    #[arg(long, visible_alias = "scope", value_enum, default_value_t = coverage::ContactScope::All)]
+    pub contacts: coverage::ContactScope,
+
+    /// Also search each chromosome on its own and print a chrom/resolution_bp table
+    #[arg(long)]
+    pub per_chrom: bool,
+
+    /// Also print per-chromosome depth (contacts, covered bins, mean and max bin depth), busiest first
+    #[arg(long)]
+    pub summary: bool,
+
+    /// Write the coverage built from the input to this file, for --load-coverage
+    #[arg(long, value_name = "PATH", conflicts_with = "load_coverage")]
+    pub save_coverage: Option<PathBuf>,
+
+    /// Write the per-bin coverage at --bin-width as a bedGraph (chrom, start, end, count; .gz compresses)
+    #[arg(long, value_name = "PATH", conflicts_with = "region")]
+    pub bedgraph: Option<PathBuf>,
+
+    /// With --bedgraph: also write the bins without contacts
+    #[arg(long, requires = "bedgraph")]
+    pub bedgraph_dense: bool,
+
+    /// Skip parsing and search over a coverage written by --save-coverage (same --bin-width and --chrom-size)
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["nodups", "format", "contacts"])]
+    pub load_coverage: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct CompareCli {
+    #[command(flatten)]
+    pub resolution: ResolutionCli,
+
+    /// .hic file built from the same pairs
+    #[arg(long, value_name = "HIC")]
+    pub hic: PathBuf,
+
+    /// Flag the comparison when the two resolutions differ by more than this factor
+    #[arg(long, default_value_t = 2.0)]
+    pub max_ratio: f64,
+}
+
+#[derive(Args, Debug)]
+pub struct ConvertCli {
+    /// Input merged_nodups file (.txt, .gz or .zst). Omit or `-` to read from stdin.
+    #[arg(value_name = "MERGED_NODUPS")]
+    pub input: Option<PathBuf>,
+    /// Output .pairs file (.gz compresses). Omit to write to stdout.
+    #[arg(short, long, value_name = "PAIRS")]
+    pub output: Option<PathBuf>,
+    /// Chromosome sizes written as #chromsize header lines
+    #[arg(short, long, value_name = "CHROM_SIZE")]
+    pub chrom_size: Option<PathBuf>,
+    /// Preserve a duplicate-count / score column as an extra `count` pairs column
+    #[arg(long)]
+    pub keep_duplicates_column: bool,
+    /// 1-based merged_nodups column holding the count (default: 17, the first extra column)
+    #[arg(long, value_name = "N", requires = "keep_duplicates_column", value_parser = clap::value_parser!(u32).range(1..))]
+    pub count_field: Option<u32>,
 }
 
 #[derive(Args, Debug)]
 pub struct FilterCli {
-    /// Input merged_nodups file (.txt or .gz). Omit to read from stdin.
+    /// Input merged_nodups file (.txt, .gz or .zst), or `-` to read from stdin
     #[arg(value_name = "MERGED_NODUPS")] 
-    pub input: Option<PathBuf>,
+    pub input: PathBuf,
     /// Region spec: either CHROM:START-END, or provide CHROM and START-END as two args
-    #[arg(value_name = "REGION_OR_CHROM")] 
-    pub region_or_chrom: String,
+    #[arg(value_name = "REGION_OR_CHROM", required_unless_present = "bed")]
+    pub region_or_chrom: Option<String>,
     /// Optional START-END when CHROM provided separately
     #[arg(value_name = "START-END")] 
     pub maybe_span: Option<String>,
-    /// Require UU-like filter (mapq>0 both ends and frag1!=frag2)
-    #[arg(long, default_value_t = false)]
-    pub uniq: bool,
+    /// BED file of regions (chrom, start, end) to use instead of a single
+    /// region; lines with either end in any interval are kept
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["region_or_chrom", "maybe_span"])]
+    pub bed: Option<PathBuf>,
+    /// Keep only lines the resolution parser would count (both mapqs at least
+    /// --min-mapq and frag1!=frag2); on by default
+    #[arg(long, visible_alias = "uniq", action = clap::ArgAction::SetTrue, default_value_t = true, overrides_with = "no_require_unique")]
+    pub require_unique: bool,
+    /// Keep every line in the region, whatever its mapqs and fragments
+    #[arg(long = "no-require-unique", action = clap::ArgAction::SetFalse, overrides_with = "require_unique")]
+    pub no_require_unique: bool,
+    /// Also write non-matching lines (outside the region or failing --require-unique) to this file (.gz compresses)
+    #[arg(long, value_name = "PATH")]
+    pub rejected: Option<PathBuf>,
+}
+
+impl FilterCli {
+    /// Whether the uniqueness filter applies: unless `--no-require-unique`
+    /// is the last of the two flags.
+    pub fn unique_filter(&self) -> bool {
+        self.require_unique && self.no_require_unique
+    }
 }
 
 pub fn run() -> Result<()> {
     let args = Cli::parse();
     match &args.cmd {
-        Commands::Resolution(r) => run_resolution(r),
-        Commands::Straw(s) => run_straw(s),
-        Commands::Filter(f) => run_filter(f),
+        Commands::Resolution(r) => run_resolution(r, &args),
+        Commands::Straw(s) => run_straw(s, args.verbose),
+        Commands::Filter(f) => run_filter(f, args.min_mapq),
+        Commands::Compare(c) => run_compare(c, &args),
+        Commands::Convert(c) => run_convert(c),
     }
 }
 
-fn run_resolution(args: &ResolutionCli) -> Result<()> {
-    // Set thread pool size
-    if args.threads > 0 {
-        rayon::ThreadPoolBuilder::new()
-            .num_threads(args.threads)
-            .build_global()
-            .unwrap();
-    }
-
-    println!("hickit – Hi-C toolkit (Rust)");
-    println!("=============================");
-
-    // Create coverage structure (auto-detect pairtools header if present)
-    let chrom_size_path = args.chrom_size.as_ref().map(|p| p.to_str().unwrap());
-    let mut pairs_mode = false;
-    let mut pairs_chr_map: Option<utils::ChrLookup> = None;
-    let genome_names: Vec<String>;
-    let genome_lengths: Vec<u32>;
-
-    // Decide source of chromosome names + lengths, and build coverage
-    let mut coverage = if let Some(path) = args.nodups.as_ref() {
-        if let Ok(Some((map, names, lengths))) = parser::sniff_pairs_header_from_path(path.as_path()) {
-            pairs_mode = true;
-            pairs_chr_map = Some(map);
-            genome_names = names;
-            genome_lengths = lengths.clone();
-            coverage::Coverage::from_lengths(args.bin_width, lengths)
-        } else {
-            if let Some(cs) = chrom_size_path {
-                let (names, lengths) = utils::read_chrom_sizes_with_names(cs)?;
-                genome_names = names;
-                genome_lengths = lengths.clone();
-                coverage::Coverage::from_lengths(args.bin_width, lengths)
-            } else {
-                genome_names = utils::get_default_genome_names();
-                genome_lengths = utils::get_default_genome_lengths();
-                coverage::Coverage::from_lengths(args.bin_width, genome_lengths.clone())
-            }
-        }
-    } else {
-        if let Some(cs) = chrom_size_path {
-            let (names, lengths) = utils::read_chrom_sizes_with_names(cs)?;
-            genome_names = names;
-            genome_lengths = lengths.clone();
-            coverage::Coverage::from_lengths(args.bin_width, lengths)
-        } else {
-            genome_names = utils::get_default_genome_names();
-            genome_lengths = utils::get_default_genome_lengths();
-            coverage::Coverage::from_lengths(args.bin_width, genome_lengths.clone())
-        }
-    };
-    // Now that we have names + lengths, print computed genome info and settings
-    let genome_size: u64 = genome_lengths.iter().map(|&x| x as u64).sum();
-    println!("Genome size: {} bp", genome_size);
-    println!("Bin width: {} bp", args.bin_width);
-    println!("Coverage threshold: {} contacts", args.count_threshold);
-    println!("Required proportion: {:.1}%", args.prop * 100.0);
-    println!("Chromosome lookup: {}", utils::chr_lookup_impl());
-    // Top-10 chromosomes by length (descending)
-    if !genome_names.is_empty() && !genome_lengths.is_empty() {
-        let mut pairs: Vec<(&str, u32)> = genome_names
-            .iter()
-            .map(|s| s.as_str())
-            .zip(genome_lengths.iter().copied())
-            .collect();
-        pairs.sort_unstable_by(|a, b| b.1.cmp(&a.1));
-        let topn = pairs.iter().take(10).collect::<Vec<_>>();
-        println!("Top 10 chromosomes by length:");
-        for (i, (nm, ln)) in topn.into_iter().enumerate() {
-            println!("  {}. {}: {} bp", i + 1, nm, ln);
-        }
+fn run_resolution(args: &ResolutionCli, global: &Cli) -> Result<()> {
+    let run = compute_pairs_resolution(args, global)?;
+
+    // Output results
+    if args.load_coverage.is_none() {
+        println!("Processed {} valid pairs", run.pairs_processed);
+        print_parse_stats(&run.parse_stats);
     }
     println!();
-    println!(
-        "Initialized coverage tracking for {} chromosomes",
-        coverage.bins.len()
-    );
+    println!("Map resolution = {} bp", run.report.resolution_bp);
 
-    // Set up progress bar
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} [{elapsed_precise}] {msg}")?
-            .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ "),
-    );
-
-    // Parse input file and build coverage
-    pb.set_message("Reading merged_nodups file...");
-    let pairs_processed = if let Some(path) = args.nodups.as_ref() {
-        let file = File::open(&path)?;
-        let is_gz = path.extension().map_or(false, |ext| ext == "gz");
-        if pairs_mode {
-            let chr_map = pairs_chr_map.expect("pairs chr_map should be set");
-            if is_gz {
-                let iter = parser::open_pairs_file(file, chr_map)?;
-                process_pairs(iter, &mut coverage, &pb, args.chunk_pairs, args.subchunk_pairs)?
-            } else {
-                let iter = parser::open_pairs_file_uncompressed(file, chr_map)?;
-                process_pairs(iter, &mut coverage, &pb, args.chunk_pairs, args.subchunk_pairs)?
-            }
-        } else {
-            if is_gz {
-                let iter = parser::open_file(file, chrom_size_path)?;
-                process_pairs(iter, &mut coverage, &pb, args.chunk_pairs, args.subchunk_pairs)?
-            } else {
-                let iter = parser::open_file_uncompressed(file, chrom_size_path)?;
-                process_pairs(iter, &mut coverage, &pb, args.chunk_pairs, args.subchunk_pairs)?
-            }
+    if args.per_chrom {
+        println!();
+        println!("chrom\tresolution_bp");
+        let per_chrom =
+            resolution::find_resolution_per_chrom(
+            &run.coverage,
+            args.prop,
+            args.count_threshold,
+            args.step_size,
+            args.max_bin_size,
+        );
+        for (chr_idx, bp) in per_chrom {
+            println!("{}\t{}", run.names[chr_idx], bp);
         }
-    } else {
-        // Read from stdin
-        let iter = parser::open_file(stdin(), chrom_size_path)?;
-        process_pairs(iter, &mut coverage, &pb, args.chunk_pairs, args.subchunk_pairs)?
-    };
+    }
 
-    pb.set_message("Computing resolution...");
+    if args.summary {
+        println!();
+        print_chrom_summary(&run.coverage, &run.names);
+    }
 
-    // Find resolution
-    let resolution =
-        resolution::find_resolution(&coverage, args.prop, args.count_threshold, args.step_size);
+    let hotspots = (args.hotspots > 0).then(|| {
+        let bin_size = args.hotspot_bin_size.unwrap_or(args.bin_width).max(args.bin_width);
+        hotspot_report(&run.coverage, &run.names, run.offset, args.hotspots, bin_size)
+    });
+    if let Some(h) = &hotspots {
+        println!();
+        print_hotspots(h);
+    }
 
-    pb.finish_and_clear();
+    if let Some(path) = &args.bedgraph {
+        let lines = run.coverage.write_bedgraph(path, &run.names, args.bedgraph_dense)?;
+        println!();
+        println!("Wrote {} coverage bins to {}", lines, path.display());
+    }
 
-    // Output results
-    println!("Processed {} valid pairs", pairs_processed);
-    println!();
-    println!("Map resolution = {} bp", resolution);
+    if let Some(path) = args.json.as_ref() {
+        let doc = ResolutionJson {
+            report: &run.report,
+            pairs_processed: run.pairs_processed,
+            contacts: args.contacts,
+            hotspots: hotspots.as_ref(),
+        };
+        let file = File::create(path).map_err(|e| anyhow::anyhow!("Create {:?}: {}", path, e))?;
+        let mut w = std::io::BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut w, &doc)?;
+        writeln!(w)?;
+        w.flush()?;
+    }
 
     Ok(())
 }
 
-fn process_pairs<I>(
-    iter: I,
-    coverage: &mut coverage::Coverage,
-    pb: &ProgressBar,
-    chunk_pairs: usize,
-    subchunk_pairs: usize,
-) -> Result<u64>
-where
-    I: Iterator<Item = Result<utils::Pair>>,
-{
-    let mut count = 0u64;
-    let mut buf: Vec<utils::Pair> = Vec::with_capacity(chunk_pairs.min(8_000_000));
-
-    for pair_result in iter {
-        let pair = pair_result?;
-        buf.push(pair);
-        if buf.len() >= chunk_pairs {
-            aggregate_pairs_chunk(&buf, coverage, subchunk_pairs);
-            buf.clear();
+/// Where the input lines went; a high rejection count usually means the
+/// chromosome names do not match the chromosome sizes.
+fn print_parse_stats(stats: &parser::ParseStats) {
+    println!(
+        "Read {} lines: {} rejected ({} unknown chromosome, {} low mapq, {} same fragment, {} malformed)",
+        stats.total_lines,
+        stats.rejected(),
+        stats.rejected_chr,
+        stats.rejected_mapq,
+        stats.rejected_frag,
+        stats.rejected_fields
+    );
+    if !stats.unmapped_chroms.is_empty() {
+        let names: Vec<String> = stats.unmapped_chroms.iter().map(|(name, lines)| format!("{} ({})", name, lines)).collect();
+        println!("Unknown chromosomes by lines: {}", names.join(", "));
+    }
+}
+
+/// `resolution --json` document: the search report plus the number of pairs
+/// read, and the hotspot report with `--hotspots`.
+#[derive(serde::Serialize)]
+struct ResolutionJson<'a> {
+    #[serde(flatten)]
+    report: &'a resolution::ResolutionReport,
+    pairs_processed: u64,
+    contacts: coverage::ContactScope,
+    #[serde(flatten)]
+    hotspots: Option<&'a Hotspots>,
+}
+
+/// Map the shared `resolution`/`compare` options (and the global
+/// `--verbose`/`--min-mapq`) onto the pipeline builder.
+fn compute_pairs_resolution(args: &ResolutionCli, global: &Cli) -> Result<pipeline::Analysis> {
+    let (chunk_pairs, subchunk_pairs) = match args.mem_budget_gb {
+        Some(gb) if gb.is_finite() && gb > 0.0 => {
+            let threads = if args.threads > 0 { args.threads } else { rayon::current_num_threads() };
+            pipeline::chunking_for_budget((gb * (1u64 << 30) as f64) as u64, threads)
         }
-        count += 1;
+        Some(gb) => anyhow::bail!("--mem-budget-gb must be a positive number of GB, not {}", gb),
+        None => (args.chunk_pairs, args.subchunk_pairs),
+    };
+    let mut builder = pipeline::AnalysisBuilder::new()
+        .format(args.format)
+        .bin_width(args.bin_width)
+        .prop(args.prop)
+        .count_threshold(args.count_threshold)
+        .step_size(args.step_size)
+        .max_bin_size(args.max_bin_size)
+        .threads(args.threads)
+        .chunking(chunk_pairs, subchunk_pairs)
+        .contacts(args.contacts)
+        .min_mapq(global.min_mapq)
+        .verbose(global.verbose)
+        .progress(true);
+    if let Some(path) = &args.nodups {
+        builder = builder.input(path);
+    }
+    if let Some(path) = &args.chrom_size {
+        builder = builder.chrom_sizes(path);
+    }
+    if let Some(path) = &args.fai {
+        builder = builder.fai(path);
+    }
+    if let Some(bp) = args.genome_size {
+        builder = builder.genome_size(bp);
+    }
+    if args.strict {
+        builder = builder.max_unmapped_fraction(args.strict_fraction);
+    }
+    if let Some(size) = &args.max_memory {
+        builder = builder.max_memory(utils::parse_byte_size(size)?);
+    }
+    if let Some(region) = &args.region {
+        builder = builder.region(region);
+    }
+    if let Some(names) = &args.include_chrom {
+        builder = builder.include_chroms(utils::parse_name_list(names)?);
+    }
+    if let Some(names) = &args.exclude_chrom {
+        builder = builder.exclude_chroms(utils::parse_name_list(names)?);
+    }
+    if let Some(path) = &args.save_coverage {
+        builder = builder.save_coverage(path);
+    }
+    if let Some(path) = &args.load_coverage {
+        builder = builder.load_coverage(path);
+    }
+    let run = builder.analyze()?;
+    if let Some(warning) = &run.genome_size_warning {
+        eprintln!("{}", warning);
+    }
+    Ok(run)
+}
 
-        if count % 1_000_000 == 0 {
-            pb.set_message(format!(
-                "Processed {:.1}M pairs...",
-                count as f64 / 1_000_000.0
-            ));
+fn run_compare(args: &CompareCli, global: &Cli) -> Result<()> {
+    let r = &args.resolution;
+    if r.region.is_some() {
+        anyhow::bail!("compare works genome-wide; --region is only supported by `resolution`");
+    }
+    if r.per_chrom {
+        anyhow::bail!("compare works genome-wide; --per-chrom is only supported by `resolution`");
+    }
+    if r.bedgraph.is_some() {
+        anyhow::bail!("--bedgraph is only supported by `resolution`");
+    }
+    if r.summary {
+        anyhow::bail!("--summary is only supported by `resolution`");
+    }
+    if r.include_chrom.is_some() || r.exclude_chrom.is_some() {
+        anyhow::bail!("compare works genome-wide on both inputs; --include-chrom and --exclude-chrom are only supported by `resolution`");
+    }
+    let pairs = compute_pairs_resolution(r, global)?;
+    let hic = straw::genome_effres_hic(
+        args.hic.as_path(),
+        r.count_threshold as f64,
+        r.prop,
+        coverage::ZeroBins::Uncovered,
+    )?;
+
+    if r.load_coverage.is_none() {
+        println!("Processed {} valid pairs", pairs.pairs_processed);
+        print_parse_stats(&pairs.parse_stats);
+    }
+    println!();
+    println!("# .hic pooled coverage: {}", args.hic.display());
+    println!("resolution_bp\tcovered_bins\ttotal_bins\tcoverage");
+    for (res, cov) in &hic.per_resolution {
+        println!("{}\t{}\t{}\t{:.3}", res, cov.covered, cov.total, cov.fraction());
+    }
+    println!();
+    println!("method\tresolution_bp");
+    println!("pairs\t{}", pairs.report.resolution_bp);
+    match hic.effective {
+        Some(h) => println!("hic\t{}", h),
+        None => println!("hic\tNA"),
+    }
+
+    match compare_ratio(pairs.report.resolution_bp, hic.effective) {
+        Some(ratio) => {
+            println!("ratio (hic/pairs)\t{:.3}", ratio);
+            if is_discrepant(ratio, args.max_ratio) {
+                println!(
+                    "\nWARNING: resolutions differ by more than {}x; check that both inputs hold the same data",
+                    args.max_ratio
+                );
+            } else {
+                println!("\nOK: resolutions agree within {}x (the .hic value is rounded up to a stored zoom level)", args.max_ratio);
+            }
         }
+        None => println!(
+            "\nWARNING: no stored .hic resolution reached {:.1}% of bins with >= {} contacts",
+            r.prop * 100.0,
+            r.count_threshold
+        ),
     }
+    Ok(())
+}
 
-    if !buf.is_empty() {
-        aggregate_pairs_chunk(&buf, coverage, subchunk_pairs);
-        buf.clear();
+/// Ratio of the .hic effective resolution to the pairs-based one.
+fn compare_ratio(pairs: u32, hic: Option<i32>) -> Option<f64> {
+    match hic {
+        Some(h) if pairs > 0 => Some(h as f64 / pairs as f64),
+        _ => None,
     }
+}
 
-    Ok(count)
+fn is_discrepant(ratio: f64, max_ratio: f64) -> bool {
+    ratio > max_ratio || ratio * max_ratio < 1.0
 }
 
-fn aggregate_pairs_chunk(pairs: &[utils::Pair], coverage: &mut coverage::Coverage, subchunk_pairs: usize) {
-    let binw = coverage.bin_width;
-    let chr_lens = &coverage.chr_lengths;
-
-    // Process in parallel: for each subchunk, build a vector of (key, count),
-    // where key packs (chrom_index, bin_index) into u64; then sort+compress.
-    let scl = subchunk_pairs.max(16_000);
-    let partials: Vec<Vec<(u64, u32)>> = pairs
-        .par_chunks(scl)
-        .map(|chunk| {
-            #[inline]
-            fn pack(ci: usize, b: u32) -> u64 { ((ci as u64) << 32) | (b as u64) }
-
-            let mut vec: Vec<(u64, u32)> = Vec::with_capacity(chunk.len() * 2);
-            for p in chunk {
-                // First end
-                let ci1 = (p.chr1 as usize).saturating_sub(1);
-                if ci1 < chr_lens.len() {
-                    let pos1 = p.pos1;
-                    if pos1 < chr_lens[ci1] {
-                        let b1 = pos1 / binw;
-                        vec.push((pack(ci1, b1), 1));
-                    }
-                }
-                // Second end
-                let ci2 = (p.chr2 as usize).saturating_sub(1);
-                if ci2 < chr_lens.len() {
-                    let pos2 = p.pos2;
-                    if pos2 < chr_lens[ci2] {
-                        let b2 = pos2 / binw;
-                        vec.push((pack(ci2, b2), 1));
-                    }
-                }
-            }
-            // sort by key and run-length compress counts
-            vec.sort_unstable_by(|a, b| a.0.cmp(&b.0));
-            let mut out: Vec<(u64, u32)> = Vec::with_capacity(vec.len());
-            let mut it = vec.into_iter();
-            if let Some((mut k, mut v)) = it.next() {
-                for (kk, vv) in it {
-                    if kk == k { v = v.saturating_add(vv); } else { out.push((k, v)); k = kk; v = vv; }
-                }
-                out.push((k, v));
+/// Per-chromosome depth at the base bin width, by total contacts descending
+/// so chromosomes that received none end up at the bottom.
+fn print_chrom_summary(coverage: &coverage::Coverage, names: &[String]) {
+    let totals = coverage.per_chrom_total_contacts();
+    let non_zero = coverage.per_chrom_non_zero_bins();
+    let max_depth = coverage.per_chrom_max_bin_depth();
+    // Excluded chromosomes have no bins
+    let mut order: Vec<usize> = (0..coverage.num_chromosomes()).filter(|&i| coverage.chr_lengths[i] > 0).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(totals[i]));
+
+    println!("Chromosome coverage ({} bp bins):", coverage.bin_width);
+    println!("chrom\tlength\ttotal_contacts\tnon_zero_bins\tmean_depth\tmax_bin_depth");
+    for i in order {
+        let name = names.get(i).map(|s| s.as_str()).unwrap_or("?");
+        let bins = (coverage.chr_lengths[i] as u64).div_ceil(coverage.bin_width as u64);
+        let mean = if bins > 0 { totals[i] as f64 / bins as f64 } else { 0.0 };
+        println!("{}\t{}\t{}\t{}\t{:.2}\t{}", name, coverage.chr_lengths[i], totals[i], non_zero[i], mean, max_depth[i]);
+    }
+}
+
+/// One bin of the `--hotspots` report, offset like the input coordinates.
+#[derive(serde::Serialize)]
+struct Hotspot {
+    chrom: String,
+    start: u64,
+    end: u64,
+    count: u64,
+    /// Share of all counted contacts
+    fraction: f64,
+}
+
+/// The `--hotspots` report: the top-N bins and their combined share of all
+/// counted contacts.
+#[derive(serde::Serialize)]
+struct Hotspots {
+    /// Requested bin size rounded to a multiple of the base bin width
+    #[serde(skip)]
+    bin_size: u32,
+    #[serde(rename = "hotspots")]
+    bins: Vec<Hotspot>,
+    #[serde(rename = "hotspot_share")]
+    share: f64,
+}
+
+fn hotspot_report(coverage: &coverage::Coverage, names: &[String], offset: u32, n: usize, bin_size: u32) -> Hotspots {
+    let total = coverage.get_total_contacts();
+    let fraction = |count: u64| if total > 0 { count as f64 / total as f64 } else { 0.0 };
+    let top = coverage.top_bins(n, bin_size);
+    let bin_size = (bin_size / coverage.bin_width).max(1) * coverage.bin_width;
+
+    let bins: Vec<Hotspot> = top
+        .iter()
+        .map(|b| {
+            let start = b.bin as u64 * bin_size as u64;
+            let end = (start + bin_size as u64).min(coverage.chr_lengths[b.chr_idx] as u64);
+            Hotspot {
+                chrom: names.get(b.chr_idx).cloned().unwrap_or_else(|| "?".to_string()),
+                start: start + offset as u64,
+                end: end + offset as u64,
+                count: b.count,
+                fraction: fraction(b.count),
             }
-            out
         })
         .collect();
+    let share = fraction(bins.iter().map(|b| b.count).sum());
+    Hotspots { bin_size, bins, share }
+}
 
-    // Merge compressed vectors into dense bins
-    for part in partials {
-        for (key, v) in part {
-            let ci = (key >> 32) as usize;
-            let b = (key & 0xFFFF_FFFF) as usize;
-            if ci < coverage.bins.len() {
-                let row = &mut coverage.bins[ci];
-                if b < row.len() {
-                    row[b] = row[b].saturating_add(v);
-                }
-            }
-        }
+/// Print the top-N bins as `chrom start end count fraction_of_total`, followed
+/// by their combined share of all counted contacts.
+fn print_hotspots(report: &Hotspots) {
+    println!("Top {} hotspot bins ({} bp):", report.bins.len(), report.bin_size);
+    println!("chrom\tstart\tend\tcount\tfraction_of_total");
+    for b in &report.bins {
+        println!("{}\t{}\t{}\t{}\t{:.6}", b.chrom, b.start, b.end, b.count, b.fraction);
     }
+    println!("Hotspot share of total contacts: {:.2}%", report.share * 100.0);
 }
 
-fn run_straw(cli: &StrawCli) -> Result<()> {
+fn run_straw(cli: &StrawCli, verbose: bool) -> Result<()> {
+    // Global rather than a local pool: the dump workers are plain threads
+    // whose block decoding runs on the global pool
+    if cli.threads > 0 {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(cli.threads)
+            .build_global()
+            .map_err(|e| anyhow::anyhow!("Set up {} straw threads: {}", cli.threads, e))?;
+    }
     match &cli.cmd {
         StrawCmd::Dump {
             matrix_type,
@@ -380,30 +1003,472 @@ fn run_straw(cli: &StrawCli) -> Result<()> {
             input,
             unit,
             binsize,
+            targets,
             output,
+            sorted,
+            global,
+            tmpdir,
+            sort_run_records,
+            chroms,
+            exclude_chroms,
+            canonicalize,
+            format,
+            compression,
+            key_order,
+            io_retries,
+            min_count,
+            intra_only,
+            inter_only,
+            quiet,
+            genome_bins,
         } => {
-            if matrix_type.to_ascii_lowercase() != "observed" {
-                anyhow::bail!("Only 'observed' is supported in this Rust port");
+            let matrix = match matrix_type.to_ascii_lowercase().as_str() {
+                "observed" => Some(straw::MatrixType::Observed),
+                "oe" => Some(straw::MatrixType::Oe),
+                "expected" => None,
+                other => anyhow::bail!("Unsupported matrix type '{}' (use observed, oe or expected)", other),
+            };
+            // Any stored type (VC, VC_SQRT, KR, SCALE, ...) is accepted; the file is checked in dump
+            let norm = norm.to_ascii_uppercase();
+            let unit = unit.to_ascii_uppercase();
+            if unit != "BP" && unit != "FRAG" {
+                anyhow::bail!("Unsupported unit '{}' (use BP or FRAG)", unit);
+            }
+            let opts = straw::DumpOptions {
+                matrix: matrix.unwrap_or_default(),
+                format: *format,
+                compression: *compression,
+                sort: match (*sorted, *global) {
+                    (true, true) => straw::SortMode::Global,
+                    (true, false) => straw::SortMode::PerPair,
+                    _ => straw::SortMode::None,
+                },
+                tmpdir: tmpdir.clone(),
+                sort_run_records: *sort_run_records,
+                chroms: chroms.as_deref().map(utils::parse_name_list).transpose()?,
+                exclude_chroms: exclude_chroms.as_deref().map(utils::parse_name_list).transpose()?,
+                canonicalize: *canonicalize,
+                io_retries: *io_retries,
+                verbose,
+                key_order: *key_order,
+                norm,
+                unit,
+                min_count: *min_count,
+                scope: match (*intra_only, *inter_only) {
+                    (true, _) => coverage::ContactScope::Cis,
+                    (_, true) => coverage::ContactScope::Trans,
+                    _ => coverage::ContactScope::All,
+                },
+                progress: !*quiet,
+            };
+            let all_matrix = matches!(targets.as_slice(), [all, _] if all.eq_ignore_ascii_case("ALL"));
+            if *genome_bins && !all_matrix {
+                anyhow::bail!("--genome-bins only applies to the ALL matrix dump");
             }
-            if norm.to_ascii_uppercase() != "NONE" {
-                anyhow::bail!("Only 'NONE' normalization is supported in this Rust port");
+            if output.is_some() && (targets.len() != 2 || all_matrix) {
+                anyhow::bail!("-o only applies to the CHR1 CHR2 text dump");
             }
-            if unit.to_ascii_uppercase() != "BP" {
-                anyhow::bail!("Only BP units are supported in this Rust port");
+            if let (Some(_), [prefix], [_, _, ..]) = (matrix, targets.as_slice(), binsize.0.as_slice()) {
+                for path in straw::dump_hic_multi_resolution(input.as_path(), &binsize.0, Path::new(prefix), &opts)? {
+                    eprintln!("Wrote {}", path.display());
+                }
+                return Ok(());
             }
-            straw::dump_hic_genome_wide(input.as_path(), *binsize, output.as_path())
+            let binsize = match binsize.0.as_slice() {
+                [b] => b,
+                _ => anyhow::bail!("Several bin sizes only apply to a genome-wide observed or oe dump"),
+            };
+            let single_output = |opts: &straw::DumpOptions| -> Result<()> {
+                if opts.chroms.is_some() || opts.exclude_chroms.is_some() || opts.sort == straw::SortMode::Global {
+                    anyhow::bail!("--chroms, --exclude-chroms and --global do not apply to a region or chromosome-pair dump");
+                }
+                if opts.scope != coverage::ContactScope::All {
+                    anyhow::bail!("--intra-only and --inter-only do not apply to a region or chromosome-pair dump");
+                }
+                Ok(())
+            };
+            match (matrix, targets.as_slice()) {
+                (Some(_), [_, output]) if all_matrix => {
+                    single_output(&opts)?;
+                    straw::dump_hic_all_matrix(input.as_path(), *binsize, Some(Path::new(output)), *genome_bins, &opts)
+                }
+                (Some(_), [chr1, chr2]) => {
+                    single_output(&opts)?;
+                    straw::dump_hic_pair_text(input.as_path(), chr1, chr2, *binsize, output.as_deref(), &opts)
+                }
+                (Some(_), [output]) => straw::dump_hic_genome_wide(input.as_path(), *binsize, Path::new(output), &opts),
+                (None, [output]) => straw::dump_expected(input.as_path(), *binsize, Path::new(output), &opts),
+                (Some(_), [region1, region2, output]) => {
+                    single_output(&opts)?;
+                    let region1 = filter::Region::parse(region1, None)?;
+                    let region2 = filter::Region::parse(region2, None)?;
+                    straw::dump_hic_region(input.as_path(), &region1, &region2, *binsize, Path::new(output), &opts)
+                }
+                (None, _) => anyhow::bail!("The expected matrix type only takes OUTPUT"),
+                _ => anyhow::bail!("Expected OUTPUT, CHR1 CHR2 or REGION1 REGION2 OUTPUT after the bin size"),
+            }
+        }
+        StrawCmd::DumpAllResolutions {
+            matrix_type,
+            norm,
+            input,
+            unit,
+            prefix,
+            sorted,
+            chroms,
+            exclude_chroms,
+            canonicalize,
+            format,
+            compression,
+            key_order,
+            io_retries,
+            min_count,
+            intra_only,
+            inter_only,
+            quiet,
+        } => {
+            let matrix = match matrix_type.to_ascii_lowercase().as_str() {
+                "observed" => straw::MatrixType::Observed,
+                "oe" => straw::MatrixType::Oe,
+                other => anyhow::bail!("Unsupported matrix type '{}' for dump-all-resolutions (use observed or oe)", other),
+            };
+            let unit = unit.to_ascii_uppercase();
+            if unit != "BP" && unit != "FRAG" {
+                anyhow::bail!("Unsupported unit '{}' (use BP or FRAG)", unit);
+            }
+            let opts = straw::DumpOptions {
+                matrix,
+                format: *format,
+                compression: *compression,
+                sort: if *sorted { straw::SortMode::PerPair } else { straw::SortMode::None },
+                chroms: chroms.as_deref().map(utils::parse_name_list).transpose()?,
+                exclude_chroms: exclude_chroms.as_deref().map(utils::parse_name_list).transpose()?,
+                canonicalize: *canonicalize,
+                io_retries: *io_retries,
+                verbose,
+                key_order: *key_order,
+                norm: norm.to_ascii_uppercase(),
+                unit,
+                min_count: *min_count,
+                scope: match (*intra_only, *inter_only) {
+                    (true, _) => coverage::ContactScope::Cis,
+                    (_, true) => coverage::ContactScope::Trans,
+                    _ => coverage::ContactScope::All,
+                },
+                progress: !*quiet,
+                ..Default::default()
+            };
+            for path in straw::dump_hic_all_resolutions(input.as_path(), prefix.as_path(), &opts)? {
+                eprintln!("Wrote {}", path.display());
+            }
+            Ok(())
+        }
+        StrawCmd::DumpRegion {
+            matrix_type,
+            norm,
+            input,
+            unit,
+            binsize,
+            region1,
+            region2,
+            output,
+            sorted,
+            canonicalize,
+            format,
+            key_order,
+            io_retries,
+        } => {
+            let matrix = match matrix_type.to_ascii_lowercase().as_str() {
+                "observed" => straw::MatrixType::Observed,
+                "oe" => straw::MatrixType::Oe,
+                other => anyhow::bail!("Unsupported matrix type '{}' for dump-region (use observed or oe)", other),
+            };
+            if !unit.eq_ignore_ascii_case("BP") {
+                anyhow::bail!("dump-region takes bp coordinates and needs the BP unit");
+            }
+            let region1 = filter::Region::parse(region1, None)?;
+            let region2 = filter::Region::parse(region2, None)?;
+            let opts = straw::DumpOptions {
+                matrix,
+                format: *format,
+                sort: if *sorted { straw::SortMode::PerPair } else { straw::SortMode::None },
+                canonicalize: *canonicalize,
+                io_retries: *io_retries,
+                verbose,
+                key_order: *key_order,
+                norm: norm.to_ascii_uppercase(),
+                ..Default::default()
+            };
+            straw::dump_hic_region(input.as_path(), &region1, &region2, *binsize, output.as_path(), &opts)
+        }
+        StrawCmd::Virtual4c { matrix_type, norm, input, binsize, viewpoint, output, chroms, io_retries } => {
+            let matrix = match matrix_type.to_ascii_lowercase().as_str() {
+                "observed" => straw::MatrixType::Observed,
+                "oe" => straw::MatrixType::Oe,
+                other => anyhow::bail!("Unsupported matrix type '{}' for virtual4c (use observed or oe)", other),
+            };
+            let opts = straw::DumpOptions {
+                matrix,
+                chroms: chroms.as_deref().map(utils::parse_name_list).transpose()?,
+                io_retries: *io_retries,
+                verbose,
+                norm: norm.to_ascii_uppercase(),
+                ..Default::default()
+            };
+            straw::dump_virtual_4c(input.as_path(), viewpoint, *binsize, output.as_deref(), &opts)
+        }
+        StrawCmd::Matrix { input, chrom, binsize, output, norm, max_memory, io_retries } => {
+            let max_bytes = match max_memory {
+                Some(size) => Some(utils::parse_byte_size(size)?),
+                None => utils::available_memory_bytes().map(|b| b / 10 * 8),
+            };
+            let opts = straw::DumpOptions { io_retries: *io_retries, verbose, norm: norm.to_ascii_uppercase(), ..Default::default() };
+            let m = straw::dense_matrix(input.as_path(), chrom, *binsize, max_bytes, &opts)?;
+            m.write(output.as_path())?;
+            eprintln!("Wrote a {} x {} matrix to {}", m.n, m.n, output.display());
+            Ok(())
+        }
+        StrawCmd::Metadata { input, output, json: _ } => straw::write_metadata(input.as_path(), output.as_deref()),
+        StrawCmd::Expected { input, unit, binsize, norm, chrom, output } => {
+            if !unit.eq_ignore_ascii_case("BP") {
+                anyhow::bail!("expected only supports the BP unit; distances are bp");
+            }
+            let norm = norm.to_ascii_uppercase();
+            let rows = straw::dump_expected_decay(input.as_path(), &norm, *binsize, chrom.as_deref(), output.as_deref())?;
+            eprintln!("Wrote {} {} expected values", rows, norm);
+            Ok(())
+        }
+        StrawCmd::Totals { input, res, include_trans, sort, output } => {
+            let (resolution, mut totals) = straw::hic_chrom_totals(input.as_path(), *res, *include_trans)?;
+            if *sort {
+                totals.sort_by(|a, b| b.per_mb().total_cmp(&a.per_mb()));
+            }
+            eprintln!("Contacts of {} chromosomes at {} bp", totals.len(), resolution);
+            straw::write_chrom_totals(&totals, output.as_deref())
+        }
+        StrawCmd::Normvec { input, norm, unit, binsize, chroms, output, skip_nan } => {
+            if !unit.eq_ignore_ascii_case("BP") {
+                anyhow::bail!("normvec only supports the BP unit; bedGraph positions are bp");
+            }
+            let chroms = chroms.as_deref().map(utils::parse_name_list).transpose()?;
+            let norm = norm.to_ascii_uppercase();
+            let (lines, n) = straw::dump_norm_vectors(input.as_path(), &norm, *binsize, chroms.as_deref(), output.as_deref(), *skip_nan)?;
+            eprintln!("Wrote {} bins of {} {} vectors", lines, n, norm);
+            Ok(())
+        }
+        StrawCmd::List { input, format } => straw::list_hic_chromosomes(input.as_path(), *format),
+        StrawCmd::Slice { cmd: SliceCmd::Decode { input, output, coords } } => {
+            straw::decode_slice(input.as_path(), output.as_deref(), *coords)
+        }
+        StrawCmd::Slice { cmd: SliceCmd::Bedpe { input, output, chrom_size } } => {
+            let sizes = match chrom_size {
+                Some(p) => {
+                    let (names, lengths) = utils::read_chrom_sizes_with_names(&p.to_string_lossy())?;
+                    Some(names.into_iter().zip(lengths).collect::<Vec<_>>())
+                }
+                None => None,
+            };
+            let n = straw::slice_to_bedpe(input.as_path(), output.as_deref(), sizes.as_deref())?;
+            eprintln!("Wrote {} records", n);
+            Ok(())
+        }
+        StrawCmd::Slice { cmd: SliceCmd::Info { input } } => {
+            let mut slice = straw::read_slice(input.as_path())?;
+            for rec in &mut slice.records {
+                rec?;
+            }
+            println!("Binsize: {}", slice.binsize);
+            println!("Records: {}", slice.records.count_read());
+            println!("# key\tchromosome");
+            for (name, key) in &slice.keys {
+                println!("{}\t{}", key, name);
+            }
+            Ok(())
+        }
+        StrawCmd::Slice { cmd: SliceCmd::Stats { input } } => {
+            let stats = straw::slice_stats(input.as_path())?;
+            println!("Binsize: {}", stats.binsize);
+            println!("Chromosomes: {}", stats.per_chrom.len());
+            println!("Records: {} ({} intra, {} inter)", stats.records, stats.intra, stats.inter);
+            println!("Total counts: {}", stats.total_counts);
+            println!("# chromosome\trecords");
+            for (name, n) in &stats.per_chrom {
+                println!("{}\t{}", name, n);
+            }
+            Ok(())
+        }
+        StrawCmd::ValidateIndex { input } => {
+            let report = straw::validate_index(input.as_path())?;
+            for a in &report.anomalies {
+                println!("{}", a);
+            }
+            println!(
+                "Checked {} matrices, {} zoom levels, {} blocks: {} anomalies",
+                report.matrices,
+                report.zoom_levels,
+                report.blocks,
+                report.anomalies.len()
+            );
+            if !report.anomalies.is_empty() {
+                anyhow::bail!("Block index of {:?} has {} anomalies", input, report.anomalies.len());
+            }
+            Ok(())
+        }
+        StrawCmd::Verify { input, fast, max_problems } => {
+            let report = straw::verify_hic(input.as_path(), *fast)?;
+            for p in report.problems.iter().take(*max_problems) {
+                println!("{}", p);
+            }
+            if report.problems.len() > *max_problems {
+                println!("... and {} more", report.problems.len() - max_problems);
+            }
+            let index = &report.index;
+            println!("Checked {} matrices, {} zoom levels, {} blocks", index.matrices, index.zoom_levels, index.blocks);
+            if *fast {
+                println!("Offsets checked for {} of block data (blocks not decoded)", utils::format_bytes(report.bytes));
+            } else {
+                println!(
+                    "Decoded {} blocks ({} compressed, {} records)",
+                    report.decoded,
+                    utils::format_bytes(report.bytes),
+                    report.records
+                );
+            }
+            if !report.problems.is_empty() {
+                anyhow::bail!("{:?} failed verification with {} problems", input, report.problems.len());
+            }
+            println!("OK");
+            Ok(())
         }
-        StrawCmd::List { input } => straw::list_hic_chromosomes(input.as_path()),
         StrawCmd::Effres {
             input,
             chromosome,
+            chromosomes,
+            per_chrom,
+            matrix,
             thr,
             pct,
-        } => straw::effres_hic(input.as_path(), chromosome.as_deref(), *thr, *pct),
+            count_zero_bins_as,
+            unit,
+            min_length,
+            include,
+            exclude,
+            include_trans,
+            resolutions,
+            stats,
+            output,
+            format,
+        } => {
+            let opts = straw::EffresOptions {
+                unit: unit.to_ascii_uppercase(),
+                thr: *thr,
+                pct: *pct,
+                zero_bins: *count_zero_bins_as,
+                min_length: *min_length,
+                include: include.clone(),
+                exclude: exclude.clone(),
+                include_trans: *include_trans,
+                resolutions: resolutions.clone(),
+                stats: *stats,
+            };
+            let report = if *per_chrom {
+                straw::effres_per_chrom_report(input.as_path(), &opts)?
+            } else {
+                let chroms: Vec<String> = chromosome.iter().chain(chromosomes).cloned().collect();
+                straw::effres_hic(input.as_path(), &chroms, &opts, *matrix)?
+            };
+            if *format == straw::EffresFormat::Tsv && !report.skipped.is_empty() {
+                eprintln!("Skipped {} chromosomes ({}); see --format text", report.skipped.len(), report.chromosome_filter.as_deref().unwrap_or(""));
+            }
+            straw::write_effres(&report, *format, output.as_deref())
+        }
     }
 }
 
-fn run_filter(cli: &FilterCli) -> Result<()> {
-    let region = filter::Region::parse(&cli.region_or_chrom, cli.maybe_span.as_deref())?;
-    filter::run_filter_file(cli.input.as_deref(), region, cli.uniq)
+fn run_convert(cli: &ConvertCli) -> Result<()> {
+    let chrom_sizes = match cli.chrom_size.as_ref() {
+        Some(p) => {
+            let (names, lengths) = utils::read_chrom_sizes_with_names(utils::utf8_path(p)?)?;
+            names.into_iter().zip(lengths).collect()
+        }
+        None => Vec::new(),
+    };
+    let opts = convert::ConvertOptions {
+        count_field: cli
+            .keep_duplicates_column
+            .then(|| cli.count_field.map_or(convert::DEFAULT_COUNT_FIELD, |n| n as usize)),
+        chrom_sizes,
+    };
+    convert::run_convert_file(cli.input.as_deref(), cli.output.as_deref(), &opts)
+}
+
+fn run_filter(cli: &FilterCli, min_mapq: u32) -> Result<()> {
+    let regions = match (&cli.bed, &cli.region_or_chrom) {
+        (Some(bed), _) => {
+            let regions = filter::load_regions_bed(bed)?;
+            eprintln!("Loaded {} merged intervals from {:?}", regions.len(), bed);
+            regions
+        }
+        (None, Some(region)) => filter::Region::parse(region, cli.maybe_span.as_deref())?.into(),
+        (None, None) => unreachable!("clap requires a region or --bed"),
+    };
+    filter::run_filter_file(cli.input.as_path(), regions, cli.unique_filter(), min_mapq, cli.rejected.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn dump_takes_one_binsize_list_before_the_targets() {
+        Cli::command().debug_assert();
+        let dump = |args: &[&str]| match Cli::try_parse_from(["hickit", "straw", "dump", "observed", "NONE", "in.hic", "BP"].iter().chain(args)) {
+            Ok(Cli { cmd: Commands::Straw(StrawCli { cmd: StrawCmd::Dump { binsize, targets, .. }, .. }), .. }) => (binsize.0, targets),
+            other => panic!("unexpected parse: {:?}", other.err()),
+        };
+        assert_eq!(dump(&["5000", "chr1", "chr2"]), (vec![5000], vec!["chr1".to_string(), "chr2".to_string()]));
+        assert_eq!(dump(&["5000,10000", "out"]), (vec![5000, 10000], vec!["out".to_string()]));
+    }
+
+    #[test]
+    fn scope_is_an_alias_of_contacts() {
+        let contacts = |args: &[&str]| match Cli::try_parse_from(["hickit", "resolution", "in.txt"].iter().chain(args)) {
+            Ok(Cli { cmd: Commands::Resolution(r), .. }) => r.contacts,
+            other => panic!("unexpected parse: {:?}", other.err()),
+        };
+        assert_eq!(contacts(&[]), coverage::ContactScope::All);
+        assert_eq!(contacts(&["--scope", "cis"]), coverage::ContactScope::Cis);
+        assert_eq!(contacts(&["--contacts", "trans"]), coverage::ContactScope::Trans);
+    }
+
+    #[test]
+    fn require_unique_is_a_flag_that_takes_no_value() {
+        let filter = |args: &[&str]| match Cli::try_parse_from(["hickit", "filter"].iter().chain(args)) {
+            Ok(Cli { cmd: Commands::Filter(f), .. }) => (f.unique_filter(), f.region_or_chrom),
+            other => panic!("unexpected parse: {:?}", other.err()),
+        };
+        let region = Some("chr1:100-200".to_string());
+        assert_eq!(filter(&["mnd.txt", "chr1:100-200"]), (true, region.clone()));
+        assert_eq!(filter(&["mnd.txt", "--uniq", "chr1:100-200"]), (true, region.clone()));
+        assert_eq!(filter(&["--uniq", "mnd.txt", "chr1:100-200"]), (true, region.clone()));
+        assert_eq!(filter(&["mnd.txt", "chr1:100-200", "--no-require-unique"]), (false, region.clone()));
+        assert_eq!(filter(&["mnd.txt", "chr1:100-200", "--no-require-unique", "--require-unique"]), (true, region));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn convert_rejects_a_non_utf8_chrom_size_path() {
+        use std::os::unix::ffi::OsStrExt;
+        let sizes = PathBuf::from(std::ffi::OsStr::from_bytes(b"sizes-\xff.txt"));
+        let cli = ConvertCli {
+            input: None,
+            output: None,
+            chrom_size: Some(sizes),
+            keep_duplicates_column: false,
+            count_field: None,
+        };
+        let err = run_convert(&cli).unwrap_err().to_string();
+        assert!(err.contains("not valid UTF-8"), "{}", err);
+    }
 }
@@ -0,0 +1,161 @@
+use anyhow::{anyhow, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Standard columns written for every record, in order.
+pub const PAIRS_COLUMNS: [&str; 8] = ["readID", "chrom1", "pos1", "chrom2", "pos2", "strand1", "strand2", "pair_type"];
+
+/// Name of the extra column holding the preserved duplicate count / score.
+pub const COUNT_COLUMN: &str = "count";
+
+/// First column after the 16 columns of the long merged_nodups layout
+/// (1-based); used by `--keep-duplicates-column` when no `--count-field` is set.
+pub const DEFAULT_COUNT_FIELD: usize = 17;
+
+pub struct ConvertOptions {
+    /// 1-based merged_nodups column copied into the extra `count` pairs column
+    pub count_field: Option<usize>,
+    /// (name, length) written as `#chromsize:` header lines
+    pub chrom_sizes: Vec<(String, u32)>,
+}
+
+/// Header of the converted file; declares the extra column when one is kept.
+pub fn pairs_header(opts: &ConvertOptions) -> String {
+    let mut h = String::from("## pairs format v1.0\n");
+    for (name, len) in &opts.chrom_sizes {
+        h.push_str(&format!("#chromsize: {} {}\n", name, len));
+    }
+    h.push_str("#columns: ");
+    h.push_str(&PAIRS_COLUMNS.join(" "));
+    if opts.count_field.is_some() {
+        h.push(' ');
+        h.push_str(COUNT_COLUMN);
+    }
+    h.push('\n');
+    h
+}
+
+/// Convert one long-format merged_nodups line to a tab-separated .pairs line
+/// (without newline). Returns `None` for lines missing the mapq2 column.
+///
+/// Strands `0`/other map to `+`/`-`; each end is `U` (mapq > 0) or `M`, giving
+/// `UU`, `UM`, `MU` or `MM` as pair_type. The kept count column is copied
+/// verbatim, or `.` when the line is too short to have it.
+pub fn convert_line(line: &str, count_field: Option<usize>) -> Option<String> {
+    let f: Vec<&str> = line.split_ascii_whitespace().collect();
+    if f.len() < 12 {
+        return None;
+    }
+    let strand = |s: &str| if s == "0" { "+" } else { "-" };
+    let end_type = |q: &str| match crate::utils::parse_u32_fast(q.as_bytes()) {
+        Some(q) if q > 0 => 'U',
+        _ => 'M',
+    };
+    let read_id = f.get(14).copied().unwrap_or(".");
+    let mut out = format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}{}",
+        read_id, f[1], f[2], f[5], f[6], strand(f[0]), strand(f[4]), end_type(f[8]), end_type(f[11])
+    );
+    if let Some(col) = count_field {
+        out.push('\t');
+        out.push_str(col.checked_sub(1).and_then(|i| f.get(i)).copied().unwrap_or("."));
+    }
+    Some(out)
+}
+
+/// Convert a merged_nodups stream; returns (records written, lines skipped).
+pub fn convert_stream<R: Read, W: Write>(reader: R, opts: &ConvertOptions, mut out: W) -> Result<(u64, u64)> {
+    let mut buf_reader = BufReader::with_capacity(256 * 1024, reader);
+    let mut line = String::with_capacity(1024);
+    let (mut written, mut skipped) = (0u64, 0u64);
+    out.write_all(pairs_header(opts).as_bytes())?;
+    loop {
+        line.clear();
+        if buf_reader.read_line(&mut line)? == 0 { break; }
+        if line.trim().is_empty() { continue; }
+        match convert_line(&line, opts.count_field) {
+            Some(rec) => {
+                out.write_all(rec.as_bytes())?;
+                out.write_all(b"\n")?;
+                written += 1;
+            }
+            None => skipped += 1,
+        }
+    }
+    out.flush()?;
+    Ok((written, skipped))
+}
+
+/// Convert `input` (or stdin) to `output` (or stdout); `.gz` paths are (de)compressed
+/// and `.zst` inputs decompressed.
+pub fn run_convert_file(input: Option<&Path>, output: Option<&Path>, opts: &ConvertOptions) -> Result<()> {
+    let is_gz = |p: &Path| p.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("gz"));
+    let reader: Box<dyn Read> = match input {
+        Some(path) if path.as_os_str() != "-" => {
+            let file = File::open(path).map_err(|e| anyhow!("Open {:?}: {}", path, e))?;
+            crate::parser::InputCompression::from_path(path).decoder(file)?
+        }
+        _ => Box::new(io::stdin().lock()),
+    };
+    let (written, skipped) = match output {
+        Some(path) => {
+            let file = BufWriter::new(File::create(path).map_err(|e| anyhow!("Create {:?}: {}", path, e))?);
+            if is_gz(path) {
+                let mut enc = GzEncoder::new(file, Compression::default());
+                let n = convert_stream(reader, opts, &mut enc)?;
+                enc.finish()?.flush()?;
+                n
+            } else {
+                convert_stream(reader, opts, file)?
+            }
+        }
+        None => convert_stream(reader, opts, io::stdout().lock())?,
+    };
+    eprintln!("Converted {} records ({} short lines skipped)", written, skipped);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_duplicate_count_column_through_round_trip() {
+        let opts = ConvertOptions { count_field: Some(DEFAULT_COUNT_FIELD), chrom_sizes: vec![("chr1".into(), 1000), ("chr2".into(), 500)] };
+        let input = "0 chr1 130 1 16 chr2 60 2 60 50M SEQ 30 50M SEQ r0 r0 7\n\
+                     16 chr1 10 1 0 chr1 90 3 0 50M SEQ 30 50M SEQ r1 r1\n\
+                     0 chr1 10 1\n";
+        let mut out = Vec::new();
+        assert_eq!(convert_stream(input.as_bytes(), &opts, &mut out).unwrap(), (2, 1));
+        let text = String::from_utf8(out).unwrap();
+
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines.contains(&"#columns: readID chrom1 pos1 chrom2 pos2 strand1 strand2 pair_type count"));
+        let records: Vec<Vec<&str>> = lines.iter().filter(|l| !l.starts_with('#')).map(|l| l.split('\t').collect()).collect();
+        assert_eq!(records[0], vec!["r0", "chr1", "130", "chr2", "60", "+", "-", "UU", "7"]);
+        assert_eq!(records[1], vec!["r1", "chr1", "10", "chr1", "90", "-", "+", "MU", "."]);
+
+        // The output reads back through the .pairs parser, header and all
+        let (map, names, _) = crate::parser::sniff_pairs_header(text.as_bytes()).unwrap().unwrap();
+        assert_eq!(names, vec!["chr1", "chr2"]);
+        let pairs: Vec<_> = crate::parser::open_pairs_file_uncompressed(text.as_bytes(), map)
+            .unwrap()
+            .map(|p| p.unwrap())
+            .collect();
+        assert_eq!(pairs.len(), 1); // only the UU record
+        assert_eq!((pairs[0].chr1, pairs[0].pos1, pairs[0].chr2, pairs[0].pos2), (1, 130, 2, 60));
+        // The kept count column is read back as the pair weight
+        assert_eq!(pairs[0].weight, 7);
+    }
+
+    #[test]
+    fn omits_count_column_by_default() {
+        let opts = ConvertOptions { count_field: None, chrom_sizes: Vec::new() };
+        assert!(pairs_header(&opts).ends_with("pair_type\n"));
+        let rec = convert_line("0 chr1 130 1 16 chr2 60 2 60 50M SEQ 30 50M SEQ r0 r0 7", None).unwrap();
+        assert_eq!(rec.split('\t').count(), PAIRS_COLUMNS.len());
+    }
+}
@@ -1,73 +1,419 @@
-use crate::utils::{get_genome_lengths, Pair};
+use crate::utils::{format_bytes, get_genome_lengths, ChrCode, Pair};
+use anyhow::{anyhow, bail, Result};
 use rayon::prelude::*;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
 
+/// Magic string at the start of a file written by [`Coverage::save`].
+pub const COVERAGE_MAGIC: &[u8] = b"HKCOVER1";
+
+/// How bins without any contact enter a coverage denominator.
+///
+/// Both `resolution` and `straw effres` report coverage as the fraction of
+/// bins whose count reaches a threshold. They share [`coverage_fraction`] so
+/// the definition is the same everywhere; the default is
+/// [`ZeroBins::Uncovered`], i.e. every bin spanning the chromosome is counted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ZeroBins {
+    /// Zero-contact bins are uncovered (denominator = all bins of the chromosome)
+    #[default]
+    Uncovered,
+    /// Zero-contact bins are ignored (denominator = bins with any contact)
+    Excluded,
+}
+
+impl ZeroBins {
+    pub fn describe(self) -> &'static str {
+        match self {
+            ZeroBins::Uncovered => "counted as uncovered (denominator = all bins)",
+            ZeroBins::Excluded => "excluded (denominator = bins with any contact)",
+        }
+    }
+}
+
+/// Which pairs contribute to coverage, by whether both ends are on one chromosome.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContactScope {
+    /// Every pair
+    #[default]
+    All,
+    /// Intra-chromosomal pairs only (`chr1 == chr2`)
+    Cis,
+    /// Inter-chromosomal pairs only (`chr1 != chr2`)
+    Trans,
+}
+
+impl ContactScope {
+    /// Whether `pair` counts under this scope.
+    #[inline]
+    pub fn keeps(self, pair: &Pair) -> bool {
+        self.keeps_intra(pair.chr1 == pair.chr2)
+    }
+
+    /// Whether a chromosome pair counts, given if both ends are on one chromosome.
+    #[inline]
+    pub fn keeps_intra(self, intra: bool) -> bool {
+        match self {
+            ContactScope::All => true,
+            ContactScope::Cis => intra,
+            ContactScope::Trans => !intra,
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            ContactScope::All => "all pairs",
+            ContactScope::Cis => "cis only (both ends on one chromosome)",
+            ContactScope::Trans => "trans only (ends on different chromosomes)",
+        }
+    }
+}
+
+/// Covered and total bin tallies; additive across chromosomes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CoverageFraction {
+    pub covered: u64,
+    pub total: u64,
+}
+
+impl CoverageFraction {
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.covered as f64 / self.total as f64
+        }
+    }
+}
+
+impl std::ops::Add for CoverageFraction {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        CoverageFraction {
+            covered: self.covered + other.covered,
+            total: self.total + other.total,
+        }
+    }
+}
+
+/// Count bins whose contacts reach `threshold`, and the denominator they are
+/// measured against.
+///
+/// `per_bin_counts` yields the summed contacts of bins (bins never seen may be
+/// omitted; zero entries are treated the same way). `total_bins` is the number
+/// of bins spanning the region at this bin size and only matters for
+/// [`ZeroBins::Uncovered`].
+pub fn coverage_fraction<I>(per_bin_counts: I, threshold: f64, total_bins: u64, mode: ZeroBins) -> CoverageFraction
+where
+    I: IntoIterator<Item = f64>,
+{
+    let mut covered = 0u64;
+    let mut nonzero = 0u64;
+    for c in per_bin_counts {
+        if c > 0.0 {
+            nonzero += 1;
+            if c >= threshold {
+                covered += 1;
+            }
+        }
+    }
+    let total = match mode {
+        ZeroBins::Uncovered => total_bins.max(nonzero),
+        ZeroBins::Excluded => nonzero,
+    };
+    if threshold <= 0.0 {
+        covered = total;
+    }
+    CoverageFraction { covered, total }
+}
+
+/// A single aggregated bin, as reported by [`Coverage::top_bins`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BinCount {
+    /// Index into `chr_lengths`/`bins` (0-based)
+    pub chr_idx: usize,
+    /// Bin index at the requested bin size
+    pub bin: usize,
+    pub count: u64,
+}
+
+/// Base-bin contact counts of every chromosome in one contiguous buffer.
+///
+/// Chromosome `i` owns `bins[offsets[i]..offsets[i + 1]]`, i.e.
+/// `ceil(len / bin_width)` bins, the last one partial unless `len` is a
+/// multiple of `bin_width`; `offsets` ends with `bins.len()`.
 pub struct Coverage {
-    pub bins: Vec<Vec<u32>>,
+    pub bins: Vec<u32>,
+    pub offsets: Vec<usize>,
     pub bin_width: u32,
     pub chr_lengths: Vec<u32>,
 }
 
 impl Coverage {
     pub fn new(bin_width: u32, chrom_size_file: Option<&str>) -> Self {
-        let chr_lengths = get_genome_lengths(chrom_size_file);
-        let bins: Vec<Vec<u32>> = chr_lengths
-            .iter()
-            .map(|&len| {
-                let num_bins = (len / bin_width) + 1;
-                vec![0u32; num_bins as usize]
-            })
-            .collect();
+        Self::from_lengths(bin_width, get_genome_lengths(chrom_size_file))
+    }
+
+    pub fn from_lengths(bin_width: u32, chr_lengths: Vec<u32>) -> Self {
+        let mut offsets = Vec::with_capacity(chr_lengths.len() + 1);
+        let mut total = 0usize;
+        offsets.push(0);
+        for &len in &chr_lengths {
+            total += len.div_ceil(bin_width) as usize;
+            offsets.push(total);
+        }
 
         Self {
-            bins,
+            bins: vec![0u32; total],
+            offsets,
             bin_width,
             chr_lengths,
         }
     }
 
-    pub fn from_lengths(bin_width: u32, chr_lengths: Vec<u32>) -> Self {
-        let bins: Vec<Vec<u32>> = chr_lengths
+    pub fn num_chromosomes(&self) -> usize {
+        self.chr_lengths.len()
+    }
+
+    /// Base bins of chromosome `chr_idx` (0-based).
+    pub fn chr_bins(&self, chr_idx: usize) -> &[u32] {
+        &self.bins[self.offsets[chr_idx]..self.offsets[chr_idx + 1]]
+    }
+
+    /// Per-chromosome bin slices, in chromosome order, for parallel scans.
+    fn par_chromosomes(&self) -> impl IndexedParallelIterator<Item = &[u32]> {
+        self.offsets.par_windows(2).map(|w| &self.bins[w[0]..w[1]])
+    }
+
+    /// Index into `bins` of the base bin holding `pos` on chromosome code
+    /// `chr`, or `None` for unknown chromosomes and positions past the end.
+    /// Input positions are 1-based, so `pos == len` is the last base and falls
+    /// in the last bin.
+    #[inline]
+    pub fn flat_index(&self, chr: ChrCode, pos: u32) -> Option<usize> {
+        let chr_idx = (chr as usize).checked_sub(1)?;
+        let len = *self.chr_lengths.get(chr_idx)?;
+        if pos > len || len == 0 {
+            return None;
+        }
+        Some(self.offsets[chr_idx] + (pos.min(len - 1) / self.bin_width) as usize)
+    }
+
+    /// Bytes needed by the dense bins for these chromosome lengths.
+    pub fn predicted_bytes(bin_width: u32, chr_lengths: &[u32]) -> u64 {
+        let bins: u64 = chr_lengths
             .iter()
-            .map(|&len| {
-                let num_bins = (len / bin_width) + 1;
-                vec![0u32; num_bins as usize]
-            })
-            .collect();
+            .map(|&len| len.div_ceil(bin_width) as u64)
+            .sum();
+        bins * std::mem::size_of::<u32>() as u64
+    }
 
-        Self {
-            bins,
-            bin_width,
-            chr_lengths,
+    /// Like [`Coverage::from_lengths`], but errors instead of allocating more
+    /// than `max_bytes` for the dense bins.
+    pub fn try_from_lengths(bin_width: u32, chr_lengths: Vec<u32>, max_bytes: Option<u64>) -> Result<Self> {
+        if bin_width == 0 {
+            bail!("Bin width must be greater than 0");
+        }
+        let need = Self::predicted_bytes(bin_width, &chr_lengths);
+        if let Some(max) = max_bytes {
+            if need > max {
+                let genome: u64 = chr_lengths.iter().map(|&x| x as u64).sum();
+                // Smallest bin width (rounded up to 10 bp) that fits the budget
+                let per_bin = std::mem::size_of::<u32>() as u64;
+                let min_width = (genome * per_bin).div_ceil(max.max(1)).div_ceil(10) * 10;
+                bail!(
+                    "Coverage bins at --bin-width {} need {} but --max-memory allows {}. \
+                     Increase --bin-width (>= {} bp should fit) or raise --max-memory.",
+                    bin_width,
+                    format_bytes(need),
+                    format_bytes(max),
+                    min_width.max(bin_width as u64 + 1)
+                );
+            }
         }
+        Ok(Self::from_lengths(bin_width, chr_lengths))
     }
 
-    pub fn increment(&mut self, chr: u8, pos: u32) {
-        let chr_idx = (chr as usize).saturating_sub(1);
-        if chr_idx >= self.bins.len() {
-            return;
+    /// Write the bins to `path`: magic, `bin_width` (u32), the chromosome
+    /// count (u64) and lengths (u32 each), then the bin count (u64) and bins
+    /// (u32 each), all little-endian.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let file = File::create(path).map_err(|e| anyhow!("Create {:?}: {}", path, e))?;
+        let mut w = BufWriter::with_capacity(256 * 1024, file);
+        w.write_all(COVERAGE_MAGIC)?;
+        w.write_all(&self.bin_width.to_le_bytes())?;
+        w.write_all(&(self.chr_lengths.len() as u64).to_le_bytes())?;
+        for len in &self.chr_lengths {
+            w.write_all(&len.to_le_bytes())?;
+        }
+        w.write_all(&(self.bins.len() as u64).to_le_bytes())?;
+        for count in &self.bins {
+            w.write_all(&count.to_le_bytes())?;
         }
+        w.flush()?;
+        Ok(())
+    }
 
-        if pos >= self.chr_lengths[chr_idx] {
-            return;
+    /// Write the base bins as a bedGraph, `chrom\tstart\tend\tcount` at
+    /// `bin_width` (the last bin of a chromosome ends at its length), gzip
+    /// compressed when `path` ends in `.gz`. Zero bins are skipped unless
+    /// `dense`. `names` labels the chromosomes in `chr_lengths` order.
+    /// Returns the number of lines written.
+    pub fn write_bedgraph(&self, path: &Path, names: &[String], dense: bool) -> Result<u64> {
+        if names.len() != self.num_chromosomes() {
+            bail!("{} chromosome names for {} coverage chromosomes", names.len(), self.num_chromosomes());
+        }
+        let file = File::create(path).map_err(|e| anyhow!("Create {:?}: {}", path, e))?;
+        let file = BufWriter::with_capacity(256 * 1024, file);
+        let mut w: Box<dyn Write> = if path.extension().is_some_and(|e| e == "gz") {
+            Box::new(flate2::write::GzEncoder::new(file, flate2::Compression::default()))
+        } else {
+            Box::new(file)
+        };
+        let width = self.bin_width as u64;
+        let mut lines = 0u64;
+        for (chr_idx, name) in names.iter().enumerate() {
+            let len = self.chr_lengths[chr_idx] as u64;
+            for (bin, &count) in self.chr_bins(chr_idx).iter().enumerate() {
+                if count == 0 && !dense {
+                    continue;
+                }
+                let start = bin as u64 * width;
+                writeln!(w, "{}\t{}\t{}\t{}", name, start, (start + width).min(len), count)?;
+                lines += 1;
+            }
         }
+        w.flush()?;
+        Ok(lines)
+    }
 
-        let bin_idx = (pos / self.bin_width) as usize;
-        if bin_idx < self.bins[chr_idx].len() {
-            let v = &mut self.bins[chr_idx][bin_idx];
-            *v = v.saturating_add(1);
+    /// Read a coverage written by [`Coverage::save`]; the offsets are rebuilt
+    /// from the lengths and must account for exactly the stored bins. Files
+    /// from before bin counts were rounded up hold `len / bin_width + 1` bins
+    /// per chromosome; the extra bin of a length divisible by the width is
+    /// always empty and is dropped.
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path).map_err(|e| anyhow!("Open {:?}: {}", path, e))?;
+        let mut r = BufReader::with_capacity(256 * 1024, file);
+        let mut magic = [0u8; COVERAGE_MAGIC.len()];
+        r.read_exact(&mut magic).map_err(|e| anyhow!("Read {:?}: {}", path, e))?;
+        if magic != COVERAGE_MAGIC {
+            bail!("{:?} is not a coverage file (missing {} magic)", path, String::from_utf8_lossy(COVERAGE_MAGIC));
+        }
+        let mut b4 = [0u8; 4];
+        let mut b8 = [0u8; 8];
+        r.read_exact(&mut b4)?;
+        let bin_width = u32::from_le_bytes(b4);
+        if bin_width == 0 {
+            bail!("{:?} has a bin width of 0", path);
+        }
+        r.read_exact(&mut b8)?;
+        let nchrom = u64::from_le_bytes(b8);
+        let mut chr_lengths = Vec::with_capacity(nchrom.min(1 << 16) as usize);
+        for _ in 0..nchrom {
+            r.read_exact(&mut b4)?;
+            chr_lengths.push(u32::from_le_bytes(b4));
+        }
+        r.read_exact(&mut b8)?;
+        let nbins = u64::from_le_bytes(b8);
+        let mut cov = Self::from_lengths(bin_width, chr_lengths);
+        let legacy: u64 = cov.chr_lengths.iter().map(|&len| (len / bin_width) as u64 + 1).sum();
+        if nbins != cov.bins.len() as u64 && nbins != legacy {
+            bail!("{:?} stores {} bins but its chromosome lengths need {}", path, nbins, cov.bins.len());
         }
+        let mut bins = vec![0u32; nbins as usize];
+        let mut buf = vec![0u8; 64 * 1024 * 4];
+        for chunk in bins.chunks_mut(64 * 1024) {
+            let bytes = &mut buf[..chunk.len() * 4];
+            r.read_exact(bytes).map_err(|e| anyhow!("Read bins of {:?}: {}", path, e))?;
+            for (bin, b) in chunk.iter_mut().zip(bytes.chunks_exact(4)) {
+                *bin = u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+            }
+        }
+        if nbins == cov.bins.len() as u64 {
+            cov.bins = bins;
+        } else {
+            let mut start = 0;
+            for (chr_idx, &len) in cov.chr_lengths.iter().enumerate() {
+                let n = cov.offsets[chr_idx + 1] - cov.offsets[chr_idx];
+                cov.bins[cov.offsets[chr_idx]..cov.offsets[chr_idx + 1]].copy_from_slice(&bins[start..start + n]);
+                start += (len / bin_width) as usize + 1;
+            }
+        }
+        Ok(cov)
     }
 
+    pub fn increment(&mut self, chr: ChrCode, pos: u32) {
+        self.increment_by(chr, pos, 1);
+    }
+
+    /// Add `w` contacts to the bin holding `pos` (saturating).
+    pub fn increment_by(&mut self, chr: ChrCode, pos: u32, w: u32) {
+        if let Some(i) = self.flat_index(chr, pos) {
+            self.bins[i] = self.bins[i].saturating_add(w);
+        }
+    }
+
+    /// Count both ends of `pair`, each by the pair's weight.
     pub fn add_pair(&mut self, pair: &Pair) {
-        self.increment(pair.chr1, pair.pos1);
-        self.increment(pair.chr2, pair.pos2);
+        self.add_pair_weighted(pair, pair.weight);
+    }
+
+    /// Count both ends of `pair` by `w`, ignoring the pair's own weight.
+    pub fn add_pair_weighted(&mut self, pair: &Pair, w: u32) {
+        self.increment_by(pair.chr1, pair.pos1, w);
+        self.increment_by(pair.chr2, pair.pos2, w);
+    }
+
+    /// Add a chunk of pairs in parallel: each subchunk of `subchunk_pairs`
+    /// collects (flat bin index, pair weight) entries, sorted and run-length
+    /// compressed, which are then merged into the dense bins.
+    pub fn add_pairs(&mut self, pairs: &[Pair], subchunk_pairs: usize) {
+        let scl = subchunk_pairs.max(16_000);
+        let cov = &*self;
+        let partials: Vec<Vec<(usize, u32)>> = pairs
+            .par_chunks(scl)
+            .map(|chunk| {
+                let mut vec: Vec<(usize, u32)> = Vec::with_capacity(chunk.len() * 2);
+                for p in chunk {
+                    // Ends on unknown chromosomes or past the chromosome end are dropped
+                    if let Some(i) = cov.flat_index(p.chr1, p.pos1) {
+                        vec.push((i, p.weight));
+                    }
+                    if let Some(i) = cov.flat_index(p.chr2, p.pos2) {
+                        vec.push((i, p.weight));
+                    }
+                }
+                // sort by key and run-length compress counts
+                vec.sort_unstable_by_key(|e| e.0);
+                let mut out: Vec<(usize, u32)> = Vec::with_capacity(vec.len());
+                let mut it = vec.into_iter();
+                if let Some((mut k, mut v)) = it.next() {
+                    for (kk, vv) in it {
+                        if kk == k { v = v.saturating_add(vv); } else { out.push((k, v)); k = kk; v = vv; }
+                    }
+                    out.push((k, v));
+                }
+                out
+            })
+            .collect();
+
+        for part in partials {
+            for (i, v) in part {
+                self.bins[i] = self.bins[i].saturating_add(v);
+            }
+        }
     }
 
     pub fn get_counts(&self, bin_size: u32) -> Vec<Vec<u32>> {
         let bins_per_chunk = bin_size / self.bin_width;
 
-        self.bins
-            .par_iter()
+        self.par_chromosomes()
             .map(|chr_bins| {
                 let mut result = Vec::new();
                 let mut i = 0;
@@ -92,8 +438,7 @@ impl Coverage {
             return self.count_good_bins_large(bin_size, threshold);
         }
 
-        self.bins
-            .par_iter()
+        self.par_chromosomes()
             .map(|chr_bins| {
                 let mut count = 0u64;
                 let chunk_size = bins_per_chunk as usize;
@@ -112,16 +457,29 @@ impl Coverage {
             .sum()
     }
 
+    /// Good bins (summed count >= `threshold`) and all bins of chromosome
+    /// `chr_idx` at `bin_size`, with every bin spanning the chromosome counted
+    /// as in [`Coverage::coverage_at`].
+    pub fn count_good_bins_for_chr(&self, chr_idx: usize, bin_size: u32, threshold: u32) -> CoverageFraction {
+        let bins_per_chunk = (bin_size / self.bin_width).max(1) as usize;
+        let effective = (bins_per_chunk as u64) * self.bin_width as u64;
+        let sums = self
+            .chr_bins(chr_idx)
+            .chunks(bins_per_chunk)
+            .map(|chunk| chunk.iter().map(|&x| x as u64).sum::<u64>() as f64);
+        let total = (self.chr_lengths[chr_idx] as u64).div_ceil(effective);
+        coverage_fraction(sums, threshold as f64, total, ZeroBins::Uncovered)
+    }
+
     // Optimized version for large bin sizes
     fn count_good_bins_large(&self, bin_size: u32, threshold: u32) -> u64 {
         let bins_per_chunk = bin_size / self.bin_width;
 
-        self.bins
-            .par_iter()
+        self.par_chromosomes()
             .map(|chr_bins| {
                 let mut count = 0u64;
                 let chunk_size = bins_per_chunk as usize;
-                let num_chunks = (chr_bins.len() + chunk_size - 1) / chunk_size;
+                let num_chunks = chr_bins.len().div_ceil(chunk_size);
 
                 // Use batched processing for better cache performance
                 for chunk_idx in 0..num_chunks {
@@ -140,25 +498,307 @@ impl Coverage {
             .sum()
     }
 
+    /// Coverage at `bin_size` using the shared [`coverage_fraction`] definition,
+    /// with every chromosome contributing `ceil(len / bin_size)` bins.
+    pub fn coverage_at(&self, bin_size: u32, threshold: u32, mode: ZeroBins) -> CoverageFraction {
+        let bins_per_chunk = (bin_size / self.bin_width).max(1) as usize;
+        let effective = (bins_per_chunk as u64) * self.bin_width as u64;
+
+        self.par_chromosomes()
+            .zip(self.chr_lengths.par_iter())
+            .map(|(chr_bins, &len)| {
+                let sums = chr_bins
+                    .chunks(bins_per_chunk)
+                    .map(|chunk| chunk.iter().map(|&x| x as u64).sum::<u64>() as f64);
+                coverage_fraction(sums, threshold as f64, (len as u64).div_ceil(effective), mode)
+            })
+            .reduce(CoverageFraction::default, |a, b| a + b)
+    }
+
     pub fn total_genome_size(&self) -> u64 {
         self.chr_lengths.iter().map(|&x| x as u64).sum()
     }
 
     pub fn get_total_contacts(&self) -> u64 {
-        self.bins
-            .par_iter()
-            .map(|chr_bins| {
-                chr_bins.iter().map(|&x| x as u64).sum::<u64>()
+        self.bins.par_iter().map(|&x| x as u64).sum()
+    }
+
+    /// The `n` bins with the highest counts after aggregating to `bin_size`.
+    ///
+    /// Each chromosome is scanned once while keeping a bounded min-heap of the
+    /// current top `n`; results are sorted by count, descending.
+    pub fn top_bins(&self, n: usize, bin_size: u32) -> Vec<BinCount> {
+        if n == 0 {
+            return Vec::new();
+        }
+        let chunk_size = (bin_size / self.bin_width).max(1) as usize;
+
+        let per_chr: Vec<Vec<BinCount>> = self
+            .par_chromosomes()
+            .enumerate()
+            .map(|(chr_idx, chr_bins)| {
+                let mut heap: BinaryHeap<Reverse<(u64, usize)>> = BinaryHeap::with_capacity(n + 1);
+                for (bin, chunk) in chr_bins.chunks(chunk_size).enumerate() {
+                    let count: u64 = chunk.iter().map(|&x| x as u64).sum();
+                    if count == 0 {
+                        continue;
+                    }
+                    if heap.len() < n {
+                        heap.push(Reverse((count, bin)));
+                    } else if let Some(&Reverse((min, _))) = heap.peek() {
+                        if count > min {
+                            heap.pop();
+                            heap.push(Reverse((count, bin)));
+                        }
+                    }
+                }
+                heap.into_iter()
+                    .map(|Reverse((count, bin))| BinCount { chr_idx, bin, count })
+                    .collect()
             })
-            .sum()
+            .collect();
+
+        let mut all: Vec<BinCount> = per_chr.into_iter().flatten().collect();
+        all.sort_unstable_by_key(|b| (Reverse(b.count), b.chr_idx, b.bin));
+        all.truncate(n);
+        all
     }
 
     pub fn get_non_zero_bins(&self) -> u64 {
-        self.bins
-            .par_iter()
-            .map(|chr_bins| {
-                chr_bins.iter().filter(|&&x| x > 0).count() as u64
-            })
-            .sum()
+        self.bins.par_iter().filter(|&&x| x > 0).count() as u64
+    }
+
+    /// Contacts (pair ends) of each chromosome, aligned with `chr_lengths`.
+    pub fn per_chrom_total_contacts(&self) -> Vec<u64> {
+        self.par_chromosomes().map(|bins| bins.iter().map(|&x| x as u64).sum()).collect()
+    }
+
+    /// Base bins with at least one contact on each chromosome, aligned with `chr_lengths`.
+    pub fn per_chrom_non_zero_bins(&self) -> Vec<u64> {
+        self.par_chromosomes().map(|bins| bins.iter().filter(|&&x| x > 0).count() as u64).collect()
+    }
+
+    /// Highest base-bin count of each chromosome, aligned with `chr_lengths`.
+    pub fn per_chrom_max_bin_depth(&self) -> Vec<u32> {
+        self.par_chromosomes().map(|bins| bins.iter().copied().max().unwrap_or(0)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_bins_mode_controls_denominator() {
+        // 10 bins spanning the region, 4 with signal, 2 of them above threshold
+        let counts = [5.0, 1.0, 7.0, 2.0];
+        let all = coverage_fraction(counts, 5.0, 10, ZeroBins::Uncovered);
+        assert_eq!(all, CoverageFraction { covered: 2, total: 10 });
+        assert_eq!(all.fraction(), 0.2);
+        let nonzero = coverage_fraction(counts, 5.0, 10, ZeroBins::Excluded);
+        assert_eq!(nonzero.fraction(), 0.5);
+        assert_eq!(coverage_fraction([], 5.0, 10, ZeroBins::Uncovered).fraction(), 0.0);
+    }
+
+    #[test]
+    fn pipeline_and_effres_definitions_agree() {
+        // 1000 bp chromosome at 50 bp base bins, queried at 200 bp: 5 bins
+        let mut cov = Coverage::from_lengths(50, vec![1000]);
+        for _ in 0..3 { cov.increment(1, 10); }
+        for _ in 0..2 { cov.increment(1, 450); }
+        cov.increment(1, 999);
+
+        // effres-style sparse per-bin sums at 200 bp: bin 0 -> 3, bin 2 -> 2, bin 4 -> 1
+        let sparse = [3.0, 2.0, 1.0];
+        for mode in [ZeroBins::Uncovered, ZeroBins::Excluded] {
+            assert_eq!(cov.coverage_at(200, 2, mode), coverage_fraction(sparse, 2.0, 5, mode));
+        }
+        assert_eq!(cov.coverage_at(200, 2, ZeroBins::Uncovered), CoverageFraction { covered: 2, total: 5 });
+    }
+
+    #[test]
+    fn save_and_load_round_trip_the_bins() {
+        let mut cov = Coverage::from_lengths(50, vec![1000, 120]);
+        cov.increment_by(1, 10, 3);
+        cov.increment(2, 119);
+        let path = std::env::temp_dir().join(format!("hickit-cov-{}.bin", std::process::id()));
+        cov.save(&path).unwrap();
+        let back = Coverage::load(&path).unwrap();
+        assert_eq!((back.bin_width, &back.chr_lengths), (50, &vec![1000, 120]));
+        assert_eq!(back.offsets, cov.offsets);
+        assert_eq!(back.bins, cov.bins);
+
+        // A truncated bin buffer is an error, not a shorter coverage
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() - 4]).unwrap();
+        assert!(Coverage::load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn bedgraph_lists_covered_bins_clamped_to_the_chromosome() {
+        let mut cov = Coverage::from_lengths(50, vec![120, 100]);
+        cov.increment_by(1, 110, 2);
+        cov.increment(2, 10);
+        let names = vec!["chr1".to_string(), "chr2".to_string()];
+        let path = std::env::temp_dir().join(format!("hickit-cov-{}.bedgraph", std::process::id()));
+        assert_eq!(cov.write_bedgraph(&path, &names, false).unwrap(), 2);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "chr1\t100\t120\t2\nchr2\t0\t50\t1\n");
+
+        // Dense output has every bin of both chromosomes
+        assert_eq!(cov.write_bedgraph(&path, &names, true).unwrap(), 5);
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().last(), Some("chr2\t50\t100\t0"));
+        assert!(cov.write_bedgraph(&path, &names[..1], true).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn per_chrom_aggregates_follow_chromosome_order() {
+        let mut cov = Coverage::from_lengths(50, vec![120, 100, 80]);
+        cov.increment_by(1, 110, 2);
+        cov.increment(1, 10);
+        cov.increment_by(2, 60, 5);
+        assert_eq!(cov.per_chrom_total_contacts(), vec![3, 5, 0]);
+        assert_eq!(cov.per_chrom_non_zero_bins(), vec![2, 1, 0]);
+        assert_eq!(cov.per_chrom_max_bin_depth(), vec![2, 5, 0]);
+    }
+
+    #[test]
+    fn predicts_dense_bin_memory() {
+        // hg19 at 50 bp: sum(ceil(len / 50)) u32 bins
+        let hg19 = crate::utils::get_default_genome_lengths();
+        let bytes = Coverage::predicted_bytes(50, &hg19);
+        assert_eq!(bytes, 247_595_624);
+        assert_eq!(Coverage::predicted_bytes(1000, &[1000, 999]), 8);
+
+        let err = Coverage::try_from_lengths(50, hg19.clone(), Some(100 << 20)).err().unwrap();
+        assert!(err.to_string().contains("--bin-width"), "{}", err);
+        assert!(Coverage::try_from_lengths(50, hg19, None).is_ok());
+    }
+
+    #[test]
+    fn flat_bins_follow_chromosome_offsets() {
+        let mut cov = Coverage::from_lengths(100, vec![250, 99, 300]);
+        assert_eq!(cov.offsets, vec![0, 3, 4, 7]);
+        assert_eq!(cov.bins.len(), 7);
+
+        let pairs = [
+            Pair { chr1: 1, pos1: 249, chr2: 3, pos2: 0, weight: 1 },
+            Pair { chr1: 2, pos1: 50, chr2: 3, pos2: 299, weight: 1 },
+            // Past the end of chr1, chromosome 0 and an unknown chromosome are dropped
+            Pair { chr1: 1, pos1: 251, chr2: 0, pos2: 10, weight: 1 },
+            Pair { chr1: 4, pos1: 10, chr2: 3, pos2: 299, weight: 1 },
+        ];
+        cov.add_pairs(&pairs, 1);
+        let mut serial = Coverage::from_lengths(100, vec![250, 99, 300]);
+        for p in &pairs { serial.add_pair(p); }
+        assert_eq!(cov.bins, serial.bins);
+
+        assert_eq!(cov.chr_bins(0), &[0, 0, 1]);
+        assert_eq!(cov.chr_bins(1), &[1]);
+        assert_eq!(cov.chr_bins(2), &[1, 0, 2]);
+        assert_eq!((cov.get_total_contacts(), cov.get_non_zero_bins()), (5, 4));
+        assert_eq!(cov.get_counts(200), vec![vec![0, 1], vec![1], vec![1, 2]]);
+    }
+
+    #[test]
+    fn last_bin_is_partial_only_when_the_length_is_not_a_multiple() {
+        // 1000 bp at 100 bp is exactly 10 bins, 1001 bp adds a 1 bp bin
+        let mut cov = Coverage::from_lengths(100, vec![1000, 1001]);
+        assert_eq!(cov.offsets, vec![0, 10, 21]);
+        for (chr, pos) in [(1, 0), (1, 999), (1, 1000), (1, 1001), (2, 999), (2, 1000), (2, 1001), (2, 1002)] {
+            cov.increment(chr, pos);
+        }
+        // pos == len is the last (1-based) base and lands in the last bin; len + 1 is past the end
+        assert_eq!(cov.chr_bins(0)[9], 2);
+        assert_eq!(cov.chr_bins(1)[9..], [1, 2]);
+        assert_eq!(cov.per_chrom_total_contacts(), vec![3, 3]);
+
+        // Every base bin lands in exactly one 200 bp bin, and the partial one counts
+        assert_eq!(cov.get_counts(200).iter().map(Vec::len).collect::<Vec<_>>(), vec![5, 6]);
+        assert_eq!(cov.count_good_bins(200, 1), 4);
+        assert_eq!(cov.count_good_bins(100_000, 1), 2);
+        assert_eq!(cov.count_good_bins_for_chr(0, 200, 0), CoverageFraction { covered: 5, total: 5 });
+        assert_eq!(cov.count_good_bins_for_chr(1, 200, 0), CoverageFraction { covered: 6, total: 6 });
+    }
+
+    #[test]
+    fn loads_coverage_saved_with_a_trailing_empty_bin() {
+        // The old layout stored len / width + 1 bins: 11 for 1000 bp, 11 for 1001 bp
+        let path = std::env::temp_dir().join(format!("hickit-cov-legacy-{}.bin", std::process::id()));
+        let mut bytes = COVERAGE_MAGIC.to_vec();
+        bytes.extend(100u32.to_le_bytes());
+        bytes.extend(2u64.to_le_bytes());
+        bytes.extend(1000u32.to_le_bytes());
+        bytes.extend(1001u32.to_le_bytes());
+        bytes.extend(22u64.to_le_bytes());
+        for bin in 0..22u32 {
+            bytes.extend(if bin == 10 { 0 } else { bin + 1 }.to_le_bytes());
+        }
+        std::fs::write(&path, &bytes).unwrap();
+        let cov = Coverage::load(&path).unwrap();
+        assert_eq!(cov.chr_bins(0), (1..=10).collect::<Vec<u32>>().as_slice());
+        assert_eq!(cov.chr_bins(1), (12..=22).collect::<Vec<u32>>().as_slice());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn weighted_pairs_add_their_weight_to_both_ends() {
+        let pairs = [
+            Pair { chr1: 1, pos1: 10, chr2: 1, pos2: 150, weight: 3 },
+            Pair { chr1: 1, pos1: 20, chr2: 2, pos2: 50, weight: 1 },
+            Pair { chr1: 2, pos1: 60, chr2: 2, pos2: 70, weight: 0 },
+        ];
+        let mut cov = Coverage::from_lengths(100, vec![200, 100]);
+        cov.add_pairs(&pairs, 1);
+        let mut serial = Coverage::from_lengths(100, vec![200, 100]);
+        for p in &pairs { serial.add_pair(p); }
+        assert_eq!(cov.bins, serial.bins);
+        assert_eq!(cov.bins, vec![4, 3, 1]);
+
+        // An explicit weight overrides the pair's own; increment_by saturates
+        serial.add_pair_weighted(&pairs[0], 2);
+        assert_eq!(serial.chr_bins(0), &[6, 5]);
+        serial.increment_by(2, 10, u32::MAX);
+        assert_eq!(serial.chr_bins(1), &[u32::MAX]);
+    }
+
+    #[test]
+    fn contact_scope_selects_cis_or_trans_pairs() {
+        let pairs = [
+            Pair { chr1: 1, pos1: 10, chr2: 1, pos2: 150, weight: 1 },
+            Pair { chr1: 2, pos1: 20, chr2: 2, pos2: 30, weight: 1 },
+            Pair { chr1: 1, pos1: 120, chr2: 2, pos2: 80, weight: 1 },
+        ];
+        let bins_for = |scope: ContactScope| {
+            let kept: Vec<Pair> = pairs.iter().filter(|p| scope.keeps(p)).cloned().collect();
+            let mut cov = Coverage::from_lengths(100, vec![200, 100]);
+            cov.add_pairs(&kept, 1);
+            cov.bins
+        };
+        assert_eq!(bins_for(ContactScope::All), vec![1, 2, 3]);
+        assert_eq!(bins_for(ContactScope::Cis), vec![1, 1, 2]);
+        assert_eq!(bins_for(ContactScope::Trans), vec![0, 1, 1]);
+    }
+
+    #[test]
+    fn top_bins_reports_highest_counts_at_bin_size() {
+        let mut cov = Coverage::from_lengths(50, vec![500, 300]);
+        // chr1: bin 0 (0-99) gets 3, bin 2 (200-299) gets 5; chr2: bin 1 (100-199) gets 4
+        for pos in [10, 60, 70] { cov.increment(1, pos); }
+        for _ in 0..5 { cov.increment(1, 250); }
+        for _ in 0..4 { cov.increment(2, 120); }
+
+        let top = cov.top_bins(2, 100);
+        assert_eq!(
+            top,
+            vec![
+                BinCount { chr_idx: 0, bin: 2, count: 5 },
+                BinCount { chr_idx: 1, bin: 1, count: 4 },
+            ]
+        );
+        assert_eq!(cov.top_bins(10, 100).len(), 3);
+        assert!(cov.top_bins(0, 100).is_empty());
     }
 }
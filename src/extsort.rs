@@ -0,0 +1,278 @@
+//! Spill-to-disk sort for slice records that may not fit in memory.
+//!
+//! Records are buffered up to `run_records`, sorted and written as a run file
+//! (raw slice record layout) into a private directory under `tmpdir`. Runs are
+//! then k-way merged with a binary heap. The private directory is removed when
+//! the sorter or its merged iterator is dropped, so temp files are cleaned up
+//! on success and on error alike.
+//!
+//! `straw dump --sorted --global` is the only user; slice merging and bin-pair
+//! aggregation, which would also need it, are not part of the crate yet.
+use anyhow::{Context, Result};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::slice::SliceRecord;
+
+/// Default number of records per in-memory run (16 bytes each, ~128 MB).
+pub const DEFAULT_RUN_RECORDS: usize = 8_000_000;
+
+/// Owns the private temp directory and deletes it on drop.
+#[derive(Debug)]
+struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    fn new(parent: &Path) -> Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        let path = parent.join(format!("hickit-sort-{}-{}", std::process::id(), nanos));
+        fs::create_dir_all(&path).with_context(|| format!("Create temp dir {:?}", path))?;
+        Ok(TempDir { path })
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+pub struct ExternalSorter {
+    run_records: usize,
+    tmpdir: PathBuf,
+    dir: Option<TempDir>,
+    buf: Vec<SliceRecord>,
+    runs: Vec<PathBuf>,
+}
+
+impl ExternalSorter {
+    /// Create a sorter spilling runs of `run_records` records into `tmpdir`
+    /// (the system temp directory when `None`).
+    pub fn new(tmpdir: Option<&Path>, run_records: usize) -> Self {
+        let run_records = run_records.max(1);
+        ExternalSorter {
+            run_records,
+            tmpdir: tmpdir.map(Path::to_path_buf).unwrap_or_else(std::env::temp_dir),
+            dir: None,
+            buf: Vec::with_capacity(run_records.min(DEFAULT_RUN_RECORDS)),
+            runs: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, rec: SliceRecord) -> Result<()> {
+        self.buf.push(rec);
+        if self.buf.len() >= self.run_records {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Number of run files written so far.
+    pub fn runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// Path of the private temp directory, once the first run was spilled.
+    pub fn temp_dir(&self) -> Option<&Path> {
+        self.dir.as_ref().map(|d| d.path.as_path())
+    }
+
+    fn spill(&mut self) -> Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        if self.dir.is_none() {
+            self.dir = Some(TempDir::new(&self.tmpdir)?);
+        }
+        let dir = &self.dir.as_ref().expect("temp dir created above").path;
+        let path = dir.join(format!("run{:06}.bin", self.runs.len()));
+        self.buf.sort_unstable_by_key(|r| r.sort_key());
+        let file = File::create(&path).with_context(|| format!("Create sort run {:?}", path))?;
+        let mut w = BufWriter::with_capacity(256 * 1024, file);
+        for rec in &self.buf {
+            rec.write_to(&mut w)?;
+        }
+        w.flush()?;
+        self.buf.clear();
+        self.runs.push(path);
+        Ok(())
+    }
+
+    /// Finish input and return the records in sorted order.
+    pub fn finish(mut self) -> Result<SortedRecords> {
+        if self.runs.is_empty() {
+            // Everything fit in one run: no disk round-trip
+            let mut buf = std::mem::take(&mut self.buf);
+            buf.sort_unstable_by_key(|r| r.sort_key());
+            return Ok(SortedRecords { inner: Merge::Memory(buf.into_iter()), _dir: None });
+        }
+        self.spill()?;
+        let mut readers = Vec::with_capacity(self.runs.len());
+        let mut heap = BinaryHeap::with_capacity(self.runs.len());
+        for (i, path) in self.runs.iter().enumerate() {
+            let file = File::open(path).with_context(|| format!("Open sort run {:?}", path))?;
+            let mut r = BufReader::with_capacity(64 * 1024, file);
+            if let Some(rec) = SliceRecord::read_from(&mut r)? {
+                heap.push(Reverse(HeapItem { rec, run: i }));
+            }
+            readers.push(r);
+        }
+        Ok(SortedRecords {
+            inner: Merge::Runs { readers, heap, failed: None },
+            _dir: self.dir.take(),
+        })
+    }
+}
+
+struct HeapItem {
+    rec: SliceRecord,
+    run: usize,
+}
+
+impl PartialEq for HeapItem {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+impl Eq for HeapItem {}
+impl PartialOrd for HeapItem {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for HeapItem {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // Tie-break on run index so equal keys keep run order
+        (self.rec.sort_key(), self.run).cmp(&(other.rec.sort_key(), other.run))
+    }
+}
+
+enum Merge {
+    Memory(std::vec::IntoIter<SliceRecord>),
+    Runs {
+        readers: Vec<BufReader<File>>,
+        heap: BinaryHeap<Reverse<HeapItem>>,
+        // Read error of a run, returned after the record popped before it
+        failed: Option<anyhow::Error>,
+    },
+}
+
+/// Sorted output of an [`ExternalSorter`]; removes the run files when dropped.
+pub struct SortedRecords {
+    inner: Merge,
+    _dir: Option<TempDir>,
+}
+
+impl Iterator for SortedRecords {
+    type Item = Result<SliceRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.inner {
+            Merge::Memory(it) => it.next().map(Ok),
+            Merge::Runs { readers, heap, failed } => {
+                // A failed run ends the merge: the error, then nothing
+                if let Some(e) = failed.take() {
+                    heap.clear();
+                    return Some(Err(e));
+                }
+                let Reverse(HeapItem { rec, run }) = heap.pop()?;
+                match SliceRecord::read_from(&mut readers[run]) {
+                    Ok(Some(next)) => heap.push(Reverse(HeapItem { rec: next, run })),
+                    Ok(None) => {}
+                    Err(e) => *failed = Some(anyhow::Error::new(e).context(format!("Read sort run {}", run))),
+                }
+                Some(Ok(rec))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slice::RECORD_BYTES;
+
+    fn rec(key1: i16, bin_x: i32, key2: i16, bin_y: i32, counts: f32) -> SliceRecord {
+        SliceRecord { key1, bin_x, key2, bin_y, counts }
+    }
+
+    #[test]
+    fn tiny_runs_force_multiway_merge_and_cleanup() {
+        let mut input = Vec::new();
+        // Deterministic shuffle over a small key space with duplicates
+        let mut x: u32 = 12345;
+        for i in 0..200 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let k1 = (x >> 8) as i16 % 3;
+            let k2 = (x >> 12) as i16 % 3;
+            input.push(rec(k1, ((x >> 16) % 50) as i32, k2, ((x >> 4) % 50) as i32, i as f32));
+        }
+
+        let mut sorter = ExternalSorter::new(None, 7);
+        for r in &input {
+            sorter.push(*r).unwrap();
+        }
+        assert!(sorter.runs() > 20, "expected many spilled runs");
+        let dir = sorter.temp_dir().unwrap().to_path_buf();
+        assert!(dir.exists());
+
+        let out: Vec<SliceRecord> = sorter.finish().unwrap().map(|r| r.unwrap()).collect();
+        assert_eq!(out.len(), input.len());
+        assert!(out.windows(2).all(|w| w[0].sort_key() <= w[1].sort_key()));
+        let mut expected = input.clone();
+        expected.sort_by_key(|r| r.sort_key());
+        let keys = |v: &[SliceRecord]| v.iter().map(|r| r.sort_key()).collect::<Vec<_>>();
+        assert_eq!(keys(&out), keys(&expected));
+
+        // Iterator dropped above: run files are gone
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn dropping_sorter_early_removes_runs() {
+        let mut sorter = ExternalSorter::new(None, 2);
+        for i in 0..5 {
+            sorter.push(rec(0, 5 - i, 0, 0, 1.0)).unwrap();
+        }
+        let dir = sorter.temp_dir().unwrap().to_path_buf();
+        drop(sorter); // e.g. an error aborted the dump
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn a_failed_run_read_keeps_the_popped_record_then_stops() {
+        let mut sorter = ExternalSorter::new(None, 2);
+        for bin in [0, 1, 2, 3] {
+            sorter.push(rec(0, bin, 0, 0, 1.0)).unwrap();
+        }
+        // Cut the first run (bins 0 and 1) inside its second record
+        let run0 = sorter.temp_dir().unwrap().join("run000000.bin");
+        let bytes = fs::read(&run0).unwrap();
+        fs::write(&run0, &bytes[..RECORD_BYTES + 5]).unwrap();
+
+        let mut out = sorter.finish().unwrap();
+        assert_eq!(out.next().unwrap().unwrap().bin_x, 0);
+        let err = format!("{:#}", out.next().unwrap().unwrap_err());
+        assert!(err.contains("Read sort run 0") && err.contains("truncated"), "{}", err);
+        assert!(out.next().is_none());
+    }
+
+    #[test]
+    fn small_input_sorts_in_memory() {
+        let mut sorter = ExternalSorter::new(None, 100);
+        sorter.push(rec(1, 0, 1, 0, 1.0)).unwrap();
+        sorter.push(rec(0, 3, 0, 4, 2.0)).unwrap();
+        assert_eq!(sorter.runs(), 0);
+        let out: Vec<SliceRecord> = sorter.finish().unwrap().map(|r| r.unwrap()).collect();
+        assert_eq!(out[0].key1, 0);
+        assert_eq!(out[1].key1, 1);
+    }
+}
@@ -1,9 +1,16 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Read, Write};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
 use std::path::Path;
 
+use rustc_hash::FxHashMap;
+
+use crate::parser::{InputCompression, ZstdDecoder};
+use crate::utils::{ChrCode, Pair};
+
 #[derive(Debug, Clone, Copy)]
 pub struct Region<'a> {
     pub chrom: &'a str,
@@ -29,6 +36,57 @@ impl<'a> Region<'a> {
         let (start, end) = parse_span(se)?;
         Ok(Region { chrom, start, end })
     }
+
+    /// Whether `pos` lies in the span (both ends inclusive, as `filter` matches).
+    #[inline]
+    pub fn contains(&self, pos: u32) -> bool {
+        pos >= self.start && pos <= self.end
+    }
+}
+
+/// Chromosome code given to pair ends outside a [`RegionScope`]; no coverage
+/// has this many chromosomes, so those ends are never counted.
+pub const OUTSIDE_REGION: ChrCode = ChrCode::MAX;
+
+/// A region resolved to its chromosome code, mapping pairs onto a coverage
+/// that holds only the region as a single chromosome (code 1, position 0 at
+/// the region start).
+#[derive(Debug, Clone, Copy)]
+pub struct RegionScope {
+    pub chr: ChrCode,
+    pub start: u32,
+    pub end: u32,
+}
+
+impl RegionScope {
+    pub fn new(region: &Region, chr: ChrCode) -> Self {
+        RegionScope { chr, start: region.start, end: region.end }
+    }
+
+    pub fn span_len(&self) -> u32 {
+        self.end - self.start + 1
+    }
+
+    /// The pair in region coordinates, or `None` when neither end is inside.
+    /// Ends outside the region get [`OUTSIDE_REGION`].
+    #[inline]
+    pub fn scope_pair(&self, p: &Pair) -> Option<Pair> {
+        let end = |chr: ChrCode, pos: u32| {
+            if chr == self.chr && pos >= self.start && pos <= self.end {
+                Some((1, pos - self.start))
+            } else {
+                None
+            }
+        };
+        match (end(p.chr1, p.pos1), end(p.chr2, p.pos2)) {
+            (None, None) => None,
+            (a, b) => {
+                let (chr1, pos1) = a.unwrap_or((OUTSIDE_REGION, 0));
+                let (chr2, pos2) = b.unwrap_or((OUTSIDE_REGION, 0));
+                Some(Pair { chr1, pos1, chr2, pos2, weight: p.weight })
+            }
+        }
+    }
 }
 
 fn parse_span(se: &str) -> Result<U32Pair> {
@@ -53,23 +111,120 @@ fn parse_span(se: &str) -> Result<U32Pair> {
 
 type U32Pair = (u32, u32);
 
-pub struct FilterOptions<'a> {
-    pub region: Region<'a>,
+/// Inclusive intervals per chromosome, sorted and merged so that a position
+/// lookup is one binary search.
+#[derive(Debug, Clone, Default)]
+pub struct RegionSet {
+    by_chrom: FxHashMap<String, Vec<U32Pair>>,
+}
+
+impl RegionSet {
+    /// Build from inclusive `(chrom, start, end)` intervals; overlapping and
+    /// adjacent intervals of a chromosome are merged.
+    pub fn from_intervals<S: Into<String>>(intervals: impl IntoIterator<Item = (S, u32, u32)>) -> Self {
+        let mut by_chrom: FxHashMap<String, Vec<U32Pair>> = FxHashMap::default();
+        for (chrom, start, end) in intervals {
+            by_chrom.entry(chrom.into()).or_default().push((start, end));
+        }
+        for spans in by_chrom.values_mut() {
+            spans.sort_unstable();
+            let mut merged: Vec<U32Pair> = Vec::with_capacity(spans.len());
+            for &(start, end) in spans.iter() {
+                match merged.last_mut() {
+                    Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+                    _ => merged.push((start, end)),
+                }
+            }
+            *spans = merged;
+        }
+        RegionSet { by_chrom }
+    }
+
+    /// Number of intervals left after merging.
+    pub fn len(&self) -> usize {
+        self.by_chrom.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_chrom.is_empty()
+    }
+
+    /// Merged intervals of `chrom`, sorted by start.
+    pub fn intervals(&self, chrom: &str) -> &[U32Pair] {
+        self.by_chrom.get(chrom).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Whether `pos` on `chrom` falls in any interval (ends inclusive).
+    #[inline]
+    pub fn contains(&self, chrom: &str, pos: u32) -> bool {
+        let spans = self.intervals(chrom);
+        // First interval ending at or after pos; merged intervals are disjoint
+        let i = spans.partition_point(|&(_, end)| end < pos);
+        spans.get(i).is_some_and(|&(start, _)| start <= pos)
+    }
+}
+
+impl From<Region<'_>> for RegionSet {
+    fn from(region: Region<'_>) -> Self {
+        RegionSet::from_intervals([(region.chrom, region.start, region.end)])
+    }
+}
+
+/// Load `chrom\tstart\tend` intervals from a BED file. BED starts are 0-based
+/// and ends exclusive, so `chr1 99 200` keeps the same positions as
+/// `chr1:100-200`. Blank, `#`, `track` and `browser` lines are skipped; extra
+/// columns are ignored.
+pub fn load_regions_bed(path: &Path) -> Result<RegionSet> {
+    let file = File::open(path).with_context(|| format!("Open BED {:?}", path))?;
+    let mut intervals = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.with_context(|| format!("Read BED {:?}", path))?;
+        let t = line.trim();
+        if t.is_empty() || t.starts_with('#') || t.starts_with("track") || t.starts_with("browser") {
+            continue;
+        }
+        let mut f = t.split_ascii_whitespace();
+        let parsed = match (f.next(), f.next(), f.next()) {
+            (Some(chrom), Some(start), Some(end)) => start.parse::<u32>().ok().zip(end.parse::<u32>().ok()).map(|se| (chrom, se)),
+            _ => None,
+        };
+        let (chrom, (start, end)) =
+            parsed.ok_or_else(|| anyhow!("BED {:?} line {}: expected CHROM START END, got {:?}", path, i + 1, t))?;
+        if start >= end {
+            return Err(anyhow!("BED {:?} line {}: empty interval {}-{}", path, i + 1, start, end));
+        }
+        intervals.push((chrom.to_string(), start + 1, end));
+    }
+    Ok(RegionSet::from_intervals(intervals))
+}
+
+pub struct FilterOptions {
+    pub regions: RegionSet,
     pub require_unique: bool,
+    /// Mapping quality both ends need under `require_unique`
+    pub min_mapq: u32,
 }
 
-/// Filter a merged_nodups(.gz) stream, emitting lines where either end overlaps the region.
+/// Filter a merged_nodups(.gz) stream, emitting lines where either end overlaps any region.
 pub fn filter_merged_nodups_stream<R: Read, W: Write>(
+    reader: R,
+    opts: &FilterOptions,
+    out: W,
+) -> Result<()> {
+    filter_merged_nodups_split(reader, opts, out, None::<io::Sink>)
+}
+
+/// Like [`filter_merged_nodups_stream`], additionally writing every non-blank
+/// line that did not match (outside the region or failing the unique filter)
+/// to `rejected`, so one pass partitions the input.
+pub fn filter_merged_nodups_split<R: Read, W: Write, X: Write>(
     reader: R,
     opts: &FilterOptions,
     mut out: W,
+    mut rejected: Option<X>,
 ) -> Result<()> {
     let mut buf_reader = BufReader::with_capacity(256 * 1024, reader);
     let mut line = String::with_capacity(1024);
-    let chrom = opts.region.chrom;
-    let start = opts.region.start;
-    let end = opts.region.end;
-    let require_unique = opts.require_unique;
 
     loop {
         line.clear();
@@ -77,16 +232,21 @@ pub fn filter_merged_nodups_stream<R: Read, W: Write>(
         if n == 0 { break; }
         if line.trim().is_empty() { continue; }
 
-        if line_matches_region(&line, chrom, start, end, require_unique) {
+        if line_matches_any(&line, opts) {
             out.write_all(line.as_bytes())?;
+        } else if let Some(rej) = rejected.as_mut() {
+            rej.write_all(line.as_bytes())?;
         }
     }
     out.flush()?;
+    if let Some(rej) = rejected.as_mut() {
+        rej.flush()?;
+    }
     Ok(())
 }
 
 #[inline]
-fn line_matches_region(line: &str, chrom: &str, start: u32, end: u32, require_unique: bool) -> bool {
+fn line_matches_any(line: &str, opts: &FilterOptions) -> bool {
     // Fast field scanner similar to parser::parse_line_juicer
     let b = line.as_bytes();
     let mut i = 0usize;
@@ -134,15 +294,15 @@ fn line_matches_region(line: &str, chrom: &str, start: u32, end: u32, require_un
     let (s5, e5) = match f5 { Some(v) => v, None => return false };
     let (s6, e6) = match f6 { Some(v) => v, None => return false };
 
-    if require_unique {
-        // Apply same early filter as main parser: frag1 != frag2 and mapq1>0 && mapq2>0
+    if opts.require_unique {
+        // Apply same early filter as main parser: frag1 != frag2 and both mapqs >= min_mapq
         let ok = match (f3, f7, f8) {
             (Some((fs, fe)), Some((gs, ge)), Some((ms, me))) => {
                 let frag1 = crate::utils::parse_u32_fast(&b[fs..fe]).unwrap_or(0);
                 let frag2 = crate::utils::parse_u32_fast(&b[gs..ge]).unwrap_or(0);
                 let mapq1 = crate::utils::parse_u32_fast(&b[ms..me]).unwrap_or(0);
                 let mapq2 = if let Some((qs, qe)) = f11 { crate::utils::parse_u32_fast(&b[qs..qe]).unwrap_or(0) } else { 0 };
-                mapq1 > 0 && mapq2 > 0 && frag1 != frag2
+                mapq1 >= opts.min_mapq && mapq2 >= opts.min_mapq && frag1 != frag2
             }
             _ => false,
         };
@@ -155,31 +315,155 @@ fn line_matches_region(line: &str, chrom: &str, start: u32, end: u32, require_un
     let pos1 = crate::utils::parse_u32_fast(&b[s2..e2]).unwrap_or(u32::MAX);
     let pos2 = crate::utils::parse_u32_fast(&b[s6..e6]).unwrap_or(u32::MAX);
 
-    (chr1 == chrom && pos1 >= start && pos1 <= end)
-        || (chr2 == chrom && pos2 >= start && pos2 <= end)
+    opts.regions.contains(chr1, pos1) || opts.regions.contains(chr2, pos2)
 }
 
-pub fn run_filter_file(input: Option<&Path>, region: Region<'_>, require_unique: bool) -> Result<()> {
-    let opts = FilterOptions { region, require_unique };
-    let stdout = io::stdout();
-    let handle = stdout.lock();
-    match input {
-        Some(path) => {
-            if path.as_os_str() == "-" { 
-                let stdin = io::stdin();
-                let lock = stdin.lock();
-                return filter_merged_nodups_stream(lock, &opts, handle);
-            }
-            let is_gz = path.extension().and_then(|e| e.to_str()).map(|e| e.eq_ignore_ascii_case("gz")).unwrap_or(false);
-            let file = File::open(path)?;
-            if is_gz { filter_merged_nodups_stream(MultiGzDecoder::new(file), &opts, handle) }
-            else { filter_merged_nodups_stream(file, &opts, handle) }
-        }
-        None => {
-            // stdin (assume plain text)
+/// Filter `input` (`-` for stdin, read as plain text) to stdout, and the
+/// non-matching lines to `rejected` when given.
+pub fn run_filter_file(
+    input: &Path,
+    regions: RegionSet,
+    require_unique: bool,
+    min_mapq: u32,
+    rejected: Option<&Path>,
+) -> Result<()> {
+    let opts = FilterOptions { regions, require_unique, min_mapq };
+    let run = |rejected: Option<&mut dyn Write>| -> Result<()> {
+        let stdout = io::stdout();
+        let handle = stdout.lock();
+        if input.as_os_str() == "-" {
             let stdin = io::stdin();
             let lock = stdin.lock();
-            filter_merged_nodups_stream(lock, &opts, handle)
+            return filter_merged_nodups_split(lock, &opts, handle, rejected);
+        }
+        let file = File::open(input)?;
+        match InputCompression::from_path(input) {
+            InputCompression::Gzip => filter_merged_nodups_split(MultiGzDecoder::new(file), &opts, handle, rejected),
+            InputCompression::Zstd => filter_merged_nodups_split(ZstdDecoder::new(file)?, &opts, handle, rejected),
+            InputCompression::Plain => filter_merged_nodups_split(file, &opts, handle, rejected),
         }
+    };
+    match rejected {
+        None => run(None),
+        Some(path) => {
+            let file = BufWriter::new(File::create(path).map_err(|e| anyhow!("Create {:?}: {}", path, e))?);
+            if is_gz(path) {
+                let mut enc = GzEncoder::new(file, Compression::default());
+                run(Some(&mut enc))?;
+                enc.finish()?.flush()?;
+                Ok(())
+            } else {
+                let mut file = file;
+                run(Some(&mut file))
+            }
+        }
+    }
+}
+
+fn is_gz(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).map(|e| e.eq_ignore_ascii_case("gz")).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matched_and_rejected_partition_the_input() {
+        let input = "0 chr1 150 1 16 chr2 60 2 60 50M SEQ 60 50M SEQ r0 r0\n\
+                     \n\
+                     0 chr2 10 1 16 chr2 90 2 60 50M SEQ 60 50M SEQ r1 r1\n\
+                     0 chr3 10 1 16 chr1 120 2 60 50M SEQ 60 50M SEQ r2 r2\n\
+                     0 chr1 130 4 16 chr1 140 4 60 50M SEQ 60 50M SEQ r3 r3\n\
+                     0 chr1 100 1 16 chr1 190 2 0 50M SEQ 60 50M SEQ r4 r4\n";
+        let mut opts = FilterOptions {
+            regions: Region::parse("chr1:100-200", None).unwrap().into(),
+            require_unique: true,
+            min_mapq: crate::parser::DEFAULT_MIN_MAPQ,
+        };
+        let (mut matched, mut rejected) = (Vec::new(), Vec::new());
+        filter_merged_nodups_split(input.as_bytes(), &opts, &mut matched, Some(&mut rejected)).unwrap();
+        let matched = String::from_utf8(matched).unwrap();
+        let rejected = String::from_utf8(rejected).unwrap();
+
+        let ids = |text: &str| text.lines().map(|l| l.split(' ').nth(14).unwrap().to_string()).collect::<Vec<_>>();
+        assert_eq!(ids(&matched), vec!["r0", "r2"]);
+        // r1 is outside the region; r3 (same fragment) and r4 (mapq 0) fail the unique filter
+        assert_eq!(ids(&rejected), vec!["r1", "r3", "r4"]);
+
+        let mut union: Vec<&str> = matched.lines().chain(rejected.lines()).collect();
+        let mut expected: Vec<&str> = input.lines().filter(|l| !l.trim().is_empty()).collect();
+        union.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(union, expected);
+
+        // r0 and r2 have mapq 60 on both ends
+        opts.min_mapq = 60;
+        let mut matched = Vec::new();
+        filter_merged_nodups_stream(input.as_bytes(), &opts, &mut matched).unwrap();
+        assert_eq!(ids(&String::from_utf8(matched).unwrap()), vec!["r0", "r2"]);
+        opts.min_mapq = 61;
+        let mut matched = Vec::new();
+        filter_merged_nodups_stream(input.as_bytes(), &opts, &mut matched).unwrap();
+        assert!(matched.is_empty());
+    }
+
+    #[test]
+    fn bed_intervals_merge_and_match_either_end() {
+        let dir = std::env::temp_dir().join(format!("hickit-bed-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let bed = dir.join("panel.bed");
+        std::fs::write(
+            &bed,
+            "track name=panel\n\
+             chr1\t99\t200\tgeneA\n\
+             chr1\t150\t300\n\
+             chr1\t300\t400\n\
+             # comment\n\
+             chr2\t999\t1000\n\
+             chr1\t1000\t1100\n",
+        )
+        .unwrap();
+        let regions = load_regions_bed(&bed).unwrap();
+        // Overlapping (99-200, 150-300) and adjacent (..300, 300-400) intervals merge
+        assert_eq!(regions.intervals("chr1"), &[(100, 400), (1001, 1100)]);
+        assert_eq!(regions.len(), 3);
+        assert!(regions.contains("chr1", 100) && regions.contains("chr1", 400));
+        assert!(!regions.contains("chr1", 99) && !regions.contains("chr1", 401) && !regions.contains("chr1", 1000));
+        assert!(regions.contains("chr2", 1000) && !regions.contains("chr2", 999));
+        assert!(!regions.contains("chr3", 150));
+
+        let input = "0 chr3 10 1 16 chr2 1000 2 60 50M SEQ 60 50M SEQ r0 r0\n\
+                     0 chr1 500 1 16 chr1 900 2 60 50M SEQ 60 50M SEQ r1 r1\n\
+                     0 chr1 1050 1 16 chr3 10 2 60 50M SEQ 60 50M SEQ r2 r2\n";
+        let opts = FilterOptions { regions, require_unique: true, min_mapq: crate::parser::DEFAULT_MIN_MAPQ };
+        let mut out = Vec::new();
+        filter_merged_nodups_stream(input.as_bytes(), &opts, &mut out).unwrap();
+        let ids: Vec<String> = String::from_utf8(out).unwrap().lines().map(|l| l.split(' ').nth(14).unwrap().to_string()).collect();
+        assert_eq!(ids, vec!["r0", "r2"]);
+
+        std::fs::write(&bed, "chr1\t200\t100\n").unwrap();
+        assert!(load_regions_bed(&bed).unwrap_err().to_string().contains("line 1"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn region_scope_keeps_pairs_with_an_end_inside() {
+        let scope = RegionScope::new(&Region::parse("chr2:1,000-1,999", None).unwrap(), 2);
+        assert_eq!(scope.span_len(), 1000);
+        let pair = |chr1, pos1, chr2, pos2| Pair { chr1, pos1, chr2, pos2, weight: 1 };
+
+        let both = scope.scope_pair(&pair(2, 1000, 2, 1999)).unwrap();
+        assert_eq!((both.chr1, both.pos1, both.chr2, both.pos2), (1, 0, 1, 999));
+        let one = scope.scope_pair(&pair(1, 1500, 2, 1500)).unwrap();
+        assert_eq!((one.chr1, one.chr2, one.pos2), (OUTSIDE_REGION, 1, 500));
+        assert!(scope.scope_pair(&pair(2, 999, 2, 2000)).is_none());
+        assert!(scope.scope_pair(&pair(1, 1500, 3, 1500)).is_none());
+
+        // Outside ends are dropped by the coverage, inside ends land in region bins
+        let mut cov = crate::coverage::Coverage::from_lengths(100, vec![scope.span_len()]);
+        cov.add_pair(&one);
+        assert_eq!(cov.get_total_contacts(), 1);
+        assert_eq!(cov.chr_bins(0)[5], 1);
     }
 }
@@ -4,3 +4,8 @@ pub mod resolution;
 pub mod utils;
 pub mod straw;
 pub mod filter;
+pub mod slice;
+pub mod bgzf;
+pub mod extsort;
+pub mod convert;
+pub mod pipeline;
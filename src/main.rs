@@ -4,6 +4,11 @@ pub mod resolution;
 pub mod utils;
 pub mod straw;
 pub mod filter;
+pub mod slice;
+pub mod bgzf;
+pub mod extsort;
+pub mod convert;
+pub mod pipeline;
 mod cli;
 
 use anyhow::Result;
@@ -1,21 +1,144 @@
-use crate::utils::{ChrLookup, Pair};
+use crate::utils::{ChrCode, ChrLookup, Pair};
 use anyhow::Result;
 use flate2::read::MultiGzDecoder;
+use std::collections::HashMap;
 use std::io::Read;
 use std::io::{BufRead, BufReader};
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::path::Path;
 
 #[derive(Clone, Copy)]
 enum ParseMode {
     Juicer,
+    JuicerShort,
+    Pairs,
+    HicPro,
+}
+
+/// Text layout of a pairs input, as chosen with `--format`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum InputFormat {
+    /// Detect the layout from the first lines of the input
+    #[default]
+    Auto,
+    /// Juicer merged_nodups
+    Juicer,
+    /// Juicer "short" (`str1 chr1 pos1 frag1 str2 chr2 pos2 frag2`), with an
+    /// optional trailing score
+    JuicerShort,
+    /// 4DN .pairs
     Pairs,
+    /// HiC-Pro .validPairs / .allValidPairs
+    Hicpro,
+}
+
+/// Compression of an input file, chosen by its extension (`.gz`, `.zst`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputCompression {
+    Plain,
+    Gzip,
+    Zstd,
+}
+
+impl InputCompression {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("gz") => InputCompression::Gzip,
+            Some(e) if e.eq_ignore_ascii_case("zst") => InputCompression::Zstd,
+            _ => InputCompression::Plain,
+        }
+    }
+
+    /// Wrap `reader` in the matching decoder, for callers that do not need a
+    /// concrete reader type.
+    pub fn decoder<'a, R: Read + 'a>(self, reader: R) -> Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            InputCompression::Plain => Box::new(reader),
+            InputCompression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+            InputCompression::Zstd => Box::new(ZstdDecoder::new(reader)?),
+        })
+    }
+}
+
+/// Streaming zstd decoder over a raw input reader.
+pub type ZstdDecoder<R> = zstd::stream::read::Decoder<'static, BufReader<R>>;
+
+/// Data lines read by a [`PairIterator`] so far, and why the lines without a
+/// pair were dropped. `total_lines` is `parsed` plus the `rejected_*` counts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParseStats {
+    /// Lines read, not counting `#` header lines of .pairs/validPairs
+    pub total_lines: u64,
+    /// Lines that yielded a pair
+    pub parsed: u64,
+    /// A chromosome name missing from the chromosome sizes
+    pub rejected_chr: u64,
+    /// An end below the minimum mapq (a .pairs type other than `UU`)
+    pub rejected_mapq: u64,
+    /// Both ends on the same restriction fragment
+    pub rejected_frag: u64,
+    /// Too few columns, or a number that does not parse
+    pub rejected_fields: u64,
+    /// The most frequent names behind `rejected_chr` (at most
+    /// [`UNMAPPED_REPORTED`]) with the lines each rejected, most first. A
+    /// line counts under its first unknown name.
+    pub unmapped_chroms: Vec<(String, u64)>,
+}
+
+impl ParseStats {
+    pub fn rejected(&self) -> u64 {
+        self.rejected_chr + self.rejected_mapq + self.rejected_frag + self.rejected_fields
+    }
+
+    /// The most frequent unknown chromosome when its lines are more than
+    /// `fraction` of all lines read, e.g. a contig left out of the sizes file.
+    pub fn unmapped_over(&self, fraction: f64) -> Option<&(String, u64)> {
+        self.unmapped_chroms
+            .first()
+            .filter(|(_, lines)| *lines as f64 > fraction * self.total_lines as f64)
+    }
+
+    fn reject(&mut self, reason: Reject) {
+        match reason {
+            Reject::Chr { .. } => self.rejected_chr += 1,
+            Reject::Mapq => self.rejected_mapq += 1,
+            Reject::Frag => self.rejected_frag += 1,
+            Reject::Fields => self.rejected_fields += 1,
+        }
+    }
+}
+
+/// Why a line yielded no pair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reject {
+    /// The chromosome name at `line[start..end]` is not in the lookup
+    Chr { start: usize, end: usize },
+    Mapq,
+    Frag,
+    Fields,
 }
 
+/// Lines and pairs echoed to stderr by a verbose [`PairIterator`].
+const VERBOSE_LINES: u64 = 3;
+
+/// Unknown chromosome names kept in [`ParseStats::unmapped_chroms`].
+pub const UNMAPPED_REPORTED: usize = 10;
+
+/// Mapping quality both ends need by default: any unique mapping.
+pub const DEFAULT_MIN_MAPQ: u32 = 1;
+
 pub struct PairIterator<R: BufRead> {
     reader: R,
     chr_map: ChrLookup,
-    buffer: String,
+    buffer: Vec<u8>,
     mode: ParseMode,
+    // .pairs: 0-based column holding the pair weight, from a `#columns:` header
+    // that declares `count`
+    count_column: Option<usize>,
+    min_mapq: u32,
+    verbose: bool,
+    stats: ParseStats,
+    // Lines rejected per unknown chromosome name
+    unmapped: HashMap<Vec<u8>, u64>,
 }
 
 impl<R: BufRead> PairIterator<R> {
@@ -23,71 +146,96 @@ impl<R: BufRead> PairIterator<R> {
         Self {
             reader,
             chr_map,
-            buffer: String::with_capacity(1024),
+            buffer: Vec::with_capacity(1024),
             mode,
+            count_column: None,
+            min_mapq: DEFAULT_MIN_MAPQ,
+            verbose: false,
+            stats: ParseStats::default(),
+            unmapped: HashMap::new(),
         }
     }
+
+    /// Echo the first lines and parsed pairs to stderr, to debug a new input.
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Mapping quality both ends of a merged_nodups or validPairs line need
+    /// (e.g. 30 for Hi-C QC). .pairs keep requiring a `UU` pair type.
+    pub fn min_mapq(mut self, mapq: u32) -> Self {
+        self.min_mapq = mapq;
+        self
+    }
+
+    /// Counts so far; the totals once the iterator is exhausted.
+    pub fn stats(&self) -> ParseStats {
+        let mut unmapped: Vec<(String, u64)> =
+            self.unmapped.iter().map(|(name, &n)| (String::from_utf8_lossy(name).into_owned(), n)).collect();
+        unmapped.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        unmapped.truncate(UNMAPPED_REPORTED);
+        ParseStats { unmapped_chroms: unmapped, ..self.stats.clone() }
+    }
 }
 
 impl<R: BufRead> Iterator for PairIterator<R> {
     type Item = Result<Pair>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        static LINE_COUNT: AtomicU64 = AtomicU64::new(0);
-        static PARSED_COUNT: AtomicU64 = AtomicU64::new(0);
-        static DEBUG_SHOWN: AtomicBool = AtomicBool::new(false);
-
         loop {
             self.buffer.clear();
-            match self.reader.read_line(&mut self.buffer) {
-                Ok(0) => {
-                    if cfg!(debug_assertions) {
-                        let line_count = LINE_COUNT.load(Ordering::Relaxed);
-                        let parsed_count = PARSED_COUNT.load(Ordering::Relaxed);
-                        if line_count > 0 {
-                            eprintln!(
-                                "Debug: Processed {} lines, parsed {} pairs",
-                                line_count, parsed_count
-                            );
-                        }
-                    }
-                    return None; // EOF
-                }
+            // Raw bytes: no UTF-8 validation of lines in the hot loop
+            match self.reader.read_until(b'\n', &mut self.buffer) {
+                Ok(0) => return None, // EOF
                 Ok(_) => {
-                    if let ParseMode::Pairs = self.mode {
+                    if let ParseMode::Pairs | ParseMode::HicPro = self.mode {
                         // Skip header/comment lines
-                        if self.buffer.as_bytes().get(0) == Some(&b'#') {
+                        if self.buffer.first() == Some(&b'#') {
+                            if let Some(columns) = self.buffer.strip_prefix(b"#columns:") {
+                                self.count_column = columns
+                                    .split(|b| b.is_ascii_whitespace())
+                                    .filter(|c| !c.is_empty())
+                                    .position(|c| c == crate::convert::COUNT_COLUMN.as_bytes());
+                            }
                             continue;
                         }
                     }
-                    let line_count = if cfg!(debug_assertions) { LINE_COUNT.fetch_add(1, Ordering::Relaxed) + 1 } else { 0 };
-                    if cfg!(debug_assertions) {
-                        if !DEBUG_SHOWN.load(Ordering::Relaxed) && line_count <= 3 {
-                            eprintln!("Debug line {}: {}", line_count, self.buffer.trim());
-                        }
-                        if line_count == 3 {
-                            DEBUG_SHOWN.store(true, Ordering::Relaxed);
-                        }
+                    self.stats.total_lines += 1;
+                    if self.verbose && self.stats.total_lines <= VERBOSE_LINES {
+                        eprintln!("Line {}: {}", self.stats.total_lines, String::from_utf8_lossy(&self.buffer).trim());
                     }
 
                     let parsed = match self.mode {
-                        ParseMode::Juicer => parse_line_juicer(&self.buffer, &self.chr_map),
-                        ParseMode::Pairs => parse_line_pairs(&self.buffer, &self.chr_map),
+                        ParseMode::Juicer => parse_line_juicer(&self.buffer, &self.chr_map, self.min_mapq),
+                        ParseMode::JuicerShort => parse_line_juicer_short(&self.buffer, &self.chr_map),
+                        ParseMode::Pairs => parse_line_pairs(&self.buffer, &self.chr_map, self.count_column),
+                        ParseMode::HicPro => parse_line_hicpro(&self.buffer, &self.chr_map, self.min_mapq),
                     };
 
-                    if let Some(pair) = parsed {
-                        let parsed_count = if cfg!(debug_assertions) { PARSED_COUNT.fetch_add(1, Ordering::Relaxed) + 1 } else { 0 };
-                        if cfg!(debug_assertions) {
-                            if parsed_count <= 3 {
+                    match parsed {
+                        Ok(pair) => {
+                            self.stats.parsed += 1;
+                            if self.verbose && self.stats.parsed <= VERBOSE_LINES {
                                 eprintln!(
-                                    "Debug: Parsed pair {}: chr{}:{} - chr{}:{}",
-                                    parsed_count, pair.chr1, pair.pos1, pair.chr2, pair.pos2
+                                    "Parsed pair {}: chr{}:{} - chr{}:{}",
+                                    self.stats.parsed, pair.chr1, pair.pos1, pair.chr2, pair.pos2
                                 );
                             }
+                            return Some(Ok(pair));
+                        }
+                        // Rejected line, continue to next
+                        Err(reason) => {
+                            if let Reject::Chr { start, end } = reason {
+                                let name = &self.buffer[start..end];
+                                match self.unmapped.get_mut(name) {
+                                    Some(n) => *n += 1,
+                                    None => { self.unmapped.insert(name.to_vec(), 1); }
+                                }
+                            }
+                            self.stats.reject(reason)
                         }
-                        return Some(Ok(pair));
                     }
-                    // Invalid line, continue to next
                 }
                 Err(e) => return Some(Err(e.into())),
             }
@@ -95,9 +243,33 @@ impl<R: BufRead> Iterator for PairIterator<R> {
     }
 }
 
-fn parse_line_juicer(line: &str, chr_map: &ChrLookup) -> Option<Pair> {
+/// Chromosome code for a name given as raw bytes.
+#[inline]
+fn lookup_chr(chr_map: &ChrLookup, name: &[u8]) -> Option<ChrCode> {
+    #[cfg(feature = "fast_chrmap")]
+    { chr_map.get_bytes(name) }
+    #[cfg(not(feature = "fast_chrmap"))]
+    { chr_map.get(std::str::from_utf8(name).ok()?).copied() }
+}
+
+/// Chromosome code for the name `name`, a slice of `line`, or a rejection
+/// recording where the unknown name sits in the line.
+#[inline]
+fn chr_code(chr_map: &ChrLookup, line: &[u8], name: &[u8]) -> Result<ChrCode, Reject> {
+    lookup_chr(chr_map, name).ok_or_else(|| {
+        let start = name.as_ptr() as usize - line.as_ptr() as usize;
+        Reject::Chr { start, end: start + name.len() }
+    })
+}
+
+/// An unsigned integer column, or a malformed-line rejection.
+#[inline]
+fn parse_field(bytes: &[u8]) -> Result<u32, Reject> {
+    crate::utils::parse_u32_fast(bytes).ok_or(Reject::Fields)
+}
+
+fn parse_line_juicer(bytes: &[u8], chr_map: &ChrLookup, min_mapq: u32) -> Result<Pair, Reject> {
     // Fast, zero-copy field scanner over ASCII whitespace
-    let bytes = line.as_bytes();
     let mut i = 0usize;
     let n = bytes.len();
 
@@ -142,83 +314,146 @@ fn parse_line_juicer(line: &str, chr_map: &ChrLookup) -> Option<Pair> {
     }
 
     // Required fields must exist (mapq2 optional, defaults to 0)
-    let (s1,e1) = f1?; // chr1
-    let (s2,e2) = f2?; // pos1
-    let (s3,e3) = f3?; // frag1
-    let (s5,e5) = f5?; // chr2
-    let (s6,e6) = f6?; // pos2
-    let (s7,e7) = f7?; // frag2
-    let (s8,e8) = f8?; // mapq1
+    let (s1,e1) = f1.ok_or(Reject::Fields)?; // chr1
+    let (s2,e2) = f2.ok_or(Reject::Fields)?; // pos1
+    let (s3,e3) = f3.ok_or(Reject::Fields)?; // frag1
+    let (s5,e5) = f5.ok_or(Reject::Fields)?; // chr2
+    let (s6,e6) = f6.ok_or(Reject::Fields)?; // pos2
+    let (s7,e7) = f7.ok_or(Reject::Fields)?; // frag2
+    let (s8,e8) = f8.ok_or(Reject::Fields)?; // mapq1
 
     // Parse only integers needed for filter first (fast reject path)
-    let frag1 = crate::utils::parse_u32_fast(&bytes[s3..e3])?;
-    let frag2 = crate::utils::parse_u32_fast(&bytes[s7..e7])?;
-    let mapq1 = crate::utils::parse_u32_fast(&bytes[s8..e8])?;
+    let frag1 = parse_field(&bytes[s3..e3])?;
+    let frag2 = parse_field(&bytes[s7..e7])?;
+    let mapq1 = parse_field(&bytes[s8..e8])?;
     let mapq2 = if let Some((s,e)) = f11 { crate::utils::parse_u32_fast(&bytes[s..e]).unwrap_or(0) } else { 0 };
-    if !(mapq1 > 0 && mapq2 > 0 && frag1 != frag2) {
-        return None;
+    if !(mapq1 >= min_mapq && mapq2 >= min_mapq) {
+        return Err(Reject::Mapq);
+    }
+    if frag1 == frag2 {
+        return Err(Reject::Frag);
     }
 
     // Passed filter: now parse chr and positions
-    let chr1 = {
-        let s = std::str::from_utf8(&bytes[s1..e1]).ok()?;
-        #[cfg(feature = "fast_chrmap")]
-        { chr_map.get(s)? }
-        #[cfg(not(feature = "fast_chrmap"))]
-        { *chr_map.get(s)? }
-    };
-    let pos1 = crate::utils::parse_u32_fast(&bytes[s2..e2])?;
-    let chr2 = {
-        let s = std::str::from_utf8(&bytes[s5..e5]).ok()?;
-        #[cfg(feature = "fast_chrmap")]
-        { chr_map.get(s)? }
-        #[cfg(not(feature = "fast_chrmap"))]
-        { *chr_map.get(s)? }
-    };
-    let pos2 = crate::utils::parse_u32_fast(&bytes[s6..e6])?;
+    let chr1 = chr_code(chr_map, bytes, &bytes[s1..e1])?;
+    let pos1 = parse_field(&bytes[s2..e2])?;
+    let chr2 = chr_code(chr_map, bytes, &bytes[s5..e5])?;
+    let pos2 = parse_field(&bytes[s6..e6])?;
 
-    Some(Pair { chr1, pos1, chr2, pos2 })
+    Ok(Pair { chr1, pos1, chr2, pos2, weight: 1 })
 }
 
-fn parse_line_pairs(line: &str, chr_map: &ChrLookup) -> Option<Pair> {
-    let line = line.trim_end();
-    if line.is_empty() || line.starts_with('#') {
-        return None;
+/// A contact score as a pair weight: a whole number, or a decimal (e.g. a
+/// normalized score) rounded to the nearest one.
+fn parse_score(bytes: &[u8]) -> Result<u32, Reject> {
+    if let Some(w) = crate::utils::parse_u32_fast(bytes) {
+        return Ok(w);
     }
+    let score: f64 = std::str::from_utf8(bytes).ok().and_then(|s| s.parse().ok()).ok_or(Reject::Fields)?;
+    if !(score.is_finite() && score >= 0.0) {
+        return Err(Reject::Fields);
+    }
+    Ok(score.round().min(u32::MAX as f64) as u32)
+}
 
-    let fields: Vec<&str> = line.split('\t').collect();
-    if fields.len() < 8 {
-        return None;
+fn parse_line_juicer_short(line: &[u8], chr_map: &ChrLookup) -> Result<Pair, Reject> {
+    // str1 chr1 pos1 frag1 str2 chr2 pos2 frag2 [score]
+    // No mapq columns, and fragments are not filtered: `straw dump --format short`
+    // and other converters write placeholder fragments
+    let mut fields = line.split(|b| b.is_ascii_whitespace()).filter(|f| !f.is_empty());
+    let mut next = || fields.next().ok_or(Reject::Fields);
+    let _str1 = next()?;
+    let chr1_str = next()?;
+    let pos1_str = next()?;
+    let _frag1 = next()?;
+    let _str2 = next()?;
+    let chr2_str = next()?;
+    let pos2_str = next()?;
+    let _frag2 = next()?;
+    let weight = match fields.next() {
+        Some(score) => parse_score(score)?,
+        None => 1,
+    };
+
+    let chr1 = chr_code(chr_map, line, chr1_str)?;
+    let pos1 = parse_field(pos1_str)?;
+    let chr2 = chr_code(chr_map, line, chr2_str)?;
+    let pos2 = parse_field(pos2_str)?;
+
+    Ok(Pair { chr1, pos1, chr2, pos2, weight })
+}
+
+/// Parse a .pairs data line. With `count_column`, that column is the pair
+/// weight; `.` or a missing value counts once.
+fn parse_line_pairs(line: &[u8], chr_map: &ChrLookup, count_column: Option<usize>) -> Result<Pair, Reject> {
+    let line = line.trim_ascii_end();
+    if line.is_empty() || line.first() == Some(&b'#') {
+        return Err(Reject::Fields);
     }
 
     // #columns: readID chrom1 pos1 chrom2 pos2 strand1 strand2 pair_type
-    let chr1_str = fields[1];
-    let pos1_str = fields[2];
-    let chr2_str = fields[3];
-    let pos2_str = fields[4];
-    let pair_type = fields[7];
-
-    // Heuristic filter to approximate mapq1>0 && mapq2>0: require both uniquely mapped
-    if pair_type != "UU" {
-        return None;
+    let mut fields = line.split(|&b| b == b'\t');
+    let mut next = || fields.next().ok_or(Reject::Fields);
+    let _read_id = next()?;
+    let chr1_str = next()?;
+    let pos1_str = next()?;
+    let chr2_str = next()?;
+    let pos2_str = next()?;
+    // strand1 and strand2 are required; pair_type is optional in the spec
+    let pair_type = match (fields.next(), fields.next(), fields.next()) {
+        (Some(_), Some(_), pair_type) => pair_type,
+        _ => return Err(Reject::Fields),
+    };
+
+    // Heuristic filter to approximate mapq1>0 && mapq2>0: require both uniquely
+    // mapped. A file without the pair_type column is taken as unique pairs.
+    if pair_type.is_some_and(|t| t != b"UU") {
+        return Err(Reject::Mapq);
     }
 
-    let chr1 = {
-        #[cfg(feature = "fast_chrmap")]
-        { chr_map.get(chr1_str)? }
-        #[cfg(not(feature = "fast_chrmap"))]
-        { chr_map.get(chr1_str).copied()? }
-    };
-    let pos1 = pos1_str.parse::<u32>().ok()?;
-    let chr2 = {
-        #[cfg(feature = "fast_chrmap")]
-        { chr_map.get(chr2_str)? }
-        #[cfg(not(feature = "fast_chrmap"))]
-        { chr_map.get(chr2_str).copied()? }
+    let chr1 = chr_code(chr_map, line, chr1_str)?;
+    let pos1 = parse_field(pos1_str)?;
+    let chr2 = chr_code(chr_map, line, chr2_str)?;
+    let pos2 = parse_field(pos2_str)?;
+    let weight = match count_column.and_then(|i| line.split(|&b| b == b'\t').nth(i)) {
+        Some(b".") | None => 1,
+        Some(count) => parse_score(count)?,
     };
-    let pos2 = pos2_str.parse::<u32>().ok()?;
 
-    Some(Pair { chr1, pos1, chr2, pos2 })
+    Ok(Pair { chr1, pos1, chr2, pos2, weight })
+}
+
+fn parse_line_hicpro(line: &[u8], chr_map: &ChrLookup, min_mapq: u32) -> Result<Pair, Reject> {
+    // #columns: readID chr1 pos1 strand1 chr2 pos2 strand2 fragSize resfrag1 resfrag2 mapq1 mapq2
+    let mut fields = line.split(|b| b.is_ascii_whitespace()).filter(|f| !f.is_empty());
+    let mut next = || fields.next().ok_or(Reject::Fields);
+    let _read_id = next()?;
+    let chr1_str = next()?;
+    let pos1_str = next()?;
+    let _strand1 = next()?;
+    let chr2_str = next()?;
+    let pos2_str = next()?;
+    let _strand2 = next()?;
+    let _frag_size = next()?;
+    let resfrag1 = next()?;
+    let resfrag2 = next()?;
+    let mapq1 = parse_field(next()?)?;
+    let mapq2 = parse_field(next()?)?;
+
+    // Same UU-like filter as merged_nodups: both ends mapped uniquely, different fragments
+    if !(mapq1 >= min_mapq && mapq2 >= min_mapq) {
+        return Err(Reject::Mapq);
+    }
+    if resfrag1 == resfrag2 {
+        return Err(Reject::Frag);
+    }
+
+    let chr1 = chr_code(chr_map, line, chr1_str)?;
+    let pos1 = parse_field(pos1_str)?;
+    let chr2 = chr_code(chr_map, line, chr2_str)?;
+    let pos2 = parse_field(pos2_str)?;
+
+    Ok(Pair { chr1, pos1, chr2, pos2, weight: 1 })
 }
 
 pub fn open_file<R: Read>(
@@ -241,6 +476,45 @@ pub fn open_file_uncompressed<R: Read>(
     Ok(PairIterator::new(buf_reader, chr_map, ParseMode::Juicer))
 }
 
+pub fn open_file_zstd<R: Read>(
+    reader: R,
+    chrom_size_file: Option<&str>,
+) -> Result<PairIterator<BufReader<ZstdDecoder<R>>>> {
+    let decoder = ZstdDecoder::new(reader)?;
+    let buf_reader = BufReader::with_capacity(256 * 1024, decoder);
+    let chr_map = crate::utils::create_lookup_map(chrom_size_file);
+    Ok(PairIterator::new(buf_reader, chr_map, ParseMode::Juicer))
+}
+
+pub fn open_short_file<R: Read>(
+    reader: R,
+    chrom_size_file: Option<&str>,
+) -> Result<PairIterator<BufReader<MultiGzDecoder<R>>>> {
+    let decoder = MultiGzDecoder::new(reader);
+    let buf_reader = BufReader::with_capacity(256 * 1024, decoder);
+    let chr_map = crate::utils::create_lookup_map(chrom_size_file);
+    Ok(PairIterator::new(buf_reader, chr_map, ParseMode::JuicerShort))
+}
+
+pub fn open_short_file_uncompressed<R: Read>(
+    reader: R,
+    chrom_size_file: Option<&str>,
+) -> Result<PairIterator<BufReader<R>>> {
+    let buf_reader = BufReader::with_capacity(256 * 1024, reader);
+    let chr_map = crate::utils::create_lookup_map(chrom_size_file);
+    Ok(PairIterator::new(buf_reader, chr_map, ParseMode::JuicerShort))
+}
+
+pub fn open_short_file_zstd<R: Read>(
+    reader: R,
+    chrom_size_file: Option<&str>,
+) -> Result<PairIterator<BufReader<ZstdDecoder<R>>>> {
+    let decoder = ZstdDecoder::new(reader)?;
+    let buf_reader = BufReader::with_capacity(256 * 1024, decoder);
+    let chr_map = crate::utils::create_lookup_map(chrom_size_file);
+    Ok(PairIterator::new(buf_reader, chr_map, ParseMode::JuicerShort))
+}
+
 pub fn open_pairs_file<R: Read>(
     reader: R,
     chr_map: ChrLookup,
@@ -258,31 +532,116 @@ pub fn open_pairs_file_uncompressed<R: Read>(
     Ok(PairIterator::new(buf_reader, chr_map, ParseMode::Pairs))
 }
 
-use std::path::Path;
-pub fn sniff_pairs_header_from_path(path: &Path) -> Result<Option<(ChrLookup, Vec<String>, Vec<u32>)>> {
+pub fn open_pairs_file_zstd<R: Read>(
+    reader: R,
+    chr_map: ChrLookup,
+) -> Result<PairIterator<BufReader<ZstdDecoder<R>>>> {
+    let decoder = ZstdDecoder::new(reader)?;
+    let buf_reader = BufReader::with_capacity(64 * 1024, decoder);
+    Ok(PairIterator::new(buf_reader, chr_map, ParseMode::Pairs))
+}
+
+pub fn open_hicpro_file<R: Read>(
+    reader: R,
+    chrom_size_file: Option<&str>,
+) -> Result<PairIterator<BufReader<MultiGzDecoder<R>>>> {
+    let decoder = MultiGzDecoder::new(reader);
+    let buf_reader = BufReader::with_capacity(256 * 1024, decoder);
+    let chr_map = crate::utils::create_lookup_map(chrom_size_file);
+    Ok(PairIterator::new(buf_reader, chr_map, ParseMode::HicPro))
+}
+
+pub fn open_hicpro_file_uncompressed<R: Read>(
+    reader: R,
+    chrom_size_file: Option<&str>,
+) -> Result<PairIterator<BufReader<R>>> {
+    let buf_reader = BufReader::with_capacity(256 * 1024, reader);
+    let chr_map = crate::utils::create_lookup_map(chrom_size_file);
+    Ok(PairIterator::new(buf_reader, chr_map, ParseMode::HicPro))
+}
+
+pub fn open_hicpro_file_zstd<R: Read>(
+    reader: R,
+    chrom_size_file: Option<&str>,
+) -> Result<PairIterator<BufReader<ZstdDecoder<R>>>> {
+    let decoder = ZstdDecoder::new(reader)?;
+    let buf_reader = BufReader::with_capacity(256 * 1024, decoder);
+    let chr_map = crate::utils::create_lookup_map(chrom_size_file);
+    Ok(PairIterator::new(buf_reader, chr_map, ParseMode::HicPro))
+}
+
+/// Guess the input layout from the leading lines of a (possibly compressed) file.
+/// Never returns [`InputFormat::Auto`]; falls back to Juicer.
+pub fn sniff_input_format_from_path(path: &Path) -> Result<InputFormat> {
     use std::fs::File;
     let file = File::open(path)?;
-    let is_gz = path
-        .extension()
-        .and_then(|e| e.to_str())
-        .map(|e| e.eq_ignore_ascii_case("gz"))
-        .unwrap_or(false);
-
-    if is_gz {
-        sniff_pairs_header(MultiGzDecoder::new(file))
-    } else {
-        sniff_pairs_header(file)
+    sniff_input_format(InputCompression::from_path(path).decoder(file)?)
+}
+
+fn sniff_input_format<R: Read>(reader: R) -> Result<InputFormat> {
+    let mut reader = BufReader::with_capacity(64 * 1024, reader);
+    let mut buf = String::new();
+    for _ in 0..2000 {
+        buf.clear();
+        if reader.read_line(&mut buf)? == 0 {
+            break;
+        }
+        let line = buf.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with("## pairs format") || line.starts_with("#chromsize:") || line.starts_with("#columns:") {
+            return Ok(InputFormat::Pairs);
+        }
+        if let Some(header) = line.strip_prefix('#') {
+            // HiC-Pro column header, e.g. "#readID chr1 pos1 strand1 ... resfrag1 resfrag2"
+            if header.split_ascii_whitespace().any(|c| c == "resfrag1") {
+                return Ok(InputFormat::Hicpro);
+            }
+            continue;
+        }
+        // First data line: HiC-Pro has +/- strands in columns 4 and 7,
+        // merged_nodups has numeric strands in columns 1 and 5
+        let f: Vec<&str> = line.split_ascii_whitespace().collect();
+        let is_strand = |s: &str| s == "+" || s == "-";
+        if f.len() >= 12 && is_strand(f[3]) && is_strand(f[6]) {
+            return Ok(InputFormat::Hicpro);
+        }
+        // Header-less .pairs: tab-delimited, numeric positions in columns 3 and
+        // 5, +/- strands in columns 6 and 7, then the pair type (`UU`, ...)
+        let is_num = |s: &str| crate::utils::parse_u32_fast(s.as_bytes()).is_some();
+        let t: Vec<&str> = line.split('\t').collect();
+        if t.len() >= 7 && is_num(t[2]) && is_num(t[4]) && is_strand(t[5]) && is_strand(t[6])
+            && t.get(7).is_none_or(|p| p.len() == 2 && p.bytes().all(|b| b.is_ascii_uppercase()))
+        {
+            return Ok(InputFormat::Pairs);
+        }
+        // Juicer short: 8 columns (9 with a score), numeric strands and positions
+        if (f.len() == 8 || f.len() == 9) && [f[0], f[2], f[4], f[6]].into_iter().all(is_num) {
+            return Ok(InputFormat::JuicerShort);
+        }
+        // merged_nodups: whitespace-separated, 12+ columns with numeric mapqs in 9 and 12;
+        // anything else is also read as Juicer, so --format settles an ambiguous file
+        return Ok(InputFormat::Juicer);
     }
+    Ok(InputFormat::Juicer)
 }
 
-fn sniff_pairs_header<R: Read>(reader: R) -> Result<Option<(ChrLookup, Vec<String>, Vec<u32>)>> {
+/// Chromosome lookup, names and lengths recovered from a `.pairs` header.
+pub type PairsHeader = (ChrLookup, Vec<String>, Vec<u32>);
+
+pub fn sniff_pairs_header_from_path(path: &Path) -> Result<Option<PairsHeader>> {
+    use std::fs::File;
+    let file = File::open(path)?;
+    sniff_pairs_header(InputCompression::from_path(path).decoder(file)?)
+}
+
+pub(crate) fn sniff_pairs_header<R: Read>(reader: R) -> Result<Option<PairsHeader>> {
     let mut reader = BufReader::with_capacity(64 * 1024, reader);
     let mut buf = String::new();
     let mut lengths: Vec<u32> = Vec::new();
     let mut names: Vec<String> = Vec::new();
-    use std::collections::HashMap;
     let mut index_of: HashMap<String, usize> = HashMap::new();
-    let mut seen_any = false;
 
     // Read a limited number of header lines to avoid slurping large files
     for _ in 0..2000 {
@@ -295,9 +654,8 @@ fn sniff_pairs_header<R: Read>(reader: R) -> Result<Option<(ChrLookup, Vec<Strin
         if !line.starts_with('#') {
             break;
         }
-        seen_any = true;
         if let Some(rest) = line.strip_prefix("#chromsize:") {
-            let parts: Vec<&str> = rest.trim().split_whitespace().collect();
+            let parts: Vec<&str> = rest.split_whitespace().collect();
             if parts.len() >= 2 {
                 if let Ok(len) = parts[1].parse::<u32>() {
                     let name = parts[0].to_string();
@@ -342,9 +700,175 @@ fn sniff_pairs_header<R: Read>(reader: R) -> Result<Option<(ChrLookup, Vec<Strin
     if !lengths.is_empty() {
         let map = crate::utils::build_lookup_from_names(names.clone());
         Ok(Some((map, names, lengths)))
-    } else if seen_any {
-        Ok(None) // header present but no lengths parsed
     } else {
-        Ok(None)
+        Ok(None) // no header, or header present but no lengths parsed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup() -> ChrLookup {
+        crate::utils::build_lookup_from_names(vec!["chr1".to_string(), "chr2".to_string()])
+    }
+
+    #[test]
+    fn reads_zstd_compressed_merged_nodups() {
+        let text = "0 chr1 130 1 16 chr2 60 2 60 50M SEQ 30 50M SEQ r0 r0\n\
+                    16 chr1 10 1 0 chr1 90 3 60 50M SEQ 30 50M SEQ r1 r1\n\
+                    0 chr2 5 1 0 chr2 500 3 60 50M SEQ 30 50M SEQ r2 r2\n";
+        let compressed = zstd::encode_all(text.as_bytes(), 0).unwrap();
+        assert_eq!(InputCompression::from_path(Path::new("merged_nodups.txt.zst")), InputCompression::Zstd);
+
+        // Default hg19 names
+        let pairs: Vec<Pair> = open_file_zstd(&compressed[..], None).unwrap().map(|p| p.unwrap()).collect();
+        assert_eq!(pairs.len(), 3);
+        assert_eq!((pairs[2].chr1, pairs[2].pos2), (2, 500));
+    }
+
+    #[test]
+    fn counts_lines_and_rejections_per_iterator() {
+        let text = "0 chr1 130 1 16 chr2 60 2 60 50M SEQ 30 50M SEQ r0 r0\n\
+                    0 chrUn 10 1 0 chr1 90 3 60 50M SEQ 30 50M SEQ r1 r1\n\
+                    0 chr1 10 1 0 chr1 90 3 0 50M SEQ 30 50M SEQ r2 r2\n\
+                    0 chr1 10 3 0 chr1 90 3 60 50M SEQ 30 50M SEQ r3 r3\n\
+                    0 chr1 10 1 0 chr1 9x 3 60 50M SEQ 30 50M SEQ r4 r4\n\
+                    0 chr1 10\n";
+        // Each iterator counts its own input, so a second run starts from zero
+        for _ in 0..2 {
+            let mut iter = open_file_uncompressed(text.as_bytes(), None).unwrap();
+            assert_eq!(iter.by_ref().count(), 1);
+            let stats = iter.stats();
+            assert_eq!(
+                stats,
+                ParseStats {
+                    total_lines: 6,
+                    parsed: 1,
+                    rejected_chr: 1,
+                    rejected_mapq: 1,
+                    rejected_frag: 1,
+                    rejected_fields: 2,
+                    unmapped_chroms: vec![("chrUn".to_string(), 1)],
+                }
+            );
+            assert_eq!(stats.parsed + stats.rejected(), stats.total_lines);
+        }
+        let mut pairs = open_pairs_file_uncompressed("## pairs format v1.0\nr1\tchr2\t5\tchr1\t9\t+\t-\tUU\n".as_bytes(), lookup()).unwrap();
+        assert_eq!(pairs.by_ref().count(), 1);
+        assert_eq!(pairs.stats(), ParseStats { total_lines: 1, parsed: 1, ..Default::default() });
+    }
+
+    #[test]
+    fn ranks_unknown_chromosomes_by_lines() {
+        // chrM twice, chrUn once (as the second end), chrEBV ahead of chrM on the last line
+        let text = "0 chrM 130 1 16 chr2 60 2 60 50M SEQ 30 50M SEQ r0 r0\n\
+                    0 chr1 10 1 0 chrUn 90 3 60 50M SEQ 30 50M SEQ r1 r1\n\
+                    0 chr2 10 1 0 chrM 90 3 60 50M SEQ 30 50M SEQ r2 r2\n\
+                    0 chrEBV 10 1 0 chrM 90 3 60 50M SEQ 30 50M SEQ r3 r3\n\
+                    0 chr1 10 1 0 chr2 90 3 60 50M SEQ 30 50M SEQ r4 r4\n";
+        let mut iter = open_file_uncompressed(text.as_bytes(), None).unwrap();
+        assert_eq!(iter.by_ref().count(), 1);
+        let stats = iter.stats();
+        assert_eq!(stats.rejected_chr, 4);
+        let expected = [("chrM", 2), ("chrEBV", 1), ("chrUn", 1)].map(|(n, c)| (n.to_string(), c));
+        assert_eq!(stats.unmapped_chroms, expected);
+        // chrM is 2 of 5 lines
+        assert_eq!(stats.unmapped_over(0.3), Some(&expected[0]));
+        assert_eq!(stats.unmapped_over(0.4), None);
+        assert_eq!(ParseStats::default().unmapped_over(0.0), None);
+    }
+
+    #[test]
+    fn min_mapq_applies_to_both_ends() {
+        // mapq 60/30, 60/20 and 10/60
+        let text = "0 chr1 130 1 16 chr2 60 2 60 50M SEQ 30 50M SEQ r0 r0\n\
+                    0 chr1 10 1 0 chr1 90 3 60 50M SEQ 20 50M SEQ r1 r1\n\
+                    0 chr2 5 1 0 chr2 500 3 10 50M SEQ 60 50M SEQ r2 r2\n";
+        let count = |q: u32| {
+            let mut iter = open_file_uncompressed(text.as_bytes(), None).unwrap().min_mapq(q);
+            (iter.by_ref().count(), iter.stats().rejected_mapq)
+        };
+        assert_eq!(count(DEFAULT_MIN_MAPQ), (3, 0));
+        assert_eq!(count(20), (2, 1));
+        assert_eq!(count(30), (1, 2));
+
+        let hicpro = "r1\tchr1\t10\t+\tchr2\t90\t-\t80\tHIC_chr1_1\tHIC_chr2_2\t42\t29\n";
+        assert_eq!(open_hicpro_file_uncompressed(hicpro.as_bytes(), None).unwrap().count(), 1);
+        assert_eq!(open_hicpro_file_uncompressed(hicpro.as_bytes(), None).unwrap().min_mapq(30).count(), 0);
+        // mapq 0 passes only when the filter is off
+        assert_eq!(parse_line_juicer(b"0 chr1 1 1 0 chr1 9 2 0 50M SEQ 0", &lookup(), 0).map(|p| p.pos2), Ok(9));
+    }
+
+    #[test]
+    fn parses_hicpro_valid_pairs() {
+        let map = lookup();
+        let line = "r1\tchr1\t1500\t+\tchr2\t8800\t-\t320\tHIC_chr1_3\tHIC_chr2_17\t42\t30\n";
+        let p = parse_line_hicpro(line.as_bytes(), &map, 1).unwrap();
+        assert_eq!((p.chr1, p.pos1, p.chr2, p.pos2), (1, 1500, 2, 8800));
+
+        // mapq filter on columns 11/12 and same-fragment guard
+        assert_eq!(parse_line_hicpro(b"r2 chr1 10 + chr1 90 - 80 HIC_chr1_1 HIC_chr1_2 0 30", &map, 1).unwrap_err(), Reject::Mapq);
+        assert_eq!(parse_line_hicpro(b"r3 chr1 10 + chr1 90 - 80 HIC_chr1_1 HIC_chr1_1 30 30", &map, 1).unwrap_err(), Reject::Frag);
+        // Truncated line and unknown chromosome
+        assert_eq!(parse_line_hicpro(b"r4 chr1 10 + chr1 90 - 80 HIC_chr1_1 HIC_chr1_2 30", &map, 1).unwrap_err(), Reject::Fields);
+        assert_eq!(parse_line_hicpro(b"r5 chrUn 10 + chr1 90 - 80 HIC_chr1_1 HIC_chr1_2 30 30", &map, 1).unwrap_err(), Reject::Chr { start: 3, end: 8 });
+    }
+
+    #[test]
+    fn parses_juicer_and_pairs_lines_from_bytes() {
+        let map = lookup();
+        let p = parse_line_juicer(b"0 chr1 130 1 16 chr2 60 2 60 50M SEQ 30 50M SEQ r0 r0\r\n", &map, 1).unwrap();
+        assert_eq!((p.chr1, p.pos1, p.chr2, p.pos2), (1, 130, 2, 60));
+        assert_eq!(parse_line_juicer(b"0 chr1 13x0 1 16 chr2 60 2 60 50M SEQ 30", &map, 1).unwrap_err(), Reject::Fields);
+
+        let p = parse_line_pairs(b"r1\tchr2\t5\tchr1\t9\t+\t-\tUU\t7\r\n", &map, None).unwrap();
+        assert_eq!((p.chr1, p.pos1, p.chr2, p.pos2, p.weight), (2, 5, 1, 9, 1));
+        // A declared count column is the weight; `.` counts once
+        assert_eq!(parse_line_pairs(b"r1\tchr2\t5\tchr1\t9\t+\t-\tUU\t7\r\n", &map, Some(8)).unwrap().weight, 7);
+        assert_eq!(parse_line_pairs(b"r1\tchr2\t5\tchr1\t9\t+\t-\tUU\t.\n", &map, Some(8)).unwrap().weight, 1);
+        assert_eq!(parse_line_pairs(b"r1\tchr2\t5\tchr1\t9\t+\t-\tUU\tx\n", &map, Some(8)).unwrap_err(), Reject::Fields);
+        assert_eq!(parse_line_pairs(b"r1\tchr2\t5\tchr1\t9\t+\t-\tNU\n", &map, None).unwrap_err(), Reject::Mapq);
+        // The 7 required columns without pair_type count as UU; fewer are malformed
+        let p = parse_line_pairs(b"r1\tchr2\t5\tchr1\t9\t+\t-\n", &map, None).unwrap();
+        assert_eq!((p.chr1, p.pos1, p.chr2, p.pos2, p.weight), (2, 5, 1, 9, 1));
+        assert_eq!(parse_line_pairs(b"r1\tchr2\t5\tchr1\t9\t+\n", &map, None).unwrap_err(), Reject::Fields);
+    }
+
+    #[test]
+    fn parses_juicer_short_with_and_without_score() {
+        let map = lookup();
+        let p = parse_line_juicer_short(b"0 chr1 130 0 16 chr2 60 1\n", &map).unwrap();
+        assert_eq!((p.chr1, p.pos1, p.chr2, p.pos2, p.weight), (1, 130, 2, 60, 1));
+        // Same fragment is kept; the score is the weight, rounded when decimal
+        assert_eq!(parse_line_juicer_short(b"0 chr1 10 0 0 chr1 90 0 7", &map).unwrap().weight, 7);
+        assert_eq!(parse_line_juicer_short(b"0 chr1 10 0 0 chr1 90 0 2.6", &map).unwrap().weight, 3);
+        assert_eq!(parse_line_juicer_short(b"0 chr1 10 0 0 chr1 90 0 -1", &map).unwrap_err(), Reject::Fields);
+        assert_eq!(parse_line_juicer_short(b"0 chr1 10 0 0 chr1 90", &map).unwrap_err(), Reject::Fields);
+        assert_eq!(parse_line_juicer_short(b"0 chrUn 10 0 0 chr1 90 0", &map).unwrap_err(), Reject::Chr { start: 2, end: 7 });
+
+        // Scores reach the coverage bins through the chunked aggregation
+        let text = "0 chr1 130 0 16 chr2 60 1 4\n0 chr1 150 0 0 chr1 900 0 2\n";
+        let pairs: Vec<Pair> = open_short_file_uncompressed(text.as_bytes(), None).unwrap().map(|p| p.unwrap()).collect();
+        let mut cov = crate::coverage::Coverage::from_lengths(100, vec![1000, 1000]);
+        cov.add_pairs(&pairs, 1);
+        assert_eq!(cov.chr_bins(0)[1], 6);
+        assert_eq!(cov.get_total_contacts(), 12);
+    }
+
+    #[test]
+    fn sniffs_input_formats() {
+        let sniff = |text: &str| sniff_input_format(text.as_bytes()).unwrap();
+        assert_eq!(sniff("## pairs format v1.0\n#chromsize: chr1 100\nr1\tchr1\t5\tchr1\t9\t+\t-\tUU\n"), InputFormat::Pairs);
+        assert_eq!(sniff("r1\tchr1\t1500\t+\tchr2\t8800\t-\t320\tHIC_chr1_3\tHIC_chr2_17\t42\t30\n"), InputFormat::Hicpro);
+        assert_eq!(sniff("#readID chr1 pos1 strand1 chr2 pos2 strand2 fragSize resfrag1 resfrag2 mapq1 mapq2\n"), InputFormat::Hicpro);
+        assert_eq!(sniff("0 chr1 130 1 16 chr1 60 2 60 50M SEQ 60 50M SEQ r0 r0\n"), InputFormat::Juicer);
+        assert_eq!(sniff("0 chr1 130 0 16 chr1 60 1\n"), InputFormat::JuicerShort);
+        assert_eq!(sniff("0 chr1 130 0 16 chr1 60 1 2.5\n"), InputFormat::JuicerShort);
+        assert_eq!(sniff(""), InputFormat::Juicer);
+        // 4DN .pairs without any header lines
+        assert_eq!(sniff("r1\tchr1\t5\tchr1\t9\t+\t-\tUU\n"), InputFormat::Pairs);
+        assert_eq!(sniff("#comment\nr1\tchr1\t5\tchr2\t9\t-\t-\n"), InputFormat::Pairs);
+        assert_eq!(sniff(".\tchr1\t5\tchr2\t9\t+\t+\tUR\t3\n"), InputFormat::Pairs);
     }
 }
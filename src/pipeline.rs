@@ -0,0 +1,880 @@
+//! The pairs-based resolution pipeline behind `hickit resolution`, as a
+//! library API: configure an [`AnalysisBuilder`], then [`AnalysisBuilder::run`]
+//! reads the pairs, builds the coverage and searches for the map resolution.
+use anyhow::{anyhow, bail, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+use std::fs::File;
+use std::io::{stdin, BufRead};
+use std::path::{Path, PathBuf};
+
+use crate::coverage::{ContactScope, Coverage};
+use crate::resolution::{self, ResolutionReport};
+use crate::{filter, parser, utils};
+
+/// Outcome of a pipeline run: the search report plus what it was computed from.
+pub struct Analysis {
+    pub report: ResolutionReport,
+    /// Pairs read from the input, including those `contacts` left out
+    pub pairs_processed: u64,
+    /// Lines read from the input and why the rejected ones were dropped
+    pub parse_stats: parser::ParseStats,
+    pub coverage: Coverage,
+    /// Chromosome name of each coverage row
+    pub names: Vec<String>,
+    /// Genomic start of coverage position 0 (the region start with a region)
+    pub offset: u32,
+    /// Set when `--genome-size` disagrees with the chromosome sizes in use
+    /// (see [`genome_size_mismatch`]), for the caller to show
+    pub genome_size_warning: Option<String>,
+}
+
+/// Bytes held per pair of an aggregated chunk: the [`utils::Pair`] in the
+/// read buffer plus up to two 16-byte `(bin, weight)` entries in the merged
+/// partials of [`Coverage::add_pairs`].
+const CHUNK_BYTES_PER_PAIR: u64 = std::mem::size_of::<utils::Pair>() as u64 + 32;
+
+/// Chunk and subchunk sizes (pairs) keeping the aggregation buffers of
+/// `threads` workers within `budget_bytes`; the coverage bins are not part of
+/// the budget (see `max_memory`).
+///
+/// A chunk of `C` pairs takes `C * CHUNK_BYTES_PER_PAIR` bytes. Each worker
+/// also sorts a subchunk of `S` pairs in a scratch vector and a compressed
+/// copy, two 16-byte entries per pair each: `64 * S` bytes, `threads * 64 * S`
+/// in flight. With `S = C / (4 * threads)` that is at most `16 * C`, so
+///
+/// `C = budget / (CHUNK_BYTES_PER_PAIR + 16)`, `S = C / (4 * threads)`
+///
+/// with `S` clamped to 16,000..=128,000 (`add_pairs` never goes below 16,000).
+/// When the clamp raises `S`, `C` shrinks to what is left of the budget after
+/// the subchunks; it is never below one subchunk.
+pub fn chunking_for_budget(budget_bytes: u64, threads: usize) -> (usize, usize) {
+    let threads = threads.max(1) as u64;
+    let chunk = budget_bytes / (CHUNK_BYTES_PER_PAIR + 16);
+    let subchunk = (chunk / (4 * threads)).clamp(16_000, 128_000);
+    let chunk = chunk.min(budget_bytes.saturating_sub(threads * 64 * subchunk) / CHUNK_BYTES_PER_PAIR);
+    (chunk.max(16_000) as usize, subchunk as usize)
+}
+
+/// Relative difference between a stated genome size and the summed
+/// chromosome lengths beyond which [`genome_size_mismatch`] warns.
+const GENOME_SIZE_TOLERANCE: f64 = 0.05;
+
+/// Where the chromosome sizes come from when neither a `.pairs` header nor
+/// a chrom.size file gives them.
+const HG19_SOURCE: &str = "built-in hg19";
+
+/// A warning when the `expected` genome size differs from `actual`, the sum
+/// of the chromosome lengths from `source`, by more than 5%.
+pub fn genome_size_mismatch(expected: u64, actual: u64, source: &str) -> Option<String> {
+    let diff = actual as f64 / expected.max(1) as f64 - 1.0;
+    (diff.abs() > GENOME_SIZE_TOLERANCE).then(|| {
+        format!(
+            "Warning: --genome-size is {} bp but the chromosome sizes ({}) sum to {} bp ({:+.1}%); check the chrom.size file",
+            expected,
+            source,
+            actual,
+            diff * 100.0
+        )
+    })
+}
+
+/// Settings of the resolution pipeline, with the CLI's defaults. Chromosome
+/// names and lengths come from a `.pairs` header when the input has one, else
+/// from [`AnalysisBuilder::chrom_sizes`], else the built-in hg19 table.
+#[derive(Debug, Clone)]
+pub struct AnalysisBuilder {
+    input: Option<PathBuf>,
+    format: parser::InputFormat,
+    chrom_sizes: Option<PathBuf>,
+    // `chrom_sizes` is a FASTA index, checked with utils::read_fai
+    fai: bool,
+    bin_width: u32,
+    prop: f64,
+    count_threshold: u32,
+    step_size: u32,
+    threads: usize,
+    chunk_pairs: usize,
+    subchunk_pairs: usize,
+    max_memory: Option<u64>,
+    max_bin_size: u32,
+    region: Option<String>,
+    include_chroms: Option<Vec<String>>,
+    exclude_chroms: Vec<String>,
+    contacts: ContactScope,
+    min_mapq: u32,
+    max_unmapped_fraction: Option<f64>,
+    genome_size: Option<u64>,
+    save_coverage: Option<PathBuf>,
+    load_coverage: Option<PathBuf>,
+    verbose: bool,
+    progress: bool,
+}
+
+impl Default for AnalysisBuilder {
+    fn default() -> Self {
+        AnalysisBuilder {
+            input: None,
+            format: parser::InputFormat::Auto,
+            chrom_sizes: None,
+            fai: false,
+            bin_width: 50,
+            prop: 0.8,
+            count_threshold: 1000,
+            step_size: 1000,
+            threads: 0,
+            chunk_pairs: 4_000_000,
+            subchunk_pairs: 128_000,
+            max_memory: None,
+            max_bin_size: resolution::DEFAULT_MAX_BIN_SIZE,
+            region: None,
+            include_chroms: None,
+            exclude_chroms: Vec::new(),
+            contacts: ContactScope::All,
+            min_mapq: parser::DEFAULT_MIN_MAPQ,
+            max_unmapped_fraction: None,
+            genome_size: None,
+            save_coverage: None,
+            load_coverage: None,
+            verbose: false,
+            progress: false,
+        }
+    }
+}
+
+impl AnalysisBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// merged_nodups, .pairs or validPairs file (`.gz`/`.zst` decompressed); stdin when unset.
+    pub fn input(mut self, path: impl Into<PathBuf>) -> Self {
+        self.input = Some(path.into());
+        self
+    }
+
+    /// Input layout; [`parser::InputFormat::Auto`] sniffs it from the file.
+    pub fn format(mut self, format: parser::InputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// `chrom.size` file used when the input has no `.pairs` header.
+    pub fn chrom_sizes(mut self, path: impl Into<PathBuf>) -> Self {
+        self.chrom_sizes = Some(path.into());
+        self.fai = false;
+        self
+    }
+
+    /// `samtools faidx` index used like [`Self::chrom_sizes`], after checking
+    /// that it is one (see [`utils::read_fai`]). Chromosome codes follow the
+    /// index order.
+    pub fn fai(mut self, path: impl Into<PathBuf>) -> Self {
+        self.chrom_sizes = Some(path.into());
+        self.fai = true;
+        self
+    }
+
+    /// Base bin width in bp; the finest resolution the search can report.
+    pub fn bin_width(mut self, bp: u32) -> Self {
+        self.bin_width = bp;
+        self
+    }
+
+    /// Fraction of bins that must reach `count_threshold`.
+    pub fn prop(mut self, prop: f64) -> Self {
+        self.prop = prop;
+        self
+    }
+
+    /// Contacts a bin needs to count as covered.
+    pub fn count_threshold(mut self, contacts: u32) -> Self {
+        self.count_threshold = contacts;
+        self
+    }
+
+    /// Step of the initial coarse search, in bp.
+    pub fn step_size(mut self, bp: u32) -> Self {
+        self.step_size = bp;
+        self
+    }
+
+    /// Threads for aggregation and the search; 0 uses the current rayon pool.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Pairs buffered before each parallel aggregation, and per worker within it.
+    pub fn chunking(mut self, chunk_pairs: usize, subchunk_pairs: usize) -> Self {
+        self.chunk_pairs = chunk_pairs;
+        self.subchunk_pairs = subchunk_pairs;
+        self
+    }
+
+    /// Upper bound in bytes for the dense coverage bins (default: 80% of available RAM).
+    pub fn max_memory(mut self, bytes: u64) -> Self {
+        self.max_memory = Some(bytes);
+        self
+    }
+
+    /// Bin size up to which the coarse search steps by `step_size` (default
+    /// 10 Mb); past it the bin size doubles up to the longest chromosome.
+    pub fn max_bin_size(mut self, bp: u32) -> Self {
+        self.max_bin_size = bp;
+        self
+    }
+
+    /// Only count pair ends inside `CHR:START-END`, searching over the region's bins.
+    pub fn region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+
+    /// Only build the coverage of these chromosomes; the others are left out
+    /// as with [`Self::exclude_chroms`].
+    pub fn include_chroms(mut self, names: Vec<String>) -> Self {
+        self.include_chroms = Some(names);
+        self
+    }
+
+    /// Leave these chromosomes out, e.g. chrM or unplaced scaffolds: they get
+    /// no bins, pair ends on them are dropped, and their length does not count
+    /// towards the genome size of the search. Names match the chromosome
+    /// sizes exactly or with the `chr` prefix added or dropped.
+    pub fn exclude_chroms(mut self, names: Vec<String>) -> Self {
+        self.exclude_chroms = names;
+        self
+    }
+
+    /// Which pairs build the coverage.
+    pub fn contacts(mut self, contacts: ContactScope) -> Self {
+        self.contacts = contacts;
+        self
+    }
+
+    /// Genome size of the sample. With chromosome sizes from a `.pairs`
+    /// header or [`Self::chrom_sizes`], it checks them (see
+    /// [`genome_size_mismatch`]); with the hg19 fallback, it is the genome
+    /// size reported for whole-genome runs. The bins always follow the
+    /// chromosome lengths.
+    pub fn genome_size(mut self, bp: u64) -> Self {
+        self.genome_size = Some(bp);
+        self
+    }
+
+    /// Mapping quality both ends of a pair need (see [`parser::PairIterator::min_mapq`]).
+    pub fn min_mapq(mut self, mapq: u32) -> Self {
+        self.min_mapq = mapq;
+        self
+    }
+
+    /// Fail the run when one chromosome missing from the chromosome sizes
+    /// names more than `fraction` of the input lines (see
+    /// [`parser::ParseStats::unmapped_over`]), instead of dropping its pairs.
+    pub fn max_unmapped_fraction(mut self, fraction: f64) -> Self {
+        self.max_unmapped_fraction = Some(fraction);
+        self
+    }
+
+    /// Write the coverage built from the pairs to this file (see [`Coverage::save`]).
+    pub fn save_coverage(mut self, path: impl Into<PathBuf>) -> Self {
+        self.save_coverage = Some(path.into());
+        self
+    }
+
+    /// Skip the input and search over a coverage written by `save_coverage`;
+    /// its bin width must equal [`AnalysisBuilder::bin_width`].
+    pub fn load_coverage(mut self, path: impl Into<PathBuf>) -> Self {
+        self.load_coverage = Some(path.into());
+        self
+    }
+
+    /// Echo the first input lines and parsed pairs to stderr.
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Print the settings and warnings and draw a progress bar while running,
+    /// as `hickit resolution` does; silent by default.
+    pub fn progress(mut self, progress: bool) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Run the pipeline and return the resolution report.
+    pub fn run(&self) -> Result<ResolutionReport> {
+        Ok(self.analyze()?.report)
+    }
+
+    /// Run the pipeline, keeping the coverage and chromosome names for
+    /// follow-up reports such as hotspots.
+    pub fn analyze(&self) -> Result<Analysis> {
+        if let (true, Some(path)) = (self.fai, &self.chrom_sizes) {
+            utils::read_fai(path)?;
+        }
+        if self.threads > 0 {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(self.threads).build()?;
+            return pool.install(|| self.analyze_in_pool());
+        }
+        self.analyze_in_pool()
+    }
+
+    fn analyze_in_pool(&self) -> Result<Analysis> {
+        self.print(format_args!("hickit – Hi-C toolkit (Rust)"));
+        self.print(format_args!("============================="));
+
+        if self.region.is_some() && (self.save_coverage.is_some() || self.load_coverage.is_some()) {
+            bail!("A region coverage cannot be saved or loaded; drop --region");
+        }
+        if self.region.is_some() && (self.include_chroms.is_some() || !self.exclude_chroms.is_empty()) {
+            bail!("--region already picks one chromosome; drop --include-chrom and --exclude-chrom");
+        }
+        if let Some(path) = &self.load_coverage {
+            return self.analyze_saved(path);
+        }
+
+        // Create coverage structure (auto-detect pairtools header if present)
+        let chrom_size_path = self.chrom_sizes.as_deref().map(utils::utf8_path).transpose()?;
+        let mut pairs_chr_map: Option<utils::ChrLookup> = None;
+
+        let format = match (self.format, self.input.as_ref()) {
+            (parser::InputFormat::Auto, Some(path)) => parser::sniff_input_format_from_path(path.as_path())?,
+            (parser::InputFormat::Auto, None) => parser::InputFormat::Juicer,
+            (f, _) => f,
+        };
+
+        // Decide source of chromosome names + lengths: pairs header, chrom.size, or hg19 defaults
+        let sniffed = match (format, self.input.as_ref()) {
+            (parser::InputFormat::Pairs, Some(path)) => parser::sniff_pairs_header_from_path(path.as_path()).ok().flatten(),
+            _ => None,
+        };
+        let (genome_names, genome_lengths, lengths_source) = if let Some((map, names, lengths)) = sniffed {
+            pairs_chr_map = Some(map);
+            (names, lengths, "the .pairs header".to_string())
+        } else if let Some(cs) = chrom_size_path {
+            let (names, lengths) = utils::read_chrom_sizes_with_names(cs)?;
+            (names, lengths, cs.to_string())
+        } else {
+            (utils::get_default_genome_names(), utils::get_default_genome_lengths(), HG19_SOURCE.to_string())
+        };
+
+        // With --region the coverage holds only the region, as a single chromosome
+        let region = self.region.as_deref().map(|r| filter::Region::parse(r, None)).transpose()?;
+        let scope = match &region {
+            Some(r) => {
+                let idx = genome_names.iter().position(|n| n == r.chrom).ok_or_else(|| {
+                    anyhow!("Region chromosome '{}' is not in the chromosome sizes", r.chrom)
+                })?;
+                if r.start >= genome_lengths[idx] {
+                    bail!("Region {}:{}-{} starts past the end of {} ({} bp)", r.chrom, r.start, r.end, r.chrom, genome_lengths[idx]);
+                }
+                let mut s = filter::RegionScope::new(r, idx as utils::ChrCode + 1);
+                s.end = s.end.min(genome_lengths[idx] - 1);
+                Some(s)
+            }
+            None => None,
+        };
+        let (retained, excluded) = self.retained_lengths(&genome_names, &genome_lengths, &lengths_source)?;
+        let coverage_lengths = match &scope {
+            Some(s) => vec![s.span_len()],
+            None => retained,
+        };
+
+        // Refuse to allocate dense bins beyond the memory budget instead of getting OOM-killed
+        let max_memory = self.max_memory.or_else(|| utils::available_memory_bytes().map(|b| b / 10 * 8));
+        let mut coverage =
+            Coverage::try_from_lengths(self.bin_width, coverage_lengths, max_memory)?;
+        // Now that we have names + lengths, print computed genome info and settings
+        let genome_size: u64 = genome_lengths.iter().map(|&x| x as u64).sum();
+        let genome_size_warning = self.check_genome_size(genome_size, &lengths_source);
+        let genome_size_override = self.genome_size_override(&lengths_source, region.is_none() && excluded.is_empty());
+        if let Some(bp) = genome_size_override {
+            self.print(format_args!("Genome size: {} bp (--genome-size; bins follow the {} lengths)", bp, HG19_SOURCE));
+        } else if excluded.is_empty() {
+            self.print(format_args!("Genome size: {} bp", genome_size));
+        } else {
+            self.print(format_args!("Genome size: {} bp without {} excluded chromosomes ({})", coverage.total_genome_size(), excluded.len(), excluded.join(", ")));
+        }
+        if let (Some(r), Some(s)) = (&region, &scope) {
+            self.print(format_args!("Region: {}:{}-{} ({} bp; resolution is limited by the reads in it)", r.chrom, s.start, s.end, s.span_len()));
+        }
+        self.print(format_args!("Bin width: {} bp", self.bin_width));
+        self.print(format_args!("Coverage threshold: {} contacts", self.count_threshold));
+        if self.contacts != ContactScope::All {
+            self.print(format_args!("Contacts: {}", self.contacts.describe()));
+        }
+        if self.min_mapq != parser::DEFAULT_MIN_MAPQ {
+            self.print(format_args!("Minimum mapq: {}", self.min_mapq));
+        }
+        self.print(format_args!("Required proportion: {:.1}%", self.prop * 100.0));
+        let format_name = clap::ValueEnum::to_possible_value(&format).map(|v| v.get_name().to_string());
+        self.print(format_args!("Input format: {}", format_name.unwrap_or_default()));
+        self.print(format_args!("Aggregation: chunks of {} pairs, subchunks of {}", self.chunk_pairs, self.subchunk_pairs));
+        self.print(format_args!("Chromosome lookup: {}", utils::chr_lookup_impl()));
+        // Top-10 chromosomes by length (descending)
+        if !genome_names.is_empty() && !genome_lengths.is_empty() {
+            let mut pairs: Vec<(&str, u32)> = genome_names
+                .iter()
+                .map(|s| s.as_str())
+                .zip(genome_lengths.iter().copied())
+                .collect();
+            pairs.sort_unstable_by_key(|p| std::cmp::Reverse(p.1));
+            let topn = pairs.iter().take(10).collect::<Vec<_>>();
+            self.print(format_args!("Top 10 chromosomes by length:"));
+            for (i, (nm, ln)) in topn.into_iter().enumerate() {
+                self.print(format_args!("  {}. {}: {} bp", i + 1, nm, ln));
+            }
+        }
+        self.print(format_args!(""));
+        self.print(format_args!("Initialized coverage tracking for {} chromosomes", coverage.num_chromosomes()));
+
+        // Parse input file and build coverage
+        let pb = parse_progress(self.input.as_deref(), self.progress)?;
+        pb.set_message("Reading merged_nodups file...");
+        let parse_stats = if let Some(path) = self.input.as_ref() {
+            let file = pb.wrap_read(File::open(path)?);
+            use parser::InputCompression::{Gzip, Plain, Zstd};
+            match format {
+                parser::InputFormat::Pairs => {
+                    // Without a #chromsize header, names come from --chrom-size (or hg19)
+                    let chr_map = pairs_chr_map.unwrap_or_else(|| utils::create_lookup_map(chrom_size_path));
+                    match parser::InputCompression::from_path(path) {
+                        Gzip => self.process_pairs(parser::open_pairs_file(file, chr_map)?, &mut coverage, &pb, scope.as_ref())?,
+                        Zstd => self.process_pairs(parser::open_pairs_file_zstd(file, chr_map)?, &mut coverage, &pb, scope.as_ref())?,
+                        Plain => self.process_pairs(parser::open_pairs_file_uncompressed(file, chr_map)?, &mut coverage, &pb, scope.as_ref())?,
+                    }
+                }
+                parser::InputFormat::JuicerShort => match parser::InputCompression::from_path(path) {
+                    Gzip => self.process_pairs(parser::open_short_file(file, chrom_size_path)?, &mut coverage, &pb, scope.as_ref())?,
+                    Zstd => self.process_pairs(parser::open_short_file_zstd(file, chrom_size_path)?, &mut coverage, &pb, scope.as_ref())?,
+                    Plain => self.process_pairs(parser::open_short_file_uncompressed(file, chrom_size_path)?, &mut coverage, &pb, scope.as_ref())?,
+                },
+                parser::InputFormat::Hicpro => match parser::InputCompression::from_path(path) {
+                    Gzip => self.process_pairs(parser::open_hicpro_file(file, chrom_size_path)?, &mut coverage, &pb, scope.as_ref())?,
+                    Zstd => self.process_pairs(parser::open_hicpro_file_zstd(file, chrom_size_path)?, &mut coverage, &pb, scope.as_ref())?,
+                    Plain => self.process_pairs(parser::open_hicpro_file_uncompressed(file, chrom_size_path)?, &mut coverage, &pb, scope.as_ref())?,
+                },
+                _ => match parser::InputCompression::from_path(path) {
+                    Gzip => self.process_pairs(parser::open_file(file, chrom_size_path)?, &mut coverage, &pb, scope.as_ref())?,
+                    Zstd => self.process_pairs(parser::open_file_zstd(file, chrom_size_path)?, &mut coverage, &pb, scope.as_ref())?,
+                    Plain => self.process_pairs(parser::open_file_uncompressed(file, chrom_size_path)?, &mut coverage, &pb, scope.as_ref())?,
+                },
+            }
+        } else {
+            // Read from stdin
+            match format {
+                parser::InputFormat::Pairs => {
+                    let chr_map = utils::create_lookup_map(chrom_size_path);
+                    let iter = parser::open_pairs_file(stdin(), chr_map)?;
+                    self.process_pairs(iter, &mut coverage, &pb, scope.as_ref())?
+                }
+                parser::InputFormat::JuicerShort => {
+                    let iter = parser::open_short_file(stdin(), chrom_size_path)?;
+                    self.process_pairs(iter, &mut coverage, &pb, scope.as_ref())?
+                }
+                parser::InputFormat::Hicpro => {
+                    let iter = parser::open_hicpro_file(stdin(), chrom_size_path)?;
+                    self.process_pairs(iter, &mut coverage, &pb, scope.as_ref())?
+                }
+                _ => {
+                    let iter = parser::open_file(stdin(), chrom_size_path)?;
+                    self.process_pairs(iter, &mut coverage, &pb, scope.as_ref())?
+                }
+            }
+        };
+
+        if let Some((name, lines)) = self.max_unmapped_fraction.and_then(|f| parse_stats.unmapped_over(f)) {
+            pb.finish_and_clear();
+            bail!(
+                "{} of {} lines ({:.2}%) name chromosome '{}', which is not in the chromosome sizes ({})",
+                lines,
+                parse_stats.total_lines,
+                *lines as f64 * 100.0 / parse_stats.total_lines as f64,
+                name,
+                lengths_source
+            );
+        }
+
+        if let Some(path) = &self.save_coverage {
+            pb.set_message("Saving coverage...");
+            coverage.save(path)?;
+        }
+
+        pb.set_message("Computing resolution...");
+
+        // Find resolution
+        let mut report = resolution::find_resolution_with_max_bin(
+            &coverage,
+            self.prop,
+            self.count_threshold,
+            self.step_size,
+            self.max_bin_size,
+            self.progress,
+        );
+        if let Some(bp) = genome_size_override {
+            report.genome_size = bp;
+        }
+
+        pb.finish_and_clear();
+
+        let (names, offset) = match (&region, &scope) {
+            (Some(r), Some(s)) => (vec![r.chrom.to_string()], s.start),
+            _ => (genome_names, 0),
+        };
+        Ok(Analysis { report, pairs_processed: parse_stats.parsed, parse_stats, coverage, names, offset, genome_size_warning })
+    }
+
+    /// The warning when `--genome-size` was given and the chromosome sizes
+    /// in use sum to something else. The hg19 fallback is not checked, as
+    /// `--genome-size` stands in for it (see [`Self::genome_size_override`]).
+    fn check_genome_size(&self, actual: u64, source: &str) -> Option<String> {
+        if source == HG19_SOURCE {
+            return None;
+        }
+        self.genome_size.and_then(|expected| genome_size_mismatch(expected, actual, source))
+    }
+
+    /// `--genome-size` as the reported genome size, when the chromosome sizes
+    /// are the hg19 fallback and the coverage spans all of them.
+    fn genome_size_override(&self, source: &str, whole_genome: bool) -> Option<u64> {
+        self.genome_size.filter(|_| whole_genome && source == HG19_SOURCE)
+    }
+
+    /// One line of the run's stdout report, when [`Self::progress`] is set.
+    fn print(&self, line: std::fmt::Arguments) {
+        if self.progress {
+            println!("{}", line);
+        }
+    }
+
+    /// `lengths` with the chromosomes left out by [`Self::include_chroms`] and
+    /// [`Self::exclude_chroms`] set to 0, so that they get no bins, and the
+    /// names of those chromosomes. A name missing from `names` is an error.
+    fn retained_lengths(&self, names: &[String], lengths: &[u32], source: &str) -> Result<(Vec<u32>, Vec<String>)> {
+        let position = |name: &str, flag: &str| {
+            let alt = match name.strip_prefix("chr") {
+                Some(bare) => bare.to_string(),
+                None => format!("chr{}", name),
+            };
+            names
+                .iter()
+                .position(|n| n == name)
+                .or_else(|| names.iter().position(|n| *n == alt))
+                .ok_or_else(|| anyhow!("Chromosome '{}' of {} is not in the chromosome sizes ({})", name, flag, source))
+        };
+        let mut keep = vec![self.include_chroms.is_none(); names.len()];
+        for name in self.include_chroms.iter().flatten() {
+            keep[position(name, "--include-chrom")?] = true;
+        }
+        for name in &self.exclude_chroms {
+            keep[position(name, "--exclude-chrom")?] = false;
+        }
+        let retained = lengths.iter().zip(&keep).map(|(&len, &k)| if k { len } else { 0 }).collect();
+        let excluded = names.iter().zip(&keep).filter(|(_, &k)| !k).map(|(n, _)| n.clone()).collect();
+        Ok((retained, excluded))
+    }
+
+    /// Search over a saved coverage instead of parsing pairs. Names come from
+    /// the chromosome sizes (or hg19), which must match the saved lengths
+    /// after the same chromosome filters.
+    fn analyze_saved(&self, path: &Path) -> Result<Analysis> {
+        let coverage = Coverage::load(path)?;
+        if coverage.bin_width != self.bin_width {
+            bail!(
+                "{:?} was built with a bin width of {} bp but --bin-width is {}; pass --bin-width {} or rebuild it",
+                path,
+                coverage.bin_width,
+                self.bin_width,
+                coverage.bin_width
+            );
+        }
+        let (names, lengths, lengths_source) = match self.chrom_sizes.as_ref() {
+            Some(cs) => {
+                let (names, lengths) = utils::read_chrom_sizes_with_names(utils::utf8_path(cs)?)?;
+                (names, lengths, cs.display().to_string())
+            }
+            None => (utils::get_default_genome_names(), utils::get_default_genome_lengths(), HG19_SOURCE.to_string()),
+        };
+        let (lengths, excluded) = self.retained_lengths(&names, &lengths, &lengths_source)?;
+        if lengths != coverage.chr_lengths {
+            bail!(
+                "{:?} holds {} chromosomes that do not match the {} chromosome sizes; pass the --chrom-size and chromosome filters it was built with",
+                path,
+                coverage.num_chromosomes(),
+                lengths.len()
+            );
+        }
+        self.print(format_args!("Loaded coverage from {}", path.display()));
+        let genome_size_warning = self.check_genome_size(coverage.total_genome_size(), &lengths_source);
+        let genome_size_override = self.genome_size_override(&lengths_source, excluded.is_empty());
+        match genome_size_override {
+            Some(bp) => self.print(format_args!("Genome size: {} bp (--genome-size; bins follow the {} lengths)", bp, HG19_SOURCE)),
+            None => self.print(format_args!("Genome size: {} bp", coverage.total_genome_size())),
+        }
+        self.print(format_args!("Bin width: {} bp", self.bin_width));
+        self.print(format_args!("Coverage threshold: {} contacts", self.count_threshold));
+        self.print(format_args!("Required proportion: {:.1}%", self.prop * 100.0));
+        self.print(format_args!(""));
+
+        let mut report = resolution::find_resolution_with_max_bin(
+            &coverage,
+            self.prop,
+            self.count_threshold,
+            self.step_size,
+            self.max_bin_size,
+            self.progress,
+        );
+        if let Some(bp) = genome_size_override {
+            report.genome_size = bp;
+        }
+        let parse_stats = parser::ParseStats::default();
+        Ok(Analysis { report, pairs_processed: 0, parse_stats, coverage, names, offset: 0, genome_size_warning })
+    }
+
+    /// Aggregate pairs into `coverage` in chunks; only pairs in `contacts`
+    /// count, and with a `scope`, only pairs with an end in the region are
+    /// kept, in region coordinates. Returns the parser's counts.
+    fn process_pairs<R: BufRead>(
+        &self,
+        iter: parser::PairIterator<R>,
+        coverage: &mut Coverage,
+        pb: &ProgressBar,
+        scope: Option<&filter::RegionScope>,
+    ) -> Result<parser::ParseStats> {
+        let mut iter = iter.verbose(self.verbose).min_mapq(self.min_mapq);
+        let mut count = 0u64;
+        // Sized once for a full chunk; --mem-budget-gb accounts for it
+        let mut buf: Vec<utils::Pair> = Vec::with_capacity(self.chunk_pairs);
+
+        for pair_result in iter.by_ref() {
+            let pair = pair_result?;
+            // Classify before region scoping, which renames the chromosomes
+            if self.contacts.keeps(&pair) {
+                match scope {
+                    Some(s) => buf.extend(s.scope_pair(&pair)),
+                    None => buf.push(pair),
+                }
+            }
+            if buf.len() >= self.chunk_pairs {
+                coverage.add_pairs(&buf, self.subchunk_pairs);
+                buf.clear();
+            }
+            count += 1;
+
+            if count.is_multiple_of(1_000_000) {
+                pb.set_message(format!(
+                    "Processed {:.1}M pairs...",
+                    count as f64 / 1_000_000.0
+                ));
+            }
+        }
+
+        if !buf.is_empty() {
+            coverage.add_pairs(&buf, self.subchunk_pairs);
+            buf.clear();
+        }
+
+        Ok(iter.stats())
+    }
+}
+
+/// Progress of the parsing phase on stderr: a bar over the bytes of `input`
+/// when it is a regular file, advanced as the parser reads them (the
+/// compressed bytes of a .gz/.zst input), otherwise a spinner; hidden
+/// unless `show` is set.
+fn parse_progress(input: Option<&Path>, show: bool) -> Result<ProgressBar> {
+    if !show {
+        return Ok(ProgressBar::hidden());
+    }
+    let size = input.and_then(|p| std::fs::metadata(p).ok()).filter(|m| m.is_file()).map(|m| m.len());
+    let Some(size) = size else {
+        let pb = ProgressBar::new_spinner();
+        pb.set_style(
+            ProgressStyle::default_spinner()
+                .template("{spinner:.green} [{elapsed_precise}] {msg}")?
+                .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ "),
+        );
+        return Ok(pb);
+    };
+    let pb = ProgressBar::new(size);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:30}] {percent}% (ETA {eta}) {msg}")?
+            .progress_chars("=> ")
+            .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ "),
+    );
+    Ok(pb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_temp(name: &str, text: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("hickit-test-{}-{}", std::process::id(), name));
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn builder_runs_the_pipeline_without_the_cli() {
+        let sizes = write_temp("builder.sizes", "chrA\t1000\nchrB\t500\n");
+        // Every 50 bp bin of chrA gets 2 cis ends; chrB only a trans end
+        let mut mnd = String::new();
+        for (i, pos) in (0..1000).step_by(100).enumerate() {
+            mnd.push_str(&format!("0 chrA {} 1 16 chrA {} 2 60 50M SEQ 60 50M SEQ c{} c{}\n", pos + 10, pos + 60, i, i));
+            mnd.push_str(&format!("0 chrA {} 1 16 chrA {} 2 60 50M SEQ 60 50M SEQ d{} d{}\n", pos + 20, pos + 70, i, i));
+        }
+        mnd.push_str("0 chrA 5 1 16 chrB 250 2 60 50M SEQ 60 50M SEQ t0 t0\n");
+        let input = write_temp("builder.mnd.txt", &mnd);
+
+        let builder = AnalysisBuilder::new().input(&input).chrom_sizes(&sizes).bin_width(50).count_threshold(2).prop(0.6).step_size(50);
+        let all = builder.analyze().unwrap();
+        assert_eq!(all.pairs_processed, 21);
+        assert_eq!((all.parse_stats.total_lines, all.parse_stats.rejected()), (21, 0));
+        assert_eq!(all.names, vec!["chrA", "chrB"]);
+        assert_eq!(all.report.total_contacts, 42);
+        assert_eq!(all.report.genome_size, 1500);
+
+        let cis = builder.clone().contacts(ContactScope::Cis).threads(2).run().unwrap();
+        assert_eq!(cis.total_contacts, 40);
+        // 20 of the 32 bins of 50 bp reach 2 contacts
+        assert_eq!((cis.resolution_bp, cis.meets_target), (50, true));
+
+        let err = builder.region("chrC:1-10").run().unwrap_err();
+        assert!(err.to_string().contains("chrC"), "{}", err);
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&sizes).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_chrom_sizes_path_is_an_error() {
+        use std::os::unix::ffi::OsStrExt;
+        let sizes = PathBuf::from(std::ffi::OsStr::from_bytes(b"sizes-\xff.txt"));
+        let err = AnalysisBuilder::new().chrom_sizes(&sizes).analyze().err().expect("a non-UTF-8 path must fail");
+        assert!(err.to_string().contains("not valid UTF-8"), "{}", err);
+    }
+
+    #[test]
+    fn parse_progress_follows_the_bytes_of_a_regular_file() {
+        let text = "0 chrA 10 1 16 chrA 60 2 60 50M SEQ 60 50M SEQ c0 c0\n".repeat(100);
+        let input = write_temp("progress.mnd.txt", &text);
+        let pb = parse_progress(Some(&input), true).unwrap();
+        assert_eq!(pb.length(), Some(text.len() as u64));
+        let mut read = String::new();
+        std::io::Read::read_to_string(&mut pb.wrap_read(File::open(&input).unwrap()), &mut read).unwrap();
+        assert_eq!(pb.position(), text.len() as u64);
+
+        // stdin and missing paths fall back to a spinner
+        assert_eq!(parse_progress(None, true).unwrap().length(), None);
+        std::fs::remove_file(&input).unwrap();
+        assert_eq!(parse_progress(Some(&input), true).unwrap().length(), None);
+    }
+
+    #[test]
+    fn excluded_chromosomes_leave_the_genome_size_and_bins() {
+        let sizes = write_temp("exclude.sizes", "chrA\t1000\nchrM\t200\n");
+        let mut mnd = String::new();
+        for pos in (0..1000).step_by(50) {
+            mnd.push_str(&format!("0 chrA {} 1 16 chrA {} 2 60 50M SEQ 60 50M SEQ c{} c{}\n", pos + 10, pos + 20, pos, pos));
+        }
+        // chrM is covered deeply but holds no signal for the nuclear genome
+        for pos in (0..200).step_by(10) {
+            mnd.push_str(&format!("0 chrM {} 1 16 chrM {} 2 60 50M SEQ 60 50M SEQ m{} m{}\n", pos, pos + 5, pos, pos));
+        }
+        mnd.push_str("0 chrA 5 1 16 chrM 50 2 60 50M SEQ 60 50M SEQ t0 t0\n");
+        let input = write_temp("exclude.mnd.txt", &mnd);
+
+        let builder = AnalysisBuilder::new().input(&input).chrom_sizes(&sizes).bin_width(50).count_threshold(2).prop(0.8).step_size(50);
+        let all = builder.run().unwrap();
+        assert_eq!((all.genome_size, all.total_contacts), (1200, 82));
+
+        let nuclear = builder.clone().exclude_chroms(vec!["M".to_string()]).analyze().unwrap();
+        assert_eq!((nuclear.report.genome_size, nuclear.report.total_contacts), (1000, 41));
+        assert_eq!(nuclear.coverage.chr_lengths, vec![1000, 0]);
+        assert_eq!(nuclear.report.non_zero_bins, 20);
+        let included = builder.clone().include_chroms(vec!["chrA".to_string()]).run().unwrap();
+        assert_eq!((included.genome_size, included.resolution_bp), (1000, 50));
+
+        let err = builder.clone().exclude_chroms(vec!["chrZ".to_string()]).run().unwrap_err();
+        assert!(err.to_string().contains("'chrZ' of --exclude-chrom"), "{}", err);
+        assert!(builder.region("chrA:1-100").exclude_chroms(vec!["chrM".to_string()]).run().is_err());
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&sizes).unwrap();
+    }
+
+    #[test]
+    fn strict_runs_fail_on_a_chromosome_missing_from_the_sizes() {
+        let sizes = write_temp("strict.sizes", "chrA\t1000\n");
+        let mut mnd = String::new();
+        for i in 0..9 {
+            mnd.push_str(&format!("0 chrA {} 1 16 chrA 900 2 60 50M SEQ 60 50M SEQ c{} c{}\n", i * 10, i, i));
+        }
+        mnd.push_str("0 chrA 5 1 16 chrB 250 2 60 50M SEQ 60 50M SEQ t0 t0\n");
+        let input = write_temp("strict.mnd.txt", &mnd);
+
+        let builder = AnalysisBuilder::new().input(&input).chrom_sizes(&sizes).count_threshold(1);
+        let lenient = builder.analyze().unwrap();
+        assert_eq!(lenient.parse_stats.unmapped_chroms, vec![("chrB".to_string(), 1)]);
+        // chrB is 1 of 10 lines
+        assert!(builder.clone().max_unmapped_fraction(0.1).run().is_ok());
+        let err = builder.max_unmapped_fraction(0.05).run().unwrap_err().to_string();
+        assert!(err.starts_with("1 of 10 lines (10.00%) name chromosome 'chrB'"), "{}", err);
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&sizes).unwrap();
+    }
+
+    #[test]
+    fn warns_when_the_genome_size_is_off_by_more_than_five_percent() {
+        assert_eq!(genome_size_mismatch(1_000_000, 1_040_000, "chrom.size"), None);
+        assert_eq!(genome_size_mismatch(1_000_000, 960_000, "chrom.size"), None);
+        assert_eq!(
+            genome_size_mismatch(3_137_161_264, 2_428_425_688, "built-in hg19").unwrap(),
+            "Warning: --genome-size is 3137161264 bp but the chromosome sizes (built-in hg19) sum to 2428425688 bp (-22.6%); check the chrom.size file"
+        );
+        assert!(genome_size_mismatch(1_000, 2_000, "x").unwrap().contains("(+100.0%)"));
+    }
+
+    #[test]
+    fn genome_size_checks_given_sizes_and_stands_in_for_hg19() {
+        let sizes = write_temp("gsize.sizes", "chr1\t1000\nchr2\t500\n");
+        let input = write_temp("gsize.mnd.txt", "0 chr1 10 1 16 chr1 60 2 60 50M SEQ 60 50M SEQ c0 c0\n");
+
+        // The mismatch comes back without progress output
+        let given = AnalysisBuilder::new().input(&input).chrom_sizes(&sizes).count_threshold(1).step_size(50);
+        assert_eq!(given.clone().genome_size(1_500).analyze().unwrap().genome_size_warning, None);
+        let off = given.genome_size(2_000).analyze().unwrap();
+        assert_eq!(off.report.genome_size, 1_500);
+        assert!(off.genome_size_warning.unwrap().contains("sum to 1500 bp (-25.0%)"));
+
+        // Without chromosome sizes, it replaces the hg19 total in the report
+        let hg19 = AnalysisBuilder::new().input(&input).bin_width(1_000_000).count_threshold(1).step_size(1_000_000);
+        let default = hg19.clone().analyze().unwrap();
+        assert_eq!(default.report.genome_size, utils::get_default_genome_lengths().iter().map(|&l| l as u64).sum::<u64>());
+        let stated = hg19.clone().genome_size(12_000_000).analyze().unwrap();
+        assert_eq!((stated.report.genome_size, stated.genome_size_warning), (12_000_000, None));
+        assert_eq!(stated.report.resolution_bp, default.report.resolution_bp);
+        let nuclear = hg19.genome_size(12_000_000).exclude_chroms(vec!["Y".to_string()]).analyze().unwrap();
+        assert_ne!(nuclear.report.genome_size, 12_000_000);
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&sizes).unwrap();
+    }
+
+    #[test]
+    fn chunk_sizes_shrink_with_the_memory_budget() {
+        let gb = 1u64 << 30;
+        let mut last = usize::MAX;
+        for budget in [8 * gb, 2 * gb, gb / 2, gb / 16] {
+            let (chunk, subchunk) = chunking_for_budget(budget, 16);
+            assert!(chunk < last, "{} pairs at {} bytes", chunk, budget);
+            // Buffers plus the subchunks in flight stay within the budget
+            assert!(chunk as u64 * CHUNK_BYTES_PER_PAIR + 16 * 64 * subchunk as u64 <= budget);
+            last = chunk;
+        }
+        assert_eq!(chunking_for_budget(0, 16), (16_000, 16_000));
+        // Fewer threads get larger subchunks, up to the cap
+        assert_eq!(chunking_for_budget(gb, 1).1, 128_000);
+    }
+}
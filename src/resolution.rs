@@ -1,66 +1,191 @@
-use crate::coverage::Coverage;
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::coverage::{Coverage, CoverageFraction, ZeroBins};
+
+/// Outcome of [`find_resolution`] together with the inputs and data summary
+/// it was computed from.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResolutionReport {
+    /// Smallest bin size meeting the target, or the search limit when none did
+    pub resolution_bp: u32,
+    pub genome_size: u64,
+    pub bin_width: u32,
+    pub count_threshold: u32,
+    pub prop: f64,
+    pub total_contacts: u64,
+    /// Base bins (`bin_width`) with at least one contact
+    pub non_zero_bins: u64,
+    /// False when the search stopped at its limit without reaching `prop`
+    pub meets_target: bool,
+}
 
 pub fn find_resolution(
     coverage: &Coverage,
     prop: f64,
     count_threshold: u32,
     step_size: u32,
-) -> u32 {
-    let genome_size = coverage.total_genome_size();
+) -> ResolutionReport {
+    find_resolution_with_max_bin(coverage, prop, count_threshold, step_size, DEFAULT_MAX_BIN_SIZE, false)
+}
 
-    let mut low = coverage.bin_width;
-    let mut high = coverage.bin_width;
+/// [`find_resolution`] with the coarse search stepping up to `max_bin_size`;
+/// past it, the bin size doubles up to the longest chromosome. The report's
+/// `meets_target` is false only when even one bin per chromosome falls short.
+/// `log` prints the data summary and the progress of the search to stdout.
+pub fn find_resolution_with_max_bin(
+    coverage: &Coverage,
+    prop: f64,
+    count_threshold: u32,
+    step_size: u32,
+    max_bin_size: u32,
+    log: bool,
+) -> ResolutionReport {
+    let genome_size = coverage.total_genome_size();
 
-    println!("Starting resolution search...");
-    println!("Genome size: {} bp", genome_size);
+    if log {
+        println!("Starting resolution search...");
+        println!("Genome size: {} bp", genome_size);
+    }
 
     // Analyze data sparsity to set reasonable bounds
     let total_contacts = coverage.get_total_contacts();
     let non_zero_bins = coverage.get_non_zero_bins();
-    let total_base_bins = genome_size / coverage.bin_width as u64;
+    let total_base_bins = coverage.bins.len() as u64;
 
-    println!("Data analysis:");
-    println!("  Total contacts: {}", total_contacts);
-    println!(
-        "  Non-zero 50bp bins: {} / {} ({:.2}%)",
+    if log {
+        println!("Data analysis:");
+        println!("  Total contacts: {}", total_contacts);
+        println!(
+            "  Non-zero 50bp bins: {} / {} ({:.2}%)",
+            non_zero_bins,
+            total_base_bins,
+            non_zero_bins as f64 * 100.0 / total_base_bins as f64
+        );
+    }
+
+    let report = |resolution_bp: u32, meets_target: bool| ResolutionReport {
+        resolution_bp,
+        genome_size,
+        bin_width: coverage.bin_width,
+        count_threshold,
+        prop,
+        total_contacts,
         non_zero_bins,
-        total_base_bins,
-        non_zero_bins as f64 * 100.0 / total_base_bins as f64
-    );
+        meets_target,
+    };
 
     // If data is very sparse, adjust search strategy
     let sparsity = non_zero_bins as f64 / total_base_bins as f64;
     let adjusted_step_size = if sparsity < 0.01 {
-        println!(
-            "  Detected sparse data ({:.4}% coverage), using larger step size",
-            sparsity * 100.0
-        );
-        step_size * 10
+        if log {
+            println!(
+                "  Detected sparse data ({:.4}% coverage), using larger step size",
+                sparsity * 100.0
+            );
+        }
+        step_size.saturating_mul(10)
     } else {
         step_size
     };
 
-    // Find reasonable upper bound with large steps, but limit maximum
-    let max_reasonable_bin = 10_000_000; // 10 Mb maximum
-    let limit = max_reasonable_bin
-        .min((genome_size.min(u64::from(u32::MAX))) as u32);
+    // Find reasonable upper bound with large steps up to the cap, then doubling
+    let longest = coverage.chr_lengths.iter().copied().max().unwrap_or(0);
+    let (resolution_bp, meets_target) = search_bin_size(
+        coverage.bin_width,
+        max_bin_size,
+        longest,
+        prop,
+        adjusted_step_size,
+        log,
+        |bin_size| coverage.coverage_at(bin_size, count_threshold, ZeroBins::Uncovered),
+    );
+    if log && !meets_target {
+        println!(
+            "Error: No bin size up to {} bp satisfies >= {:.1}% bins with >= {} contacts.",
+            resolution_bp,
+            prop * 100.0,
+            count_threshold
+        );
+        println!(
+            "Returning upper limit ({} bp). Result does not satisfy the target proportion.",
+            resolution_bp
+        );
+    }
+    report(resolution_bp, meets_target)
+}
+
+/// Resolution of every chromosome on its own, as (0-based chromosome index,
+/// bin size). Runs the same coarse and binary search as [`find_resolution`]
+/// over each chromosome's bins, so small contigs are not drowned out by the
+/// large chromosomes. A chromosome that never reaches `prop` reports its
+/// length rounded up to `bin_width`; as in [`find_resolution_with_max_bin`],
+/// steps past `max_bin_size` double the bin size. Chromosomes without bins
+/// (length 0, as left out by `--exclude-chrom`) are skipped.
+pub fn find_resolution_per_chrom(
+    coverage: &Coverage,
+    prop: f64,
+    count_threshold: u32,
+    step_size: u32,
+    max_bin_size: u32,
+) -> Vec<(usize, u32)> {
+    (0..coverage.num_chromosomes())
+        .into_par_iter()
+        .filter(|&chr_idx| coverage.chr_lengths[chr_idx] > 0)
+        .map(|chr_idx| {
+            let len = coverage.chr_lengths[chr_idx];
+            let bins = coverage.chr_bins(chr_idx);
+            let non_zero = bins.iter().filter(|&&c| c > 0).count() as f64;
+            let sparsity = non_zero / bins.len().max(1) as f64;
+            let step = if sparsity < 0.01 { step_size.saturating_mul(10) } else { step_size };
+            let (bp, _) =
+                search_bin_size(coverage.bin_width, max_bin_size, len, prop, step, false, |bin_size| {
+                    coverage.count_good_bins_for_chr(chr_idx, bin_size, count_threshold)
+                });
+            (chr_idx, bp)
+        })
+        .collect()
+}
+
+/// Bin size up to which the coarse search steps linearly (10 Mb).
+pub const DEFAULT_MAX_BIN_SIZE: u32 = 10_000_000;
+
+/// Smallest multiple of `bin_width` up to `max_len` (rounded up) at which
+/// `tally` reaches `prop`: a coarse scan in `step_size` increments up to
+/// `max_bin_size`, then doubling, finds an upper bound, and a binary search
+/// narrows it down. Returns (bin size, whether `prop` was met); `log` prints
+/// the progress of the genome-wide search.
+fn search_bin_size(
+    bin_width: u32,
+    max_bin_size: u32,
+    max_len: u32,
+    prop: f64,
+    step_size: u32,
+    log: bool,
+    tally: impl Fn(u32) -> CoverageFraction,
+) -> (u32, bool) {
+    let limit = round_to_bin_multiple(max_len.max(1), bin_width);
+    let cap = round_to_bin_multiple(max_bin_size.max(1), bin_width).min(limit);
+    let mut low = bin_width;
+    let mut high = bin_width;
     let mut iteration = 0;
     let mut found_upper = false;
+    let mut doubling = false;
 
     loop {
         iteration += 1;
-        if iteration % 10 == 0 {
+        if log && iteration % 10 == 0 {
             println!(
                 "  Coarse search iteration {}: testing bin size {}",
                 iteration, high
             );
         }
 
-        let good_bins = coverage.count_good_bins(high, count_threshold);
-        let total_bins = genome_size / high as u64;
+        let t = tally(high);
+        let (good_bins, total_bins) = (t.covered, t.total);
         let required_bins = (prop * total_bins as f64) as u64;
 
-        if iteration <= 5 {
+        if log && iteration <= 5 {
             println!(
                 "  Bin size: {}, Good bins: {}, Total bins: {}, Required: {}",
                 high, good_bins, total_bins, required_bins
@@ -68,71 +193,74 @@ pub fn find_resolution(
         }
 
         if good_bins >= required_bins {
-            println!(
-                "Found upper bound: {} bp (good bins: {}/{})",
-                high, good_bins, total_bins
-            );
+            if log {
+                println!(
+                    "Found upper bound: {} bp (good bins: {}/{})",
+                    high, good_bins, total_bins
+                );
+            }
             found_upper = true;
             break;
         }
 
-        // If we've gone too far without finding an upper bound, stop
+        // One bin per chromosome is as coarse as it gets
         if high >= limit {
-            println!(
-                "Warning: Reached search limit ({} bp) without meeting requirement.",
-                limit
-            );
-            // Ensure 'high' is within limit and aligned to bin multiple
-            high = round_to_bin_multiple(limit, coverage.bin_width);
+            if log {
+                println!(
+                    "Warning: Reached search limit ({} bp) without meeting requirement.",
+                    limit
+                );
+            }
             break;
         }
+        if high >= cap && !doubling {
+            doubling = true;
+            if log {
+                println!(
+                    "Reached --max-bin-size ({} bp) without meeting requirement; doubling up to {} bp",
+                    cap, limit
+                );
+            }
+        }
 
         low = high;
         // Increase and align to multiple of base bin width
-        let mut next = high.saturating_add(adjusted_step_size);
-        next = round_to_bin_multiple(next, coverage.bin_width);
+        let mut next = if doubling { high.saturating_mul(2) } else { high.saturating_add(step_size).min(cap) };
+        next = round_to_bin_multiple(next, bin_width);
         if next == high { // avoid stalling if step < bin width
-            next = next.saturating_add(coverage.bin_width);
+            next = next.saturating_add(bin_width);
         }
-        high = next;
+        high = next.min(limit);
     }
 
     if !found_upper {
-        println!(
-            "Error: No bin size up to {} bp satisfies >= {:.1}% bins with >= {} contacts.",
-            high,
-            prop * 100.0,
-            count_threshold
-        );
-        println!(
-            "Returning upper limit ({} bp). Result does not satisfy the target proportion.",
-            high
-        );
-        return high;
+        return (high, false);
     }
 
-    println!("Binary search range: {} - {} bp", low, high);
+    if log {
+        println!("Binary search range: {} - {} bp", low, high);
+    }
 
     // Binary search for exact resolution
     let mut binary_iteration = 0;
-    while high > low + coverage.bin_width {
+    while high > low + bin_width {
         binary_iteration += 1;
-        let mid = round_to_bin_multiple(low + (high - low) / 2, coverage.bin_width);
+        let mid = round_to_bin_multiple(low + (high - low) / 2, bin_width);
 
-        if binary_iteration % 5 == 0 || binary_iteration <= 3 {
+        if log && (binary_iteration % 5 == 0 || binary_iteration <= 3) {
             println!(
                 "  Binary search iteration {}: testing {}",
                 binary_iteration, mid
             );
         }
 
-        let good_bins = coverage.count_good_bins(mid, count_threshold);
-        let total_bins = genome_size / mid as u64;
+        let t = tally(mid);
+        let (good_bins, total_bins) = (t.covered, t.total);
         let required_bins = (prop * total_bins as f64) as u64;
 
         if good_bins >= required_bins {
             high = mid;
-            if binary_iteration <= 3 {
+            if log && binary_iteration <= 3 {
                 println!(
                     "    Success: {} good bins >= {} required",
                     good_bins, required_bins
@@ -140,7 +268,7 @@ pub fn find_resolution(
             }
         } else {
             low = mid;
-            if binary_iteration <= 3 {
+            if log && binary_iteration <= 3 {
                 println!(
                     "    Failed: {} good bins < {} required",
                     good_bins, required_bins
@@ -150,20 +278,24 @@ pub fn find_resolution(
 
         // Safety check to prevent infinite loop
         if binary_iteration > 100 {
-            println!(
-                "Warning: Binary search taking too long, stopping at iteration {}",
-                binary_iteration
-            );
+            if log {
+                println!(
+                    "Warning: Binary search taking too long, stopping at iteration {}",
+                    binary_iteration
+                );
+            }
             break;
         }
     }
 
-    println!("Final resolution: {} bp", high);
-    high
+    if log {
+        println!("Final resolution: {} bp", high);
+    }
+    (high, true)
 }
 
 fn round_to_bin_multiple(value: u32, bin_width: u32) -> u32 {
-    ((value + bin_width - 1) / bin_width) * bin_width
+    value.div_ceil(bin_width) * bin_width
 }
 
 #[cfg(test)]
@@ -177,4 +309,63 @@ mod tests {
         assert_eq!(round_to_bin_multiple(125, 50), 150);
         assert_eq!(round_to_bin_multiple(1, 50), 50);
     }
+
+    #[test]
+    fn report_carries_search_inputs_and_data_summary() {
+        let mut coverage = Coverage::try_from_lengths(50, vec![1000], None).unwrap();
+        for pos in (0..1000).step_by(10) {
+            coverage.add_pair(&crate::utils::Pair { chr1: 1, pos1: pos, chr2: 1, pos2: pos, weight: 1 });
+        }
+        // 10 contacts per 50 bp bin: 100 bp bins are the first to reach 20
+        let report = find_resolution(&coverage, 1.0, 20, 50);
+        assert_eq!(report.resolution_bp, 100);
+        assert!(report.meets_target);
+        assert_eq!((report.genome_size, report.bin_width, report.count_threshold), (1000, 50, 20));
+        assert_eq!((report.total_contacts, report.non_zero_bins), (200, 20));
+
+        let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["resolution_bp"], 100);
+        assert_eq!(json["prop"], 1.0);
+    }
+
+    #[test]
+    fn per_chrom_search_resolves_each_chromosome_on_its_own() {
+        // chr1 is dense (10 contacts per 50 bp bin), chr2 has one end every 200 bp
+        let mut coverage = Coverage::from_lengths(50, vec![1000, 1000]);
+        for pos in (0..1000).step_by(5) {
+            coverage.increment(1, pos);
+        }
+        for pos in (0..1000).step_by(200) {
+            coverage.increment(2, pos);
+        }
+        let per_chrom = find_resolution_per_chrom(&coverage, 1.0, 10, 50, DEFAULT_MAX_BIN_SIZE);
+        assert_eq!(per_chrom, vec![(0, 50), (1, 1000)]);
+        // The genome-wide search only needs 50% of the combined bins at 50 bp
+        assert_eq!(find_resolution(&coverage, 0.5, 10, 50).resolution_bp, 50);
+    }
+
+    #[test]
+    fn sparse_data_with_a_huge_step_size_does_not_overflow() {
+        // 1 of 2000 bins covered triggers the 10x step
+        let mut coverage = Coverage::from_lengths(50, vec![100_000]);
+        coverage.increment(1, 10);
+        assert_eq!(find_resolution_per_chrom(&coverage, 1.0, 1, u32::MAX, DEFAULT_MAX_BIN_SIZE), vec![(0, 100_000)]);
+        assert_eq!(find_resolution_with_max_bin(&coverage, 1.0, 1, u32::MAX, DEFAULT_MAX_BIN_SIZE, false).resolution_bp, 100_000);
+    }
+
+    #[test]
+    fn search_doubles_past_the_max_bin_size() {
+        // One contact every 400 bp on 4 kb: 2 contacts need 800 bp bins
+        let mut coverage = Coverage::from_lengths(50, vec![4000]);
+        for pos in (0..4000).step_by(400) {
+            coverage.increment(1, pos);
+        }
+        let report = find_resolution_with_max_bin(&coverage, 1.0, 2, 50, 200, false);
+        assert_eq!((report.resolution_bp, report.meets_target), (800, true));
+        assert_eq!(find_resolution(&coverage, 1.0, 2, 50).resolution_bp, 800);
+
+        // Even a single 4 kb bin has only 10 contacts
+        let report = find_resolution_with_max_bin(&coverage, 1.0, 11, 50, 200, false);
+        assert_eq!((report.resolution_bp, report.meets_target), (4000, false));
+    }
 }
@@ -0,0 +1,148 @@
+use std::io::{self, Read, Write};
+
+// Magic string for slice files (no NUL terminator)
+pub const HICSLICE_MAGIC: &[u8] = b"HICSLICE";
+
+/// Size of one serialized record: i16 key1, i32 binX, i16 key2, i32 binY, f32 value.
+pub const RECORD_BYTES: usize = 16;
+
+/// Most chromosome keys a header can hold: keys are i16.
+const MAX_KEYS: i32 = 1 << 16;
+
+/// Longest chromosome name a header may store, in bytes.
+const MAX_NAME_BYTES: i32 = 1 << 16;
+
+/// One contact as stored in a `.slc(.gz)` slice file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SliceRecord {
+    pub key1: i16,
+    pub bin_x: i32,
+    pub key2: i16,
+    pub bin_y: i32,
+    pub counts: f32,
+}
+
+impl SliceRecord {
+    /// Genome-wide order: chromosome key, then bin, for both anchors.
+    #[inline]
+    pub fn sort_key(&self) -> (i16, i32, i16, i32) {
+        (self.key1, self.bin_x, self.key2, self.bin_y)
+    }
+
+    #[inline]
+    pub fn to_bytes(&self) -> [u8; RECORD_BYTES] {
+        let mut b = [0u8; RECORD_BYTES];
+        b[0..2].copy_from_slice(&self.key1.to_le_bytes());
+        b[2..6].copy_from_slice(&self.bin_x.to_le_bytes());
+        b[6..8].copy_from_slice(&self.key2.to_le_bytes());
+        b[8..12].copy_from_slice(&self.bin_y.to_le_bytes());
+        b[12..16].copy_from_slice(&self.counts.to_le_bytes());
+        b
+    }
+
+    #[inline]
+    pub fn from_bytes(b: &[u8; RECORD_BYTES]) -> Self {
+        SliceRecord {
+            key1: i16::from_le_bytes([b[0], b[1]]),
+            bin_x: i32::from_le_bytes([b[2], b[3], b[4], b[5]]),
+            key2: i16::from_le_bytes([b[6], b[7]]),
+            bin_y: i32::from_le_bytes([b[8], b[9], b[10], b[11]]),
+            counts: f32::from_le_bytes([b[12], b[13], b[14], b[15]]),
+        }
+    }
+
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&self.to_bytes())
+    }
+
+    /// Read the next record; `Ok(None)` on a clean end of stream.
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<Option<Self>> {
+        let mut b = [0u8; RECORD_BYTES];
+        let mut filled = 0;
+        while filled < RECORD_BYTES {
+            let n = r.read(&mut b[filled..])?;
+            if n == 0 {
+                if filled == 0 {
+                    return Ok(None);
+                }
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("truncated slice record ({} of {} bytes)", filled, RECORD_BYTES),
+                ));
+            }
+            filled += n;
+        }
+        Ok(Some(Self::from_bytes(&b)))
+    }
+}
+
+/// Resolution and chromosome key table at the start of a slice file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SliceHeader {
+    pub binsize: i32,
+    /// (chromosome name, key) as written by [`write_header`]
+    pub keys: Vec<(String, i16)>,
+}
+
+impl SliceHeader {
+    /// Chromosome name of `key`, if the table has it.
+    pub fn name(&self, key: i16) -> Option<&str> {
+        self.keys.iter().find(|(_, k)| *k == key).map(|(n, _)| n.as_str())
+    }
+}
+
+/// Read and check the slice header written by [`write_header`].
+pub fn read_header<R: Read>(r: &mut R) -> io::Result<SliceHeader> {
+    let mut magic = [0u8; HICSLICE_MAGIC.len()];
+    r.read_exact(&mut magic)?;
+    if magic != HICSLICE_MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a slice file (missing HICSLICE magic)"));
+    }
+    let mut b4 = [0u8; 4];
+    let mut read_i32 = |r: &mut R| -> io::Result<i32> {
+        r.read_exact(&mut b4)?;
+        Ok(i32::from_le_bytes(b4))
+    };
+    let binsize = read_i32(r)?;
+    let nkeys = read_i32(r)?;
+    if !(0..=MAX_KEYS).contains(&nkeys) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("bad key count {} (0 to {})", nkeys, MAX_KEYS)));
+    }
+    // Sizes come from the file: only trust them as far as the bytes go
+    let mut keys = Vec::with_capacity((nkeys as usize).min(1024));
+    for _ in 0..nkeys {
+        let len = read_i32(r)?;
+        if !(0..=MAX_NAME_BYTES).contains(&len) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("bad name length {} (0 to {} bytes)", len, MAX_NAME_BYTES),
+            ));
+        }
+        let mut name = Vec::new();
+        r.by_ref().take(len as u64).read_to_end(&mut name)?;
+        if name.len() < len as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("truncated chromosome name ({} of {} bytes)", name.len(), len),
+            ));
+        }
+        let mut key = [0u8; 2];
+        r.read_exact(&mut key)?;
+        keys.push((String::from_utf8_lossy(&name).into_owned(), i16::from_le_bytes(key)));
+    }
+    Ok(SliceHeader { binsize, keys })
+}
+
+/// Write the slice header: magic, resolution, then the chromosome key table.
+pub fn write_header<W: Write>(w: &mut W, binsize: i32, keys: &[(String, i16)]) -> io::Result<()> {
+    w.write_all(HICSLICE_MAGIC)?;
+    w.write_all(&binsize.to_le_bytes())?;
+    w.write_all(&(keys.len() as i32).to_le_bytes())?;
+    for (name, key) in keys {
+        let nb = name.as_bytes();
+        w.write_all(&(nb.len() as i32).to_le_bytes())?;
+        w.write_all(nb)?;
+        w.write_all(&key.to_le_bytes())?;
+    }
+    Ok(())
+}
@@ -1,49 +1,138 @@
 use anyhow::{anyhow, Context, Result};
-use flate2::read::ZlibDecoder;
+use flate2::read::{MultiGzDecoder, ZlibDecoder};
 use flate2::write::GzEncoder;
 use flate2::Compression;
-use std::collections::BTreeMap;
-use std::collections::HashMap;
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::RangeInclusive;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Mutex, PoisonError};
 
-// Magic string for slice files (no NUL terminator)
-const HICSLICE_MAGIC: &[u8] = b"HICSLICE";
+use crate::bgzf::BgzfWriter;
+use crate::coverage::{coverage_fraction, ContactScope, CoverageFraction, ZeroBins};
+use crate::extsort::{ExternalSorter, DEFAULT_RUN_RECORDS};
+use crate::filter::Region;
+use crate::slice::{self, SliceRecord};
 
 // Minimal structures
 #[derive(Clone, Debug)]
 struct IndexEntry { size: i64, position: i64 }
 
+/// A chromosome from the .hic header; index 0 is the "All" pseudo-chromosome.
 #[derive(Clone, Debug)]
-struct Chromosome { name: String, index: i32, length: i64 }
+pub struct Chromosome { pub name: String, pub index: i32, pub length: i64 }
 
+/// An open .hic file. Header metadata is read on open; matrices and blocks are
+/// read on demand, from a file ([`HicFile::open`]) or any seekable reader
+/// ([`HicFile::from_reader`]).
+///
+/// Versions 6 to 9 are read. Version 9 changed these fields (v8 in brackets):
+/// - header: an `nvi_pos`/`nvi_len` pair (i64) after the genome id, locating
+///   the normalized expected values and normalization vector index; chromosome
+///   lengths are i64 [i32]
+/// - footer: the byte count at the master index position is i64 [i32]; expected
+///   vectors have an i64 value count and f32 values and scale factors [i32,
+///   f64]; vector index entries end with an i64 size [i32]
+/// - normalization vectors: i64 length and f32 values [i32, f64]
+/// - blocks: two extra flag bytes for int (rather than short) bin positions,
+///   and intra-chromosomal blocks are numbered along the diagonal (see
+///   [`region_block_numbers`])
+///
+/// The master index entries (key, i64 position, i32 size) and the matrix and
+/// zoom level records are the same in both.
 #[allow(dead_code)]
 #[derive(Debug)]
-struct HicFile {
-    file: BufReader<File>,
+pub struct HicFile<R = BufReader<File>> {
+    // Locked for block reads, which worker threads share
+    file: Mutex<R>,
     version: i32,
     master: i64,
     genome_id: String,
+    // Header key/value attributes, e.g. `software` and the `statistics` text
+    attributes: Vec<(String, String)>,
     nvi_pos: i64,
     nvi_len: i64,
     chromosomes: Vec<Chromosome>,
     resolutions: Vec<i32>,
+    frag_resolutions: Vec<i32>,
+    // Restriction-site count per chromosome (empty when there are no FRAG resolutions)
+    frag_site_counts: Vec<i32>,
+    // Names the file in errors
     path: PathBuf,
+    // Expected value and normalization vector indexes from the footer, read on first use
+    footer: Option<Footer>,
+    // Matrix position per (chr1, chr2) from the master index, read on first use
+    matrix_positions: Option<HashMap<(i32, i32), i64>>,
+    // Zoom levels of the matrix records read so far, with their block index positions
+    matrix_zooms: HashMap<(i32, i32), Vec<(ZoomHeader, u64)>>,
+    // Extra attempts for a block read failing with a transient IO error
+    io_retries: u32,
+    // Report each retried block read on stderr
+    verbose: bool,
+}
+
+/// Index of the footer sections after the master index.
+#[derive(Clone, Debug, Default)]
+struct Footer { expected: Vec<ExpectedEntry>, norms: Vec<NormEntry> }
+
+/// One entry of the footer's normalization vector index.
+#[derive(Clone, Debug)]
+struct NormEntry { norm: String, chr_idx: i32, unit: String, resolution: i32, position: i64 }
+
+/// Location of one expected-value record; `norm` is `NONE` for the raw section.
+#[derive(Clone, Debug)]
+struct ExpectedEntry { norm: String, unit: String, resolution: i32, position: i64 }
+
+/// Expected contacts by distance (in bins) at one resolution, shared by all
+/// chromosomes, with the per-chromosome factors that scale it to each one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExpectedValues {
+    pub values: Vec<f64>,
+    /// (chromosome index, factor); chromosomes without one use the vector as is
+    pub chr_scale_factors: Vec<(i32, f64)>,
+}
+
+impl ExpectedValues {
+    /// Expected count at `distance` bins on chromosome `chr_idx`
+    /// (`values[distance] / factor`); `None` beyond the stored vector.
+    pub fn at(&self, chr_idx: i32, distance: usize) -> Option<f64> {
+        let v = *self.values.get(distance)?;
+        match self.chr_scale_factors.iter().find(|(c, _)| *c == chr_idx) {
+            Some(&(_, f)) => Some(v / f),
+            None => Some(v),
+        }
+    }
 }
 
 impl HicFile {
-    fn open(path: &Path) -> Result<Self> {
+    pub fn open(path: &Path) -> Result<Self> {
         let file = File::open(path).with_context(|| format!("Open {:?}", path))?;
-        let mut reader = BufReader::new(file);
+        HicFile::read_header(BufReader::new(file), path.to_path_buf())
+    }
+}
+
+impl<R: Read + Seek> HicFile<R> {
+    /// Read the header from `reader`, e.g. a `Cursor` over an in-memory
+    /// file; errors name it `<reader>`.
+    pub fn from_reader(reader: R) -> Result<Self> {
+        HicFile::read_header(reader, PathBuf::from("<reader>"))
+    }
+
+    fn read_header(mut reader: R, path: PathBuf) -> Result<Self> {
         if !read_magic(&mut reader)? { return Err(anyhow!("Not a .hic file: missing HIC magic")); }
         let version = read_i32(&mut reader)?;
         if version < 6 { return Err(anyhow!("Unsupported .hic version {} (<6)", version)); }
         let master = read_i64(&mut reader)?;
         let genome_id = read_cstring(&mut reader)?;
         let (nvi_pos, nvi_len) = if version > 8 { (read_i64(&mut reader)?, read_i64(&mut reader)?) } else { (0, 0) };
-        let nattr = read_i32(&mut reader)?;
-        for _ in 0..nattr { let _ = read_cstring(&mut reader)?; let _ = read_cstring(&mut reader)?; }
+        let nattr = read_i32(&mut reader)?.max(0) as usize;
+        let mut attributes = Vec::with_capacity(nattr);
+        for _ in 0..nattr { attributes.push((read_cstring(&mut reader)?, read_cstring(&mut reader)?)); }
         let num_chromosomes = read_i32(&mut reader)? as usize;
         let mut chromosomes = Vec::with_capacity(num_chromosomes);
         for i in 0..num_chromosomes {
@@ -54,30 +143,615 @@ impl HicFile {
         let nbp = read_i32(&mut reader)? as usize;
         let mut resolutions = Vec::with_capacity(nbp);
         for _ in 0..nbp { resolutions.push(read_i32(&mut reader)?); }
-        Ok(HicFile { file: reader, version, master, genome_id, nvi_pos, nvi_len, chromosomes, resolutions, path: path.to_path_buf() })
+        let nfrag = read_i32(&mut reader)?.max(0) as usize;
+        let mut frag_resolutions = Vec::with_capacity(nfrag);
+        for _ in 0..nfrag { frag_resolutions.push(read_i32(&mut reader)?); }
+        // Site positions per chromosome are only present when FRAG resolutions exist
+        let mut frag_site_counts = Vec::new();
+        if nfrag > 0 {
+            for _ in 0..num_chromosomes {
+                let nsites = read_i32(&mut reader)?;
+                frag_site_counts.push(nsites);
+                skip_bytes(&mut reader, nsites.max(0) as u64 * 4)?;
+            }
+        }
+        Ok(HicFile {
+            file: Mutex::new(reader), version, master, genome_id, attributes, nvi_pos, nvi_len, chromosomes, resolutions,
+            frag_resolutions, frag_site_counts, path, footer: None, matrix_positions: None, matrix_zooms: HashMap::new(),
+            io_retries: DEFAULT_IO_RETRIES, verbose: false,
+        })
     }
 
-    fn get_matrix_zoom_data(&mut self, chr1_idx: i32, chr2_idx: i32, unit: &str, resolution: i32) -> Result<Option<MatrixZoomData>> {
-        let (c1, c2) = if chr1_idx <= chr2_idx { (chr1_idx, chr2_idx) } else { (chr2_idx, chr1_idx) };
-        self.file.seek(SeekFrom::Start(self.master as u64))?;
-        if self.version > 8 { let _ = read_i64(&mut self.file)?; } else { let _ = read_i32(&mut self.file)?; }
-        let key = format!("{}_{}", c1, c2);
-        let nentries = read_i32(&mut self.file)?;
-        let mut my_file_pos: Option<i64> = None;
+    /// Retry block reads that fail with a transient IO error (EIO, timeout,
+    /// interrupt) up to `retries` times, with exponential backoff. Useful on
+    /// network filesystems; the default is [`DEFAULT_IO_RETRIES`].
+    pub fn set_io_retries(&mut self, retries: u32) {
+        self.io_retries = retries;
+    }
+
+    /// Report each retried block read on stderr (off by default).
+    pub fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
+    }
+
+    /// The compressed bytes of the block at `idx` (empty for an empty entry),
+    /// retrying transient IO errors `io_retries` times.
+    fn read_block_bytes(&self, idx: &IndexEntry) -> Result<Vec<u8>> {
+        if idx.size <= 0 { return Ok(Vec::new()); }
+        let read_compressed = || -> std::io::Result<Vec<u8>> {
+            let mut f = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+            let mut comp = vec![0u8; idx.size as usize];
+            f.seek(SeekFrom::Start(idx.position as u64))?;
+            f.read_exact(&mut comp)?;
+            Ok(comp)
+        };
+        with_io_retries(self.io_retries, self.verbose, || format!("Reading block at {} of {:?}", idx.position, self.path), read_compressed)
+            .with_context(|| format!("Read block at {} ({} bytes) of {:?}", idx.position, idx.size, self.path))
+    }
+
+    /// Read and inflate block `number` of `mzd` and hand its records to `f`
+    /// (see [`Self::for_each_block`]).
+    fn with_block(&self, mzd: &MatrixZoomData, number: i32, f: impl FnOnce(&mut BlockRecords<'_>) -> Result<()>) -> Result<()> {
+        let idx = &mzd.block_map[&number];
+        let buf = self
+            .read_block_bytes(idx)
+            .and_then(|comp| inflate_block(&comp))
+            .with_context(|| self.block_context(mzd, number, idx))?;
+        self.hand_records(mzd, number, idx, &buf, f)
+    }
+
+    /// Call `f` with the records of the inflated block `buf`. Errors decoding
+    /// them name the block; errors of `f` itself are returned as they are.
+    fn hand_records(
+        &self,
+        mzd: &MatrixZoomData,
+        number: i32,
+        idx: &IndexEntry,
+        buf: &[u8],
+        f: impl FnOnce(&mut BlockRecords<'_>) -> Result<()>,
+    ) -> Result<()> {
+        let mut records = BlockRecords::new(buf, mzd.version).with_context(|| self.block_context(mzd, number, idx))?;
+        f(&mut records)?;
+        records.finish().with_context(|| self.block_context(mzd, number, idx))
+    }
+
+    /// Names a block in a decoding error: its number, file position and
+    /// chromosome pair.
+    fn block_context(&self, mzd: &MatrixZoomData, number: i32, idx: &IndexEntry) -> String {
+        format!(
+            "Decode block {} at {} of {}-{}, resolution {}",
+            number,
+            idx.position,
+            self.chromosomes[mzd.c1 as usize].name,
+            self.chromosomes[mzd.c2 as usize].name,
+            mzd.resolution
+        )
+    }
+
+    /// Inflate every block of `mzd` on the rayon pool and hand each block's
+    /// records to `f` in block-number order, parsed as `f` reads them. Blocks
+    /// go a batch at a time (a few per thread): the compressed bytes are read
+    /// in file order, then inflated in parallel, so at most one batch is in
+    /// flight. A record that fails to parse ends with an error naming its block.
+    fn for_each_block(&self, mzd: &MatrixZoomData, mut f: impl FnMut(&mut BlockRecords<'_>) -> Result<()>) -> Result<()> {
+        let entries: Vec<(&i32, &IndexEntry)> = mzd.block_map.iter().collect();
+        for batch in entries.chunks(rayon::current_num_threads() * BLOCKS_PER_THREAD) {
+            let compressed = batch
+                .iter()
+                .map(|(_, idx)| self.read_block_bytes(idx))
+                .collect::<Result<Vec<_>>>()?;
+            let inflated: Vec<Result<Vec<u8>>> = compressed.par_iter().map(|comp| inflate_block(comp)).collect();
+            for ((&number, idx), buf) in batch.iter().zip(inflated) {
+                let buf = buf.with_context(|| self.block_context(mzd, number, idx))?;
+                self.hand_records(mzd, number, idx, &buf, &mut f)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    /// Genome ID from the header, e.g. `hg19` or a path to a chrom.sizes file.
+    pub fn genome_id(&self) -> &str {
+        &self.genome_id
+    }
+
+    /// Header attributes as stored, in file order.
+    pub fn attributes(&self) -> &[(String, String)] {
+        &self.attributes
+    }
+
+    /// Chromosomes in header order, including the "All" entry at index 0.
+    pub fn chromosomes(&self) -> &[Chromosome] {
+        &self.chromosomes
+    }
+
+    /// Stored BP resolutions, in header order.
+    pub fn resolutions(&self) -> &[i32] {
+        &self.resolutions
+    }
+
+    /// All contacts between two chromosomes at `unit`/`resolution`, decoded from
+    /// every block. Names match like `effres` (`chr1`, `1`, `CHR1`). `bin_x` is
+    /// always on `chr1`'s axis and `bin_y` on `chr2`'s, whichever order the file
+    /// stores the pair in. A pair without a matrix yields no records.
+    pub fn query(&mut self, chr1: &str, chr2: &str, unit: &str, resolution: i32) -> Result<Vec<ContactRecord>> {
+        let (c1, c2) = match self.resolve_chromosomes(&[chr1.to_string(), chr2.to_string()])?[..] {
+            [a, b] => (a, b),
+            _ => unreachable!("two names resolve to two indices"),
+        };
+        self.require_resolution(unit, resolution)?;
+        let mzd = match self.get_matrix_zoom_data(c1, c2, unit, resolution)? {
+            Some(m) => m,
+            None => return Ok(Vec::new()),
+        };
+        let swapped = c1 > c2;
+        let mut out = Vec::new();
+        self.for_each_block(&mzd, |records| {
+            out.extend(records.map(|rec| {
+                if swapped { ContactRecord { bin_x: rec.bin_y, bin_y: rec.bin_x, counts: rec.counts } } else { rec }
+            }));
+            Ok(())
+        })?;
+        Ok(out)
+    }
+
+    /// Resolve a chromosome name to its index with flexible matching:
+    /// case-insensitive and with an optional "chr" prefix (`chr1`, `1`, `CHR1`).
+    fn find_chromosome(&self, name: &str) -> Option<i32> {
+        let req_s = name.to_lowercase();
+        let req_trim = req_s.trim_start_matches("chr");
+        self.chromosomes
+            .iter()
+            .filter(|c| c.index > 0)
+            .find(|c| {
+                let nm = c.name.to_lowercase();
+                nm == req_s || nm.trim_start_matches("chr") == req_trim
+            })
+            .map(|c| c.index)
+    }
+
+    /// Comma-separated names of the real chromosomes (for error messages).
+    fn chromosome_names(&self) -> String {
+        self.chromosomes
+            .iter()
+            .filter(|c| c.index > 0)
+            .map(|c| c.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Resolve every requested name, failing on the first unknown one.
+    fn resolve_chromosomes(&self, names: &[String]) -> Result<Vec<i32>> {
+        names
+            .iter()
+            .map(|n| {
+                self.find_chromosome(n).ok_or_else(|| {
+                    anyhow!("Chromosome '{}' not found in {:?}; available: {}", n, self.path, self.chromosome_names())
+                })
+            })
+            .collect()
+    }
+
+    /// Fail with a clear message for fragment-only files that have no BP zoom levels.
+    fn require_bp(&self) -> Result<()> {
+        if self.resolutions.is_empty() {
+            let frag = self.frag_resolutions.iter().map(|r| r.to_string()).collect::<Vec<_>>().join(", ");
+            return Err(anyhow!(
+                "{:?} has no BP resolutions; it only stores FRAG resolutions ({}), which must be requested with the FRAG unit",
+                self.path,
+                if frag.is_empty() { "none".to_string() } else { frag }
+            ));
+        }
+        Ok(())
+    }
+
+    /// Stored resolutions of `unit` (`BP` or `FRAG`).
+    fn unit_resolutions(&self, unit: &str) -> &[i32] {
+        if unit.eq_ignore_ascii_case("FRAG") { &self.frag_resolutions } else { &self.resolutions }
+    }
+
+    /// Fail unless `unit` is `BP` or `FRAG` and the file stores matrices in it.
+    fn require_unit(&self, unit: &str) -> Result<()> {
+        if unit.eq_ignore_ascii_case("BP") {
+            return self.require_bp();
+        }
+        if !unit.eq_ignore_ascii_case("FRAG") {
+            return Err(anyhow!("Unsupported unit '{}' (use BP or FRAG)", unit));
+        }
+        if self.frag_resolutions.is_empty() {
+            let bp = self.resolutions.iter().map(|r| r.to_string()).collect::<Vec<_>>().join(", ");
+            return Err(anyhow!(
+                "{:?} has no FRAG (fragment) resolutions; it only stores BP resolutions ({})",
+                self.path,
+                if bp.is_empty() { "none".to_string() } else { bp }
+            ));
+        }
+        Ok(())
+    }
+
+    /// Fail unless the file stores `unit` at `resolution`, listing the stored ones.
+    fn require_resolution(&self, unit: &str, resolution: i32) -> Result<()> {
+        self.require_unit(unit)?;
+        let available = self.unit_resolutions(unit);
+        if !available.contains(&resolution) {
+            return Err(anyhow!(
+                "Resolution {} {} not in {:?}; available: {:?}",
+                resolution, unit, self.path, available
+            ));
+        }
+        Ok(())
+    }
+
+    /// Extent of chromosome `chr_idx` along a matrix axis in `unit`: its length
+    /// in bp, or its restriction-site count from the header for `FRAG`.
+    fn chrom_extent(&self, chr_idx: i32, unit: &str) -> i64 {
+        if unit.eq_ignore_ascii_case("FRAG") {
+            self.frag_site_counts.get(chr_idx as usize).map_or(0, |&n| n as i64)
+        } else {
+            self.chromosomes[chr_idx as usize].length
+        }
+    }
+
+    /// Base pairs per unit of the `All` pseudo-chromosome, whose length is the
+    /// genome size scaled down (by 1000 in Juicer's files). The genome-wide
+    /// ALL matrix stores its positions and bin sizes in that unit.
+    fn all_scale(&self) -> Result<i64> {
+        let all = self.chromosomes.first().filter(|c| c.index == 0 && c.length > 0).ok_or_else(|| {
+            anyhow!("{:?} has no \"All\" pseudo-chromosome with a length to scale the ALL matrix by", self.path)
+        })?;
+        let genome: i64 = self.chromosomes.iter().filter(|c| c.index > 0).map(|c| c.length).sum();
+        if genome <= 0 {
+            return Err(anyhow!("{:?} lists no chromosomes to place the ALL matrix on", self.path));
+        }
+        Ok((genome / all.length).max(1))
+    }
+
+    /// Bin sizes of the genome-wide ALL matrix (`0_0`), as stored, in units of
+    /// [`Self::all_scale`]; empty when the file has no ALL matrix.
+    fn all_matrix_bin_sizes(&mut self) -> Result<Vec<i32>> {
+        let Some(zooms) = self.matrix_zooms(0, 0)? else {
+            return Ok(Vec::new());
+        };
+        Ok(zooms.iter().filter(|(z, _)| z.unit == "BP").map(|(z, _)| z.bin_size).collect())
+    }
+
+    /// Per-bin contact totals of the intra-chromosomal matrix at `unit`/`resolution`,
+    /// indexed by bin (see [`add_bin_counts`]); `None` when the matrix is absent
+    /// and empty when it has no records.
+    fn intra_bin_counts(&mut self, chr_idx: i32, unit: &str, resolution: i32) -> Result<Option<Vec<f64>>> {
+        let mzd = match self.get_matrix_zoom_data(chr_idx, chr_idx, unit, resolution)? {
+            Some(m) => m,
+            None => return Ok(None),
+        };
+        let nbins = num_bins_for(self.chrom_extent(chr_idx, unit), resolution);
+        let mut counts = Vec::new();
+        self.for_each_block(&mzd, |records| {
+            for rec in records {
+                add_anchor_count(&mut counts, rec.bin_x, rec.counts, nbins);
+                add_anchor_count(&mut counts, rec.bin_y, rec.counts, nbins);
+            }
+            Ok(())
+        })?;
+        Ok(Some(counts))
+    }
+
+    /// Per-bin totals of each chromosome in `chroms` (indices), as
+    /// [`Self::intra_bin_counts`]. With `trans`, the marginals of its
+    /// inter-chromosomal matrices are added: in a `c1_c2` matrix `bin_x` lies
+    /// on `c1` and `bin_y` on `c2`, and each pair is read once even when both
+    /// chromosomes are requested. Partners outside `chroms` still contribute.
+    /// `None` when no matrix of the chromosome is stored.
+    fn bin_counts(&mut self, chroms: &[i32], unit: &str, resolution: i32, trans: bool) -> Result<Vec<Option<Vec<f64>>>> {
+        let mut out = Vec::with_capacity(chroms.len());
+        for &ci in chroms {
+            out.push(self.intra_bin_counts(ci, unit, resolution)?);
+        }
+        if !trans {
+            return Ok(out);
+        }
+        let slot = |c: i32| chroms.iter().position(|&ci| ci == c);
+        let nbins: Vec<usize> = chroms.iter().map(|&ci| num_bins_for(self.chrom_extent(ci, unit), resolution)).collect();
+        let all: Vec<i32> = self.chromosomes.iter().filter(|c| c.index > 0).map(|c| c.index).collect();
+        for (i, &c1) in all.iter().enumerate() {
+            for &c2 in &all[i + 1..] {
+                let (s1, s2) = (slot(c1), slot(c2));
+                if s1.is_none() && s2.is_none() {
+                    continue;
+                }
+                let Some(mzd) = self.get_matrix_zoom_data(c1, c2, unit, resolution)? else { continue };
+                // Matrices are keyed with the lower index first: x lies on mzd.c1
+                let (sx, sy) = if mzd.c1 == c1 { (s1, s2) } else { (s2, s1) };
+                for s in [sx, sy].into_iter().flatten() {
+                    out[s].get_or_insert_with(Vec::new);
+                }
+                self.for_each_block(&mzd, |records| {
+                    for rec in records {
+                        if let Some(s) = sx {
+                            add_anchor_count(out[s].as_mut().expect("set above"), rec.bin_x, rec.counts, nbins[s]);
+                        }
+                        if let Some(s) = sy {
+                            add_anchor_count(out[s].as_mut().expect("set above"), rec.bin_y, rec.counts, nbins[s]);
+                        }
+                    }
+                    Ok(())
+                })?;
+            }
+        }
+        Ok(out)
+    }
+
+    /// Footer sections after the master index: expected values, normalized
+    /// expected values, then the normalization vector index.
+    fn footer(&mut self) -> Result<&Footer> {
+        if self.footer.is_none() {
+            let r = unpoisoned(&mut self.file);
+            let v9 = self.version > 8;
+            r.seek(SeekFrom::Start(self.master as u64))?;
+            if v9 { let _ = read_i64(r)?; } else { let _ = read_i32(r)?; }
+            let nentries = read_i32(r)?;
+            for _ in 0..nentries {
+                let _ = read_cstring(r)?;
+                skip_bytes(r, 8 + 4)?;
+            }
+            let mut footer = Footer::default();
+            let nexpected = read_i32(r)?;
+            for _ in 0..nexpected { footer.expected.push(index_expected_vector(r, self.version, false)?); }
+            // v9 headers point at the normalized expected values and the vector
+            // index. They follow the raw expected values when the file is written
+            // in one go, but normalizations added later (Juicer Tools `addNorm`)
+            // are appended after the footer, leaving a stale, often empty, copy here.
+            if v9 && self.nvi_pos > 0 {
+                r.seek(SeekFrom::Start(self.nvi_pos as u64))?;
+            }
+            let nnorm_expected = read_i32(r)?;
+            for _ in 0..nnorm_expected { footer.expected.push(index_expected_vector(r, self.version, true)?); }
+            let nvectors = read_i32(r)?;
+            for _ in 0..nvectors {
+                let norm = read_cstring(r)?;
+                let chr_idx = read_i32(r)?;
+                let unit = read_cstring(r)?;
+                let resolution = read_i32(r)?;
+                let position = read_i64(r)?;
+                if v9 { let _ = read_i64(r)?; } else { let _ = read_i32(r)?; }
+                footer.norms.push(NormEntry { norm, chr_idx, unit, resolution, position });
+            }
+            self.footer = Some(footer);
+        }
+        Ok(self.footer.as_ref().expect("footer read above"))
+    }
+
+    fn norm_entries(&mut self) -> Result<&[NormEntry]> {
+        Ok(&self.footer()?.norms)
+    }
+
+    /// The expected vector for `norm` (`NONE` for raw counts) at
+    /// `unit`/`resolution`; `None` when the file does not store it.
+    pub fn expected_values(&mut self, norm: &str, unit: &str, resolution: i32) -> Result<Option<ExpectedValues>> {
+        let position = match self
+            .footer()?
+            .expected
+            .iter()
+            .find(|e| e.resolution == resolution && e.unit == unit && e.norm == norm)
+        {
+            Some(e) => e.position,
+            None => return Ok(None),
+        };
+        let r = unpoisoned(&mut self.file);
+        r.seek(SeekFrom::Start(position as u64))?;
+        let v9 = self.version > 8;
+        let read_value = |r: &mut R| if v9 { read_f32(r).map(|v| v as f64) } else { read_f64(r) };
+        let nvalues = if v9 { read_i64(r)? } else { read_i32(r)? as i64 };
+        let values = (0..nvalues).map(|_| read_value(r)).collect::<Result<Vec<_>>>()?;
+        let nscale = read_i32(r)?;
+        let chr_scale_factors = (0..nscale)
+            .map(|_| Ok((read_i32(r)?, read_value(r)?)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Some(ExpectedValues { values, chr_scale_factors }))
+    }
+
+    /// The expected vector of one chromosome for `norm` at `unit`/`resolution`,
+    /// indexed by distance in bins and divided by the chromosome's scale
+    /// factor, as [`ExpectedValues::at`] reads it; `None` when the file does
+    /// not store it. The counterpart of [`Self::read_norm_vector`].
+    pub fn read_expected_vector(&mut self, chr_idx: i32, unit: &str, resolution: i32, norm: &str) -> Result<Option<Vec<f64>>> {
+        Ok(self
+            .expected_values(norm, unit, resolution)?
+            .map(|e| (0..e.values.len()).filter_map(|d| e.at(chr_idx, d)).collect()))
+    }
+
+    /// Like [`Self::expected_values`], but a missing vector is an error that
+    /// lists the expected vectors stored at this resolution.
+    fn require_expected_values(&mut self, norm: &str, unit: &str, resolution: i32) -> Result<ExpectedValues> {
+        if let Some(v) = self.expected_values(norm, unit, resolution)? {
+            return Ok(v);
+        }
+        let mut here: Vec<String> = self
+            .footer()?
+            .expected
+            .iter()
+            .filter(|e| e.resolution == resolution && e.unit == unit)
+            .map(|e| e.norm.clone())
+            .collect();
+        here.sort_unstable();
+        here.dedup();
+        Err(anyhow!(
+            "{:?} has no {} expected values at {} {}; available there: {}",
+            self.path,
+            norm,
+            resolution,
+            unit,
+            if here.is_empty() { "none".to_string() } else { here.join(", ") }
+        ))
+    }
+
+    /// Distinct normalization types with at least one stored vector, sorted.
+    pub fn normalization_types(&mut self) -> Result<Vec<String>> {
+        let mut types: Vec<String> = self.norm_entries()?.iter().map(|e| e.norm.clone()).collect();
+        types.sort_unstable();
+        types.dedup();
+        Ok(types)
+    }
+
+    /// Normalization types with vectors at each `(unit, resolution)`, sorted
+    /// by unit, then resolution; resolutions without any vector are left out.
+    pub fn normalizations_by_resolution(&mut self) -> Result<Vec<(String, i32, Vec<String>)>> {
+        let mut by_res: BTreeMap<(String, i32), BTreeSet<String>> = BTreeMap::new();
+        for e in self.norm_entries()? {
+            by_res.entry((e.unit.clone(), e.resolution)).or_default().insert(e.norm.clone());
+        }
+        Ok(by_res.into_iter().map(|((unit, res), norms)| (unit, res, norms.into_iter().collect())).collect())
+    }
+
+    /// The header summary `straw list` prints.
+    pub fn listing(&mut self) -> Result<HicListing> {
+        let sorted = |v: &[i32]| {
+            let mut v = v.to_vec();
+            v.sort_unstable();
+            v
+        };
+        Ok(HicListing {
+            genome_id: self.genome_id.clone(),
+            version: self.version,
+            chromosomes: self
+                .chromosomes
+                .iter()
+                .filter(|c| c.index > 0)
+                .map(|c| ListedChromosome { name: c.name.clone(), index: c.index, length: c.length })
+                .collect(),
+            resolutions: sorted(&self.resolutions),
+            frag_resolutions: sorted(&self.frag_resolutions),
+            normalizations: self.normalization_types()?,
+        })
+    }
+
+    /// Like [`Self::read_norm_vector`], but a missing vector is an error that
+    /// lists the normalizations stored for this chromosome and resolution.
+    fn require_norm_vector(&mut self, chr_idx: i32, unit: &str, resolution: i32, norm: &str) -> Result<Vec<f64>> {
+        if let Some(v) = self.read_norm_vector(chr_idx, unit, resolution, norm)? {
+            return Ok(v);
+        }
+        let mut here: Vec<String> = self
+            .norm_entries()?
+            .iter()
+            .filter(|e| e.chr_idx == chr_idx && e.resolution == resolution && e.unit == unit)
+            .map(|e| e.norm.clone())
+            .collect();
+        here.sort_unstable();
+        here.dedup();
+        Err(anyhow!(
+            "{:?} has no {} normalization vector for {} at {} {}; available there: {}",
+            self.path,
+            norm,
+            self.chromosomes[chr_idx as usize].name,
+            resolution,
+            unit,
+            if here.is_empty() { "none".to_string() } else { here.join(", ") }
+        ))
+    }
+
+    /// The `norm` vector (e.g. VC, VC_SQRT, KR, SCALE) of one chromosome at
+    /// `unit`/`resolution`; `None` when the file does not store it.
+    fn read_norm_vector(&mut self, chr_idx: i32, unit: &str, resolution: i32, norm: &str) -> Result<Option<Vec<f64>>> {
+        let position = match self
+            .norm_entries()?
+            .iter()
+            .find(|e| e.chr_idx == chr_idx && e.resolution == resolution && e.unit == unit && e.norm == norm)
+        {
+            Some(e) => e.position,
+            None => return Ok(None),
+        };
+        let r = unpoisoned(&mut self.file);
+        r.seek(SeekFrom::Start(position as u64))?;
+        let values = if self.version > 8 {
+            let n = read_i64(r)?;
+            (0..n).map(|_| read_f32(r).map(|v| v as f64)).collect::<Result<Vec<_>>>()?
+        } else {
+            let n = read_i32(r)?;
+            (0..n).map(|_| read_f64(r)).collect::<Result<Vec<_>>>()?
+        };
+        Ok(Some(values))
+    }
+
+    /// Entries of the master index: (`c1_c2` key, matrix position, matrix size).
+    fn master_entries(&mut self) -> Result<Vec<(String, i64, i32)>> {
+        let r = unpoisoned(&mut self.file);
+        r.seek(SeekFrom::Start(self.master as u64))?;
+        if self.version > 8 { let _ = read_i64(r)?; } else { let _ = read_i32(r)?; }
+        let nentries = read_i32(r)?;
+        let mut entries = Vec::with_capacity(nentries.max(0) as usize);
         for _ in 0..nentries {
-            let k = read_cstring(&mut self.file)?;
-            let fpos = read_i64(&mut self.file)?;
-            let _size = read_i32(&mut self.file)?;
-            if k == key { my_file_pos = Some(fpos); }
+            let k = read_cstring(r)?;
+            let fpos = read_i64(r)?;
+            let size = read_i32(r)?;
+            entries.push((k, fpos, size));
+        }
+        Ok(entries)
+    }
+
+    /// Matrix position of every chromosome pair, parsed from the master index
+    /// once. Keys are stored with the lower index first; the first of
+    /// duplicate keys wins, and keys that are not `c1_c2` are ignored.
+    fn matrix_positions(&mut self) -> Result<&HashMap<(i32, i32), i64>> {
+        if self.matrix_positions.is_none() {
+            let mut positions = HashMap::new();
+            for (key, pos, _) in self.master_entries()? {
+                if let Some((c1, c2)) = key.split_once('_').and_then(|(a, b)| Some((a.parse().ok()?, b.parse().ok()?))) {
+                    positions.entry((c1, c2)).or_insert(pos);
+                }
+            }
+            self.matrix_positions = Some(positions);
+        }
+        Ok(self.matrix_positions.as_ref().expect("master index read above"))
+    }
+
+    /// Zoom levels of the `c1_c2` matrix record (`c1 <= c2`), each with the
+    /// position of its block index; read once per pair. `None` without a matrix.
+    fn matrix_zooms(&mut self, c1: i32, c2: i32) -> Result<Option<&[(ZoomHeader, u64)]>> {
+        if !self.matrix_zooms.contains_key(&(c1, c2)) {
+            let Some(&pos) = self.matrix_positions()?.get(&(c1, c2)) else { return Ok(None) };
+            let r = unpoisoned(&mut self.file);
+            r.seek(SeekFrom::Start(pos as u64))?;
+            let _c1 = read_i32(r)?;
+            let _c2 = read_i32(r)?;
+            let nres = read_i32(r)?;
+            let mut zooms = Vec::with_capacity(nres.max(0) as usize);
+            for _ in 0..nres {
+                let z = read_zoom_header(r)?;
+                let index_pos = r.stream_position()?;
+                skip_bytes(r, z.nblocks.max(0) as u64 * (4 + 8 + 4))?;
+                zooms.push((z, index_pos));
+            }
+            self.matrix_zooms.insert((c1, c2), zooms);
         }
-        let my_file_pos = match my_file_pos { Some(p) => p, None => return Ok(None) };
-        let (block_map, sum_counts, block_bin_count, block_col_count) = read_matrix(&mut self.file, my_file_pos, unit, resolution)?;
+        Ok(self.matrix_zooms.get(&(c1, c2)).map(Vec::as_slice))
+    }
+
+    /// Block index and bin layout of one zoom level; the block index is read
+    /// from the file each time, the master index and matrix record only once.
+    fn get_matrix_zoom_data(&mut self, chr1_idx: i32, chr2_idx: i32, unit: &str, resolution: i32) -> Result<Option<MatrixZoomData>> {
+        let (c1, c2) = if chr1_idx <= chr2_idx { (chr1_idx, chr2_idx) } else { (chr2_idx, chr1_idx) };
+        let context = format!(
+            "No {} {} matrix for {} vs {} in {:?}",
+            unit, resolution, self.chromosomes[c1 as usize].name, self.chromosomes[c2 as usize].name, self.path
+        );
+        let Some(zooms) = self.matrix_zooms(c1, c2).context(context.clone())? else { return Ok(None) };
+        // The header lists resolutions file-wide; a matrix record may still lack one
+        let Some(&(ref z, index_pos)) = zooms.iter().find(|(z, _)| z.unit == unit && z.bin_size == resolution) else {
+            return Err(anyhow!("Resolution {} at unit {} not found in matrix", resolution, unit).context(context));
+        };
+        let (sum_counts, block_bin_count, block_col_count, nblocks) = (z.sum_counts, z.block_bin_count, z.block_col_count, z.nblocks);
+        let r = unpoisoned(&mut self.file);
+        r.seek(SeekFrom::Start(index_pos)).context(context.clone())?;
+        let block_map = read_block_index(r, nblocks).context(context)?.into_iter().collect();
         Ok(Some(MatrixZoomData {
             version: self.version,
             resolution,
             is_intra: c1 == c2,
-            num_bins1: (self.chromosomes[c1 as usize].length / resolution as i64) as i32,
-            num_bins2: (self.chromosomes[c2 as usize].length / resolution as i64) as i32,
+            num_bins1: (self.chrom_extent(c1, unit) / resolution as i64) as i32,
+            num_bins2: (self.chrom_extent(c2, unit) / resolution as i64) as i32,
             block_map,
             sum_counts,
             block_bin_count,
@@ -88,6 +762,58 @@ impl HicFile {
     }
 }
 
+/// Read-only view of a .hic file for library use: its chromosomes and
+/// resolutions, and the contact records of any chromosome pair.
+///
+/// ```no_run
+/// # fn main() -> anyhow::Result<()> {
+/// let mut reader = hickit::straw::HicReader::open(std::path::Path::new("in.hic"))?;
+/// let total: f64 = reader.contacts("chr1", "chr1", "BP", 10_000)?.map(|r| r.counts as f64).sum();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct HicReader<R = BufReader<File>> {
+    hic: HicFile<R>,
+}
+
+impl HicReader {
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(HicReader { hic: HicFile::open(path)? })
+    }
+}
+
+impl<R: Read + Seek> HicReader<R> {
+    /// A reader over the .hic bytes of `reader` (see [`HicFile::from_reader`]).
+    pub fn from_reader(reader: R) -> Result<Self> {
+        Ok(HicReader { hic: HicFile::from_reader(reader)? })
+    }
+
+    /// Chromosomes in header order, without the "All" pseudo-chromosome.
+    pub fn chromosomes(&self) -> impl Iterator<Item = &Chromosome> {
+        self.hic.chromosomes().iter().filter(|c| c.index > 0)
+    }
+
+    /// Stored BP resolutions, in header order.
+    pub fn resolutions(&self) -> &[i32] {
+        self.hic.resolutions()
+    }
+
+    /// Contact records between `chr1` and `chr2` at `unit`/`resolution`, with
+    /// the same name matching and axis order as [`HicFile::query`]. Blocks are
+    /// decoded before this returns, so read errors surface here rather than
+    /// mid-iteration.
+    pub fn contacts(&mut self, chr1: &str, chr2: &str, unit: &str, resolution: i32) -> Result<impl Iterator<Item = ContactRecord>> {
+        Ok(self.hic.query(chr1, chr2, unit, resolution)?.into_iter())
+    }
+
+    /// The underlying file, for normalization vectors, expected values and
+    /// header attributes.
+    pub fn file(&mut self) -> &mut HicFile<R> {
+        &mut self.hic
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug)]
 struct MatrixZoomData {
@@ -104,27 +830,74 @@ struct MatrixZoomData {
     c2: i32,
 }
 
-fn read_matrix<R: Read + Seek>(r: &mut R, my_file_pos: i64, unit: &str, resolution: i32) -> Result<(BTreeMap<i32, IndexEntry>, f32, i32, i32)> {
-    r.seek(SeekFrom::Start(my_file_pos as u64))?;
-    let _c1 = read_i32(r)?;
-    let _c2 = read_i32(r)?;
-    let nres = read_i32(r)?;
-    let mut found = false;
-    let mut block_map = BTreeMap::new();
-    let mut sum_counts = 0f32;
-    let mut block_bin_count = 0;
-    let mut block_col_count = 0;
-    for _ in 0..nres {
-        let (bm, sum, bbc, bcc, is_match) = read_matrix_zoom_data(r, unit, resolution)?;
-        if is_match {
-            block_map = bm; sum_counts = sum; block_bin_count = bbc; block_col_count = bcc; found = true; break;
+impl MatrixZoomData {
+    /// Numbers of the stored blocks that can hold records with `bin_x` in
+    /// `range_x` and `bin_y` in `range_y` (bins on the stored axes).
+    fn blocks_for_region(&self, range_x: &RangeInclusive<i32>, range_y: &RangeInclusive<i32>) -> Vec<i32> {
+        region_block_numbers(self.version, self.is_intra, self.block_bin_count, self.block_col_count, range_x, range_y)
+            .into_iter()
+            .filter(|n| self.block_map.contains_key(n))
+            .collect()
+    }
+}
+
+/// Block numbers covering a bin rectangle, following straw's two layouts.
+/// Blocks form a grid of `block_bin_count`-bin squares numbered
+/// `row * block_col_count + col` (row from `bin_y`), except for v9
+/// intra-chromosomal matrices, which are tiled along the diagonal: the column
+/// is the position along the diagonal, `(x + y) / 2 / block_bin_count`, and
+/// the row the log2 depth of the distance `|x - y|` from it. An intra matrix
+/// stores each contact once, so the v8 grid is also searched transposed.
+fn region_block_numbers(
+    version: i32,
+    is_intra: bool,
+    block_bin_count: i32,
+    block_col_count: i32,
+    range_x: &RangeInclusive<i32>,
+    range_y: &RangeInclusive<i32>,
+) -> BTreeSet<i32> {
+    let mut blocks = BTreeSet::new();
+    if block_bin_count <= 0 || range_x.is_empty() || range_y.is_empty() {
+        return blocks;
+    }
+    let (x1, x2, y1, y2) = (*range_x.start() as i64, *range_x.end() as i64, *range_y.start() as i64, *range_y.end() as i64);
+    let (bbc, cols) = (block_bin_count as i64, block_col_count as i64);
+    let mut insert = |row: i64, col: i64| {
+        if let Ok(n) = i32::try_from(row * cols + col) { blocks.insert(n); }
+    };
+    if version > 8 && is_intra {
+        let depth = |d: i64| (1.0 + d as f64 / std::f64::consts::SQRT_2 / bbc as f64).log2().floor() as i64;
+        let overlaps = x1 <= y2 && y1 <= x2;
+        let nearest = if overlaps { 0 } else { (x1 - y2).abs().min((x2 - y1).abs()) };
+        let furthest = (x1 - y2).abs().max((x2 - y1).abs());
+        for row in depth(nearest)..=depth(furthest) {
+            for col in (x1 + y1) / 2 / bbc..=(x2 + y2) / 2 / bbc { insert(row, col); }
+        }
+    } else {
+        for row in y1 / bbc..=y2 / bbc {
+            for col in x1 / bbc..=x2 / bbc { insert(row, col); }
+        }
+        if is_intra {
+            for row in x1 / bbc..=x2 / bbc {
+                for col in y1 / bbc..=y2 / bbc { insert(row, col); }
+            }
         }
     }
-    if !found { return Err(anyhow!("Resolution {} at unit {} not found in matrix", resolution, unit)); }
-    Ok((block_map, sum_counts, block_bin_count, block_col_count))
+    blocks
+}
+
+/// Header of one zoom level in a matrix record, up to its block count.
+#[derive(Debug)]
+struct ZoomHeader {
+    unit: String,
+    sum_counts: f32,
+    bin_size: i32,
+    block_bin_count: i32,
+    block_col_count: i32,
+    nblocks: i32,
 }
 
-fn read_matrix_zoom_data<R: Read + Seek>(r: &mut R, my_unit: &str, my_binsize: i32) -> Result<(BTreeMap<i32, IndexEntry>, f32, i32, i32, bool)> {
+fn read_zoom_header<R: Read>(r: &mut R) -> Result<ZoomHeader> {
     let unit = read_cstring(r)?;
     let _old_zoom = read_i32(r)?;
     let sum_counts = read_f32(r)?;
@@ -134,365 +907,4669 @@ fn read_matrix_zoom_data<R: Read + Seek>(r: &mut R, my_unit: &str, my_binsize: i
     let bin_size = read_i32(r)?;
     let block_bin_count = read_i32(r)?;
     let block_col_count = read_i32(r)?;
-    let is_match = unit == my_unit && bin_size == my_binsize;
     let nblocks = read_i32(r)?;
-    let mut block_map = BTreeMap::new();
-    if is_match {
-        for _ in 0..nblocks {
+    Ok(ZoomHeader { unit, sum_counts, bin_size, block_bin_count, block_col_count, nblocks })
+}
+
+/// The `nblocks` (block number, index entry) pairs following a zoom header, in stored order.
+fn read_block_index<R: Read>(r: &mut R, nblocks: i32) -> Result<Vec<(i32, IndexEntry)>> {
+    (0..nblocks)
+        .map(|_| {
             let block_number = read_i32(r)?;
-            let file_position = read_i64(r)?;
-            let size_in_bytes = read_i32(r)? as i64;
-            block_map.insert(block_number, IndexEntry { size: size_in_bytes, position: file_position });
+            let position = read_i64(r)?;
+            let size = read_i32(r)? as i64;
+            Ok((block_number, IndexEntry { size, position }))
+        })
+        .collect()
+}
+
+/// Index one expected-value record of the footer and skip over the vector and
+/// its per-chromosome scale factors (f32 values in v9, f64 before).
+fn index_expected_vector<R: Read + Seek>(r: &mut R, version: i32, normalized: bool) -> Result<ExpectedEntry> {
+    let norm = if normalized { read_cstring(r)? } else { "NONE".to_string() };
+    let unit = read_cstring(r)?;
+    let resolution = read_i32(r)?;
+    let position = r.stream_position()? as i64;
+    let (nvalues, width) = if version > 8 { (read_i64(r)?, 4) } else { (read_i32(r)? as i64, 8) };
+    r.seek(SeekFrom::Current(nvalues * width))?;
+    let nscale = read_i32(r)? as i64;
+    r.seek(SeekFrom::Current(nscale * (4 + width)))?;
+    Ok(ExpectedEntry { norm, unit, resolution, position })
+}
+
+/// `counts / (norm1[bin_x] * norm2[bin_y])`, or `None` when a factor is
+/// missing, NaN or zero (straw skips those records).
+fn normalized_counts(counts: f32, bin_x: i32, bin_y: i32, norm1: &[f64], norm2: &[f64]) -> Option<f32> {
+    let n1 = *norm1.get(usize::try_from(bin_x).ok()?)?;
+    let n2 = *norm2.get(usize::try_from(bin_y).ok()?)?;
+    let v = counts as f64 / (n1 * n2);
+    (n1 != 0.0 && n2 != 0.0 && v.is_finite()).then_some(v as f32)
+}
+
+/// `counts / expected`, or `None` when the expected value is missing, zero or
+/// not finite, so such records are skipped instead of written as infinities.
+fn observed_over_expected(counts: f32, expected: Option<f64>) -> Option<f32> {
+    let e = expected.filter(|e| *e > 0.0 && e.is_finite())?;
+    let v = counts as f64 / e;
+    v.is_finite().then_some(v as f32)
+}
+
+/// One stored contact: bin indices on both axes and the (observed) count.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContactRecord { pub bin_x: i32, pub bin_y: i32, pub counts: f32 }
+
+impl ContactRecord {
+    /// Upper-triangle form for intra-chromosomal records (`bin_x <= bin_y`).
+    /// Inter-chromosomal records are returned unchanged, since their axes
+    /// belong to different chromosomes.
+    fn canonicalized(self, is_intra: bool) -> Self {
+        if is_intra && self.bin_x > self.bin_y {
+            ContactRecord { bin_x: self.bin_y, bin_y: self.bin_x, counts: self.counts }
+        } else {
+            self
         }
-    } else {
-        let skip = nblocks as i64 * (4 + 8 + 4) as i64;
-        r.seek(SeekFrom::Current(skip))?;
     }
-    Ok((block_map, sum_counts, block_bin_count, block_col_count, is_match))
 }
 
-#[derive(Clone, Debug)]
-struct ContactRecord { bin_x: i32, bin_y: i32, counts: f32 }
-
-fn read_block(path: &Path, idx: &IndexEntry, version: i32) -> Result<Vec<ContactRecord>> {
-    if idx.size <= 0 { return Ok(Vec::new()); }
-    let mut f = File::open(path).with_context(|| format!("Open {:?}", path))?;
-    let mut comp = vec![0u8; idx.size as usize];
-    f.seek(SeekFrom::Start(idx.position as u64))?;
-    f.read_exact(&mut comp)?;
-    let mut dec = ZlibDecoder::new(&comp[..]);
-    let mut buf = Vec::new();
-    dec.read_to_end(&mut buf)?;
-    let mut cur = std::io::Cursor::new(buf);
-
-    let n_records = read_i32(&mut cur)? as usize;
-    let mut out = Vec::with_capacity(n_records);
-    if version < 7 {
-        for _ in 0..n_records {
-            let bin_x = read_i32(&mut cur)?;
-            let bin_y = read_i32(&mut cur)?;
-            let counts = read_f32(&mut cur)?;
-            out.push(ContactRecord { bin_x, bin_y, counts });
-        }
-        return Ok(out);
-    }
-
-    let bin_x_offset = read_i32(&mut cur)?;
-    let bin_y_offset = read_i32(&mut cur)?;
-    let use_short = read_u8(&mut cur)? == 0;
-    let mut use_short_bin_x = true;
-    let mut use_short_bin_y = true;
-    if version > 8 {
-        use_short_bin_x = read_u8(&mut cur)? == 0;
-        use_short_bin_y = read_u8(&mut cur)? == 0;
-    }
-    let typ = read_u8(&mut cur)?;
-    match typ {
-        1 => {
-            if use_short_bin_x && use_short_bin_y {
-                let row_count = read_i16(&mut cur)? as i32;
-                for _ in 0..row_count {
-                    let bin_y = bin_y_offset + read_i16(&mut cur)? as i32;
-                    let col_count = read_i16(&mut cur)? as i32;
-                    for _ in 0..col_count {
-                        let bin_x = bin_x_offset + read_i16(&mut cur)? as i32;
-                        let counts = if use_short { read_i16(&mut cur)? as f32 } else { read_f32(&mut cur)? };
-                        out.push(ContactRecord { bin_x, bin_y, counts });
-                    }
-                }
-            } else if use_short_bin_x && !use_short_bin_y {
-                let row_count = read_i32(&mut cur)?;
-                for _ in 0..row_count {
-                    let bin_y = bin_y_offset + read_i32(&mut cur)?;
-                    let col_count = read_i16(&mut cur)? as i32;
-                    for _ in 0..col_count {
-                        let bin_x = bin_x_offset + read_i16(&mut cur)? as i32;
-                        let counts = if use_short { read_i16(&mut cur)? as f32 } else { read_f32(&mut cur)? };
-                        out.push(ContactRecord { bin_x, bin_y, counts });
-                    }
-                }
-            } else if !use_short_bin_x && use_short_bin_y {
-                let row_count = read_i16(&mut cur)? as i32;
-                for _ in 0..row_count {
-                    let bin_y = bin_y_offset + read_i16(&mut cur)? as i32;
-                    let col_count = read_i32(&mut cur)?;
-                    for _ in 0..col_count {
-                        let bin_x = bin_x_offset + read_i32(&mut cur)?;
-                        let counts = if use_short { read_i16(&mut cur)? as f32 } else { read_f32(&mut cur)? };
-                        out.push(ContactRecord { bin_x, bin_y, counts });
-                    }
-                }
-            } else {
-                let row_count = read_i32(&mut cur)?;
-                for _ in 0..row_count {
-                    let bin_y = bin_y_offset + read_i32(&mut cur)?;
-                    let col_count = read_i32(&mut cur)?;
-                    for _ in 0..col_count {
-                        let bin_x = bin_x_offset + read_i32(&mut cur)?;
-                        let counts = if use_short { read_i16(&mut cur)? as f32 } else { read_f32(&mut cur)? };
-                        out.push(ContactRecord { bin_x, bin_y, counts });
-                    }
-                }
-            }
-        }
-        2 => {
-            let n_pts = read_i32(&mut cur)?;
-            let w = read_i16(&mut cur)? as i32;
-            for i in 0..n_pts {
-                let row = i / w;
-                let col = i - row * w;
-                let bin_x = bin_x_offset + col;
-                let bin_y = bin_y_offset + row;
-                if use_short {
-                    let c = read_i16(&mut cur)?;
-                    if c != -32768 { out.push(ContactRecord { bin_x, bin_y, counts: c as f32 }); }
-                } else {
-                    let counts = read_f32(&mut cur)?;
-                    if !counts.is_nan() { out.push(ContactRecord { bin_x, bin_y, counts }); }
+/// Extra attempts for block reads failing with a transient IO error.
+pub const DEFAULT_IO_RETRIES: u32 = 2;
+
+/// Wait before the first retry; doubled for each further one.
+const IO_RETRY_BASE_DELAY: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Errors worth retrying: EIO and timeouts, as seen on NFS/Lustre, and interrupts.
+fn is_transient_io_error(e: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    matches!(e.kind(), ErrorKind::Interrupted | ErrorKind::TimedOut | ErrorKind::WouldBlock)
+        || e.raw_os_error() == Some(5) // EIO
+}
+
+/// Run `op`, retrying transient IO errors up to `retries` times with
+/// exponential backoff. With `verbose`, each retry is logged to stderr with `what()`.
+fn with_io_retries<T>(
+    retries: u32,
+    verbose: bool,
+    what: impl Fn() -> String,
+    mut op: impl FnMut() -> std::io::Result<T>,
+) -> std::io::Result<T> {
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Err(e) if attempt < retries && is_transient_io_error(&e) => {
+                let delay = IO_RETRY_BASE_DELAY * 2u32.pow(attempt);
+                attempt += 1;
+                if verbose {
+                    eprintln!("{} failed ({}); retry {}/{} in {:?}", what(), e, attempt, retries, delay);
                 }
+                std::thread::sleep(delay);
             }
+            result => return result,
         }
-        _ => {}
     }
-    Ok(out)
 }
 
-pub fn dump_hic_genome_wide(input: &Path, binsize: i32, output: &Path) -> Result<()> {
-    let mut hic = HicFile::open(input)?;
-    // Build chromosome keys (skip index <= 0 per C++ code)
-    let mut chr_keys: BTreeMap<String, i16> = BTreeMap::new();
-    let mut key_counter: i16 = 0;
-    for chr in &hic.chromosomes {
-        if chr.index > 0 { chr_keys.insert(chr.name.clone(), key_counter); key_counter += 1; }
+/// Blocks decoded per rayon thread in one batch of [`HicFile::for_each_block`].
+const BLOCKS_PER_THREAD: usize = 4;
+
+/// Records a genome-wide dump worker queues for the writer in one message.
+const RECORDS_PER_MESSAGE: usize = 1 << 16;
+
+/// The zlib-inflated bytes of a block read by [`HicFile::read_block_bytes`] (empty
+/// for an empty entry).
+fn inflate_block(comp: &[u8]) -> Result<Vec<u8>> {
+    if comp.is_empty() { return Ok(Vec::new()); }
+    let mut buf = Vec::new();
+    ZlibDecoder::new(comp).read_to_end(&mut buf).context("inflate")?;
+    Ok(buf)
+}
+
+/// Little-endian fields of an inflated block. A read past the end fails with
+/// the offset instead of yielding a short block.
+struct BlockCursor<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl BlockCursor<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let bytes = self.buf.get(self.pos..self.pos + N).ok_or_else(|| {
+            anyhow!("block truncated: {} bytes needed at offset {} of {}", N, self.pos, self.buf.len())
+        })?;
+        self.pos += N;
+        Ok(bytes.try_into().expect("slice of N bytes"))
     }
 
-    // Open output .slc.gz
-    let out = File::create(output).with_context(|| format!("Create {:?}", output))?;
-    let mut enc = GzEncoder::new(BufWriter::new(out), Compression::default());
+    fn u8(&mut self) -> Result<u8> { Ok(self.take::<1>()?[0]) }
+    fn i16(&mut self) -> Result<i16> { Ok(i16::from_le_bytes(self.take()?)) }
+    fn i32(&mut self) -> Result<i32> { Ok(i32::from_le_bytes(self.take()?)) }
+    fn f32(&mut self) -> Result<f32> { Ok(f32::from_le_bytes(self.take()?)) }
 
-    // Write header
-    enc.write_all(HICSLICE_MAGIC)?;
-    enc.write_all(&(binsize as i32).to_le_bytes())?;
-    enc.write_all(&(chr_keys.len() as i32).to_le_bytes())?;
-    for (name, key) in &chr_keys {
-        let nb = name.as_bytes();
-        enc.write_all(&(nb.len() as i32).to_le_bytes())?;
-        enc.write_all(nb)?;
-        enc.write_all(&(*key as i16).to_le_bytes())?;
+    /// A bin or count stored as an i16 when `short`, else as an i32 (v9 blocks).
+    fn int(&mut self, short: bool) -> Result<i32> {
+        if short { Ok(self.i16()? as i32) } else { self.i32() }
     }
 
-    // Iterate chromosome pairs
-    let n = hic.chromosomes.len();
-    for i in 0..n {
-        let c1_idx = hic.chromosomes[i].index;
-        if c1_idx <= 0 { continue; }
-        for j in i..n {
-            let c2_idx = hic.chromosomes[j].index;
-            if c2_idx <= 0 { continue; }
-            if let Some(mzd) = hic.get_matrix_zoom_data(c1_idx, c2_idx, "BP", binsize)? {
-                let key1 = *chr_keys.get(&hic.chromosomes[mzd.c1 as usize].name).unwrap();
-                let key2 = *chr_keys.get(&hic.chromosomes[mzd.c2 as usize].name).unwrap();
-                for (_, idx) in mzd.block_map.iter() {
-                    let records = read_block(&hic.path, idx, mzd.version)?;
-                    for rec in records {
-                        if rec.counts > 0.0 && rec.counts.is_finite() {
-                            enc.write_all(&key1.to_le_bytes())?;
-                            enc.write_all(&rec.bin_x.to_le_bytes())?;
-                            enc.write_all(&key2.to_le_bytes())?;
-                            enc.write_all(&rec.bin_y.to_le_bytes())?;
-                            enc.write_all(&rec.counts.to_le_bytes())?;
-                        }
-                    }
-                }
-            }
+    /// A row, column or record count, which a valid block never stores negative.
+    fn count(&mut self, short: bool, what: &str) -> Result<i32> {
+        let at = self.pos;
+        let n = self.int(short)?;
+        if n < 0 {
+            return Err(anyhow!("negative {} count {} at offset {}", what, n, at));
         }
+        Ok(n)
     }
 
-    enc.finish()?.flush()?;
-    Ok(())
+    fn remaining(&self) -> usize {
+        self.buf.len() - self.pos
+    }
 }
 
-// ----------------- low-level readers -----------------
-fn read_magic<R: Read>(r: &mut R) -> Result<bool> { let s = read_cstring(r)?; Ok(s.starts_with("HIC")) }
-fn read_u8<R: Read>(r: &mut R) -> Result<u8> { let mut b=[0u8;1]; r.read_exact(&mut b)?; Ok(b[0]) }
-fn read_i16<R: Read>(r: &mut R) -> Result<i16> { let mut b=[0u8;2]; r.read_exact(&mut b)?; Ok(i16::from_le_bytes(b)) }
-fn read_i32<R: Read>(r: &mut R) -> Result<i32> { let mut b=[0u8;4]; r.read_exact(&mut b)?; Ok(i32::from_le_bytes(b)) }
-fn read_i64<R: Read>(r: &mut R) -> Result<i64> { let mut b=[0u8;8]; r.read_exact(&mut b)?; Ok(i64::from_le_bytes(b)) }
-fn read_f32<R: Read>(r: &mut R) -> Result<f32> { let mut b=[0u8;4]; r.read_exact(&mut b)?; Ok(f32::from_le_bytes(b)) }
-fn _read_f64<R: Read>(r: &mut R) -> Result<f64> { let mut b=[0u8;8]; r.read_exact(&mut b)?; Ok(f64::from_le_bytes(b)) }
-fn read_cstring<R: Read>(r: &mut R) -> Result<String> {
-    let mut buf = Vec::new();
-    let mut byte = [0u8;1];
-    loop {
-        r.read_exact(&mut byte)?;
-        if byte[0] == 0 { break; }
-        buf.push(byte[0]);
-    }
-    Ok(String::from_utf8(buf).unwrap_or_default())
+/// The records of an inflated block, parsed one at a time as they are read,
+/// so a dense block is never held as a `Vec<ContactRecord>`. The header is
+/// checked by [`BlockRecords::new`]. A record that cannot be read ends the
+/// iteration; [`BlockRecords::finish`] then returns why.
+struct BlockRecords<'a> {
+    cur: BlockCursor<'a>,
+    declared: usize,
+    layout: BlockLayout,
+    error: Option<anyhow::Error>,
 }
 
-pub fn list_hic_chromosomes(input: &Path) -> Result<()> {
-    let hic = HicFile::open(input)?;
-    // Print available BP resolutions
-    let mut res = hic.resolutions.clone();
-    res.sort_unstable();
-    println!("# Resolutions (BP): {}", res.iter().map(|r| r.to_string()).collect::<Vec<_>>().join(", "));
-    // Print chromosomes table
-    println!("# Chromosomes (name\tlength)");
-    for chr in hic.chromosomes.iter() {
-        if chr.index > 0 {
-            println!("{}\t{}", chr.name, chr.length);
-        }
-    }
-    Ok(())
+/// Payload layout of a block, with the position of a [`BlockRecords`] in it.
+enum BlockLayout {
+    /// v6: `left` more bin_x, bin_y, counts triples
+    Triples { left: usize },
+    /// Type 1: rows of a y bin and its x bins; the flags pick i16 or i32
+    Sparse { x_offset: i32, y_offset: i32, short_counts: bool, short_x: bool, short_y: bool, rows_left: i32, bin_y: i32, cols_left: i32 },
+    /// Type 2: `n` cells of a grid `width` bins wide, in row order; `i` is next
+    Dense { x_offset: i32, y_offset: i32, short_counts: bool, n: i32, width: i32, i: i32 },
+    /// Every record read, or stopped by an error
+    Done,
 }
 
-pub fn effres_hic(input: &Path, chrom_req: Option<&str>, thr: i32, pct: f64) -> Result<()> {
-    let mut hic = HicFile::open(input)?;
-    // If no chromosome provided, compute min/mean/max coverage across chromosomes per resolution
-    if chrom_req.is_none() {
-        println!("# File: {}", input.display());
-        println!("# Mode: all chromosomes coverage summary");
-        println!("# Filters: length >= 2,500,000 bp; exclude no-signal contigs per resolution");
-        println!("# Threshold per bin: {} contacts", thr);
-        println!("resolution_bp\tmin_cov\tmean_cov\tmax_cov");
-
-        let mut resolutions = hic.resolutions.clone();
-        resolutions.sort_unstable();
-
-        // Collect usable chromosome indices: index>0 and length >= 2,500,000 bp
-        let chr_idxs: Vec<i32> = hic
-            .chromosomes
-            .iter()
-            .filter(|c| c.index > 0 && c.length >= 2_500_000)
-            .map(|c| c.index)
-            .collect();
+impl<'a> BlockRecords<'a> {
+    /// Read the header of an inflated block. Fails on an unknown block type,
+    /// a negative count, or a header that ends early. An empty buffer (an
+    /// empty index entry) holds no records.
+    fn new(buf: &'a [u8], version: i32) -> Result<Self> {
+        let mut cur = BlockCursor { buf, pos: 0 };
+        if buf.is_empty() {
+            return Ok(BlockRecords { cur, declared: 0, layout: BlockLayout::Done, error: None });
+        }
+        let declared = cur.count(false, "record")? as usize;
+        if version < 7 {
+            // bin_x, bin_y, counts: 12 bytes per record
+            if declared > cur.remaining() / 12 {
+                return Err(anyhow!("declares {} records but only {} bytes follow", declared, cur.remaining()));
+            }
+            return Ok(BlockRecords { cur, declared, layout: BlockLayout::Triples { left: declared }, error: None });
+        }
 
-        for res in resolutions {
-            let mut covs: Vec<f64> = Vec::with_capacity(chr_idxs.len());
-            for &ci in &chr_idxs {
-                let cov_opt = match hic.get_matrix_zoom_data(ci, ci, "BP", res)? {
-                    None => None,
-                    Some(mzd) => {
-                        let mut counts: HashMap<i32, f64> = HashMap::new();
-                        for (_, idx) in mzd.block_map.iter() {
-                            let records = read_block(&hic.path, idx, mzd.version)?;
-                            for rec in records {
-                                *counts.entry(rec.bin_x).or_insert(0.0) += rec.counts as f64;
-                                *counts.entry(rec.bin_y).or_insert(0.0) += rec.counts as f64;
-                            }
-                        }
-                        if counts.is_empty() {
-                            None // exclude no-signal contig for this resolution
-                        } else {
-                            let covered = counts.values().filter(|&&v| v >= thr as f64).count();
-                            Some(covered as f64 / counts.len() as f64)
-                        }
+        let x_offset = cur.i32()?;
+        let y_offset = cur.i32()?;
+        let short_counts = cur.u8()? == 0;
+        let (mut short_x, mut short_y) = (true, true);
+        if version > 8 {
+            short_x = cur.u8()? == 0;
+            short_y = cur.u8()? == 0;
+        }
+        let typ_at = cur.pos;
+        let layout = match cur.u8()? {
+            1 => {
+                let rows_left = cur.count(short_y, "row")?;
+                BlockLayout::Sparse { x_offset, y_offset, short_counts, short_x, short_y, rows_left, bin_y: 0, cols_left: 0 }
+            }
+            2 => {
+                let n = cur.count(false, "dense point")?;
+                let width = cur.i16()? as i32;
+                if width <= 0 && n > 0 {
+                    return Err(anyhow!("dense block of {} points has width {}", n, width));
+                }
+                BlockLayout::Dense { x_offset, y_offset, short_counts, n, width, i: 0 }
+            }
+            typ => return Err(anyhow!("unknown block type {} at offset {}", typ, typ_at)),
+        };
+        Ok(BlockRecords { cur, declared, layout, error: None })
+    }
+
+    /// The next record, `None` after the last one.
+    fn read_next(&mut self) -> Result<Option<ContactRecord>> {
+        let cur = &mut self.cur;
+        loop {
+            match &mut self.layout {
+                BlockLayout::Done => return Ok(None),
+                BlockLayout::Triples { left } => {
+                    if *left == 0 {
+                        break;
                     }
-                };
-                if let Some(cov) = cov_opt { covs.push(cov); }
+                    *left -= 1;
+                    return Ok(Some(ContactRecord { bin_x: cur.i32()?, bin_y: cur.i32()?, counts: cur.f32()? }));
+                }
+                BlockLayout::Sparse { x_offset, y_offset, short_counts, short_x, short_y, rows_left, bin_y, cols_left } => {
+                    if *cols_left > 0 {
+                        *cols_left -= 1;
+                        let bin_x = *x_offset + cur.int(*short_x)?;
+                        let counts = if *short_counts { cur.i16()? as f32 } else { cur.f32()? };
+                        return Ok(Some(ContactRecord { bin_x, bin_y: *bin_y, counts }));
+                    }
+                    if *rows_left == 0 {
+                        break;
+                    }
+                    *rows_left -= 1;
+                    *bin_y = *y_offset + cur.int(*short_y)?;
+                    *cols_left = cur.count(*short_x, "column")?;
+                }
+                BlockLayout::Dense { x_offset, y_offset, short_counts, n, width, i } => {
+                    if *i >= *n {
+                        break;
+                    }
+                    let (row, col) = (*i / *width, *i % *width);
+                    *i += 1;
+                    let (bin_x, bin_y) = (*x_offset + col, *y_offset + row);
+                    // -32768 and NaN mark empty cells
+                    if *short_counts {
+                        let c = cur.i16()?;
+                        if c != -32768 { return Ok(Some(ContactRecord { bin_x, bin_y, counts: c as f32 })); }
+                    } else {
+                        let counts = cur.f32()?;
+                        if !counts.is_nan() { return Ok(Some(ContactRecord { bin_x, bin_y, counts })); }
+                    }
+                }
+            }
+        }
+        self.layout = BlockLayout::Done;
+        Ok(None)
+    }
+
+    /// Why the records stopped early, if they did: a block that ends before
+    /// its records do, or a negative column count.
+    fn finish(&mut self) -> Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Iterator for BlockRecords<'_> {
+    type Item = ContactRecord;
+
+    fn next(&mut self) -> Option<ContactRecord> {
+        match self.read_next() {
+            Ok(rec) => rec,
+            Err(e) => {
+                self.error = Some(e);
+                self.layout = BlockLayout::Done;
+                None
+            }
+        }
+    }
+}
+
+/// Record ordering for slice output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortMode {
+    /// Block order as stored in the file
+    #[default]
+    None,
+    /// Sort by (binX, binY) within each chromosome pair, in memory
+    PerPair,
+    /// Sort the whole file by (key1, binX, key2, binY) with a spill-to-disk merge
+    Global,
+}
+
+/// Values written by [`dump_hic_genome_wide`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MatrixType {
+    /// Stored (optionally normalized) counts
+    #[default]
+    Observed,
+    /// Counts divided by the expected value: the distance-decay vector for
+    /// intra-chromosomal pairs, the matrix mean for inter-chromosomal ones
+    Oe,
+}
+
+/// File layout of dumped contacts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DumpFormat {
+    /// Binary slice file (gzip-compressed unless [`SliceCompression`] says otherwise)
+    #[default]
+    Slc,
+    /// Juicer "short with score" text, `str1 chr1 pos1 frag1 str2 chr2 pos2 frag2 score`,
+    /// with strands and fragments fixed to 0 and positions at bin starts
+    Short,
+    /// Tab-separated `chrom1 start1 end1 chrom2 start2 end2 count` with bin
+    /// bounds in bp (the last bin ends at the chromosome end); intra records
+    /// are written upper-triangular
+    Bedpe,
+}
+
+impl DumpFormat {
+    /// Text layouts with bp positions, which need the BP unit.
+    fn is_text(self) -> bool {
+        self != DumpFormat::Slc
+    }
+}
+
+/// Order of the chromosome keys of a slice file: keys are numbered from 0 in
+/// this order, and the header lists them in it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum KeyOrder {
+    /// Lexicographic by name (chr1, chr10, chr11, ..., chr2)
+    Name,
+    /// .hic header order, i.e. by chromosome index
+    #[default]
+    Index,
+    /// By name with digit runs compared as numbers (chr2 before chr10)
+    Natural,
+}
+
+/// Container of a slice file. The header and records are the same in all
+/// three; readers sniff the gzip magic, which BGZF shares.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SliceCompression {
+    /// Raw records, for an intermediate read straight back
+    None,
+    #[default]
+    Gzip,
+    /// Blocked gzip (as samtools/tabix), readable by any gzip reader
+    Bgzf,
+}
+
+/// Options for [`dump_hic_genome_wide`].
+#[derive(Clone, Debug)]
+pub struct DumpOptions {
+    pub matrix: MatrixType,
+    pub format: DumpFormat,
+    /// Container of slice output; text formats are compressed by a `.gz` extension
+    pub compression: SliceCompression,
+    pub sort: SortMode,
+    /// Directory for external-sort run files (system temp dir when `None`)
+    pub tmpdir: Option<PathBuf>,
+    /// Records per in-memory sort run before spilling to disk
+    pub sort_run_records: usize,
+    /// Only dump pairs among these chromosomes (flexible name matching); all when `None`
+    pub chroms: Option<Vec<String>>,
+    /// Leave out these chromosomes and their pairs (flexible name matching)
+    pub exclude_chroms: Option<Vec<String>>,
+    /// Swap intra-chromosomal records so that `bin_x <= bin_y`
+    pub canonicalize: bool,
+    /// Retries of a block read failing with a transient IO error
+    pub io_retries: u32,
+    /// Numbering and header order of the slice chromosome keys
+    pub key_order: KeyOrder,
+    /// Normalization vector type (`VC`, `VC_SQRT`, `KR`); `NONE` writes raw counts
+    pub norm: String,
+    /// Matrix unit, `BP` or `FRAG` (bins of restriction fragments)
+    pub unit: String,
+    /// Skip records whose written value is below this; `None` keeps every value above zero
+    pub min_count: Option<f32>,
+    /// Chromosome pairs of a genome-wide dump: all, intra- (`cis`) or inter-chromosomal (`trans`)
+    pub scope: ContactScope,
+    /// Show a progress bar on stderr during a genome-wide dump
+    pub progress: bool,
+    /// Report retried block reads on stderr
+    pub verbose: bool,
+}
+
+impl Default for DumpOptions {
+    fn default() -> Self {
+        DumpOptions {
+            matrix: MatrixType::Observed,
+            format: DumpFormat::Slc,
+            compression: SliceCompression::Gzip,
+            sort: SortMode::None,
+            tmpdir: None,
+            sort_run_records: DEFAULT_RUN_RECORDS,
+            chroms: None,
+            exclude_chroms: None,
+            canonicalize: false,
+            io_retries: DEFAULT_IO_RETRIES,
+            key_order: KeyOrder::Index,
+            norm: "NONE".to_string(),
+            unit: "BP".to_string(),
+            min_count: None,
+            scope: ContactScope::All,
+            progress: false,
+            verbose: false,
+        }
+    }
+}
+
+impl DumpOptions {
+    /// Whether intra records are swapped to `bin_x <= bin_y`: on request, and
+    /// always for BEDPE, which is read as an upper triangle.
+    fn canonicalize_intra(&self) -> bool {
+        self.canonicalize || self.format == DumpFormat::Bedpe
+    }
+}
+
+/// Norm and expected data a dump needs, checked against the file before any
+/// output is created.
+struct DumpValues {
+    unit: String,
+    normalize: bool,
+    min_count: Option<f32>,
+    expected: Option<ExpectedValues>,
+    norm_cache: HashMap<i32, Vec<f64>>,
+}
+
+impl DumpValues {
+    fn prepare(hic: &mut HicFile, input: &Path, binsize: i32, opts: &DumpOptions) -> Result<Self> {
+        let normalize = !opts.norm.eq_ignore_ascii_case("NONE");
+        if normalize {
+            // Fail before creating the output when the file has no such vectors at all
+            let available = hic.normalization_types()?;
+            if !available.contains(&opts.norm) {
+                return Err(anyhow!(
+                    "{:?} has no {} normalization; available: {}",
+                    input,
+                    opts.norm,
+                    if available.is_empty() { "none (only NONE can be dumped)".to_string() } else { available.join(", ") }
+                ));
+            }
+        }
+        // O/E of normalized counts uses the expected vector of the same normalization
+        let expected = match opts.matrix {
+            MatrixType::Oe => {
+                let norm = if normalize { opts.norm.as_str() } else { "NONE" };
+                Some(hic.require_expected_values(norm, &opts.unit, binsize)?)
+            }
+            MatrixType::Observed => None,
+        };
+        Ok(DumpValues { unit: opts.unit.clone(), normalize, min_count: opts.min_count, expected, norm_cache: HashMap::new() })
+    }
+
+    /// Load the norm vectors of both chromosomes of `mzd` (once per chromosome).
+    fn load_norms(&mut self, hic: &mut HicFile, mzd: &MatrixZoomData, binsize: i32, norm: &str) -> Result<()> {
+        if self.normalize {
+            for c in [mzd.c1, mzd.c2] {
+                if let std::collections::hash_map::Entry::Vacant(slot) = self.norm_cache.entry(c) {
+                    slot.insert(hic.require_norm_vector(c, &self.unit, binsize, norm)?);
+                }
             }
-            if covs.is_empty() {
-                println!("{}\t{:.3}\t{:.3}\t{:.3}", res, 0.0, 0.0, 0.0);
+        }
+        Ok(())
+    }
+
+    /// The value to write for `rec` of matrix `mzd`: normalized, then divided
+    /// by its expected value; `None` for records straw would skip and those
+    /// below the minimum count. Call [`Self::load_norms`] for the matrix first.
+    fn value(&self, mzd: &MatrixZoomData, rec: &ContactRecord) -> Option<f32> {
+        let mut counts = rec.counts;
+        if self.normalize {
+            counts = normalized_counts(counts, rec.bin_x, rec.bin_y, &self.norm_cache[&mzd.c1], &self.norm_cache[&mzd.c2])?;
+        }
+        if let Some(exp) = &self.expected {
+            let e = if mzd.is_intra {
+                exp.at(mzd.c1, rec.bin_x.abs_diff(rec.bin_y) as usize)
             } else {
-                let min = covs
-                    .iter()
-                    .copied()
-                    .fold(f64::INFINITY, f64::min);
-                let max = covs
-                    .iter()
-                    .copied()
-                    .fold(f64::NEG_INFINITY, f64::max);
-                let mean = covs.iter().sum::<f64>() / (covs.len() as f64);
-                println!("{}\t{:.3}\t{:.3}\t{:.3}", res, min, mean, max);
+                // Inter-chromosomal pairs have no distance; straw divides by the matrix mean
+                Some(mzd.sum_counts as f64 / (mzd.num_bins1 as f64 * mzd.num_bins2 as f64))
+            };
+            counts = observed_over_expected(counts, e)?;
+        }
+        self.keeps(counts).then_some(counts)
+    }
+
+    /// Whether a final value is written: above zero, finite, and at least the minimum count.
+    fn keeps(&self, counts: f32) -> bool {
+        counts > 0.0 && counts.is_finite() && self.min_count.is_none_or(|m| counts >= m)
+    }
+}
+
+/// Records written and skipped by a genome-wide dump, reported once it is done.
+#[derive(Debug, Default)]
+struct DumpTally {
+    written: u64,
+    /// Records dropped as zero, non-finite or below `--min-count`
+    skipped: u64,
+    /// Chromosome pairs left out by the contact scope, not decoded
+    pairs_skipped: u64,
+}
+
+impl DumpTally {
+    fn report(&self, opts: &DumpOptions) {
+        let reason = match opts.min_count {
+            Some(m) => format!("zero, non-finite or below {}", m),
+            None => "zero or non-finite".to_string(),
+        };
+        eprintln!("Wrote {} records, skipped {} ({})", self.written, self.skipped, reason);
+        if opts.scope != ContactScope::All {
+            eprintln!("Left out {} chromosome pairs; kept {}", self.pairs_skipped, opts.scope.describe());
+        }
+    }
+}
+
+/// A dump output: a file, gzip- or BGZF-compressed or plain, or stdout.
+enum DumpSink {
+    Gz(GzEncoder<BufWriter<File>>),
+    Bgzf(BgzfWriter<BufWriter<File>>),
+    Plain(BufWriter<File>),
+    Stdout(BufWriter<std::io::StdoutLock<'static>>),
+}
+
+impl DumpSink {
+    fn create(path: &Path, gz: bool) -> Result<Self> {
+        Self::create_compressed(path, if gz { SliceCompression::Gzip } else { SliceCompression::None })
+    }
+
+    fn create_compressed(path: &Path, compression: SliceCompression) -> Result<Self> {
+        let out = BufWriter::new(File::create(path).with_context(|| format!("Create {:?}", path))?);
+        Ok(match compression {
+            SliceCompression::None => DumpSink::Plain(out),
+            SliceCompression::Gzip => DumpSink::Gz(GzEncoder::new(out, Compression::default())),
+            SliceCompression::Bgzf => DumpSink::Bgzf(BgzfWriter::new(out, Compression::default())),
+        })
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            DumpSink::Gz(enc) => enc.finish()?.flush()?,
+            DumpSink::Bgzf(enc) => enc.finish()?.flush()?,
+            DumpSink::Plain(mut out) => out.flush()?,
+            DumpSink::Stdout(mut out) => out.flush()?,
+        }
+        Ok(())
+    }
+}
+
+impl Write for DumpSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            DumpSink::Gz(enc) => enc.write(buf),
+            DumpSink::Bgzf(enc) => enc.write(buf),
+            DumpSink::Plain(out) => out.write(buf),
+            DumpSink::Stdout(out) => out.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            DumpSink::Gz(enc) => enc.flush(),
+            DumpSink::Bgzf(enc) => enc.flush(),
+            DumpSink::Plain(out) => out.flush(),
+            DumpSink::Stdout(out) => out.flush(),
+        }
+    }
+}
+
+/// Writes dumped records in the chosen [`DumpFormat`]. Slice files get their
+/// header on creation, in the chosen [`SliceCompression`] container; text
+/// outputs are gzip-compressed when the path ends in `.gz`.
+struct RecordWriter {
+    sink: DumpSink,
+    format: DumpFormat,
+    binsize: i64,
+    /// Chromosome name of each slice key
+    names: Vec<String>,
+    /// Chromosome length of each slice key, for BEDPE bin ends
+    lengths: Vec<i64>,
+}
+
+impl RecordWriter {
+    fn create(path: &Path, opts: &DumpOptions, binsize: i32, key_table: &[(String, i16)], hic: &HicFile) -> Result<Self> {
+        let format = opts.format;
+        let mut sink = if format == DumpFormat::Slc {
+            DumpSink::create_compressed(path, opts.compression)?
+        } else {
+            DumpSink::create(path, path.extension().is_some_and(|e| e == "gz"))?
+        };
+        if format == DumpFormat::Slc {
+            slice::write_header(&mut sink, binsize, key_table)?;
+        }
+        let mut names = vec![String::new(); key_table.len()];
+        let mut lengths = vec![0; key_table.len()];
+        for (name, key) in key_table {
+            names[*key as usize] = name.clone();
+            lengths[*key as usize] = hic.chromosomes.iter().find(|c| c.name == *name).map_or(0, |c| c.length);
+        }
+        Ok(RecordWriter { sink, format, binsize: binsize as i64, names, lengths })
+    }
+
+    fn write(&mut self, rec: &SliceRecord) -> Result<()> {
+        match self.format {
+            DumpFormat::Slc => rec.write_to(&mut self.sink)?,
+            DumpFormat::Short => writeln!(
+                self.sink,
+                "0 {} {} 0 0 {} {} 0 {}",
+                self.names[rec.key1 as usize],
+                rec.bin_x as i64 * self.binsize,
+                self.names[rec.key2 as usize],
+                rec.bin_y as i64 * self.binsize,
+                rec.counts
+            )?,
+            DumpFormat::Bedpe => {
+                let bounds = |key: i16, bin: i32| {
+                    let start = bin as i64 * self.binsize;
+                    (start, (start + self.binsize).min(self.lengths[key as usize]))
+                };
+                let ((s1, e1), (s2, e2)) = (bounds(rec.key1, rec.bin_x), bounds(rec.key2, rec.bin_y));
+                writeln!(
+                    self.sink,
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    self.names[rec.key1 as usize], s1, e1, self.names[rec.key2 as usize], s2, e2, rec.counts
+                )?
             }
         }
-        return Ok(());
+        Ok(())
     }
 
-    // Single chromosome path (original: resolution vs coverage and effective resolution thresholding)
-    // Collect available chromosomes with owned names to avoid borrowing conflicts
-    let avail: Vec<(String, i32)> = hic
-        .chromosomes
+    fn finish(self) -> Result<()> {
+        self.sink.finish()
+    }
+}
+
+/// Slice keys of the chromosomes a dump writes.
+struct SliceKeys {
+    /// (name, key) in key order, for the header
+    table: Vec<(String, i16)>,
+    /// Key of each .hic chromosome index; `None` for those left out
+    by_index: Vec<Option<i16>>,
+}
+
+impl SliceKeys {
+    /// Key of the chromosome with .hic index `chr_idx`, which must be dumped.
+    fn of(&self, chr_idx: i32) -> i16 {
+        self.by_index[chr_idx as usize].expect("dumped chromosomes have a key")
+    }
+}
+
+/// Slice key of every real chromosome (index > 0), or of the `selected` ones,
+/// numbered densely in `order`.
+fn slice_keys(hic: &HicFile, selected: Option<&HashSet<i32>>, order: KeyOrder) -> SliceKeys {
+    let mut chroms: Vec<&Chromosome> =
+        hic.chromosomes.iter().filter(|c| c.index > 0 && selected.is_none_or(|s| s.contains(&c.index))).collect();
+    match order {
+        KeyOrder::Name => chroms.sort_by(|a, b| a.name.cmp(&b.name)),
+        KeyOrder::Index => {}
+        KeyOrder::Natural => chroms.sort_by(|a, b| natural_cmp(&a.name, &b.name)),
+    }
+    let mut by_index = vec![None; hic.chromosomes.len()];
+    let table = chroms
         .iter()
-        .filter(|c| c.index > 0)
-        .map(|c| (c.name.clone(), c.index))
+        .enumerate()
+        .map(|(key, c)| {
+            by_index[c.index as usize] = Some(key as i16);
+            (c.name.clone(), key as i16)
+        })
+        .collect();
+    SliceKeys { table, by_index }
+}
+
+/// Compare names with runs of digits taken as numbers, so `chr2 < chr10`;
+/// equal numbers with different leading zeros fall back to the text.
+fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    let (mut a_rest, mut b_rest) = (a.as_bytes(), b.as_bytes());
+    while let (Some(&x), Some(&y)) = (a_rest.first(), b_rest.first()) {
+        let ord = if x.is_ascii_digit() && y.is_ascii_digit() {
+            let run = |s: &[u8]| s.iter().take_while(|c| c.is_ascii_digit()).count();
+            let (na, nb) = (run(a_rest), run(b_rest));
+            fn strip_zeros(digits: &[u8]) -> &[u8] {
+                &digits[digits.iter().take_while(|&&c| c == b'0').count()..]
+            }
+            let (da, db) = (strip_zeros(&a_rest[..na]), strip_zeros(&b_rest[..nb]));
+            let ord = da.len().cmp(&db.len()).then_with(|| da.cmp(db));
+            a_rest = &a_rest[na..];
+            b_rest = &b_rest[nb..];
+            ord
+        } else {
+            a_rest = &a_rest[1..];
+            b_rest = &b_rest[1..];
+            x.cmp(&y)
+        };
+        if ord != std::cmp::Ordering::Equal {
+            return ord;
+        }
+    }
+    a_rest.len().cmp(&b_rest.len()).then_with(|| a.cmp(b))
+}
+
+/// Indices of the chromosomes a dump keeps: `opts.chroms` (every chromosome
+/// when `None`) less `opts.exclude_chroms`; `None` when neither is set. Names
+/// match like `effres`, and an unknown name fails listing the available ones.
+fn dump_selection(hic: &HicFile, opts: &DumpOptions) -> Result<Option<HashSet<i32>>> {
+    if opts.chroms.is_none() && opts.exclude_chroms.is_none() {
+        return Ok(None);
+    }
+    let mut selected: HashSet<i32> = match &opts.chroms {
+        Some(names) => hic.resolve_chromosomes(names)?.into_iter().collect(),
+        None => hic.chromosomes.iter().filter(|c| c.index > 0).map(|c| c.index).collect(),
+    };
+    if let Some(names) = &opts.exclude_chroms {
+        for idx in hic.resolve_chromosomes(names)? {
+            selected.remove(&idx);
+        }
+    }
+    if selected.is_empty() {
+        if opts.exclude_chroms.is_none() {
+            return Err(anyhow!("--chroms names no chromosome of {:?} to dump", hic.path));
+        }
+        return Err(anyhow!("--chroms and --exclude-chroms leave no chromosome of {:?} to dump", hic.path));
+    }
+    Ok(Some(selected))
+}
+
+/// One genome-wide dump output: records go straight to the writer, are
+/// collected per chromosome pair, or feed a spill-to-disk sorter, by
+/// `opts.sort`.
+struct GenomeOutput {
+    writer: RecordWriter,
+    sort: SortMode,
+    pair_records: Vec<SliceRecord>,
+    sorter: Option<ExternalSorter>,
+}
+
+impl GenomeOutput {
+    fn create(path: &Path, binsize: i32, key_table: &[(String, i16)], hic: &HicFile, opts: &DumpOptions) -> Result<Self> {
+        Ok(GenomeOutput {
+            writer: RecordWriter::create(path, opts, binsize, key_table, hic)?,
+            sort: opts.sort,
+            pair_records: Vec::new(),
+            sorter: match opts.sort {
+                SortMode::Global => Some(ExternalSorter::new(opts.tmpdir.as_deref(), opts.sort_run_records)),
+                _ => None,
+            },
+        })
+    }
+
+    fn push(&mut self, rec: SliceRecord) -> Result<()> {
+        match self.sort {
+            SortMode::None => self.writer.write(&rec),
+            SortMode::PerPair => {
+                self.pair_records.push(rec);
+                Ok(())
+            }
+            SortMode::Global => self.sorter.as_mut().expect("sorter for global sort").push(rec),
+        }
+    }
+
+    /// Close the current chromosome pair (writes its records when sorting per pair).
+    fn end_pair(&mut self) -> Result<()> {
+        if self.sort == SortMode::PerPair {
+            self.pair_records.sort_unstable_by_key(|r| (r.bin_x, r.bin_y));
+            for r in self.pair_records.drain(..) {
+                self.writer.write(&r)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        if let Some(sorter) = self.sorter.take() {
+            for rec in sorter.finish()? {
+                self.writer.write(&rec?)?;
+            }
+        }
+        self.writer.finish()
+    }
+}
+
+/// Fail for text formats, which write bp positions, with a non-BP unit.
+fn require_text_unit(opts: &DumpOptions) -> Result<()> {
+    if opts.format.is_text() && !opts.unit.eq_ignore_ascii_case("BP") {
+        let name = if opts.format == DumpFormat::Bedpe { "bedpe" } else { "short" };
+        return Err(anyhow!("--format {} writes bp positions and needs the BP unit", name));
+    }
+    Ok(())
+}
+
+pub fn dump_hic_genome_wide(input: &Path, binsize: i32, output: &Path, opts: &DumpOptions) -> Result<()> {
+    let mut hic = HicFile::open(input)?;
+    hic.set_io_retries(opts.io_retries);
+    hic.set_verbose(opts.verbose);
+    dump_hic_genome_wide_with_open(&mut hic, binsize, output, opts, "")
+}
+
+/// [`dump_hic_genome_wide`] of a file already open, so several dumps share
+/// its header, matrix lookups and footer. `label` starts the progress line.
+fn dump_hic_genome_wide_with_open(hic: &mut HicFile, binsize: i32, output: &Path, opts: &DumpOptions, label: &str) -> Result<()> {
+    require_text_unit(opts)?;
+    hic.require_resolution(&opts.unit, binsize)?;
+    let selected = dump_selection(hic, opts)?;
+    let input = hic.path.clone();
+    let mut values = DumpValues::prepare(hic, &input, binsize, opts)?;
+    let wanted = |idx: i32| idx > 0 && selected.as_ref().is_none_or(|s| s.contains(&idx));
+    // Build chromosome keys (skip index <= 0 per C++ code)
+    let chr_keys = slice_keys(hic, selected.as_ref(), opts.key_order);
+
+    // Open the output (a slice file starts with its header)
+    let mut out = GenomeOutput::create(output, binsize, &chr_keys.table, hic, opts)?;
+    let mut tally = DumpTally::default();
+
+    // Locate the matrices of every chromosome pair first: their block sizes
+    // drive the progress bar, as pairs differ in size by orders of magnitude
+    let mut matrices = Vec::new();
+    let n = hic.chromosomes.len();
+    for i in 0..n {
+        let c1_idx = hic.chromosomes[i].index;
+        if !wanted(c1_idx) { continue; }
+        for j in i..n {
+            let c2_idx = hic.chromosomes[j].index;
+            if !wanted(c2_idx) { continue; }
+            if !opts.scope.keeps_intra(c1_idx == c2_idx) {
+                tally.pairs_skipped += 1;
+                continue;
+            }
+            matrices.extend(hic.get_matrix_zoom_data(c1_idx, c2_idx, &opts.unit, binsize)?);
+        }
+    }
+    let total_bytes: u64 = matrices.iter().flat_map(|m| m.block_map.values()).map(|e| e.size.max(0) as u64).sum();
+    let pb = dump_progress(opts, total_bytes)?;
+    pb.set_prefix(label.to_string());
+    for mzd in &matrices {
+        values.load_norms(hic, mzd, binsize, &opts.norm)?;
+    }
+    let keys: Vec<(i16, i16)> = matrices
+        .iter()
+        .map(|m| (chr_keys.of(m.c1), chr_keys.of(m.c2)))
         .collect();
-    // Flexible name matching: case-insensitive, optional "chr" prefix
-    let req_s = chrom_req.unwrap().to_lowercase();
-    let req_trim = req_s.trim_start_matches("chr").to_string();
-    let mut c_idx_opt: Option<i32> = None;
-    for (name, idx) in &avail {
-        let nm = name.to_lowercase();
-        if nm == req_s || nm.trim_start_matches("chr") == &req_trim {
-            c_idx_opt = Some(*idx);
-            break;
-        }
-    }
-    let c_idx = match c_idx_opt {
-        Some(i) => i,
-        None => {
-            eprintln!(
-                "[ERROR] 未找到染色体 '{}', 可选值: {}",
-                chrom_req.unwrap(),
-                avail
-                    .iter()
-                    .map(|(n, _)| n.as_str())
-                    .collect::<Vec<_>>()
-                    .join(", ")
+
+    // Pairs are decoded on a pool of workers, each taking the next pair in
+    // order and queueing the records of its blocks on that pair's bounded
+    // channel. The pairs are written from their channels in order, so the
+    // output is the same for any thread count, while a pair's block reads
+    // overlap with those of the pairs after it.
+    let workers = rayon::current_num_threads().clamp(1, matrices.len().max(1));
+    let (senders, receivers): (Vec<_>, Vec<_>) = matrices
+        .iter()
+        .map(|_| {
+            let (tx, rx) = mpsc::sync_channel::<Result<(Vec<SliceRecord>, u64)>>(BLOCKS_PER_THREAD);
+            (Mutex::new(Some(tx)), rx)
+        })
+        .unzip();
+    let next = AtomicUsize::new(0);
+    let (hic, values) = (&*hic, &values);
+    std::thread::scope(|s| -> Result<()> {
+        for _ in 0..workers {
+            s.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(mzd) = matrices.get(i) else { break };
+                    let tx = senders[i].lock().unwrap().take().expect("each pair is decoded once");
+                    let (key1, key2) = keys[i];
+                    // Blocks arrive in block-number order, as the index lists them
+                    let mut sizes = mzd.block_map.values().map(|e| e.size.max(0) as u64);
+                    let send = |kept, skipped| tx.send(Ok((kept, skipped))).map_err(|_| anyhow!("dump output closed"));
+                    let decoded = hic.for_each_block(mzd, |records| {
+                        // A dense block goes out in pieces, keeping the records in flight bounded
+                        let mut kept = Vec::new();
+                        let mut skipped = 0;
+                        for rec in records {
+                            let Some(counts) = values.value(mzd, &rec) else {
+                                skipped += 1;
+                                continue;
+                            };
+                            let rec = ContactRecord { counts, ..rec };
+                            let rec = if opts.canonicalize_intra() { rec.canonicalized(mzd.is_intra) } else { rec };
+                            kept.push(SliceRecord { key1, bin_x: rec.bin_x, key2, bin_y: rec.bin_y, counts: rec.counts });
+                            if kept.len() == RECORDS_PER_MESSAGE {
+                                send(std::mem::take(&mut kept), std::mem::take(&mut skipped))?;
+                            }
+                        }
+                        pb.inc(sizes.next().unwrap_or(0));
+                        send(kept, skipped)
+                    });
+                    // A failed send means the writer stopped: leave the remaining pairs
+                    if let Err(e) = decoded {
+                        if tx.send(Err(e)).is_err() {
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+        for (done, rx) in receivers.into_iter().enumerate() {
+            let mzd = &matrices[done];
+            let pair = format!(
+                "{}-{} ({}/{} pairs)",
+                hic.chromosomes[mzd.c1 as usize].name,
+                hic.chromosomes[mzd.c2 as usize].name,
+                done + 1,
+                matrices.len()
             );
-            return Ok(());
+            for block in rx {
+                let (records, skipped) = block?;
+                tally.skipped += skipped;
+                tally.written += records.len() as u64;
+                for rec in records {
+                    out.push(rec)?;
+                }
+                pb.set_message(format!("{}, {} records", pair, tally.written));
+            }
+            out.end_pair()?;
         }
+        Ok(())
+    })?;
+    pb.finish_and_clear();
+
+    out.finish()?;
+    tally.report(opts);
+    Ok(())
+}
+
+/// Progress bar of a genome-wide dump over `total_bytes` of compressed
+/// blocks, on stderr; hidden unless `opts.progress` is set.
+fn dump_progress(opts: &DumpOptions, total_bytes: u64) -> Result<ProgressBar> {
+    if !opts.progress {
+        return Ok(ProgressBar::hidden());
+    }
+    let pb = ProgressBar::new(total_bytes);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} {prefix}[{elapsed_precise}] [{bar:30}] {percent}% (ETA {eta}) {msg}")?
+            .progress_chars("=> "),
+    );
+    Ok(pb)
+}
+
+/// Extensions of dump outputs, longest first, stripped from a multi-resolution prefix.
+const DUMP_EXTENSIONS: [&str; 4] = [".short.gz", ".bedpe.gz", ".slc.gz", ".slc"];
+
+/// Output path of one resolution of a multi-resolution dump:
+/// `<prefix>.<binsize>.slc.gz` (`.slc` without compression), or `.short.gz` /
+/// `.bedpe.gz` for text formats. A prefix that already ends in one of these
+/// extensions has the bin size inserted before it (`out.slc.gz` gives
+/// `out.<binsize>.slc.gz`).
+pub fn multi_resolution_path(prefix: &Path, binsize: i32, opts: &DumpOptions) -> PathBuf {
+    let ext = match opts.format {
+        DumpFormat::Slc if opts.compression == SliceCompression::None => "slc",
+        DumpFormat::Slc => "slc.gz",
+        DumpFormat::Short => "short.gz",
+        DumpFormat::Bedpe => "bedpe.gz",
     };
+    let prefix = prefix.to_string_lossy();
+    let stem = DUMP_EXTENSIONS.iter().find_map(|e| prefix.strip_suffix(e)).filter(|s| !s.is_empty()).unwrap_or(&prefix);
+    PathBuf::from(format!("{}.{}.{}", stem, binsize, ext))
+}
 
-    println!("# File: {}", input.display());
-    let cname = hic.chromosomes[c_idx as usize].name.clone();
-    println!("# Chromosome: {}", cname);
-    println!("# Threshold per bin: {} contacts", thr);
-    println!("# Required coverage: {:.1}% bins\n", pct * 100.0);
-    println!("resolution_bp\tcoverage");
+/// Dump several resolutions genome-wide in one pass over the file, one
+/// output per resolution at [`multi_resolution_path`]; returns the paths in
+/// ascending binsize order. Each output is what [`dump_hic_genome_wide`]
+/// writes at its binsize, header included.
+///
+/// For observed counts without normalization, the finest requested
+/// resolution is read and every coarser one that is a multiple of it is
+/// summed from its records (bin index divided by the ratio), so those need
+/// not be stored in the file. Other resolutions, and every resolution of a
+/// normalized or O/E dump, are read from the file. Summed outputs are
+/// ordered by bin within each chromosome pair.
+pub fn dump_hic_multi_resolution(input: &Path, binsizes: &[i32], prefix: &Path, opts: &DumpOptions) -> Result<Vec<PathBuf>> {
+    let mut binsizes = binsizes.to_vec();
+    binsizes.sort_unstable();
+    binsizes.dedup();
+    let finest = *binsizes.first().ok_or_else(|| anyhow!("No bin sizes given"))?;
+    let mut hic = HicFile::open(input)?;
+    hic.set_io_retries(opts.io_retries);
+    hic.set_verbose(opts.verbose);
+    require_text_unit(opts)?;
+    hic.require_unit(&opts.unit)?;
+    let can_sum = opts.matrix == MatrixType::Observed
+        && opts.norm.eq_ignore_ascii_case("NONE")
+        && hic.unit_resolutions(&opts.unit).contains(&finest);
+    // (binsize, factor) of the outputs summed from the finest resolution
+    let summed: Vec<(i32, i32)> =
+        binsizes.iter().filter(|&&r| can_sum && r != finest && r % finest == 0).map(|&r| (r, r / finest)).collect();
+    let read: Vec<i32> = binsizes.iter().copied().filter(|r| !summed.iter().any(|(s, _)| s == r)).collect();
+    for &r in &read {
+        hic.require_resolution(&opts.unit, r)?;
+    }
+    let selected = dump_selection(&hic, opts)?;
+    let mut values = Vec::with_capacity(read.len());
+    for &r in &read {
+        values.push(DumpValues::prepare(&mut hic, input, r, opts)?);
+    }
+    let wanted = |idx: i32| idx > 0 && selected.as_ref().is_none_or(|s| s.contains(&idx));
+    let chr_keys = slice_keys(&hic, selected.as_ref(), opts.key_order);
+    let mut outputs: BTreeMap<i32, (PathBuf, GenomeOutput)> = BTreeMap::new();
+    for &r in &binsizes {
+        let path = multi_resolution_path(prefix, r, opts);
+        let out = GenomeOutput::create(&path, r, &chr_keys.table, &hic, opts)?;
+        outputs.insert(r, (path, out));
+    }
+    let mut tally = DumpTally::default();
 
-    let mut resolutions = hic.resolutions.clone();
-    resolutions.sort_unstable();
-    let mut eff_res: Option<i32> = None;
-    for res in resolutions {
-        match hic.get_matrix_zoom_data(c_idx, c_idx, "BP", res)? {
-            None => {
-                println!("{}\t{:.3}", res, 0.0);
-            }
-            Some(mzd) => {
-                // Accumulate per-bin counts using a sparse map to mirror the Python reference
-                let mut counts: HashMap<i32, f64> = HashMap::new();
-                for (_, idx) in mzd.block_map.iter() {
-                    let records = read_block(&hic.path, idx, mzd.version)?;
+    let n = hic.chromosomes.len();
+    for i in 0..n {
+        let c1_idx = hic.chromosomes[i].index;
+        if !wanted(c1_idx) { continue; }
+        for j in i..n {
+            let c2_idx = hic.chromosomes[j].index;
+            if !wanted(c2_idx) { continue; }
+            if !opts.scope.keeps_intra(c1_idx == c2_idx) {
+                tally.pairs_skipped += 1;
+                continue;
+            }
+            for (&binsize, values) in read.iter().zip(values.iter_mut()) {
+                let Some(mzd) = hic.get_matrix_zoom_data(c1_idx, c2_idx, &opts.unit, binsize)? else { continue };
+                let (key1, key2) = (chr_keys.of(mzd.c1), chr_keys.of(mzd.c2));
+                values.load_norms(&mut hic, &mzd, binsize, &opts.norm)?;
+                let mut sums: Vec<HashMap<(i32, i32), f64>> =
+                    if binsize == finest { summed.iter().map(|_| HashMap::new()).collect() } else { Vec::new() };
+                let out = &mut outputs.get_mut(&binsize).expect("output per binsize").1;
+                hic.for_each_block(&mzd, |records| {
+                    for rec in records {
+                        // Sums take raw counts below the minimum too; it applies to the summed value
+                        if rec.counts > 0.0 && rec.counts.is_finite() {
+                            for ((_, factor), sum) in summed.iter().zip(sums.iter_mut()) {
+                                *sum.entry((rec.bin_x / factor, rec.bin_y / factor)).or_default() += rec.counts as f64;
+                            }
+                        }
+                        let Some(counts) = values.value(&mzd, &rec) else {
+                            tally.skipped += 1;
+                            continue;
+                        };
+                        let rec = ContactRecord { counts, ..rec };
+                        let rec = if opts.canonicalize_intra() { rec.canonicalized(mzd.is_intra) } else { rec };
+                        out.push(SliceRecord { key1, bin_x: rec.bin_x, key2, bin_y: rec.bin_y, counts: rec.counts })?;
+                        tally.written += 1;
+                    }
+                    Ok(())
+                })?;
+                out.end_pair()?;
+                for ((coarse, _), sum) in summed.iter().zip(sums) {
+                    let mut records: Vec<ContactRecord> = sum
+                        .into_iter()
+                        .map(|((bin_x, bin_y), counts)| ContactRecord { bin_x, bin_y, counts: counts as f32 })
+                        .map(|rec| if opts.canonicalize_intra() { rec.canonicalized(mzd.is_intra) } else { rec })
+                        .collect();
+                    records.sort_unstable_by_key(|r| (r.bin_x, r.bin_y));
+                    let out = &mut outputs.get_mut(coarse).expect("output per binsize").1;
                     for rec in records {
-                        *counts.entry(rec.bin_x).or_insert(0.0) += rec.counts as f64;
-                        *counts.entry(rec.bin_y).or_insert(0.0) += rec.counts as f64;
+                        if opts.min_count.is_some_and(|m| rec.counts < m) {
+                            tally.skipped += 1;
+                            continue;
+                        }
+                        out.push(SliceRecord { key1, bin_x: rec.bin_x, key2, bin_y: rec.bin_y, counts: rec.counts })?;
+                        tally.written += 1;
                     }
+                    out.end_pair()?;
                 }
-                let mut cov = 0.0f64;
-                if !counts.is_empty() {
-                    let covered = counts.values().filter(|&&v| v >= thr as f64).count();
-                    cov = covered as f64 / counts.len() as f64;
-                }
-                println!("{}\t{:.3}", res, cov);
-                if eff_res.is_none() && cov >= pct {
-                    eff_res = Some(res);
+            }
+        }
+    }
+
+    let mut paths = Vec::with_capacity(outputs.len());
+    for (_, (path, out)) in outputs {
+        out.finish()?;
+        paths.push(path);
+    }
+    tally.report(opts);
+    Ok(paths)
+}
+
+/// Dump every resolution the file stores in `opts.unit` genome-wide, finest
+/// first, each to its [`multi_resolution_path`] as [`dump_hic_genome_wide`]
+/// writes it. The file is opened once and its header, matrix lookups and
+/// footer are shared by the dumps; each resolution gets its own progress bar,
+/// labelled with its place in the list. Returns the paths written.
+pub fn dump_hic_all_resolutions(input: &Path, prefix: &Path, opts: &DumpOptions) -> Result<Vec<PathBuf>> {
+    let mut hic = HicFile::open(input)?;
+    hic.set_io_retries(opts.io_retries);
+    hic.set_verbose(opts.verbose);
+    hic.require_unit(&opts.unit)?;
+    let mut resolutions = hic.unit_resolutions(&opts.unit).to_vec();
+    resolutions.sort_unstable();
+    let mut paths = Vec::with_capacity(resolutions.len());
+    for (i, &binsize) in resolutions.iter().enumerate() {
+        let path = multi_resolution_path(prefix, binsize, opts);
+        let label = format!("{} {} ({}/{}) ", binsize, opts.unit, i + 1, resolutions.len());
+        dump_hic_genome_wide_with_open(&mut hic, binsize, &path, opts, &label)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Dump the contacts between two genomic regions to a slice file. Regions
+/// are inclusive bp spans converted to bin ranges at `binsize`; chromosome
+/// names match like `effres`. Only the blocks of this chromosome pair that
+/// cover the ranges are decoded, and records are kept when their bins fall
+/// inside both ranges (either way round for an intra-chromosomal pair, whose
+/// matrix is symmetric). The key table lists every chromosome, as in the
+/// genome-wide dump. `opts.chroms` and the global sort do not apply; any sort
+/// orders records by bin. Regions are in bp, so only the BP unit is accepted.
+pub fn dump_hic_region(input: &Path, region1: &Region, region2: &Region, binsize: i32, output: &Path, opts: &DumpOptions) -> Result<()> {
+    if !opts.unit.eq_ignore_ascii_case("BP") {
+        return Err(anyhow!("Region dumps take bp coordinates and need the BP unit, not {}", opts.unit));
+    }
+    let mut hic = HicFile::open(input)?;
+    hic.set_io_retries(opts.io_retries);
+    hic.set_verbose(opts.verbose);
+    hic.require_resolution("BP", binsize)?;
+    let (c1, c2) = match hic.resolve_chromosomes(&[region1.chrom.to_string(), region2.chrom.to_string()])?[..] {
+        [a, b] => (a, b),
+        _ => unreachable!("two names resolve to two indices"),
+    };
+    let mut values = DumpValues::prepare(&mut hic, input, binsize, opts)?;
+    let bins = |r: &Region| (r.start as i64 / binsize as i64) as i32..=(r.end as i64 / binsize as i64) as i32;
+    // Bin ranges on the stored axes: the file keeps each pair once, lower index first
+    let (range_x, range_y) = if c1 <= c2 { (bins(region1), bins(region2)) } else { (bins(region2), bins(region1)) };
+
+    let chr_keys = slice_keys(&hic, None, opts.key_order);
+    let mut writer = RecordWriter::create(output, opts, binsize, &chr_keys.table, &hic)?;
+
+    if let Some(mzd) = hic.get_matrix_zoom_data(c1, c2, "BP", binsize)? {
+        let (key1, key2) = (chr_keys.of(mzd.c1), chr_keys.of(mzd.c2));
+        values.load_norms(&mut hic, &mzd, binsize, &opts.norm)?;
+        let inside = |x: i32, y: i32| range_x.contains(&x) && range_y.contains(&y);
+        // Only decode the blocks covering the query; they overlap its edges, so records are still filtered
+        let mut records: Vec<SliceRecord> = Vec::new();
+        for number in mzd.blocks_for_region(&range_x, &range_y) {
+            hic.with_block(&mzd, number, |block| {
+                for rec in block {
+                    if !(inside(rec.bin_x, rec.bin_y) || (mzd.is_intra && inside(rec.bin_y, rec.bin_x))) { continue; }
+                    let Some(counts) = values.value(&mzd, &rec) else { continue };
+                    let rec = ContactRecord { counts, ..rec };
+                    let rec = if opts.canonicalize_intra() { rec.canonicalized(mzd.is_intra) } else { rec };
+                    records.push(SliceRecord { key1, bin_x: rec.bin_x, key2, bin_y: rec.bin_y, counts: rec.counts });
                 }
+                Ok(())
+            })?;
+        }
+        if opts.sort != SortMode::None {
+            records.sort_unstable_by_key(|r| (r.bin_x, r.bin_y));
+        }
+        for r in &records { writer.write(r)?; }
+    }
+
+    writer.finish()
+}
+
+/// Straw's text dump of one chromosome pair: `pos_x\tpos_y\tcounts` lines
+/// with bin start positions, to `output` (`.gz` compressed) or stdout. Names
+/// match like `effres`; the pair is written in stored order, lower chromosome
+/// index on the x axis, whichever order the names were given in. Matrix type,
+/// normalization, unit, `opts.canonicalize` and any sort (by bin) apply as in
+/// [`dump_hic_genome_wide`]; `opts.format` and `opts.chroms` do not. For the
+/// FRAG unit, positions are fragment numbers (`bin * binsize`), as in straw.
+/// A pair without a matrix writes nothing.
+pub fn dump_hic_pair_text(input: &Path, chr1: &str, chr2: &str, binsize: i32, output: Option<&Path>, opts: &DumpOptions) -> Result<()> {
+    let mut hic = HicFile::open(input)?;
+    hic.set_io_retries(opts.io_retries);
+    hic.set_verbose(opts.verbose);
+    hic.require_resolution(&opts.unit, binsize)?;
+    let (c1, c2) = match hic.resolve_chromosomes(&[chr1.to_string(), chr2.to_string()])?[..] {
+        [a, b] => (a, b),
+        _ => unreachable!("two names resolve to two indices"),
+    };
+    let mut values = DumpValues::prepare(&mut hic, input, binsize, opts)?;
+    let mut w = match output {
+        Some(path) => DumpSink::create(path, path.extension().is_some_and(|e| e == "gz"))?,
+        None => DumpSink::Stdout(BufWriter::new(std::io::stdout().lock())),
+    };
+    if let Some(mzd) = hic.get_matrix_zoom_data(c1, c2, &opts.unit, binsize)? {
+        values.load_norms(&mut hic, &mzd, binsize, &opts.norm)?;
+        let mut records = Vec::new();
+        hic.for_each_block(&mzd, |block| {
+            for rec in block {
+                let Some(counts) = values.value(&mzd, &rec) else { continue };
+                let rec = ContactRecord { counts, ..rec };
+                records.push(if opts.canonicalize { rec.canonicalized(mzd.is_intra) } else { rec });
             }
+            Ok(())
+        })?;
+        if opts.sort != SortMode::None {
+            records.sort_unstable_by_key(|r| (r.bin_x, r.bin_y));
+        }
+        let res = binsize as i64;
+        for r in &records {
+            writeln!(w, "{}\t{}\t{}", r.bin_x as i64 * res, r.bin_y as i64 * res, r.counts)?;
         }
     }
+    w.finish()
+}
 
-    if let Some(r) = eff_res {
-        println!(
-            "\nEffective resolution on {}: {} bp (≥{:.0}% bins ≥ {} contacts)",
-            cname, r, pct * 100.0, thr
-        );
-    } else {
-        println!(
-            "\nNo resolution met the {:.0}% / {} contacts criterion.",
-            pct * 100.0, thr
-        );
+/// Dump the genome-wide ALL matrix (the `All` pseudo-chromosome, index 0)
+/// that Juicebox shows as the whole-genome view, as text. Its bins span the
+/// chromosomes laid end to end in header order, and its resolutions are
+/// coarser than the per-chromosome ones: `binsize` is matched in bp against
+/// the matrix's own zoom levels (see [`HicFile::all_scale`]), not the header
+/// list. Lines are `chrom1 pos1 chrom2 pos2 counts`, each bin placed on the
+/// chromosome holding its start; with `genome_bins`, the stored
+/// `bin_x bin_y counts` instead. Only observed, unnormalized BP counts exist
+/// for this matrix; `opts.canonicalize` and the sort apply as in
+/// [`dump_hic_pair_text`]. The output (`.gz` compressed) defaults to stdout.
+pub fn dump_hic_all_matrix(input: &Path, binsize: i32, output: Option<&Path>, genome_bins: bool, opts: &DumpOptions) -> Result<()> {
+    if opts.matrix != MatrixType::Observed || opts.norm != "NONE" || opts.unit != "BP" {
+        return Err(anyhow!("The ALL matrix only holds observed counts without normalization; use observed NONE ... BP"));
+    }
+    let mut hic = HicFile::open(input)?;
+    hic.set_io_retries(opts.io_retries);
+    hic.set_verbose(opts.verbose);
+    let scale = hic.all_scale()?;
+    let stored = hic.all_matrix_bin_sizes()?;
+    let available: Vec<i64> = stored.iter().map(|&b| b as i64 * scale).collect();
+    let Some(all_binsize) = stored.iter().copied().find(|&b| b as i64 * scale == binsize as i64) else {
+        return Err(match available.is_empty() {
+            true => anyhow!("{:?} has no ALL matrix", input),
+            false => anyhow!("The ALL matrix of {:?} is not stored at {} bp; available: {:?}", input, binsize, available),
+        });
+    };
+    let mzd = hic.get_matrix_zoom_data(0, 0, "BP", all_binsize)?.ok_or_else(|| anyhow!("{:?} has no ALL matrix", input))?;
+    let mut records = Vec::new();
+    hic.for_each_block(&mzd, |block| {
+        for rec in block.filter(|r| r.counts > 0.0 && r.counts.is_finite()) {
+            records.push(if opts.canonicalize { rec.canonicalized(true) } else { rec });
+        }
+        Ok(())
+    })?;
+    if opts.sort != SortMode::None {
+        records.sort_unstable_by_key(|r| (r.bin_x, r.bin_y));
+    }
+
+    // Start of each chromosome on the concatenated genome, in bp
+    let mut starts: Vec<(i64, &str)> = Vec::new();
+    let mut offset = 0i64;
+    for chr in hic.chromosomes.iter().filter(|c| c.index > 0) {
+        starts.push((offset, &chr.name));
+        offset += chr.length;
+    }
+    let locate = |bin: i32| -> (&str, i64) {
+        let genome_pos = bin as i64 * all_binsize as i64 * scale;
+        let (start, name) = starts[starts.partition_point(|&(s, _)| s <= genome_pos).saturating_sub(1)];
+        (name, genome_pos - start)
+    };
+    let mut w = match output {
+        Some(path) => DumpSink::create(path, path.extension().is_some_and(|e| e == "gz"))?,
+        None => DumpSink::Stdout(BufWriter::new(std::io::stdout().lock())),
+    };
+    for r in &records {
+        if genome_bins {
+            writeln!(w, "{}\t{}\t{}", r.bin_x, r.bin_y, r.counts)?;
+        } else {
+            let ((chr1, pos1), (chr2, pos2)) = (locate(r.bin_x), locate(r.bin_y));
+            writeln!(w, "{}\t{}\t{}\t{}\t{}", chr1, pos1, chr2, pos2, r.counts)?;
+        }
+    }
+    w.finish()
+}
+
+/// A `CHR:POS` viewpoint (commas allowed in the position).
+fn parse_viewpoint(spec: &str) -> Result<(&str, u32)> {
+    let (chrom, pos) = spec.rsplit_once(':').ok_or_else(|| anyhow!("Viewpoint must be CHR:POS, got '{}'", spec))?;
+    let pos = pos.replace(',', "").parse().map_err(|_| anyhow!("Invalid viewpoint position in '{}'", spec))?;
+    Ok((chrom, pos))
+}
+
+/// Virtual 4C: every contact of the bin holding the `CHR:POS` viewpoint at
+/// `binsize`, across the genome, as `partner_chrom\tpartner_start\tcount`
+/// lines ordered like the file's chromosomes, then by bin. Only the blocks
+/// of each matrix that cover the viewpoint's row and column are decoded.
+/// Matrix type and normalization apply as in [`dump_hic_genome_wide`], and
+/// `opts.chroms` limits the partner chromosomes; the output (`.gz`
+/// compressed) defaults to stdout. Positions are bp, so only the BP unit is
+/// accepted.
+pub fn dump_virtual_4c(input: &Path, viewpoint: &str, binsize: i32, output: Option<&Path>, opts: &DumpOptions) -> Result<()> {
+    if !opts.unit.eq_ignore_ascii_case("BP") {
+        return Err(anyhow!("Virtual 4C takes a bp viewpoint and needs the BP unit, not {}", opts.unit));
+    }
+    let (vp_name, vp_pos) = parse_viewpoint(viewpoint)?;
+    let mut hic = HicFile::open(input)?;
+    hic.set_io_retries(opts.io_retries);
+    hic.set_verbose(opts.verbose);
+    hic.require_resolution("BP", binsize)?;
+    let vp_chr = hic.resolve_chromosomes(&[vp_name.to_string()])?[0];
+    let vp_len = hic.chromosomes[vp_chr as usize].length;
+    if vp_pos as i64 > vp_len {
+        return Err(anyhow!("Viewpoint {} lies past the end of {} ({} bp)", viewpoint, hic.chromosomes[vp_chr as usize].name, vp_len));
+    }
+    let vp_bin = (vp_pos as i64 / binsize as i64) as i32;
+    let selected = dump_selection(&hic, opts)?;
+    let mut values = DumpValues::prepare(&mut hic, input, binsize, opts)?;
+    let mut w = match output {
+        Some(path) => DumpSink::create(path, path.extension().is_some_and(|e| e == "gz"))?,
+        None => DumpSink::Stdout(BufWriter::new(std::io::stdout().lock())),
+    };
+
+    let partners: Vec<(i32, String)> = hic
+        .chromosomes
+        .iter()
+        .filter(|c| c.index > 0 && selected.as_ref().is_none_or(|s| s.contains(&c.index)))
+        .map(|c| (c.index, c.name.clone()))
+        .collect();
+    for (partner, name) in partners {
+        let Some(mzd) = hic.get_matrix_zoom_data(vp_chr, partner, "BP", binsize)? else { continue };
+        values.load_norms(&mut hic, &mzd, binsize, &opts.norm)?;
+        // The viewpoint is on the x axis when its chromosome is stored first
+        let vp_on_x = mzd.c1 == vp_chr;
+        let all = 0..=(hic.chrom_extent(partner, "BP") / binsize as i64) as i32;
+        let (range_x, range_y) = if vp_on_x { (vp_bin..=vp_bin, all) } else { (all, vp_bin..=vp_bin) };
+        let mut profile: BTreeMap<i32, f32> = BTreeMap::new();
+        for number in mzd.blocks_for_region(&range_x, &range_y) {
+            hic.with_block(&mzd, number, |block| {
+                for rec in block {
+                    let other = if mzd.is_intra {
+                        if rec.bin_x == vp_bin { rec.bin_y } else if rec.bin_y == vp_bin { rec.bin_x } else { continue }
+                    } else if vp_on_x && rec.bin_x == vp_bin {
+                        rec.bin_y
+                    } else if !vp_on_x && rec.bin_y == vp_bin {
+                        rec.bin_x
+                    } else {
+                        continue;
+                    };
+                    let Some(counts) = values.value(&mzd, &rec) else { continue };
+                    *profile.entry(other).or_default() += counts;
+                }
+                Ok(())
+            })?;
+        }
+        for (bin, counts) in profile {
+            writeln!(w, "{}\t{}\t{}", name, bin as i64 * binsize as i64, counts)?;
+        }
+    }
+    w.finish()
+}
+
+/// Square contact matrix of one chromosome, row-major.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DenseMatrix {
+    /// Bins per side: `ceil(length / binsize)`
+    pub n: usize,
+    pub values: Vec<f32>,
+}
+
+impl DenseMatrix {
+    pub fn get(&self, row: usize, col: usize) -> f32 {
+        self.values[row * self.n + col]
+    }
+
+    /// Write as tab-separated rows, or as a NumPy `.npy` (`<f4`, C order)
+    /// when `path` ends in `.npy`; `.gz` text is compressed.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let mut w = DumpSink::create(path, ext == "gz")?;
+        if ext.eq_ignore_ascii_case("npy") {
+            self.write_npy(&mut w)?;
+        } else {
+            let mut line = String::new();
+            for row in self.values.chunks(self.n.max(1)) {
+                line.clear();
+                for (i, v) in row.iter().enumerate() {
+                    if i > 0 { line.push('\t'); }
+                    line.push_str(&v.to_string());
+                }
+                line.push('\n');
+                w.write_all(line.as_bytes())?;
+            }
+        }
+        w.finish()
+    }
+
+    /// NPY format 1.0: magic, version, header length, then a dict padded with
+    /// spaces so the data starts on a 64-byte boundary.
+    fn write_npy<W: Write>(&self, w: &mut W) -> Result<()> {
+        let mut header = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}", self.n, self.n);
+        let unpadded = 6 + 2 + 2 + header.len() + 1;
+        header.push_str(&" ".repeat(unpadded.next_multiple_of(64) - unpadded));
+        header.push('\n');
+        w.write_all(b"\x93NUMPY\x01\x00")?;
+        w.write_all(&(header.len() as u16).to_le_bytes())?;
+        w.write_all(header.as_bytes())?;
+        for v in &self.values {
+            w.write_all(&v.to_le_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// The intra-chromosomal matrix of `chrom` at `binsize` (BP) as a dense
+/// `n × n` matrix, mirrored across the diagonal; bins at or past
+/// `ceil(length / binsize)` are dropped. `opts.norm` applies as in
+/// [`dump_hic_genome_wide`]. Fails before allocating when the matrix needs
+/// more than `max_bytes`, and for an unknown chromosome or resolution.
+pub fn dense_matrix(input: &Path, chrom: &str, binsize: i32, max_bytes: Option<u64>, opts: &DumpOptions) -> Result<DenseMatrix> {
+    let mut hic = HicFile::open(input)?;
+    hic.set_io_retries(opts.io_retries);
+    hic.set_verbose(opts.verbose);
+    let chr = hic.resolve_chromosomes(&[chrom.to_string()])?[0];
+    hic.require_resolution("BP", binsize)?;
+    let c = &hic.chromosomes[chr as usize];
+    let n = num_bins_for(c.length, binsize);
+    let need = (n as u64) * (n as u64) * std::mem::size_of::<f32>() as u64;
+    if let Some(max) = max_bytes {
+        if need > max {
+            return Err(anyhow!(
+                "A dense {} matrix at {} bp has {} × {} bins and needs {}, above the {} limit; use a larger bin size or raise --max-memory",
+                c.name,
+                binsize,
+                n,
+                n,
+                crate::utils::format_bytes(need),
+                crate::utils::format_bytes(max)
+            ));
+        }
+    }
+    let opts = DumpOptions { unit: "BP".into(), ..opts.clone() };
+    let mut values = DumpValues::prepare(&mut hic, input, binsize, &opts)?;
+    let mut m = DenseMatrix { n, values: vec![0.0; n * n] };
+    if let Some(mzd) = hic.get_matrix_zoom_data(chr, chr, "BP", binsize)? {
+        values.load_norms(&mut hic, &mzd, binsize, &opts.norm)?;
+        hic.for_each_block(&mzd, |records| {
+            for rec in records {
+                let (x, y) = (rec.bin_x as usize, rec.bin_y as usize);
+                if rec.bin_x < 0 || rec.bin_y < 0 || x >= n || y >= n {
+                    continue;
+                }
+                let Some(v) = values.value(&mzd, &rec) else { continue };
+                m.values[x * n + y] = v;
+                m.values[y * n + x] = v;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(m)
+}
+
+/// Write the expected contact decay at `binsize` as a TSV of
+/// `chrom, distance_bins, expected` for each chromosome (scaled by its factor),
+/// up to the chromosome's bin count. Uses `opts.norm` (`NONE` for raw) and
+/// `opts.chroms`; `.gz` outputs are compressed.
+pub fn dump_expected(input: &Path, binsize: i32, output: &Path, opts: &DumpOptions) -> Result<()> {
+    let mut hic = HicFile::open(input)?;
+    hic.require_resolution(&opts.unit, binsize)?;
+    let selected = dump_selection(&hic, opts)?;
+    let norm = if opts.norm.eq_ignore_ascii_case("NONE") { "NONE" } else { opts.norm.as_str() };
+    let expected = hic.require_expected_values(norm, &opts.unit, binsize)?;
+
+    let mut w = DumpSink::create(output, output.extension().is_some_and(|e| e == "gz"))?;
+    writeln!(w, "chrom\tdistance_bins\texpected")?;
+    for chr in hic.chromosomes.iter().filter(|c| c.index > 0) {
+        if selected.as_ref().is_some_and(|s| !s.contains(&chr.index)) { continue; }
+        for d in 0..num_bins_for(hic.chrom_extent(chr.index, &opts.unit), binsize) {
+            match expected.at(chr.index, d) {
+                Some(v) => writeln!(w, "{}\t{}\t{}", chr.name, d, v)?,
+                None => break,
+            }
+        }
+    }
+    w.finish()
+}
+
+/// Write the expected contact decay stored for `norm` at `binsize` bp as
+/// `distance_bp expected` rows, to compare with a P(s) curve. Without
+/// `chrom` the shared vector is written as stored, over its whole length;
+/// with it, each value is divided by that chromosome's scale factor and the
+/// rows stop at its bin count. The vector comes from the same footer parsing
+/// as the O/E dumps. The output (`.gz` compressed) defaults to stdout.
+/// Returns the rows written.
+pub fn dump_expected_decay(input: &Path, norm: &str, binsize: i32, chrom: Option<&str>, output: Option<&Path>) -> Result<usize> {
+    let mut hic = HicFile::open(input)?;
+    hic.require_resolution("BP", binsize)?;
+    let expected = hic.require_expected_values(norm, "BP", binsize)?;
+    let values: Vec<f64> = match chrom {
+        Some(name) => {
+            let idx = hic.resolve_chromosomes(&[name.to_string()])?[0];
+            let bins = num_bins_for(hic.chrom_extent(idx, "BP"), binsize);
+            (0..bins).map_while(|d| expected.at(idx, d)).collect()
+        }
+        None => expected.values,
+    };
+
+    let mut w = match output {
+        Some(path) => DumpSink::create(path, path.extension().is_some_and(|e| e == "gz"))?,
+        None => DumpSink::Stdout(BufWriter::new(std::io::stdout().lock())),
+    };
+    writeln!(w, "distance_bp\texpected")?;
+    for (d, v) in values.iter().enumerate() {
+        writeln!(w, "{}\t{}", d as i64 * binsize as i64, v)?;
+    }
+    w.finish()?;
+    Ok(values.len())
+}
+
+/// Write the stored `norm` vectors at `binsize` bp as bedGraph, one
+/// `chrom start end value` line per bin (0-based, the last bin ending at the
+/// chromosome length), e.g. to see where KR balancing failed. NaN factors
+/// are written as `nan`, or left out with `skip_nan`. Named `chroms` must
+/// each have a vector; otherwise chromosomes without one are skipped, and
+/// a file with none at all fails listing the types stored at `binsize`.
+/// Bins past the chromosome end are not written. The output (`.gz`
+/// compressed) defaults to stdout. Returns the lines and chromosomes written.
+pub fn dump_norm_vectors(
+    input: &Path,
+    norm: &str,
+    binsize: i32,
+    chroms: Option<&[String]>,
+    output: Option<&Path>,
+    skip_nan: bool,
+) -> Result<(u64, usize)> {
+    let mut hic = HicFile::open(input)?;
+    hic.require_resolution("BP", binsize)?;
+    let targets: Vec<i32> = match chroms {
+        Some(names) => hic.resolve_chromosomes(names)?,
+        None => hic.chromosomes.iter().filter(|c| c.index > 0).map(|c| c.index).collect(),
+    };
+    let mut vectors = Vec::with_capacity(targets.len());
+    for &ci in &targets {
+        let vector = match chroms {
+            Some(_) => Some(hic.require_norm_vector(ci, "BP", binsize, norm)?),
+            None => hic.read_norm_vector(ci, "BP", binsize, norm)?,
+        };
+        vectors.extend(vector.map(|v| (ci, v)));
+    }
+    if vectors.is_empty() {
+        let here = hic
+            .normalizations_by_resolution()?
+            .into_iter()
+            .find(|(unit, res, _)| unit == "BP" && *res == binsize)
+            .map_or_else(|| "none".to_string(), |(_, _, norms)| norms.join(", "));
+        return Err(anyhow!("{:?} has no {} normalization vectors at {} BP; available there: {}", input, norm, binsize, here));
+    }
+
+    let mut w = match output {
+        Some(path) => DumpSink::create(path, path.extension().is_some_and(|e| e == "gz"))?,
+        None => DumpSink::Stdout(BufWriter::new(std::io::stdout().lock())),
+    };
+    let mut lines = 0u64;
+    for (ci, vector) in &vectors {
+        let chr = &hic.chromosomes[*ci as usize];
+        for (bin, &v) in vector.iter().take(num_bins_for(chr.length, binsize)).enumerate() {
+            if v.is_nan() && skip_nan { continue; }
+            let start = bin as i64 * binsize as i64;
+            let end = (start + binsize as i64).min(chr.length);
+            if v.is_nan() {
+                writeln!(w, "{}\t{}\t{}\tnan", chr.name, start, end)?;
+            } else {
+                writeln!(w, "{}\t{}\t{}\t{}", chr.name, start, end, v)?;
+            }
+            lines += 1;
+        }
+    }
+    w.finish()?;
+    Ok((lines, vectors.len()))
+}
+
+/// Observed contacts of one chromosome, as `straw totals` lists them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChromTotals {
+    pub name: String,
+    pub length: i64,
+    /// Its intra-chromosomal matrix, each contact once
+    pub intra: f64,
+    /// Its inter-chromosomal matrices; `None` unless they were summed
+    pub trans: Option<f64>,
+}
+
+impl ChromTotals {
+    /// Intra plus any trans contacts per Mb of chromosome.
+    pub fn per_mb(&self) -> f64 {
+        (self.intra + self.trans.unwrap_or(0.0)) / (self.length.max(1) as f64 / 1e6)
+    }
+}
+
+/// Observed contacts of every chromosome at `resolution` bp, or the coarsest
+/// stored one when `None`, in header order, with the resolution used. Each
+/// total is the count sum a matrix stores in its zoom header (the one the
+/// O/E dump takes inter-chromosomal means from), so no block is decoded.
+/// With `trans`, each inter-chromosomal matrix counts for both chromosomes.
+/// A chromosome without a matrix has no contacts.
+pub fn hic_chrom_totals(input: &Path, resolution: Option<i32>, trans: bool) -> Result<(i32, Vec<ChromTotals>)> {
+    let mut hic = HicFile::open(input)?;
+    hic.require_bp()?;
+    let resolution = match resolution {
+        Some(r) => r,
+        None => *hic.resolutions.iter().max().expect("require_bp checked for resolutions"),
+    };
+    hic.require_resolution("BP", resolution)?;
+    let chroms: Vec<i32> = hic.chromosomes.iter().filter(|c| c.index > 0).map(|c| c.index).collect();
+    let slot = |c: i32| chroms.iter().position(|&ci| ci == c).expect("a real chromosome");
+    let mut intra = vec![0.0; chroms.len()];
+    let mut inter = vec![0.0; chroms.len()];
+    for (i, &c1) in chroms.iter().enumerate() {
+        for &c2 in if trans { &chroms[i..] } else { &chroms[i..=i] } {
+            let Some(mzd) = hic.get_matrix_zoom_data(c1, c2, "BP", resolution)? else { continue };
+            let sum = mzd.sum_counts as f64;
+            if c1 == c2 {
+                intra[slot(c1)] += sum;
+            } else {
+                inter[slot(c1)] += sum;
+                inter[slot(c2)] += sum;
+            }
+        }
+    }
+    let totals = chroms
+        .iter()
+        .zip(intra.into_iter().zip(inter))
+        .map(|(&ci, (intra, inter))| {
+            let chr = &hic.chromosomes[ci as usize];
+            ChromTotals { name: chr.name.clone(), length: chr.length, intra, trans: trans.then_some(inter) }
+        })
+        .collect();
+    Ok((resolution, totals))
+}
+
+/// Write `chrom length intra [trans] per_mb` rows under a header, the trans
+/// column only when the totals have one. Output (`.gz` compressed) defaults
+/// to stdout.
+pub fn write_chrom_totals(totals: &[ChromTotals], output: Option<&Path>) -> Result<()> {
+    let mut w = match output {
+        Some(path) => DumpSink::create(path, path.extension().is_some_and(|e| e == "gz"))?,
+        None => DumpSink::Stdout(BufWriter::new(std::io::stdout().lock())),
+    };
+    let trans = totals.iter().any(|t| t.trans.is_some());
+    writeln!(w, "{}", if trans { "chrom\tlength\tintra\ttrans\tper_mb" } else { "chrom\tlength\tintra\tper_mb" })?;
+    for t in totals {
+        write!(w, "{}\t{}\t{:.0}", t.name, t.length, t.intra)?;
+        if trans {
+            write!(w, "\t{:.0}", t.trans.unwrap_or(0.0))?;
+        }
+        writeln!(w, "\t{:.1}", t.per_mb())?;
+    }
+    w.finish()
+}
+
+/// Open a slice file written by `dump`, gzip- or BGZF-compressed or plain
+/// (sniffed from the first bytes), and read its header.
+pub fn open_slice(input: &Path) -> Result<(slice::SliceHeader, Box<dyn Read>)> {
+    let file = File::open(input).with_context(|| format!("Open {:?}", input))?;
+    let mut reader = BufReader::new(file);
+    let gz = reader.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+    let mut r: Box<dyn Read> = if gz { Box::new(BufReader::new(MultiGzDecoder::new(reader))) } else { Box::new(reader) };
+    let header = slice::read_header(&mut r).with_context(|| format!("Read slice header of {:?}", input))?;
+    Ok((header, r))
+}
+
+/// A slice file opened by [`read_slice`]: its header, and its records as an
+/// iterator that can be consumed while the names are looked up.
+pub struct SliceFile {
+    pub binsize: i32,
+    /// (chromosome name, key) in header order
+    pub keys: Vec<(String, i16)>,
+    /// Chromosome name of each key
+    pub names: HashMap<i16, String>,
+    pub records: SliceRecords,
+}
+
+/// Records of a [`SliceFile`]. A record cut off at the end of the file, or
+/// using a key missing from the header table, is an error naming it.
+pub struct SliceRecords {
+    path: PathBuf,
+    reader: Box<dyn Read>,
+    known: HashSet<i16>,
+    read: u64,
+    failed: bool,
+}
+
+impl SliceRecords {
+    /// Number of records returned so far.
+    pub fn count_read(&self) -> u64 {
+        self.read
+    }
+}
+
+impl Iterator for SliceRecords {
+    type Item = Result<SliceRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let n = self.read + 1;
+        let rec = match SliceRecord::read_from(&mut self.reader).with_context(|| format!("Read record {} of {:?}", n, self.path)) {
+            Ok(Some(rec)) => rec,
+            Ok(None) => return None,
+            Err(e) => {
+                self.failed = true;
+                return Some(Err(e));
+            }
+        };
+        if let Some(key) = [rec.key1, rec.key2].into_iter().find(|k| !self.known.contains(k)) {
+            self.failed = true;
+            return Some(Err(anyhow!("Record {} uses key {} missing from the key table of {:?}", n, key, self.path)));
+        }
+        self.read = n;
+        Some(Ok(rec))
+    }
+}
+
+/// Open a slice file written by `dump` (any [`SliceCompression`]) and parse
+/// its header; the records are read as the iterator is advanced.
+pub fn read_slice(input: &Path) -> Result<SliceFile> {
+    let (header, reader) = open_slice(input)?;
+    let names: HashMap<i16, String> = header.keys.iter().map(|(n, k)| (*k, n.clone())).collect();
+    let known = names.keys().copied().collect();
+    Ok(SliceFile {
+        binsize: header.binsize,
+        keys: header.keys,
+        names,
+        records: SliceRecords { path: input.to_path_buf(), reader, known, read: 0, failed: false },
+    })
+}
+
+/// Write the records of a slice file as `chrom1 bin1 chrom2 bin2 counts`
+/// (tab-separated) to `output`, or stdout; `coords` writes bin starts in bp
+/// (`bin × binsize`) instead of bin numbers. A truncated file is an error
+/// naming the record where it ends.
+pub fn decode_slice(input: &Path, output: Option<&Path>, coords: bool) -> Result<()> {
+    let mut slice = read_slice(input)?;
+    let mut w = match output {
+        Some(path) => DumpSink::create(path, path.extension().is_some_and(|e| e == "gz"))?,
+        None => DumpSink::Stdout(BufWriter::new(std::io::stdout().lock())),
+    };
+    let scale = if coords { slice.binsize as i64 } else { 1 };
+    for rec in &mut slice.records {
+        let rec = rec?;
+        writeln!(
+            w,
+            "{}\t{}\t{}\t{}\t{}",
+            slice.names[&rec.key1],
+            rec.bin_x as i64 * scale,
+            slice.names[&rec.key2],
+            rec.bin_y as i64 * scale,
+            rec.counts
+        )?;
+    }
+    w.finish()
+}
+
+/// Write the records of a slice file as sparse BEDPE, `chrom1 start1 end1
+/// chrom2 start2 end2 count` (tab-separated, 0-based bin bounds in bp), to
+/// `output` or stdout; returns the number of records. A slice does not carry
+/// chromosome lengths, so the last bin of a chromosome ends at its length only
+/// when `chrom_sizes` (name, length) are given; every slice chromosome must be
+/// in them then.
+pub fn slice_to_bedpe(input: &Path, output: Option<&Path>, chrom_sizes: Option<&[(String, u32)]>) -> Result<u64> {
+    let mut slice = read_slice(input)?;
+    let binsize = slice.binsize as i64;
+    let mut lengths: HashMap<i16, i64> = HashMap::new();
+    if let Some(sizes) = chrom_sizes {
+        for (name, key) in &slice.keys {
+            let len = sizes
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|&(_, len)| len as i64)
+                .ok_or_else(|| anyhow!("Chromosome '{}' of {:?} is missing from the chromosome sizes", name, input))?;
+            lengths.insert(*key, len);
+        }
+    }
+    let mut w = match output {
+        Some(path) => DumpSink::create(path, path.extension().is_some_and(|e| e == "gz"))?,
+        None => DumpSink::Stdout(BufWriter::new(std::io::stdout().lock())),
+    };
+    let bounds = |key: i16, bin: i32| {
+        let start = bin as i64 * binsize;
+        (start, lengths.get(&key).map_or(start + binsize, |&len| (start + binsize).min(len)))
+    };
+    for rec in &mut slice.records {
+        let rec = rec?;
+        let ((s1, e1), (s2, e2)) = (bounds(rec.key1, rec.bin_x), bounds(rec.key2, rec.bin_y));
+        writeln!(
+            w,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            slice.names[&rec.key1], s1, e1, slice.names[&rec.key2], s2, e2, rec.counts
+        )?;
+    }
+    w.finish()?;
+    Ok(slice.records.count_read())
+}
+
+/// Summary of a slice file from [`slice_stats`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SliceStats {
+    pub binsize: i32,
+    pub records: u64,
+    pub total_counts: f64,
+    pub intra: u64,
+    pub inter: u64,
+    /// Records with an anchor on each chromosome of the key table, in table
+    /// order; an intra-chromosomal record counts once
+    pub per_chrom: Vec<(String, u64)>,
+}
+
+/// Stream every record of a slice file and tally it, checking the file on
+/// the way: a missing `HICSLICE` magic, a record whose chromosome key is not
+/// in the header table, or a trailing partial record is an error.
+pub fn slice_stats(input: &Path) -> Result<SliceStats> {
+    let slice = read_slice(input)?;
+    let slot: HashMap<i16, usize> = slice.keys.iter().enumerate().map(|(i, (_, k))| (*k, i)).collect();
+    let mut per_chrom = vec![0u64; slice.keys.len()];
+    let mut stats = SliceStats { binsize: slice.binsize, records: 0, total_counts: 0.0, intra: 0, inter: 0, per_chrom: Vec::new() };
+    for rec in slice.records {
+        let rec = rec?;
+        let (i1, i2) = (slot[&rec.key1], slot[&rec.key2]);
+        per_chrom[i1] += 1;
+        if i1 == i2 {
+            stats.intra += 1;
+        } else {
+            per_chrom[i2] += 1;
+            stats.inter += 1;
+        }
+        stats.total_counts += rec.counts as f64;
+        stats.records += 1;
+    }
+    stats.per_chrom = slice.keys.into_iter().map(|(name, _)| name).zip(per_chrom).collect();
+    Ok(stats)
+}
+
+// ----------------- low-level readers -----------------
+/// The reader of a [`HicFile`] borrowed mutably, so without locking. A lock
+/// poisoned by a panicking block read is still usable: every read seeks first.
+fn unpoisoned<R>(file: &mut Mutex<R>) -> &mut R { file.get_mut().unwrap_or_else(PoisonError::into_inner) }
+/// Read past `n` bytes without seeking, which would drop a `BufReader`'s buffer.
+fn skip_bytes<R: Read>(r: &mut R, n: u64) -> Result<()> {
+    let skipped = std::io::copy(&mut r.take(n), &mut std::io::sink())?;
+    if skipped < n { return Err(anyhow!("unexpected end of file skipping {} bytes", n)); }
+    Ok(())
+}
+fn read_magic<R: Read>(r: &mut R) -> Result<bool> { let s = read_cstring(r)?; Ok(s.starts_with("HIC")) }
+fn read_i32<R: Read>(r: &mut R) -> Result<i32> { let mut b=[0u8;4]; r.read_exact(&mut b)?; Ok(i32::from_le_bytes(b)) }
+fn read_i64<R: Read>(r: &mut R) -> Result<i64> { let mut b=[0u8;8]; r.read_exact(&mut b)?; Ok(i64::from_le_bytes(b)) }
+fn read_f32<R: Read>(r: &mut R) -> Result<f32> { let mut b=[0u8;4]; r.read_exact(&mut b)?; Ok(f32::from_le_bytes(b)) }
+fn read_f64<R: Read>(r: &mut R) -> Result<f64> { let mut b=[0u8;8]; r.read_exact(&mut b)?; Ok(f64::from_le_bytes(b)) }
+fn read_cstring<R: Read>(r: &mut R) -> Result<String> {
+    let mut buf = Vec::new();
+    let mut byte = [0u8;1];
+    loop {
+        r.read_exact(&mut byte)?;
+        if byte[0] == 0 { break; }
+        buf.push(byte[0]);
+    }
+    Ok(String::from_utf8(buf).unwrap_or_default())
+}
+
+/// Result of [`validate_index`].
+#[derive(Debug, Clone, Default)]
+pub struct IndexReport {
+    pub matrices: usize,
+    pub zoom_levels: usize,
+    pub blocks: usize,
+    /// One line per problem found, naming the matrix, zoom level and block
+    pub anomalies: Vec<String>,
+}
+
+/// Check the block index of every matrix without decoding any block: each
+/// master-index entry and each block must lie inside the file with a positive
+/// size, a matrix must name the chromosomes of its key, and block numbers must
+/// be unique within a zoom level.
+pub fn validate_index(input: &Path) -> Result<IndexReport> {
+    let mut hic = HicFile::open(input)?;
+    Ok(walk_index(&mut hic, input)?.report)
+}
+
+/// A block listed inside the file by the index, for [`verify_hic`].
+struct IndexedBlock {
+    /// Index into [`IndexWalk::zooms`]
+    zoom: usize,
+    number: i32,
+    entry: IndexEntry,
+}
+
+/// What [`walk_index`] found: the report, then every block that lies inside
+/// the file, labelled by its zoom level (`chr1-chr2 BP 5000`).
+struct IndexWalk {
+    report: IndexReport,
+    zooms: Vec<String>,
+    blocks: Vec<IndexedBlock>,
+}
+
+fn walk_index(hic: &mut HicFile, input: &Path) -> Result<IndexWalk> {
+    let file_len = std::fs::metadata(input).with_context(|| format!("Stat {:?}", input))?.len() as i64;
+    let mut walk = IndexWalk { report: IndexReport::default(), zooms: Vec::new(), blocks: Vec::new() };
+    let report = &mut walk.report;
+    let out_of_file = |position: i64, size: i64| position < 0 || size <= 0 || position.saturating_add(size) > file_len;
+    if hic.master < 0 || hic.master >= file_len {
+        // Typical of a truncated transfer: the footer was never written
+        report.anomalies.push(format!("master index at {} is outside the file ({} bytes)", hic.master, file_len));
+        return Ok(walk);
+    }
+
+    for (key, position, size) in hic.master_entries().with_context(|| format!("Read the master index of {:?}", input))? {
+        report.matrices += 1;
+        let label = match key.split_once('_').map(|(a, b)| (a.parse::<usize>(), b.parse::<usize>())) {
+            Some((Ok(a), Ok(b))) if a < hic.chromosomes.len() && b < hic.chromosomes.len() => {
+                format!("{}-{}", hic.chromosomes[a].name, hic.chromosomes[b].name)
+            }
+            _ => {
+                report.anomalies.push(format!("matrix '{}': key does not name two chromosomes of the header", key));
+                key.clone()
+            }
+        };
+        if out_of_file(position, size as i64) {
+            report.anomalies.push(format!(
+                "{}: matrix record at {} ({} bytes) is outside the file ({} bytes)",
+                label, position, size, file_len
+            ));
+            continue;
+        }
+        let r = unpoisoned(&mut hic.file);
+        r.seek(SeekFrom::Start(position as u64))?;
+        let (c1, c2) = (read_i32(r)?, read_i32(r)?);
+        if key != format!("{}_{}", c1, c2) {
+            report.anomalies.push(format!("{}: matrix record is for {}_{}", label, c1, c2));
+        }
+        let nres = read_i32(r)?;
+        for _ in 0..nres {
+            let z = read_zoom_header(r).with_context(|| format!("Read zoom level of {} in {:?}", label, input))?;
+            report.zoom_levels += 1;
+            let zoom = walk.zooms.len();
+            walk.zooms.push(format!("{} {} {}", label, z.unit, z.bin_size));
+            let mut seen = HashSet::new();
+            for (number, idx) in read_block_index(r, z.nblocks)? {
+                report.blocks += 1;
+                if !seen.insert(number) {
+                    report.anomalies.push(format!("{}: block {} is listed twice", walk.zooms[zoom], number));
+                }
+                if out_of_file(idx.position, idx.size) {
+                    report.anomalies.push(format!(
+                        "{}: block {} at {} ({} bytes) is outside the file ({} bytes)",
+                        walk.zooms[zoom], number, idx.position, idx.size, file_len
+                    ));
+                } else {
+                    walk.blocks.push(IndexedBlock { zoom, number, entry: idx });
+                }
+            }
+        }
+    }
+    Ok(walk)
+}
+
+/// Result of [`verify_hic`].
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    /// Matrices, zoom levels and blocks walked, with the index anomalies
+    pub index: IndexReport,
+    /// Compressed bytes of the blocks inside the file (decoded unless fast)
+    pub bytes: u64,
+    /// Blocks inflated and parsed; 0 in fast mode
+    pub decoded: usize,
+    /// Records in the decoded blocks
+    pub records: u64,
+    /// Index anomalies, then block problems in index order
+    pub problems: Vec<String>,
+}
+
+/// Check a .hic file end to end: the index as [`validate_index`] does, then
+/// (unless `fast`) every block inside the file is read, inflated and parsed,
+/// and must hold exactly the records it declares with no bytes left over.
+/// Blocks are decoded in parallel batches, like a dump.
+pub fn verify_hic(input: &Path, fast: bool) -> Result<VerifyReport> {
+    let mut hic = HicFile::open(input)?;
+    let walk = walk_index(&mut hic, input)?;
+    let mut report = VerifyReport {
+        bytes: walk.blocks.iter().map(|b| b.entry.size as u64).sum(),
+        problems: walk.report.anomalies.clone(),
+        index: walk.report,
+        ..Default::default()
+    };
+    if fast {
+        return Ok(report);
+    }
+    let version = hic.version;
+    for batch in walk.blocks.chunks(rayon::current_num_threads() * BLOCKS_PER_THREAD) {
+        let compressed: Vec<Result<Vec<u8>>> =
+            batch.iter().map(|b| hic.read_block_bytes(&b.entry)).collect();
+        let checked: Vec<Result<usize>> = compressed.into_par_iter().map(|comp| check_block(&comp?, version)).collect();
+        for (block, result) in batch.iter().zip(checked) {
+            match result {
+                Ok(records) => {
+                    report.decoded += 1;
+                    report.records += records as u64;
+                }
+                Err(e) => report.problems.push(format!(
+                    "{}: block {} at {} ({} bytes): {:#}",
+                    walk.zooms[block.zoom], block.number, block.entry.position, block.entry.size, e
+                )),
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Decode one block strictly for [`verify_hic`]: the record count it
+/// declares must match the records parsed, and they must use up every
+/// inflated byte. Returns the number of records.
+fn check_block(comp: &[u8], version: i32) -> Result<usize> {
+    let buf = inflate_block(comp)?;
+    let mut records = BlockRecords::new(&buf, version)?;
+    let held = records.by_ref().count();
+    records.finish()?;
+    if held != records.declared {
+        return Err(anyhow!("declares {} records but holds {}", records.declared, held));
+    }
+    if records.cur.remaining() > 0 {
+        return Err(anyhow!("{} bytes left after the records", records.cur.remaining()));
+    }
+    Ok(held)
+}
+
+/// Output layout of `straw list`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ListFormat {
+    /// Commented header block, then `name\tlength` rows
+    #[default]
+    Text,
+    /// A `#resolutions` line, then `name\tindex\tlength` rows under a header
+    Tsv,
+    /// One JSON object (see [`HicListing`])
+    Json,
+}
+
+/// A chromosome as listed by `straw list --format json`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ListedChromosome {
+    pub name: String,
+    pub index: i32,
+    pub length: i64,
+}
+
+/// `straw list --format json` document.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct HicListing {
+    pub genome_id: String,
+    pub version: i32,
+    /// Real chromosomes, without the "All" pseudo-chromosome
+    pub chromosomes: Vec<ListedChromosome>,
+    /// BP resolutions, ascending
+    pub resolutions: Vec<i32>,
+    /// FRAG resolutions, ascending (empty for most files)
+    pub frag_resolutions: Vec<i32>,
+    /// Stored normalization types (`NONE` is always available)
+    pub normalizations: Vec<String>,
+}
+
+/// `straw metadata` document: the parsed .hic header. Field names are
+/// camelCase and only ever added to, so consumers can rely on them.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HicMetadata {
+    pub version: i32,
+    pub genome_id: String,
+    /// Header attributes, sorted by key
+    pub attributes: BTreeMap<String, String>,
+    /// Every header chromosome, including the "All" pseudo-chromosome at index 0
+    pub chromosomes: Vec<ListedChromosome>,
+    /// BP resolutions in header order
+    pub bp_resolutions: Vec<i32>,
+    /// FRAG resolutions in header order
+    pub frag_resolutions: Vec<i32>,
+    /// Normalized-vector index position and length; `null` before version 9
+    pub nvi_position: Option<i64>,
+    pub nvi_length: Option<i64>,
+}
+
+impl From<&HicFile> for HicMetadata {
+    fn from(hic: &HicFile) -> Self {
+        let v9 = hic.version > 8;
+        HicMetadata {
+            version: hic.version,
+            genome_id: hic.genome_id.clone(),
+            attributes: hic.attributes.iter().cloned().collect(),
+            chromosomes: hic
+                .chromosomes
+                .iter()
+                .map(|c| ListedChromosome { name: c.name.clone(), index: c.index, length: c.length })
+                .collect(),
+            bp_resolutions: hic.resolutions.clone(),
+            frag_resolutions: hic.frag_resolutions.clone(),
+            nvi_position: v9.then_some(hic.nvi_pos),
+            nvi_length: v9.then_some(hic.nvi_len),
+        }
+    }
+}
+
+/// Write the header of `input` as pretty-printed [`HicMetadata`] JSON to
+/// `output`, or stdout.
+pub fn write_metadata(input: &Path, output: Option<&Path>) -> Result<()> {
+    let hic = HicFile::open(input)?;
+    let meta = HicMetadata::from(&hic);
+    let mut w = match output {
+        Some(path) => DumpSink::create(path, false)?,
+        None => DumpSink::Stdout(BufWriter::new(std::io::stdout().lock())),
+    };
+    serde_json::to_writer_pretty(&mut w, &meta)?;
+    writeln!(w)?;
+    w.finish()
+}
+
+pub fn list_hic_chromosomes(input: &Path, format: ListFormat) -> Result<()> {
+    let mut hic = HicFile::open(input)?;
+    let mut w = BufWriter::new(std::io::stdout().lock());
+    write_listing(&mut hic, format, &mut w)?;
+    w.flush()?;
+    Ok(())
+}
+
+fn write_listing<W: Write>(hic: &mut HicFile, format: ListFormat, w: &mut W) -> Result<()> {
+    let listing = hic.listing()?;
+    let join = |v: &[i32], sep: &str| v.iter().map(|r| r.to_string()).collect::<Vec<_>>().join(sep);
+    match format {
+        ListFormat::Json => {
+            serde_json::to_writer_pretty(&mut *w, &listing)?;
+            writeln!(w)?;
+            return Ok(());
+        }
+        ListFormat::Tsv => {
+            writeln!(w, "#resolutions\t{}", join(&listing.resolutions, ","))?;
+            writeln!(w, "name\tindex\tlength")?;
+            for c in &listing.chromosomes {
+                writeln!(w, "{}\t{}\t{}", c.name, c.index, c.length)?;
+            }
+            return Ok(());
+        }
+        ListFormat::Text => {}
+    }
+    writeln!(w, "# Genome: {}", listing.genome_id)?;
+    writeln!(w, "# Version: {}", listing.version)?;
+    if !hic.attributes.is_empty() {
+        writeln!(w, "# Attributes:")?;
+        for (key, value) in &hic.attributes {
+            // Values such as `statistics` span several lines; keep them commented
+            let mut lines = value.trim_end().lines();
+            writeln!(w, "#   {}: {}", key, lines.next().unwrap_or(""))?;
+            for line in lines {
+                writeln!(w, "#     {}", line)?;
+            }
+        }
+    }
+    // Print available BP (and FRAG, if any) resolutions
+    if listing.resolutions.is_empty() {
+        writeln!(w, "# Resolutions (BP): none (FRAG-only file)")?;
+    } else {
+        writeln!(w, "# Resolutions (BP): {}", join(&listing.resolutions, ", "))?;
+    }
+    if !listing.frag_resolutions.is_empty() {
+        writeln!(w, "# Resolutions (FRAG): {}", join(&listing.frag_resolutions, ", "))?;
+    }
+    writeln!(w, "# Normalizations: NONE{}", listing.normalizations.iter().map(|n| format!(", {}", n)).collect::<String>())?;
+    for (unit, res, types) in hic.normalizations_by_resolution()? {
+        writeln!(w, "#   {} {}: {}", unit, res, types.join(", "))?;
+    }
+    // Print chromosomes table
+    writeln!(w, "# Chromosomes (name\tlength)")?;
+    for c in &listing.chromosomes {
+        writeln!(w, "{}\t{}", c.name, c.length)?;
+    }
+    Ok(())
+}
+
+/// Credit both anchors of each record to the dense per-bin totals `counts`.
+/// It is sized to `nbins` at the first record and grows for bins past that, so
+/// it stays empty for a matrix without records; bins never touched hold 0.
+pub fn add_bin_counts(counts: &mut Vec<f64>, records: &[ContactRecord], nbins: usize) {
+    for rec in records {
+        add_anchor_count(counts, rec.bin_x, rec.counts, nbins);
+        add_anchor_count(counts, rec.bin_y, rec.counts, nbins);
+    }
+}
+
+/// Credit one anchor to `counts`, sized as in [`add_bin_counts`].
+#[inline]
+fn add_anchor_count(counts: &mut Vec<f64>, bin: i32, value: f32, nbins: usize) {
+    let Ok(b) = usize::try_from(bin) else { return };
+    if b >= counts.len() {
+        counts.resize(nbins.max(b + 1), 0.0);
+    }
+    counts[b] += value as f64;
+}
+
+fn num_bins_for(length: i64, resolution: i32) -> usize {
+    if resolution <= 0 || length <= 0 { return 0; }
+    ((length + resolution as i64 - 1) / resolution as i64) as usize
+}
+
+/// Output layout of `straw effres`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum EffresFormat {
+    /// `#` comment lines with the parameters, then the table
+    #[default]
+    Text,
+    /// Only the table header and rows
+    Tsv,
+    /// One JSON object (see [`EffresReport`])
+    Json,
+}
+
+/// A scanned resolution the file does not store, with the stored one its
+/// bins were summed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct AggregatedResolution {
+    pub resolution: i32,
+    pub from: i32,
+}
+
+/// How much `straw effres` reports per resolution.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum EffresStats {
+    /// Coverage only (min/mean/max across chromosomes)
+    #[default]
+    Basic,
+    /// Also contacts, bin tallies and, across chromosomes, the median
+    /// coverage, passing chromosomes and per-bin count quartiles
+    Full,
+}
+
+/// Contacts and bin tallies behind a coverage value. Every contact credits
+/// the bins of both its anchors, so `contacts` is half the per-bin sum.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct BinTally {
+    pub contacts: f64,
+    pub covered_bins: u64,
+    pub total_bins: u64,
+}
+
+/// Coverage at one resolution.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct EffresCoverage {
+    pub resolution: i32,
+    pub coverage: f64,
+    /// With `--stats full`
+    #[serde(flatten)]
+    pub tally: Option<BinTally>,
+}
+
+/// Coverage spread across chromosomes at one resolution.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct EffresSummaryRow {
+    pub resolution: i32,
+    pub min: f64,
+    pub mean: f64,
+    pub max: f64,
+    /// With `--stats full`
+    #[serde(flatten)]
+    pub full: Option<EffresSummaryStats>,
+}
+
+/// The `--stats full` columns of an [`EffresSummaryRow`], over the
+/// chromosomes with signal at that resolution.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct EffresSummaryStats {
+    pub median: f64,
+    pub chromosomes: usize,
+    /// Chromosomes whose coverage reaches `pct`
+    pub passing: usize,
+    #[serde(flatten)]
+    pub tally: BinTally,
+    /// Quartiles of the per-bin counts over the bins in the denominator
+    /// (zero bins included when they count as uncovered)
+    pub bin_count_quartiles: [f64; 3],
+}
+
+/// Running totals of the chromosomes of one summary row, for `--stats full`.
+#[derive(Default)]
+struct SummaryTally {
+    contacts: f64,
+    bins: CoverageFraction,
+    /// Per-bin counts of the bins in the denominator
+    values: Vec<f64>,
+}
+
+impl SummaryTally {
+    fn add(&mut self, counts: &[f64], fraction: CoverageFraction) {
+        self.contacts += counts.iter().sum::<f64>() / 2.0;
+        self.bins = self.bins + fraction;
+        let before = self.values.len();
+        self.values.extend(counts.iter().copied().filter(|&c| c > 0.0));
+        let zeros = fraction.total.saturating_sub((self.values.len() - before) as u64);
+        self.values.extend(std::iter::repeat_n(0.0, zeros as usize));
+    }
+
+    fn finish(mut self, covs: &[f64], pct: f64) -> EffresSummaryStats {
+        let mut sorted = covs.to_vec();
+        sorted.sort_unstable_by(f64::total_cmp);
+        self.values.sort_unstable_by(f64::total_cmp);
+        EffresSummaryStats {
+            median: quantile(&sorted, 0.5),
+            chromosomes: covs.len(),
+            passing: covs.iter().filter(|&&c| c >= pct).count(),
+            tally: BinTally { contacts: self.contacts, covered_bins: self.bins.covered, total_bins: self.bins.total },
+            bin_count_quartiles: [0.25, 0.5, 0.75].map(|p| quantile(&self.values, p)),
+        }
+    }
+}
+
+/// Quantile `p` of ascending `sorted`, interpolating between ranks; 0 when empty.
+fn quantile(sorted: &[f64], p: f64) -> f64 {
+    let Some(last) = sorted.len().checked_sub(1) else { return 0.0 };
+    let rank = p * last as f64;
+    let (lo, hi) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (rank - lo as f64)
+}
+
+/// One chromosome of the `--matrix` grid: coverage per resolution, `None`
+/// where the chromosome has no signal.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct EffresGridRow {
+    pub chromosome: String,
+    pub coverage: Vec<Option<f64>>,
+}
+
+/// What an `effres` run computed, by mode.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum EffresResult {
+    /// Min/mean/max coverage over the chromosomes of at least 2.5 Mb
+    Summary { rows: Vec<EffresSummaryRow> },
+    /// Coverage of every chromosome of at least 2.5 Mb at every resolution
+    Matrix { resolutions: Vec<i32>, rows: Vec<EffresGridRow> },
+    /// Effective resolution of every chromosome of at least 2.5 Mb
+    PerChrom { rows: Vec<ChromEffres> },
+    /// Coverage of one chromosome per resolution, and the finest reaching `pct`
+    Chromosome { chromosome: String, rows: Vec<EffresCoverage>, effective_resolution: Option<i32> },
+    /// Coverage of several chromosomes, and their min/mean/max per resolution
+    Chromosomes { chromosomes: Vec<ChromosomeCoverage>, summary: Vec<EffresSummaryRow> },
+}
+
+/// One chromosome of [`EffresResult::Chromosomes`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ChromosomeCoverage {
+    pub chromosome: String,
+    pub rows: Vec<EffresCoverage>,
+    pub effective_resolution: Option<i32>,
+}
+
+/// Result of `straw effres` with the parameters it was computed with.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct EffresReport {
+    pub file: PathBuf,
+    pub unit: String,
+    pub thr: i32,
+    pub pct: f64,
+    pub zero_bins: ZeroBins,
+    /// Whether bins also counted inter-chromosomal contacts
+    pub include_trans: bool,
+    /// Resolutions not stored in the file, summed from a stored one
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aggregated: Vec<AggregatedResolution>,
+    /// Which chromosomes the all-chromosome modes use; `None` for one chromosome
+    pub chromosome_filter: Option<String>,
+    /// Chromosomes the filter left out, with the reason
+    pub skipped: Vec<SkippedChromosome>,
+    #[serde(flatten)]
+    pub result: EffresResult,
+}
+
+/// Settings of `straw effres`, with the CLI's defaults.
+#[derive(Clone, Debug)]
+pub struct EffresOptions {
+    /// `BP`, or `FRAG` to scan the fragment resolutions
+    pub unit: String,
+    /// Contacts a bin needs to count as covered
+    pub thr: i32,
+    /// Coverage fraction a resolution must reach
+    pub pct: f64,
+    pub zero_bins: ZeroBins,
+    /// All-chromosome modes leave out chromosomes shorter than this (bp)
+    pub min_length: i64,
+    /// When not empty, the all-chromosome modes use only these, whatever their length
+    pub include: Vec<String>,
+    /// Always left out of the all-chromosome modes
+    pub exclude: Vec<String>,
+    /// Add each chromosome's inter-chromosomal contacts to its bins
+    pub include_trans: bool,
+    /// Resolutions to scan instead of the stored ones; those not stored are
+    /// summed from a finer stored one
+    pub resolutions: Vec<i32>,
+    pub stats: EffresStats,
+}
+
+impl Default for EffresOptions {
+    fn default() -> Self {
+        EffresOptions {
+            unit: "BP".to_string(),
+            thr: 1000,
+            pct: 0.8,
+            zero_bins: ZeroBins::Uncovered,
+            min_length: EFFRES_MIN_CHROM_LEN,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            include_trans: false,
+            resolutions: Vec::new(),
+            stats: EffresStats::Basic,
+        }
+    }
+}
+
+impl EffresOptions {
+    fn report(
+        &self,
+        input: &Path,
+        plan: &[(i32, i32)],
+        chromosome_filter: Option<String>,
+        skipped: Vec<SkippedChromosome>,
+        result: EffresResult,
+    ) -> EffresReport {
+        let aggregated = plan
+            .iter()
+            .filter(|(res, from)| res != from)
+            .map(|&(resolution, from)| AggregatedResolution { resolution, from })
+            .collect();
+        EffresReport {
+            file: input.to_path_buf(),
+            unit: self.unit.clone(),
+            thr: self.thr,
+            pct: self.pct,
+            zero_bins: self.zero_bins,
+            include_trans: self.include_trans,
+            aggregated,
+            chromosome_filter,
+            skipped,
+            result,
+        }
+    }
+
+    /// The chromosome filter as printed in the `# Filters:` line.
+    fn describe_filter(&self) -> String {
+        let mut parts = Vec::new();
+        if self.include.is_empty() {
+            parts.push(format!("length >= {} bp", self.min_length));
+        } else {
+            parts.push(format!("only {}", self.include.join(", ")));
+        }
+        if !self.exclude.is_empty() {
+            parts.push(format!("excluding {}", self.exclude.join(", ")));
+        }
+        parts.join("; ")
+    }
+}
+
+/// A chromosome left out of the all-chromosome effres modes.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct SkippedChromosome {
+    pub name: String,
+    pub reason: String,
+}
+
+// (chromosome index, extent in the effres unit, name)
+type EffresChrom = (i32, i64, String);
+
+/// Chromosomes (index, extent in `unit`, name) for the all-chromosome effres
+/// modes, and those skipped. `exclude` always wins; a non-empty `include`
+/// selects chromosomes regardless of `min_length`. Unknown names are an error.
+fn effres_chromosomes(hic: &HicFile, opts: &EffresOptions) -> Result<(Vec<EffresChrom>, Vec<SkippedChromosome>)> {
+    let include: HashSet<i32> = hic.resolve_chromosomes(&opts.include)?.into_iter().collect();
+    let exclude: HashSet<i32> = hic.resolve_chromosomes(&opts.exclude)?.into_iter().collect();
+    let mut chroms = Vec::new();
+    let mut skipped = Vec::new();
+    for c in hic.chromosomes.iter().filter(|c| c.index > 0) {
+        let reason = if exclude.contains(&c.index) {
+            Some("excluded".to_string())
+        } else if !include.is_empty() {
+            (!include.contains(&c.index)).then(|| "not included".to_string())
+        } else {
+            (c.length < opts.min_length).then(|| format!("shorter than {} bp", opts.min_length))
+        };
+        match reason {
+            Some(reason) => skipped.push(SkippedChromosome { name: c.name.clone(), reason }),
+            None => chroms.push((c.index, hic.chrom_extent(c.index, &opts.unit), c.name.clone())),
+        }
+    }
+    Ok((chroms, skipped))
+}
+
+/// Effective resolution of each chromosome of `chroms` (with a combined
+/// summary when there are several), or with none the coverage of the
+/// chromosomes selected by the filters of `opts` per resolution (as
+/// min/mean/max, or the full grid with `matrix`).
+pub fn effres_hic(input: &Path, chroms: &[String], opts: &EffresOptions, matrix: bool) -> Result<EffresReport> {
+    let mut hic = HicFile::open(input)?;
+    let (unit, thr, pct, zero_bins) = (opts.unit.as_str(), opts.thr, opts.pct, opts.zero_bins);
+    hic.require_unit(unit)?;
+    let plan = effres_resolutions(&hic, opts)?;
+    let resolutions: Vec<i32> = plan.iter().map(|&(res, _)| res).collect();
+
+    // If no chromosome provided, compute min/mean/max coverage across chromosomes per resolution
+    if chroms.is_empty() {
+        let (chroms, skipped) = effres_chromosomes(&hic, opts)?;
+        let report = |result| opts.report(input, &plan, Some(opts.describe_filter()), skipped, result);
+
+        // grid[r][c]: coverage of chromosome c at resolution r; None for a no-signal contig
+        let ids: Vec<i32> = chroms.iter().map(|&(ci, _, _)| ci).collect();
+        let mut planned = PlannedCounts::new(&ids, opts);
+        let full = opts.stats == EffresStats::Full;
+        let mut grid: Vec<Vec<Option<f64>>> = Vec::with_capacity(resolutions.len());
+        let mut tallies: Vec<SummaryTally> = Vec::new();
+        for &(res, from) in &plan {
+            let counts = planned.at(&mut hic, res, from)?;
+            let mut tally = SummaryTally::default();
+            let row = chroms
+                .iter()
+                .zip(counts)
+                .map(|(&(_, clen, _), counts)| match counts {
+                    Some(counts) if !counts.is_empty() => {
+                        let total = num_bins_for(clen, res) as u64;
+                        let fraction = coverage_fraction(counts.iter().copied(), thr as f64, total, zero_bins);
+                        if full {
+                            tally.add(&counts, fraction);
+                        }
+                        Some(fraction.fraction())
+                    }
+                    _ => None,
+                })
+                .collect();
+            grid.push(row);
+            if full {
+                tallies.push(tally);
+            }
+        }
+
+        if matrix {
+            let rows = chroms
+                .into_iter()
+                .enumerate()
+                .map(|(c, (_, _, chromosome))| EffresGridRow { chromosome, coverage: grid.iter().map(|row| row[c]).collect() })
+                .collect();
+            return Ok(report(EffresResult::Matrix { resolutions, rows }));
+        }
+
+        let mut tallies = tallies.into_iter();
+        let rows = resolutions
+            .iter()
+            .zip(&grid)
+            .map(|(&resolution, row)| {
+                // exclude no-signal contigs for this resolution
+                let covs: Vec<f64> = row.iter().flatten().copied().collect();
+                summary_row(resolution, &covs, tallies.next(), pct)
+            })
+            .collect();
+        return Ok(report(EffresResult::Summary { rows }));
+    }
+
+    // Requested chromosomes: resolution vs coverage and the effective resolution of each
+    let ids = hic.resolve_chromosomes(chroms)?;
+    let extents: Vec<i64> = ids.iter().map(|&ci| hic.chrom_extent(ci, unit)).collect();
+    let mut tables: Vec<ChromosomeCoverage> = ids
+        .iter()
+        .map(|&ci| ChromosomeCoverage {
+            chromosome: hic.chromosomes[ci as usize].name.clone(),
+            rows: Vec::with_capacity(resolutions.len()),
+            effective_resolution: None,
+        })
+        .collect();
+    let full = opts.stats == EffresStats::Full;
+    let mut summary = Vec::with_capacity(resolutions.len());
+    let mut planned = PlannedCounts::new(&ids, opts);
+    for &(res, from) in &plan {
+        let counts = planned.at(&mut hic, res, from)?;
+        let mut covs = Vec::new();
+        let mut summary_tally = SummaryTally::default();
+        for ((table, &clen), counts) in tables.iter_mut().zip(&extents).zip(counts) {
+            let total = num_bins_for(clen, res) as u64;
+            let mut coverage = 0.0f64;
+            let mut tally = None;
+            if let Some(counts) = counts {
+                if !counts.is_empty() {
+                    let fraction = coverage_fraction(counts.iter().copied(), thr as f64, total, zero_bins);
+                    coverage = fraction.fraction();
+                    covs.push(coverage);
+                    if full {
+                        summary_tally.add(&counts, fraction);
+                    }
+                    tally = Some(BinTally { contacts: counts.iter().sum::<f64>() / 2.0, covered_bins: fraction.covered, total_bins: fraction.total });
+                }
+                if table.effective_resolution.is_none() && coverage >= pct {
+                    table.effective_resolution = Some(res);
+                }
+            }
+            let tally = full.then(|| {
+                tally.unwrap_or_else(|| {
+                    let empty = coverage_fraction(std::iter::empty(), thr as f64, total, zero_bins);
+                    BinTally { contacts: 0.0, covered_bins: empty.covered, total_bins: empty.total }
+                })
+            });
+            table.rows.push(EffresCoverage { resolution: res, coverage, tally });
+        }
+        // like the all-chromosome summary, without the no-signal chromosomes
+        summary.push(summary_row(res, &covs, full.then_some(summary_tally), pct));
+    }
+    let result = match <[_; 1]>::try_from(tables) {
+        Ok([ChromosomeCoverage { chromosome, rows, effective_resolution }]) => {
+            EffresResult::Chromosome { chromosome, rows, effective_resolution }
+        }
+        Err(chromosomes) => EffresResult::Chromosomes { chromosomes, summary },
+    };
+    Ok(opts.report(input, &plan, None, Vec::new(), result))
+}
+
+/// Min/mean/max of the coverages `covs` at `resolution`; all 0 when empty.
+fn summary_row(resolution: i32, covs: &[f64], tally: Option<SummaryTally>, pct: f64) -> EffresSummaryRow {
+    let full = tally.map(|t| t.finish(covs, pct));
+    if covs.is_empty() {
+        return EffresSummaryRow { resolution, min: 0.0, mean: 0.0, max: 0.0, full };
+    }
+    let min = covs.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = covs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let mean = covs.iter().sum::<f64>() / (covs.len() as f64);
+    EffresSummaryRow { resolution, min, mean, max, full }
+}
+
+/// Write an [`EffresReport`] to `output`, or stdout.
+pub fn write_effres(report: &EffresReport, format: EffresFormat, output: Option<&Path>) -> Result<()> {
+    let mut w = match output {
+        Some(path) => DumpSink::create(path, false)?,
+        None => DumpSink::Stdout(BufWriter::new(std::io::stdout().lock())),
+    };
+    write_effres_to(report, format, &mut w)?;
+    w.finish()
+}
+
+/// The `# Filters:` line and one `# Skipped` line per reason.
+fn write_effres_filters<W: Write>(report: &EffresReport, per_resolution: bool, w: &mut W) -> Result<()> {
+    let filter = report.chromosome_filter.as_deref().unwrap_or("none");
+    let no_signal = if per_resolution { "; exclude no-signal contigs per resolution" } else { "" };
+    writeln!(w, "# Filters: {}{}", filter, no_signal)?;
+    let mut by_reason: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for s in &report.skipped {
+        by_reason.entry(s.reason.as_str()).or_default().push(s.name.as_str());
+    }
+    for (reason, names) in by_reason {
+        writeln!(w, "# Skipped ({}): {}", reason, names.join(", "))?;
+    }
+    Ok(())
+}
+
+fn write_bin_tally<W: Write>(w: &mut W, t: &BinTally) -> Result<()> {
+    write!(w, "\t{}\t{}\t{}", t.contacts, t.covered_bins, t.total_bins)?;
+    Ok(())
+}
+
+fn write_effres_to<W: Write>(report: &EffresReport, format: EffresFormat, w: &mut W) -> Result<()> {
+    if format == EffresFormat::Json {
+        serde_json::to_writer_pretty(&mut *w, report)?;
+        writeln!(w)?;
+        return Ok(());
+    }
+    let text = format == EffresFormat::Text;
+    let unit_label = report.unit.to_ascii_lowercase();
+    if text {
+        writeln!(w, "# File: {}", report.file.display())?;
+        if report.include_trans {
+            writeln!(w, "# Contacts: intra- and inter-chromosomal")?;
+        }
+        if !report.aggregated.is_empty() {
+            let list: Vec<String> = report.aggregated.iter().map(|a| format!("{} from {}", a.resolution, a.from)).collect();
+            writeln!(w, "# Aggregated (not stored): {}", list.join(", "))?;
+        }
+    }
+    // Row tables gain a `source` column once some resolution was aggregated
+    let marked = !report.aggregated.is_empty();
+    let source_header = if marked { "\tsource" } else { "" };
+    let source = |res: i32| match (marked, report.aggregated.iter().any(|a| a.resolution == res)) {
+        (false, _) => "",
+        (true, true) => "\taggregated",
+        (true, false) => "\tstored",
+    };
+    match &report.result {
+        EffresResult::Summary { rows } => {
+            if text {
+                writeln!(w, "# Mode: all chromosomes coverage summary")?;
+                write_effres_filters(report, true, w)?;
+                writeln!(w, "# Threshold per bin: {} contacts", report.thr)?;
+                writeln!(w, "# Zero-contact bins: {}", report.zero_bins.describe())?;
+            }
+            write_summary_rows(w, rows, &unit_label, source_header, &source)?;
+        }
+        EffresResult::Matrix { resolutions, rows } => {
+            if text {
+                writeln!(w, "# Mode: all chromosomes coverage matrix")?;
+                write_effres_filters(report, true, w)?;
+                writeln!(w, "# Threshold per bin: {} contacts", report.thr)?;
+                writeln!(w, "# Zero-contact bins: {}", report.zero_bins.describe())?;
+            }
+            write!(w, "chromosome")?;
+            for res in resolutions {
+                write!(w, "\t{}", res)?;
+            }
+            writeln!(w)?;
+            for row in rows {
+                write!(w, "{}", row.chromosome)?;
+                for cov in &row.coverage {
+                    match cov {
+                        Some(cov) => write!(w, "\t{:.3}", cov)?,
+                        None => write!(w, "\tNA")?,
+                    }
+                }
+                writeln!(w)?;
+            }
+        }
+        EffresResult::PerChrom { rows } => {
+            if text {
+                writeln!(w, "# Mode: per-chromosome effective resolution")?;
+                write_effres_filters(report, false, w)?;
+                writeln!(w, "# Threshold per bin: {} contacts", report.thr)?;
+                writeln!(w, "# Zero-contact bins: {}", report.zero_bins.describe())?;
+                writeln!(w, "# Required coverage: {:.1}% bins", report.pct * 100.0)?;
+            }
+            writeln!(w, "chromosome\teff_res_{}\tcoverage_at_eff{}", unit_label, source_header)?;
+            for c in rows {
+                match c.effective {
+                    Some((res, cov)) => writeln!(w, "{}\t{}\t{:.3}{}", c.name, res, cov, source(res))?,
+                    None => writeln!(w, "{}\tNA\tNA{}", c.name, if marked { "\tNA" } else { "" })?,
+                }
+            }
+        }
+        EffresResult::Chromosome { chromosome, rows, effective_resolution } => {
+            if text {
+                writeln!(w, "# Chromosome: {}", chromosome)?;
+                writeln!(w, "# Threshold per bin: {} contacts", report.thr)?;
+                writeln!(w, "# Zero-contact bins: {}", report.zero_bins.describe())?;
+                writeln!(w, "# Required coverage: {:.1}% bins\n", report.pct * 100.0)?;
+            }
+            write_coverage_header(w, rows, None, &unit_label, source_header)?;
+            write_coverage_rows(w, rows, None, &source)?;
+            if text {
+                write_effective_line(w, report, chromosome, *effective_resolution, &unit_label)?;
+            }
+        }
+        EffresResult::Chromosomes { chromosomes, summary } => {
+            if !text {
+                // one long table, the summary being in the JSON only
+                let rows = chromosomes.first().map_or(&[][..], |c| &c.rows[..]);
+                write_coverage_header(w, rows, Some("chromosome"), &unit_label, source_header)?;
+                for c in chromosomes {
+                    write_coverage_rows(w, &c.rows, Some(&c.chromosome), &source)?;
+                }
+                return Ok(());
+            }
+            let names: Vec<&str> = chromosomes.iter().map(|c| c.chromosome.as_str()).collect();
+            writeln!(w, "# Chromosomes: {}", names.join(", "))?;
+            writeln!(w, "# Threshold per bin: {} contacts", report.thr)?;
+            writeln!(w, "# Zero-contact bins: {}", report.zero_bins.describe())?;
+            writeln!(w, "# Required coverage: {:.1}% bins", report.pct * 100.0)?;
+            for c in chromosomes {
+                writeln!(w, "\n# Chromosome: {}", c.chromosome)?;
+                write_coverage_header(w, &c.rows, None, &unit_label, source_header)?;
+                write_coverage_rows(w, &c.rows, None, &source)?;
+                write_effective_line(w, report, &c.chromosome, c.effective_resolution, &unit_label)?;
+            }
+            writeln!(w, "\n# Summary over the {} chromosomes (no-signal ones left out per resolution)", chromosomes.len())?;
+            write_summary_rows(w, summary, &unit_label, source_header, &source)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_summary_rows<W: Write>(
+    w: &mut W,
+    rows: &[EffresSummaryRow],
+    unit_label: &str,
+    source_header: &str,
+    source: &impl Fn(i32) -> &'static str,
+) -> Result<()> {
+    let full = rows.iter().any(|r| r.full.is_some());
+    let full_header = "\tmedian_cov\tchromosomes\tpassing\tcontacts\tcovered_bins\ttotal_bins\tbin_q1\tbin_median\tbin_q3";
+    writeln!(w, "resolution_{}\tmin_cov\tmean_cov\tmax_cov{}{}", unit_label, if full { full_header } else { "" }, source_header)?;
+    for r in rows {
+        write!(w, "{}\t{:.3}\t{:.3}\t{:.3}", r.resolution, r.min, r.mean, r.max)?;
+        if let Some(f) = &r.full {
+            let [q1, q2, q3] = f.bin_count_quartiles;
+            write!(w, "\t{:.3}\t{}\t{}", f.median, f.chromosomes, f.passing)?;
+            write_bin_tally(w, &f.tally)?;
+            write!(w, "\t{}\t{}\t{}", q1, q2, q3)?;
+        }
+        writeln!(w, "{}", source(r.resolution))?;
+    }
+    Ok(())
+}
+
+/// Header of a coverage table, with a leading `key` column if given.
+fn write_coverage_header<W: Write>(w: &mut W, rows: &[EffresCoverage], key: Option<&str>, unit_label: &str, source_header: &str) -> Result<()> {
+    let full = rows.iter().any(|r| r.tally.is_some());
+    let key = key.map(|k| format!("{}\t", k)).unwrap_or_default();
+    writeln!(w, "{}resolution_{}\tcoverage{}{}", key, unit_label, if full { "\tcontacts\tcovered_bins\ttotal_bins" } else { "" }, source_header)?;
+    Ok(())
+}
+
+fn write_coverage_rows<W: Write>(w: &mut W, rows: &[EffresCoverage], key: Option<&str>, source: &impl Fn(i32) -> &'static str) -> Result<()> {
+    for r in rows {
+        if let Some(key) = key {
+            write!(w, "{}\t", key)?;
+        }
+        write!(w, "{}\t{:.3}", r.resolution, r.coverage)?;
+        if let Some(t) = &r.tally {
+            write_bin_tally(w, t)?;
+        }
+        writeln!(w, "{}", source(r.resolution))?;
+    }
+    Ok(())
+}
+
+fn write_effective_line<W: Write>(w: &mut W, report: &EffresReport, chromosome: &str, effective: Option<i32>, unit_label: &str) -> Result<()> {
+    match effective {
+        Some(r) => writeln!(
+            w,
+            "\nEffective resolution on {}: {} {} (≥{:.0}% bins ≥ {} contacts)",
+            chromosome,
+            r,
+            unit_label,
+            report.pct * 100.0,
+            report.thr
+        )?,
+        None => writeln!(w, "\nNo resolution met the {:.0}% / {} contacts criterion.", report.pct * 100.0, report.thr)?,
+    }
+    Ok(())
+}
+
+/// Effective resolution of one chromosome, from [`per_chrom_effres_hic`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ChromEffres {
+    pub name: String,
+    /// Finest resolution whose coverage reaches `pct`, with that coverage
+    #[serde(serialize_with = "serialize_effective")]
+    pub effective: Option<(i32, f64)>,
+}
+
+/// `effective` as an [`EffresCoverage`] object (or null) in JSON.
+fn serialize_effective<S: serde::Serializer>(effective: &Option<(i32, f64)>, s: S) -> std::result::Result<S::Ok, S::Error> {
+    serde::Serialize::serialize(&effective.map(|(resolution, coverage)| EffresCoverage { resolution, coverage, tally: None }), s)
+}
+
+/// Contigs shorter than this are left out of the all-chromosome effres modes by default.
+pub const EFFRES_MIN_CHROM_LEN: i64 = 2_500_000;
+
+/// Sum per-bin counts at resolution `from` into bins of `to`, a multiple of `from`.
+fn rebin_counts(counts: &[f64], from: i32, to: i32) -> Vec<f64> {
+    let coarse = |bin: usize| (bin as i64 * from as i64 / to as i64) as usize;
+    let mut out = vec![0.0; counts.len().checked_sub(1).map_or(0, |last| coarse(last) + 1)];
+    for (bin, &c) in counts.iter().enumerate() {
+        out[coarse(bin)] += c;
+    }
+    out
+}
+
+/// Resolutions `effres` scans, ascending, each with the stored resolution its
+/// counts are read at: itself when stored, otherwise the finest stored one
+/// dividing it, or the finest stored one when none does (its bins are then
+/// assigned by their start). With no `--resolutions`, the stored ones.
+fn effres_resolutions(hic: &HicFile, opts: &EffresOptions) -> Result<Vec<(i32, i32)>> {
+    let mut stored = hic.unit_resolutions(&opts.unit).to_vec();
+    stored.sort_unstable();
+    if opts.resolutions.is_empty() {
+        return Ok(stored.into_iter().map(|r| (r, r)).collect());
+    }
+    let Some(&finest) = stored.first() else {
+        return Err(anyhow!("{:?} stores no {} resolutions", hic.path, opts.unit));
+    };
+    let mut requested = opts.resolutions.clone();
+    requested.sort_unstable();
+    requested.dedup();
+    requested
+        .into_iter()
+        .map(|res| {
+            if stored.contains(&res) {
+                return Ok((res, res));
+            }
+            if res < finest {
+                return Err(anyhow!(
+                    "Resolution {} is finer than the finest stored {} resolution ({}) and cannot be summed from it",
+                    res, opts.unit, finest
+                ));
+            }
+            Ok((res, stored.iter().copied().find(|&s| res % s == 0).unwrap_or(finest)))
+        })
+        .collect()
+}
+
+/// Bin counts of a fixed set of chromosomes at planned (resolution, source)
+/// pairs; an aggregated resolution is summed from its source, which is
+/// decoded once however many resolutions it serves.
+struct PlannedCounts<'a> {
+    ids: &'a [i32],
+    opts: &'a EffresOptions,
+    sources: Vec<(i32, Vec<Option<Vec<f64>>>)>,
+}
+
+impl<'a> PlannedCounts<'a> {
+    fn new(ids: &'a [i32], opts: &'a EffresOptions) -> Self {
+        PlannedCounts { ids, opts, sources: Vec::new() }
+    }
+
+    fn at(&mut self, hic: &mut HicFile, res: i32, from: i32) -> Result<Vec<Option<Vec<f64>>>> {
+        let (unit, trans) = (self.opts.unit.as_str(), self.opts.include_trans);
+        if res == from {
+            return hic.bin_counts(self.ids, unit, res, trans);
+        }
+        if !self.sources.iter().any(|(f, _)| *f == from) {
+            self.sources.push((from, hic.bin_counts(self.ids, unit, from, trans)?));
+        }
+        let (_, fine) = self.sources.iter().find(|(f, _)| *f == from).expect("decoded above");
+        Ok(fine.iter().map(|counts| counts.as_deref().map(|c| rebin_counts(c, from, res))).collect())
+    }
+}
+
+/// Effective resolution of every chromosome selected by the filters of
+/// `opts`, computed like the single-chromosome `effres`.
+pub fn per_chrom_effres_hic(input: &Path, opts: &EffresOptions) -> Result<Vec<ChromEffres>> {
+    let mut hic = HicFile::open(input)?;
+    let (chroms, _) = effres_chromosomes(&hic, opts)?;
+    per_chrom_effres(&mut hic, chroms, opts)
+}
+
+/// The matrices are read once at the finest resolution dividing a coarser one
+/// and re-binned, rather than decoded again at every zoom level, for all the
+/// chromosomes still searching. Chromosomes without signal or without a
+/// resolution reaching `pct` have no effective resolution.
+fn per_chrom_effres(hic: &mut HicFile, chroms: Vec<EffresChrom>, opts: &EffresOptions) -> Result<Vec<ChromEffres>> {
+    let (unit, thr, pct, zero_bins) = (opts.unit.as_str(), opts.thr as f64, opts.pct, opts.zero_bins);
+    hic.require_unit(unit)?;
+    let plan = effres_resolutions(hic, opts)?;
+
+    // Counts decoded so far, finest first, for the chromosomes still without an
+    // effective resolution then; a resolution re-bins the finest one dividing it
+    let mut decoded: Vec<(i32, Vec<Vec<f64>>)> = Vec::new();
+    let mut effective: Vec<Option<(i32, f64)>> = vec![None; chroms.len()];
+    for &(res, source) in &plan {
+        let pending: Vec<usize> = (0..chroms.len()).filter(|&c| effective[c].is_none()).collect();
+        if pending.is_empty() { break; }
+        let usable = |f: i32| res % f == 0 || f == source;
+        if !decoded.iter().any(|&(f, _)| usable(f)) {
+            let ids: Vec<i32> = pending.iter().map(|&c| chroms[c].0).collect();
+            let mut fine = vec![Vec::new(); chroms.len()];
+            for (&c, counts) in pending.iter().zip(hic.bin_counts(&ids, unit, source, opts.include_trans)?) {
+                fine[c] = counts.unwrap_or_default();
+            }
+            decoded.push((source, fine));
+        }
+        let (from, fine) = decoded.iter().find(|&&(f, _)| usable(f)).expect("decoded above");
+        for c in pending {
+            if fine[c].is_empty() { continue; }
+            let counts = rebin_counts(&fine[c], *from, res);
+            let total = num_bins_for(chroms[c].1, res) as u64;
+            let cov = coverage_fraction(counts.iter().copied(), thr, total, zero_bins).fraction();
+            if cov >= pct {
+                effective[c] = Some((res, cov));
+            }
+        }
+    }
+    Ok(chroms.into_iter().zip(effective).map(|((_, _, name), effective)| ChromEffres { name, effective }).collect())
+}
+
+/// [`per_chrom_effres_hic`] as an [`EffresReport`], written as a
+/// `chromosome eff_res_bp coverage_at_eff` table with `NA` for chromosomes
+/// that have none.
+pub fn effres_per_chrom_report(input: &Path, opts: &EffresOptions) -> Result<EffresReport> {
+    let mut hic = HicFile::open(input)?;
+    let (chroms, skipped) = effres_chromosomes(&hic, opts)?;
+    let rows = per_chrom_effres(&mut hic, chroms, opts)?;
+    let plan = effres_resolutions(&hic, opts)?;
+    Ok(opts.report(input, &plan, Some(opts.describe_filter()), skipped, EffresResult::PerChrom { rows }))
+}
+
+/// Genome-wide coverage per stored BP resolution, pooling the bins of every
+/// chromosome the way `resolution` does for pairs input.
+#[derive(Debug, Clone)]
+pub struct GenomeEffres {
+    /// (resolution, pooled covered/total bins), finest resolution first
+    pub per_resolution: Vec<(i32, CoverageFraction)>,
+    /// Finest resolution whose pooled coverage reaches `pct`
+    pub effective: Option<i32>,
+}
+
+/// Pooled effective resolution of a .hic file: for each BP resolution, bins of
+/// all chromosomes are tallied together from the intra-chromosomal matrices,
+/// and chromosomes without a matrix contribute uncovered bins. Only the stored
+/// resolutions can be reported, so the answer is rounded up to the next zoom level.
+pub fn genome_effres_hic(input: &Path, thr: f64, pct: f64, zero_bins: ZeroBins) -> Result<GenomeEffres> {
+    let mut hic = HicFile::open(input)?;
+    hic.require_bp()?;
+    let mut resolutions = hic.resolutions.clone();
+    resolutions.sort_unstable();
+    let chr_idxs: Vec<(i32, i64)> = hic
+        .chromosomes
+        .iter()
+        .filter(|c| c.index > 0)
+        .map(|c| (c.index, c.length))
+        .collect();
+
+    let mut per_resolution = Vec::with_capacity(resolutions.len());
+    let mut effective = None;
+    for res in resolutions {
+        let mut pooled = CoverageFraction::default();
+        for &(ci, clen) in &chr_idxs {
+            let total = num_bins_for(clen, res) as u64;
+            let counts = hic.intra_bin_counts(ci, "BP", res)?.unwrap_or_default();
+            pooled = pooled + coverage_fraction(counts.iter().copied(), thr, total, zero_bins);
+        }
+        if effective.is_none() && pooled.fraction() >= pct {
+            effective = Some(res);
+        }
+        per_resolution.push((res, pooled));
+    }
+    Ok(GenomeEffres { per_resolution, effective })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cstr(s: &str) -> Vec<u8> {
+        let mut v = s.as_bytes().to_vec();
+        v.push(0);
+        v
+    }
+
+    /// Serialize a v8 header: chromosomes (name, length), BP and FRAG resolutions.
+    /// Each chromosome gets `len / 100` evenly spaced restriction sites when FRAG
+    /// resolutions are present.
+    fn v8_header(chroms: &[(&str, i32)], bp: &[i32], frag: &[i32]) -> Vec<u8> {
+        let mut h = Vec::new();
+        h.extend(cstr("HIC"));
+        h.extend(8i32.to_le_bytes());
+        h.extend(0i64.to_le_bytes()); // master index position
+        h.extend(cstr("test_genome"));
+        h.extend(2i32.to_le_bytes()); // attributes
+        for (key, value) in [("software", "hickit-test"), ("statistics", "Sequenced Read Pairs: 10\nUnique: 8\n")] {
+            h.extend(cstr(key));
+            h.extend(cstr(value));
+        }
+        h.extend((chroms.len() as i32).to_le_bytes());
+        for (name, len) in chroms {
+            h.extend(cstr(name));
+            h.extend(len.to_le_bytes());
+        }
+        h.extend((bp.len() as i32).to_le_bytes());
+        for r in bp { h.extend(r.to_le_bytes()); }
+        h.extend((frag.len() as i32).to_le_bytes());
+        for r in frag { h.extend(r.to_le_bytes()); }
+        if !frag.is_empty() {
+            for (_, len) in chroms {
+                let nsites = len / 100;
+                h.extend(nsites.to_le_bytes());
+                for s in 0..nsites { h.extend((s * 100 + 50).to_le_bytes()); }
+            }
+        }
+        h
+    }
+
+    /// A zlib-compressed v8 type-1 block with short bins and short counts.
+    /// `records` are (bin_x, bin_y, count); rows are keyed by bin_y.
+    fn block_v8_type1(records: &[(i16, i16, i16)]) -> Vec<u8> {
+        let mut rows: BTreeMap<i16, Vec<(i16, i16)>> = BTreeMap::new();
+        for &(x, y, c) in records { rows.entry(y).or_default().push((x, c)); }
+        let mut raw = Vec::new();
+        raw.extend((records.len() as i32).to_le_bytes());
+        raw.extend(0i32.to_le_bytes()); // bin_x offset
+        raw.extend(0i32.to_le_bytes()); // bin_y offset
+        raw.push(0); // short counts
+        raw.push(1); // type 1
+        raw.extend((rows.len() as i16).to_le_bytes());
+        for (y, cols) in &rows {
+            raw.extend(y.to_le_bytes());
+            raw.extend((cols.len() as i16).to_le_bytes());
+            for (x, c) in cols {
+                raw.extend(x.to_le_bytes());
+                raw.extend(c.to_le_bytes());
+            }
+        }
+        let mut enc = flate2::write::ZlibEncoder::new(Vec::new(), Compression::default());
+        enc.write_all(&raw).unwrap();
+        enc.finish().unwrap()
+    }
+
+    /// zlib-compressed v9 block: record count, offsets (10, 20), the short
+    /// counts / short bin_x / short bin_y flags, then `typ` and its payload.
+    fn block_v9(n_records: i32, short: [bool; 3], typ: u8, payload: &[u8]) -> Vec<u8> {
+        let mut raw = Vec::new();
+        raw.extend(n_records.to_le_bytes());
+        raw.extend(10i32.to_le_bytes());
+        raw.extend(20i32.to_le_bytes());
+        raw.extend(short.map(|s| if s { 0u8 } else { 1 }));
+        raw.push(typ);
+        raw.extend(payload);
+        let mut enc = flate2::write::ZlibEncoder::new(Vec::new(), Compression::default());
+        enc.write_all(&raw).unwrap();
+        enc.finish().unwrap()
+    }
+
+    /// Every record of a compressed block, read through [`BlockRecords`].
+    fn decode_block(comp: &[u8], version: i32) -> Result<Vec<ContactRecord>> {
+        let buf = inflate_block(comp)?;
+        let mut records = BlockRecords::new(&buf, version)?;
+        let out = records.by_ref().collect();
+        records.finish()?;
+        Ok(out)
+    }
+
+    #[test]
+    fn decodes_every_block_encoding_and_rejects_malformed_ones() {
+        let le = |vals: &[i64], widths: &[usize]| -> Vec<u8> {
+            vals.iter().zip(widths).flat_map(|(&v, &w)| v.to_le_bytes()[..w].to_vec()).collect()
+        };
+        let decoded = |comp: &[u8], version: i32| -> Vec<(i32, i32, f32)> {
+            decode_block(comp, version).unwrap().iter().map(|r| (r.bin_x, r.bin_y, r.counts)).collect()
+        };
+        let expected = vec![(11, 22, 5.0), (13, 22, 7.0)];
+        // One row (y 2) with columns x 1 and 3; the flags pick i16 or i32 per axis
+        for (short_x, short_y) in [(true, true), (true, false), (false, true), (false, false)] {
+            let (wx, wy) = (if short_x { 2 } else { 4 }, if short_y { 2 } else { 4 });
+            let payload = le(&[1, 2, 2, 1, 5, 3, 7], &[wy, wy, wx, wx, 2, wx, 2]);
+            assert_eq!(decoded(&block_v9(2, [true, short_x, short_y], 1, &payload), 9), expected, "{} {}", short_x, short_y);
+        }
+        // Float counts
+        let payload = le(&[1, 2, 1, 1, 0x40a0_0000], &[2, 2, 2, 2, 4]);
+        assert_eq!(decoded(&block_v9(1, [false, true, true], 1, &payload), 9), vec![(11, 22, 5.0)]);
+        // Dense type 2: 4 points, 2 wide; the -32768 sentinel marks an empty cell
+        let payload = le(&[4, 2, 1, -32768, 0, 3], &[4, 2, 2, 2, 2, 2]);
+        assert_eq!(decoded(&block_v9(3, [true, true, true], 2, &payload), 9), vec![(10, 20, 1.0), (10, 21, 0.0), (11, 21, 3.0)]);
+        // v6: bin_x, bin_y, counts triples
+        let mut raw = le(&[1, 4, 6, 0x4000_0000], &[4, 4, 4, 4]);
+        let mut enc = flate2::write::ZlibEncoder::new(Vec::new(), Compression::default());
+        enc.write_all(&raw).unwrap();
+        assert_eq!(decoded(&enc.finish().unwrap(), 6), vec![(4, 6, 2.0)]);
+
+        let err = |comp: &[u8], version: i32| format!("{:#}", decode_block(comp, version).unwrap_err());
+        assert_eq!(err(&block_v9(1, [true; 3], 3, &[]), 9), "unknown block type 3 at offset 15");
+        assert_eq!(err(&block_v9(1, [true; 3], 1, &le(&[-1], &[2])), 9), "negative row count -1 at offset 16");
+        assert_eq!(err(&block_v9(1, [true; 3], 1, &le(&[1, 2, -4], &[2, 2, 2])), 9), "negative column count -4 at offset 20");
+        assert_eq!(err(&block_v9(-2, [true; 3], 1, &[]), 9), "negative record count -2 at offset 0");
+        assert_eq!(err(&block_v9(4, [true; 3], 2, &le(&[4, 0], &[4, 2])), 9), "dense block of 4 points has width 0");
+        // The last count is cut off
+        assert_eq!(
+            err(&block_v9(2, [true; 3], 1, &le(&[1, 2, 2, 1, 5, 3], &[2, 2, 2, 2, 2, 2])), 9),
+            "block truncated: 2 bytes needed at offset 28 of 28"
+        );
+        // v6 declaring more records than the block holds
+        raw[..4].copy_from_slice(&3i32.to_le_bytes());
+        let mut enc = flate2::write::ZlibEncoder::new(Vec::new(), Compression::default());
+        enc.write_all(&raw).unwrap();
+        assert_eq!(err(&enc.finish().unwrap(), 6), "declares 3 records but only 12 bytes follow");
+    }
+
+    #[test]
+    fn block_records_are_parsed_as_they_are_read() {
+        let le = |vals: &[i64], widths: &[usize]| -> Vec<u8> {
+            vals.iter().zip(widths).flat_map(|(&v, &w)| v.to_le_bytes()[..w].to_vec()).collect()
+        };
+        let mut enc = flate2::write::ZlibEncoder::new(Vec::new(), Compression::default());
+        enc.write_all(&le(&[2, 4, 6, 0x4000_0000, 5, 7, 0x4040_0000], &[4, 4, 4, 4, 4, 4, 4])).unwrap();
+        let blocks = [
+            (block_v8_type1(&[(1, 3, 4), (5, 2, 7), (6, 2, 1)]), 8),
+            (block_v9(2, [true, false, true], 1, &le(&[1, 2, 2, 1, 5, 3, 7], &[2, 2, 4, 4, 2, 4, 2])), 9),
+            (block_v9(3, [true; 3], 2, &le(&[4, 2, 1, -32768, 0, 3], &[4, 2, 2, 2, 2, 2])), 9),
+            (block_v9(2, [false, true, true], 2, &le(&[3, 2, 0x7fc0_0000, 0x40a0_0000, 0x3f80_0000], &[4, 2, 4, 4, 4])), 9),
+            (enc.finish().unwrap(), 6),
+        ];
+        for (comp, version) in &blocks {
+            let buf = inflate_block(comp).unwrap();
+            let mut records = BlockRecords::new(&buf, *version).unwrap();
+            let first = records.next().unwrap();
+            // Only the first record has been read, not the whole payload
+            assert!(records.cur.remaining() > 0, "version {}", version);
+            let streamed: Vec<ContactRecord> = std::iter::once(first).chain(records.by_ref()).collect();
+            records.finish().unwrap();
+            assert_eq!(records.cur.remaining(), 0);
+            assert_eq!(streamed, decode_block(comp, *version).unwrap());
+            assert_eq!(check_block(comp, *version).unwrap(), streamed.len());
+        }
+
+        // Records before a truncation are yielded, then the error is kept
+        let buf = inflate_block(&block_v9(2, [true; 3], 1, &le(&[1, 2, 2, 1, 5, 3], &[2, 2, 2, 2, 2, 2]))).unwrap();
+        let mut records = BlockRecords::new(&buf, 9).unwrap();
+        assert_eq!(records.by_ref().map(|r| (r.bin_x, r.bin_y, r.counts)).collect::<Vec<_>>(), vec![(11, 22, 5.0)]);
+        assert_eq!(records.finish().unwrap_err().to_string(), "block truncated: 2 bytes needed at offset 28 of 28");
+        assert!(records.finish().is_ok());
+        assert_eq!(BlockRecords::new(&[], 9).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn block_errors_name_the_block_and_chromosome_pair() {
+        let chroms = [("All", 1), ("chr1", 1000), ("chr2", 500)];
+        let mut bytes = TestHic::new(&chroms, 100).matrix(1, 2, &[(7, 4, 2)]).build();
+        // The type byte follows the record count, two offsets and the short-counts flag
+        let block_pos = v8_header(&chroms, &[100], &[]).len();
+        let block = block_v8_type1(&[(7, 4, 2)]);
+        let mut raw = Vec::new();
+        flate2::read::ZlibDecoder::new(&block[..]).read_to_end(&mut raw).unwrap();
+        raw[13] = 9;
+        let mut enc = flate2::write::ZlibEncoder::new(Vec::new(), Compression::default());
+        enc.write_all(&raw).unwrap();
+        let broken = enc.finish().unwrap();
+        assert_eq!(broken.len(), block.len(), "the patched block must keep its size");
+        bytes[block_pos..block_pos + block.len()].copy_from_slice(&broken);
+        let path = write_temp("block_error.hic", &bytes);
+        let out = write_temp("block_error.slc.gz", &[]);
+        let err = dump_hic_genome_wide(&path, 100, &out, &DumpOptions::default()).unwrap_err();
+        let mut hic = HicFile::open(&path).unwrap();
+        let query = hic.query("chr1", "chr2", "BP", 100).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&out).unwrap();
+        let expected = format!("Decode block 0 at {} of chr1-chr2, resolution 100: unknown block type 9 at offset 13", block_pos);
+        assert_eq!(format!("{:#}", err), expected);
+        assert_eq!(format!("{:#}", query), expected);
+    }
+
+    #[test]
+    fn canonicalizes_lower_triangle_intra_records() {
+        let records = decode_block(&block_v8_type1(&[(1, 3, 4), (5, 2, 7)]), 8).unwrap();
+
+        let raw: Vec<(i32, i32)> = records.iter().map(|r| (r.bin_x, r.bin_y)).collect();
+        assert!(raw.contains(&(5, 2)), "fixture must contain a lower-triangle record");
+
+        let intra: Vec<(i32, i32, f32)> = records
+            .iter()
+            .cloned()
+            .map(|r| r.canonicalized(true))
+            .map(|r| (r.bin_x, r.bin_y, r.counts))
+            .collect();
+        assert_eq!(intra, vec![(2, 5, 7.0), (1, 3, 4.0)]);
+
+        // Inter-chromosomal axes are different chromosomes: never swapped
+        let inter = records[0].clone().canonicalized(false);
+        assert_eq!((inter.bin_x, inter.bin_y), (5, 2));
+    }
+
+    /// (chr1 index, chr2 index, (bin_x, bin_y, count) records) of one test matrix.
+    type TestMatrix<'a> = (i32, i32, &'a [(i16, i16, i16)]);
+
+    /// (normalization type, chromosome index, values) of one test norm vector.
+    type TestNorm<'a> = (&'a str, i32, &'a [f64]);
+
+    /// (normalization type, values, (chromosome index, scale factor)) of one
+    /// expected vector; `NONE` goes to the raw section, others to the normalized one.
+    type TestExpected<'a> = (&'a str, &'a [f64], &'a [(i32, f64)]);
+
+    /// Builder of a complete v8 test file with one resolution and one block per
+    /// matrix. The footer holds the master index, the expected vectors and the norm vectors.
+    struct TestHic<'a> {
+        chroms: &'a [(&'a str, i32)],
+        unit: &'a str,
+        res: i32,
+        matrices: Vec<TestMatrix<'a>>,
+        norms: Vec<TestNorm<'a>>,
+        expected: Vec<TestExpected<'a>>,
+    }
+
+    impl<'a> TestHic<'a> {
+        /// A file over `chroms` with the single BP resolution `res` and nothing stored yet.
+        fn new(chroms: &'a [(&'a str, i32)], res: i32) -> Self {
+            TestHic { chroms, unit: "BP", res, matrices: Vec::new(), norms: Vec::new(), expected: Vec::new() }
+        }
+
+        /// Store the resolution, matrices, expected and norm vectors in `unit` (`BP` or `FRAG`).
+        fn unit(mut self, unit: &'a str) -> Self {
+            self.unit = unit;
+            self
+        }
+
+        /// One block of (bin_x, bin_y, count) records for chromosome indexes `c1`, `c2`.
+        fn matrix(mut self, c1: i32, c2: i32, records: &'a [(i16, i16, i16)]) -> Self {
+            self.matrices.push((c1, c2, records));
+            self
+        }
+
+        fn matrices(mut self, matrices: &[TestMatrix<'a>]) -> Self {
+            self.matrices.extend_from_slice(matrices);
+            self
+        }
+
+        /// The `norm` vector of chromosome index `chr`.
+        fn norm(mut self, norm: &'a str, chr: i32, values: &'a [f64]) -> Self {
+            self.norms.push((norm, chr, values));
+            self
+        }
+
+        fn norms(mut self, norms: &[TestNorm<'a>]) -> Self {
+            self.norms.extend_from_slice(norms);
+            self
+        }
+
+        /// An expected vector with per-chromosome scale factors; `NONE` goes to
+        /// the raw section, others to the normalized one.
+        fn expected(mut self, norm: &'a str, values: &'a [f64], factors: &'a [(i32, f64)]) -> Self {
+            self.expected.push((norm, values, factors));
+            self
+        }
+
+        fn build(&self) -> Vec<u8> {
+            let TestHic { chroms, unit, res, .. } = *self;
+            let (matrices, norms, expected) = (&self.matrices, &self.norms, &self.expected);
+            let mut f = if unit == "FRAG" { v8_header(chroms, &[], &[res]) } else { v8_header(chroms, &[res], &[]) };
+            let mut norm_positions = Vec::new();
+            for (_, _, values) in norms {
+                norm_positions.push(f.len() as i64);
+                f.extend((values.len() as i32).to_le_bytes());
+                for v in values.iter() { f.extend(v.to_le_bytes()); }
+            }
+            let mut entries = Vec::new();
+            for &(c1, c2, records) in matrices {
+                let block = block_v8_type1(records);
+                let block_pos = f.len() as i64;
+                f.extend(&block);
+                let matrix_pos = f.len() as i64;
+                let mut m = Vec::new();
+                m.extend(c1.to_le_bytes());
+                m.extend(c2.to_le_bytes());
+                m.extend(1i32.to_le_bytes()); // one zoom level
+                m.extend(cstr(unit));
+                m.extend(0i32.to_le_bytes()); // old zoom index
+                let sum: f32 = records.iter().map(|&(_, _, c)| c as f32).sum();
+                m.extend(sum.to_le_bytes());
+                for _ in 0..3 { m.extend(0f32.to_le_bytes()); } // occupied, stddev, p95
+                m.extend(res.to_le_bytes());
+                m.extend(100i32.to_le_bytes()); // block bin count
+                m.extend(1i32.to_le_bytes()); // block column count
+                m.extend(1i32.to_le_bytes()); // one block
+                m.extend(0i32.to_le_bytes());
+                m.extend(block_pos.to_le_bytes());
+                m.extend((block.len() as i32).to_le_bytes());
+                f.extend(&m);
+                entries.push((format!("{}_{}", c1, c2), matrix_pos, m.len() as i32));
+            }
+            let master = f.len() as i64;
+            f[8..16].copy_from_slice(&master.to_le_bytes());
+            f.extend(0i32.to_le_bytes()); // nBytes (unused by the reader)
+            f.extend((entries.len() as i32).to_le_bytes());
+            for (key, pos, size) in entries {
+                f.extend(cstr(&key));
+                f.extend(pos.to_le_bytes());
+                f.extend(size.to_le_bytes());
+            }
+            for normalized in [false, true] {
+                let section: Vec<_> = expected.iter().filter(|(norm, _, _)| (*norm != "NONE") == normalized).collect();
+                f.extend((section.len() as i32).to_le_bytes());
+                for (norm, values, factors) in section {
+                    if normalized { f.extend(cstr(norm)); }
+                    f.extend(cstr(unit));
+                    f.extend(res.to_le_bytes());
+                    f.extend((values.len() as i32).to_le_bytes());
+                    for v in values.iter() { f.extend(v.to_le_bytes()); }
+                    f.extend((factors.len() as i32).to_le_bytes());
+                    for (chr, v) in factors.iter() {
+                        f.extend(chr.to_le_bytes());
+                        f.extend(v.to_le_bytes());
+                    }
+                }
+            }
+            f.extend((norms.len() as i32).to_le_bytes());
+            for ((norm, chr, values), pos) in norms.iter().zip(norm_positions) {
+                f.extend(cstr(norm));
+                f.extend(chr.to_le_bytes());
+                f.extend(cstr(unit));
+                f.extend(res.to_le_bytes());
+                f.extend(pos.to_le_bytes());
+                f.extend((4 + 8 * values.len() as i32).to_le_bytes());
+            }
+            f
+        }
+    }
+
+    #[test]
+    fn verify_decodes_every_block_and_names_the_broken_one() {
+        let chroms = [("All", 1), ("chr1", 1000), ("chr2", 500)];
+        let intra: &[(i16, i16, i16)] = &[(0, 2, 5), (3, 3, 1)];
+        let mut bytes = TestHic::new(&chroms, 100).matrix(1, 1, intra).matrix(1, 2, &[(7, 4, 2)]).build();
+        let path = write_temp("verify.hic", &bytes);
+        let report = verify_hic(&path, false).unwrap();
+        assert!(report.problems.is_empty(), "{:?}", report.problems);
+        assert_eq!((report.index.blocks, report.decoded, report.records), (2, 2, 3));
+
+        // Break the deflate stream of chr1-chr1's block: the index still checks out
+        let block_pos = v8_header(&chroms, &[100], &[]).len();
+        let block_len = block_v8_type1(intra).len();
+        for b in &mut bytes[block_pos + 2..block_pos + block_len - 4] {
+            *b = !*b;
+        }
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(verify_hic(&path, true).unwrap().problems.is_empty());
+        let report = verify_hic(&path, false).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((report.decoded, report.records), (1, 1));
+        assert_eq!(report.problems.len(), 1, "{:?}", report.problems);
+        assert!(report.problems[0].starts_with(&format!("chr1-chr1 BP 100: block 0 at {}", block_pos)), "{}", report.problems[0]);
+
+        // A block declaring more records than it holds
+        let mut raw = Vec::new();
+        raw.extend(2i32.to_le_bytes());
+        raw.extend([0u8; 8]);
+        raw.extend([0, 1]);
+        raw.extend([1i16, 0, 1, 0, 4].iter().flat_map(|v| v.to_le_bytes()));
+        let mut enc = flate2::write::ZlibEncoder::new(Vec::new(), Compression::default());
+        enc.write_all(&raw).unwrap();
+        let comp = enc.finish().unwrap();
+        assert!(check_block(&comp, 8).unwrap_err().to_string().contains("declares 2 records but holds 1"));
+        assert_eq!(decode_block(&comp, 8).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn validate_index_flags_blocks_outside_the_file() {
+        let chroms = [("All", 1), ("chr1", 1000), ("chr2", 500)];
+        let intra: &[(i16, i16, i16)] = &[(0, 2, 5), (3, 3, 1)];
+        let mut bytes = TestHic::new(&chroms, 100).matrix(1, 1, intra).matrix(1, 2, &[(7, 4, 2)]).build();
+        let path = write_temp("validate_ok.hic", &bytes);
+        let report = validate_index(&path).unwrap();
+        assert_eq!((report.matrices, report.zoom_levels, report.blocks), (2, 2, 2));
+        assert!(report.anomalies.is_empty(), "{:?}", report.anomalies);
+
+        // Patch the size of chr1-chr1's block entry, which follows the block itself
+        let matrix_pos = v8_header(&chroms, &[100], &[]).len() + block_v8_type1(intra).len();
+        let size_pos = matrix_pos + 12 + cstr("BP").len() + 4 + 16 + 12 + 4 + 4 + 8;
+        bytes[size_pos..size_pos + 4].copy_from_slice(&1_000_000i32.to_le_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+        let report = validate_index(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(report.blocks, 2);
+        assert_eq!(report.anomalies.len(), 1, "{:?}", report.anomalies);
+        assert!(report.anomalies[0].starts_with("chr1-chr1 BP 100: block 0"), "{}", report.anomalies[0]);
+        assert!(report.anomalies[0].contains("(1000000 bytes) is outside the file"));
+    }
+
+    #[test]
+    fn reader_lists_chromosomes_and_iterates_contacts() {
+        let chroms = [("All", 1), ("chr1", 1000), ("chr2", 500)];
+        let bytes = TestHic::new(&chroms, 100).matrix(1, 1, &[(0, 2, 5), (3, 3, 1)]).matrix(1, 2, &[(7, 4, 2)]).build();
+        let path = write_temp("reader.hic", &bytes);
+        let mut reader = HicReader::open(&path).unwrap();
+
+        let names: Vec<&str> = reader.chromosomes().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["chr1", "chr2"]);
+        assert_eq!(reader.resolutions(), &[100]);
+        let total: f32 = reader.contacts("chr1", "chr1", "BP", 100).unwrap().map(|r| r.counts).sum();
+        assert_eq!(total, 6.0);
+        let trans: Vec<ContactRecord> = reader.contacts("chr2", "chr1", "BP", 100).unwrap().collect();
+        assert_eq!(trans, vec![ContactRecord { bin_x: 4, bin_y: 7, counts: 2.0 }]);
+        assert!(reader.contacts("chr1", "chr1", "BP", 50).is_err());
+        assert_eq!(reader.file().version(), 8);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn query_decodes_pairs_by_name() {
+        let chroms = [("All", 1), ("chr1", 1000), ("chr2", 500)];
+        let bytes = TestHic::new(&chroms, 100).matrix(1, 1, &[(0, 2, 5), (3, 3, 1)]).matrix(1, 2, &[(7, 4, 2)]).build();
+        let mut hic = HicFile::from_reader(std::io::Cursor::new(bytes)).unwrap();
+
+        let rec = |x, y, c| ContactRecord { bin_x: x, bin_y: y, counts: c };
+        let mut intra = hic.query("1", "CHR1", "BP", 100).unwrap();
+        intra.sort_by_key(|r| (r.bin_x, r.bin_y));
+        assert_eq!(intra, vec![rec(0, 2, 5.0), rec(3, 3, 1.0)]);
+        assert_eq!(hic.query("chr1", "chr2", "BP", 100).unwrap(), vec![rec(7, 4, 2.0)]);
+        // Reversed request: axes follow the requested order
+        assert_eq!(hic.query("chr2", "chr1", "BP", 100).unwrap(), vec![rec(4, 7, 2.0)]);
+        // No matrix stored for chr2-chr2
+        assert!(hic.query("chr2", "chr2", "BP", 100).unwrap().is_empty());
+
+        assert!(hic.query("chr3", "chr1", "BP", 100).unwrap_err().to_string().contains("chr3"));
+        assert!(hic.query("chr1", "chr1", "BP", 50).is_err());
+    }
+
+    #[test]
+    fn in_memory_file_is_read_end_to_end() {
+        let chroms = [("All", 1), ("chr1", 1000)];
+        let bytes = TestHic::new(&chroms, 100).matrix(1, 1, &[(0, 2, 5), (3, 3, 1)]).build();
+        let mut hic = HicFile::from_reader(std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!((hic.version(), hic.genome_id()), (8, "test_genome"));
+        assert_eq!(hic.chromosomes().iter().map(|c| (c.name.as_str(), c.length)).collect::<Vec<_>>(), vec![("All", 1), ("chr1", 1000)]);
+        assert_eq!(hic.resolutions(), &[100]);
+
+        let mzd = hic.get_matrix_zoom_data(1, 1, "BP", 100).unwrap().unwrap();
+        assert_eq!((mzd.num_bins1, mzd.sum_counts, mzd.block_map.len()), (10, 6.0, 1));
+        let err = hic.get_matrix_zoom_data(1, 1, "BP", 50).unwrap_err();
+        assert!(err.to_string().contains("<reader>"), "{}", err);
+        let mut records = Vec::new();
+        hic.with_block(&mzd, 0, |block| {
+            records.extend(block.map(|r| (r.bin_x, r.bin_y, r.counts)));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(records, vec![(0, 2, 5.0), (3, 3, 1.0)]);
+
+        let mut reader = HicReader::from_reader(std::io::Cursor::new(TestHic::new(&chroms, 100).build())).unwrap();
+        assert_eq!(reader.contacts("chr1", "chr1", "BP", 100).unwrap().count(), 0);
+    }
+
+    /// Counts the bytes read through it.
+    #[derive(Debug)]
+    struct CountingReader<R> {
+        inner: R,
+        read: std::sync::Arc<AtomicUsize>,
+    }
+
+    impl<R: Read> Read for CountingReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.read.fetch_add(n, Ordering::Relaxed);
+            Ok(n)
+        }
+    }
+
+    impl<R: Seek> Seek for CountingReader<R> {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn master_index_and_matrix_records_are_read_once() {
+        let names: Vec<String> = (1..=1000).map(|i| format!("contig{}", i)).collect();
+        let chroms: Vec<(&str, i32)> = std::iter::once(("All", 1)).chain(names.iter().map(|n| (n.as_str(), 300))).collect();
+        let matrices: Vec<TestMatrix> = (1..=1000).map(|c| (c, c, &[(0, 1, 2)][..])).collect();
+        let bytes = TestHic::new(&chroms, 100).matrices(&matrices).build();
+        let len = bytes.len();
+        let read = std::sync::Arc::new(AtomicUsize::new(0));
+        let mut hic = HicFile::from_reader(CountingReader { inner: std::io::Cursor::new(bytes), read: read.clone() }).unwrap();
+
+        for c in 1..=1000 {
+            for _ in 0..3 {
+                let mzd = hic.get_matrix_zoom_data(c, c, "BP", 100).unwrap().unwrap();
+                assert_eq!((mzd.c1, mzd.sum_counts, mzd.block_map.len()), (c, 2.0, 1));
+            }
+        }
+        // Scanning the master index (~20 kB) on every lookup read ~60 MB here
+        assert!(read.load(Ordering::Relaxed) < 2 * len, "read {} bytes of a {} byte file", read.load(Ordering::Relaxed), len);
+
+        assert!(hic.get_matrix_zoom_data(2, 1, "BP", 100).unwrap().is_none());
+        let err = hic.get_matrix_zoom_data(1, 1, "BP", 50).unwrap_err();
+        assert_eq!(format!("{:#}", err), "No BP 50 matrix for contig1 vs contig1 in \"<reader>\": Resolution 50 at unit BP not found in matrix");
+    }
+
+    #[test]
+    fn reads_norm_vectors_from_footer() {
+        let chroms = [("All", 1), ("chr1", 300), ("chr2", 200)];
+        let vc1 = [1.0, 2.0, 0.5, 4.0];
+        let kr1 = [1.0, f64::NAN, 2.0, 1.0];
+        let bytes = TestHic::new(&chroms, 100).matrix(1, 1, &[(0, 2, 6)]).norm("VC", 1, &vc1).norm("KR", 1, &kr1).norm("VC", 2, &[3.0; 3]).build();
+        let path = write_temp("norms.hic", &bytes);
+        let mut hic = HicFile::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(hic.read_norm_vector(1, "BP", 100, "VC").unwrap().unwrap(), vc1);
+        assert_eq!(hic.read_norm_vector(2, "BP", 100, "VC").unwrap().unwrap(), vec![3.0; 3]);
+        assert!(hic.read_norm_vector(2, "BP", 100, "KR").unwrap().is_none());
+        assert!(hic.read_norm_vector(1, "BP", 50, "VC").unwrap().is_none());
+        assert_eq!(hic.normalization_types().unwrap(), vec!["KR", "VC"]);
+        assert_eq!(hic.normalizations_by_resolution().unwrap(), vec![("BP".to_string(), 100, vec!["KR".to_string(), "VC".to_string()])]);
+        assert_eq!(hic.genome_id(), "test_genome");
+        assert_eq!(hic.attributes()[0], ("software".to_string(), "hickit-test".to_string()));
+        assert!(hic.attributes()[1].1.contains("Unique: 8"));
+
+        let listed = |hic: &mut HicFile, format| {
+            let mut out = Vec::new();
+            write_listing(hic, format, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(listed(&mut hic, ListFormat::Tsv), "#resolutions\t100\nname\tindex\tlength\nchr1\t1\t300\nchr2\t2\t200\n");
+        let json: serde_json::Value = serde_json::from_str(&listed(&mut hic, ListFormat::Json)).unwrap();
+        assert_eq!(json["genome_id"], "test_genome");
+        assert_eq!(json["version"], 8);
+        assert_eq!(json["chromosomes"][1], serde_json::json!({"name": "chr2", "index": 2, "length": 200}));
+        assert_eq!(json["resolutions"], serde_json::json!([100]));
+        assert!(listed(&mut hic, ListFormat::Text).contains("#     Unique: 8\n"));
+
+        let meta = serde_json::to_value(HicMetadata::from(&hic)).unwrap();
+        assert_eq!(meta["genomeId"], "test_genome");
+        assert_eq!(meta["attributes"]["software"], "hickit-test");
+        assert_eq!(meta["chromosomes"][0]["name"], "All");
+        assert_eq!(meta["bpResolutions"], serde_json::json!([100]));
+        assert_eq!(meta["fragResolutions"], serde_json::json!([]));
+        assert!(meta["nviPosition"].is_null());
+        let err = hic.require_norm_vector(2, "BP", 100, "KR").unwrap_err().to_string();
+        assert!(err.contains("no KR") && err.contains("chr2") && err.ends_with("available there: VC"), "{}", err);
+
+        let kr = hic.read_norm_vector(1, "BP", 100, "KR").unwrap().unwrap();
+        assert_eq!(normalized_counts(6.0, 0, 2, &vc1, &vc1), Some(12.0));
+        assert_eq!(normalized_counts(6.0, 0, 2, &kr, &kr), Some(3.0));
+        // NaN factor, out-of-range bin and zero factor are all skipped
+        assert_eq!(normalized_counts(6.0, 1, 2, &kr, &kr), None);
+        assert_eq!(normalized_counts(6.0, 0, 9, &vc1, &vc1), None);
+        assert_eq!(normalized_counts(6.0, 0, 1, &vc1, &[0.0, 0.0]), None);
+    }
+
+    /// Decode a .slc.gz written by the dump: key table and records.
+    fn read_slice_file(path: &Path) -> (Vec<(String, i16)>, Vec<SliceRecord>) {
+        let slice = read_slice(path).unwrap();
+        (slice.keys, slice.records.map(|r| r.unwrap()).collect())
+    }
+
+    #[test]
+    fn slice_keys_follow_the_chosen_order() {
+        let chroms = [("All", 1), ("chr2", 500), ("chr10", 800), ("chr1", 1000)];
+        let matrices: [TestMatrix; 2] = [(1, 3, &[(2, 7, 4)]), (2, 2, &[(0, 1, 3)])];
+        let input = write_temp("key_order.hic", &TestHic::new(&chroms, 100).matrices(&matrices).build());
+        let out = write_temp("key_order.slc.gz", &[]);
+        let text = write_temp("key_order.txt", &[]);
+        for (order, names) in [
+            (KeyOrder::Index, ["chr2", "chr10", "chr1"]),
+            (KeyOrder::Name, ["chr1", "chr10", "chr2"]),
+            (KeyOrder::Natural, ["chr1", "chr2", "chr10"]),
+        ] {
+            let opts = DumpOptions { key_order: order, progress: false, ..Default::default() };
+            dump_hic_genome_wide(&input, 100, &out, &opts).unwrap();
+            let (keys, _) = read_slice_file(&out);
+            assert_eq!(keys, names.iter().enumerate().map(|(k, n)| (n.to_string(), k as i16)).collect::<Vec<_>>(), "{:?}", order);
+            // Records still name their own chromosomes whatever the numbering
+            decode_slice(&out, Some(&text), false).unwrap();
+            assert_eq!(std::fs::read_to_string(&text).unwrap(), "chr2\t2\tchr1\t7\t4\nchr10\t0\tchr10\t1\t3\n");
+        }
+        for p in [&input, &out, &text] {
+            std::fs::remove_file(p).unwrap();
+        }
+
+        assert_eq!(natural_cmp("chr2", "chr10"), std::cmp::Ordering::Less);
+        assert_eq!(natural_cmp("chrX", "chr10"), std::cmp::Ordering::Greater);
+        assert_eq!(natural_cmp("scaffold_02", "scaffold_2"), std::cmp::Ordering::Less);
+        assert_eq!(natural_cmp("chr1", "chr1_random"), std::cmp::Ordering::Less);
+    }
+
+    #[test]
+    fn decodes_slice_to_text_and_reports_truncation() {
+        let keys = vec![("chr1".to_string(), 0i16), ("chr2".to_string(), 1)];
+        let mut raw = Vec::new();
+        slice::write_header(&mut raw, 100, &keys).unwrap();
+        for rec in [SliceRecord { key1: 0, bin_x: 2, key2: 1, bin_y: 5, counts: 3.5 }, SliceRecord { key1: 1, bin_x: 0, key2: 1, bin_y: 1, counts: 1.0 }] {
+            rec.write_to(&mut raw).unwrap();
+        }
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(&raw).unwrap();
+        let input = write_temp("decode.slc.gz", &gz.finish().unwrap());
+        let out = write_temp("decode.tsv", &[]);
+
+        decode_slice(&input, Some(&out), false).unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "chr1\t2\tchr2\t5\t3.5\nchr2\t0\tchr2\t1\t1\n");
+        decode_slice(&input, Some(&out), true).unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "chr1\t200\tchr2\t500\t3.5\nchr2\t0\tchr2\t100\t1\n");
+
+        // Plain (uncompressed) slices are read too; a cut-off record is an error
+        std::fs::write(&input, &raw[..raw.len() - 3]).unwrap();
+        let err = format!("{:#}", decode_slice(&input, Some(&out), false).unwrap_err());
+        assert!(err.contains("Read record 2") && err.contains("truncated"), "{}", err);
+        std::fs::write(&input, b"NOTASLICE").unwrap();
+        let err = format!("{:#}", decode_slice(&input, Some(&out), false).unwrap_err());
+        assert!(err.contains("HICSLICE"), "{}", err);
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&out).unwrap();
+    }
+
+    #[test]
+    fn read_slice_round_trips_a_dump() {
+        let chroms = [("All", 1), ("chr1", 1000), ("chr2", 450)];
+        let matrices: [TestMatrix; 2] = [(1, 1, &[(0, 2, 5), (3, 3, 2)]), (1, 2, &[(7, 4, 2)])];
+        let input = write_temp("read_slice.hic", &TestHic::new(&chroms, 100).matrices(&matrices).build());
+        let out = write_temp("read_slice.slc.gz", &[]);
+        dump_hic_genome_wide(&input, 100, &out, &DumpOptions { sort: SortMode::Global, ..Default::default() }).unwrap();
+
+        let mut slice = read_slice(&out).unwrap();
+        assert_eq!(slice.binsize, 100);
+        assert_eq!(slice.names, HashMap::from([(0, "chr1".to_string()), (1, "chr2".to_string())]));
+        let mut text = Vec::new();
+        for rec in &mut slice.records {
+            let rec = rec.unwrap();
+            text.push(format!("{}\t{}\t{}\t{}\t{}", slice.names[&rec.key1], rec.bin_x, slice.names[&rec.key2], rec.bin_y, rec.counts));
+        }
+        assert_eq!(text, vec!["chr1\t0\tchr1\t2\t5", "chr1\t3\tchr1\t3\t2", "chr1\t7\tchr2\t4\t2"]);
+        assert_eq!(slice.records.count_read(), 3);
+
+        // The iterator stops after the first bad record
+        let mut raw = Vec::new();
+        MultiGzDecoder::new(File::open(&out).unwrap()).read_to_end(&mut raw).unwrap();
+        raw.truncate(raw.len() - 3);
+        std::fs::write(&out, &raw).unwrap();
+        let results: Vec<_> = read_slice(&out).unwrap().records.collect();
+        assert_eq!(results.len(), 3);
+        assert!(format!("{:#}", results[2].as_ref().unwrap_err()).contains("Read record 3"));
+        std::fs::remove_file(&out).unwrap();
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn slice_to_bedpe_clamps_the_last_bin_to_the_chromosome_sizes() {
+        let keys = vec![("chr1".to_string(), 0i16), ("chr2".to_string(), 1)];
+        let mut raw = Vec::new();
+        slice::write_header(&mut raw, 100, &keys).unwrap();
+        SliceRecord { key1: 0, bin_x: 2, key2: 0, bin_y: 9, counts: 3.0 }.write_to(&mut raw).unwrap();
+        SliceRecord { key1: 0, bin_x: 0, key2: 1, bin_y: 4, counts: 1.5 }.write_to(&mut raw).unwrap();
+        let input = write_temp("to_bedpe.slc", &raw);
+        let out = write_temp("to_bedpe.bedpe", &[]);
+
+        let sizes = vec![("chr1".to_string(), 950), ("chr2".to_string(), 420), ("chrX".to_string(), 10)];
+        assert_eq!(slice_to_bedpe(&input, Some(&out), Some(&sizes)).unwrap(), 2);
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "chr1\t200\t300\tchr1\t900\t950\t3\nchr1\t0\t100\tchr2\t400\t420\t1.5\n"
+        );
+        // Without sizes every bin spans the full bin size
+        slice_to_bedpe(&input, Some(&out), None).unwrap();
+        assert!(std::fs::read_to_string(&out).unwrap().starts_with("chr1\t200\t300\tchr1\t900\t1000\t3\n"));
+        let err = slice_to_bedpe(&input, Some(&out), Some(&sizes[..1])).unwrap_err().to_string();
+        assert!(err.contains("'chr2'"), "{}", err);
+        std::fs::remove_file(&out).unwrap();
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn slice_stats_tally_records_and_flag_corruption() {
+        let keys = vec![("chr1".to_string(), 0i16), ("chr2".to_string(), 1), ("chr3".to_string(), 2)];
+        let mut raw = Vec::new();
+        slice::write_header(&mut raw, 100, &keys).unwrap();
+        for (key1, key2, counts) in [(0, 0, 2.0), (0, 1, 1.5), (1, 1, 4.0), (1, 1, 0.5)] {
+            SliceRecord { key1, bin_x: 0, key2, bin_y: 1, counts }.write_to(&mut raw).unwrap();
+        }
+        let input = write_temp("stats.slc", &raw);
+        let stats = slice_stats(&input).unwrap();
+        assert_eq!((stats.binsize, stats.records, stats.intra, stats.inter), (100, 4, 3, 1));
+        assert_eq!(stats.total_counts, 8.0);
+        let per_chrom: Vec<(&str, u64)> = stats.per_chrom.iter().map(|(n, c)| (n.as_str(), *c)).collect();
+        assert_eq!(per_chrom, vec![("chr1", 2), ("chr2", 3), ("chr3", 0)]);
+
+        // Unknown key, trailing partial record
+        let mut bad = raw.clone();
+        SliceRecord { key1: 0, bin_x: 0, key2: 7, bin_y: 0, counts: 1.0 }.write_to(&mut bad).unwrap();
+        std::fs::write(&input, &bad).unwrap();
+        let err = format!("{:#}", slice_stats(&input).unwrap_err());
+        assert!(err.contains("Record 5 uses key 7"), "{}", err);
+        std::fs::write(&input, &raw[..raw.len() - 5]).unwrap();
+        let err = format!("{:#}", slice_stats(&input).unwrap_err());
+        assert!(err.contains("Read record 4") && err.contains("truncated"), "{}", err);
+
+        // Corrupt headers are reported, not allocated: a huge key count, a huge
+        // or negative name length, and a name cut short
+        let header = |nkeys: i32, name_len: i32, name: &[u8]| {
+            let mut h = slice::HICSLICE_MAGIC.to_vec();
+            h.extend(100i32.to_le_bytes());
+            h.extend(nkeys.to_le_bytes());
+            h.extend(name_len.to_le_bytes());
+            h.extend(name);
+            h
+        };
+        for (bytes, expected) in [
+            (header(i32::MAX, 4, b"chr1"), "bad key count 2147483647"),
+            (header(-1, 4, b"chr1"), "bad key count -1"),
+            (header(1, i32::MAX, b"chr1"), "bad name length 2147483647"),
+            (header(1, -3, b""), "bad name length -3"),
+            (header(1, 10, b"chr1"), "truncated chromosome name (4 of 10 bytes)"),
+        ] {
+            std::fs::write(&input, &bytes).unwrap();
+            let err = format!("{:#}", slice_stats(&input).unwrap_err());
+            assert!(err.contains("Read slice header") && err.contains(expected), "{}", err);
+        }
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn dump_applies_vc_and_vc_sqrt_vectors() {
+        let chroms = [("All", 1), ("chr1", 300), ("chr2", 200)];
+        let vc1 = [1.0, 2.0, 4.0];
+        let sqrt1 = [1.0, 1.5, 2.0];
+        let bytes = TestHic::new(&chroms, 100)
+            .matrix(1, 1, &[(0, 2, 8), (1, 1, 3)])
+            .matrix(1, 2, &[(1, 0, 6)])
+            .norm("VC", 1, &vc1)
+            .norm("VC", 2, &[3.0, 0.0])
+            .norm("VC_SQRT", 1, &sqrt1)
+            .norm("VC_SQRT", 2, &[2.0, 2.0])
+            .build();
+        let input = write_temp("dump_norm.hic", &bytes);
+        let dump = |norm: &str| {
+            let out = write_temp(&format!("dump_norm_{}.slc.gz", norm), &[]);
+            let opts = DumpOptions { norm: norm.to_string(), sort: SortMode::Global, ..Default::default() };
+            dump_hic_genome_wide(&input, 100, &out, &opts).unwrap();
+            let (keys, records) = read_slice_file(&out);
+            std::fs::remove_file(&out).unwrap();
+            assert_eq!(keys, vec![("chr1".to_string(), 0), ("chr2".to_string(), 1)]);
+            records.iter().map(|r| (r.key2, r.bin_x, r.bin_y, r.counts)).collect::<Vec<_>>()
+        };
+
+        assert_eq!(dump("NONE"), vec![(0, 0, 2, 8.0), (0, 1, 1, 3.0), (1, 1, 0, 6.0)]);
+        assert_eq!(dump("VC"), vec![(0, 0, 2, 2.0), (0, 1, 1, 0.75), (1, 1, 0, 1.0)]);
+        assert_eq!(dump("VC_SQRT"), vec![(0, 0, 2, 4.0), (0, 1, 1, 3.0 / 2.25), (1, 1, 0, 2.0)]);
+        let err = dump_hic_genome_wide(&input, 100, Path::new("unused.slc.gz"), &DumpOptions { norm: "KR".into(), ..Default::default() });
+        assert!(err.unwrap_err().to_string().contains("available: VC, VC_SQRT"));
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn kr_dump_skips_nan_bins_of_either_chromosome() {
+        let chroms = [("All", 1), ("chr1", 300), ("chr2", 200)];
+        let kr1 = [1.0, f64::NAN, 2.0];
+        // chr1 bin 1 was dropped by the balancing; the inter record on it goes too
+        let matrices: [TestMatrix; 2] = [(1, 1, &[(0, 2, 8), (1, 1, 3)]), (1, 2, &[(1, 0, 6), (2, 1, 4)])];
+        let bytes = TestHic::new(&chroms, 100).matrices(&matrices).norm("KR", 1, &kr1).norm("KR", 2, &[2.0, 0.5]).build();
+        let input = write_temp("dump_kr.hic", &bytes);
+        let out = write_temp("dump_kr.slc.gz", &[]);
+        dump_hic_genome_wide(&input, 100, &out, &DumpOptions { norm: "KR".into(), ..Default::default() }).unwrap();
+        let records: Vec<(i16, i32, i16, i32, f32)> =
+            read_slice_file(&out).1.iter().map(|r| (r.key1, r.bin_x, r.key2, r.bin_y, r.counts)).collect();
+        assert_eq!(records, vec![(0, 0, 0, 2, 4.0), (0, 2, 1, 1, 4.0)]);
+
+        // KR stored for chr1 only: the dump fails on chr2 and names what is there
+        let partial = TestHic::new(&chroms, 100).matrices(&matrices).norm("KR", 1, &kr1).norm("VC", 2, &[1.0, 1.0]).build();
+        let input2 = write_temp("dump_kr_partial.hic", &partial);
+        let err = dump_hic_genome_wide(&input2, 100, &out, &DumpOptions { norm: "KR".into(), ..Default::default() }).unwrap_err().to_string();
+        assert!(err.contains("no KR normalization vector for chr2") && err.ends_with("available there: VC"), "{}", err);
+        for p in [&input, &input2, &out] {
+            std::fs::remove_file(p).unwrap();
+        }
+    }
+
+    #[test]
+    fn genome_wide_dump_does_not_depend_on_the_thread_count() {
+        let chroms = [("All", 1), ("chr1", 400), ("chr2", 300), ("chr3", 200)];
+        let matrices: [TestMatrix; 5] = [
+            (1, 1, &[(0, 0, 5), (0, 3, 2), (2, 2, 7)]),
+            (1, 2, &[(3, 0, 1), (1, 2, 4)]),
+            (1, 3, &[(0, 1, 3)]),
+            (2, 2, &[(0, 1, 6), (2, 2, 1)]),
+            (3, 3, &[(1, 1, 9)]),
+        ];
+        let input = write_temp("dump_threads.hic", &TestHic::new(&chroms, 100).matrices(&matrices).build());
+        let dump = |threads: usize, name: &str| {
+            let out = write_temp(name, &[]);
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            pool.install(|| dump_hic_genome_wide(&input, 100, &out, &DumpOptions::default())).unwrap();
+            let bytes = std::fs::read(&out).unwrap();
+            std::fs::remove_file(&out).unwrap();
+            bytes
+        };
+        let sequential = dump(1, "dump_threads1.slc.gz");
+        assert_eq!(dump(4, "dump_threads4.slc.gz"), sequential);
+        assert_eq!(dump(8, "dump_threads8.slc.gz"), sequential);
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn expected_decay_is_written_by_distance_in_bp() {
+        let chroms = [("All", 1), ("chr1", 250), ("chr2", 500)];
+        let bytes = TestHic::new(&chroms, 100)
+            .matrix(1, 1, &[(0, 0, 1)])
+            .expected("NONE", &[4.0, 2.0, 1.0, 0.5], &[(1, 2.0)])
+            .expected("KR", &[1.0, 0.5], &[])
+            .build();
+        let input = write_temp("expected_decay.hic", &bytes);
+        let out = write_temp("expected_decay.tsv", &[]);
+        assert_eq!(dump_expected_decay(&input, "NONE", 100, None, Some(&out)).unwrap(), 4);
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "distance_bp\texpected\n0\t4\n100\t2\n200\t1\n300\t0.5\n");
+        // chr1 is scaled by its factor and spans 3 bins; chr2 has no factor
+        dump_expected_decay(&input, "NONE", 100, Some("1"), Some(&out)).unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "distance_bp\texpected\n0\t2\n100\t1\n200\t0.5\n");
+        assert_eq!(dump_expected_decay(&input, "NONE", 100, Some("chr2"), Some(&out)).unwrap(), 4);
+        assert_eq!(dump_expected_decay(&input, "KR", 100, None, Some(&out)).unwrap(), 2);
+
+        let err = dump_expected_decay(&input, "VC", 100, None, Some(&out)).unwrap_err().to_string();
+        assert!(err.ends_with("available there: KR, NONE"), "{}", err);
+        assert!(dump_expected_decay(&input, "NONE", 100, Some("chrX"), Some(&out)).is_err());
+        for p in [&input, &out] {
+            std::fs::remove_file(p).unwrap();
+        }
+    }
+
+    #[test]
+    fn chromosome_totals_come_from_the_matrix_sums() {
+        let chroms = [("All", 1), ("chr1", 2_000_000), ("chr2", 500_000), ("chrUn", 1000)];
+        let matrices: [TestMatrix; 3] = [(1, 1, &[(0, 2, 5), (3, 3, 2)]), (1, 2, &[(7, 4, 3)]), (2, 2, &[(0, 0, 4)])];
+        let input = write_temp("totals.hic", &TestHic::new(&chroms, 100).matrices(&matrices).build());
+        let (res, totals) = hic_chrom_totals(&input, None, false).unwrap();
+        assert_eq!(res, 100);
+        let rows: Vec<(&str, f64, Option<f64>)> = totals.iter().map(|t| (t.name.as_str(), t.intra, t.trans)).collect();
+        assert_eq!(rows, vec![("chr1", 7.0, None), ("chr2", 4.0, None), ("chrUn", 0.0, None)]);
+        assert_eq!(totals[0].per_mb(), 3.5);
+
+        // The chr1-chr2 matrix counts for both
+        let (_, totals) = hic_chrom_totals(&input, Some(100), true).unwrap();
+        assert_eq!(totals.iter().map(|t| t.trans).collect::<Vec<_>>(), vec![Some(3.0), Some(3.0), Some(0.0)]);
+        assert_eq!(totals[1].per_mb(), 14.0);
+        let out = write_temp("totals.tsv", &[]);
+        write_chrom_totals(&totals, Some(&out)).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "chrom\tlength\tintra\ttrans\tper_mb\nchr1\t2000000\t7\t3\t5.0\nchr2\t500000\t4\t3\t14.0\nchrUn\t1000\t0\t0\t0.0\n"
+        );
+        assert!(hic_chrom_totals(&input, Some(5000), false).is_err());
+        for p in [&input, &out] {
+            std::fs::remove_file(p).unwrap();
+        }
+    }
+
+    #[test]
+    fn norm_vectors_are_written_as_bedgraph() {
+        let chroms = [("All", 1), ("chr1", 250), ("chr2", 200)];
+        // chr1 stores one factor past its last bin; chr2 has only VC
+        let norms: [TestNorm; 2] = [("KR", 1, &[1.5, f64::NAN, 0.5, 9.0]), ("VC", 2, &[1.0, 1.0])];
+        let input = write_temp("normvec.hic", &TestHic::new(&chroms, 100).matrix(1, 1, &[(0, 0, 1)]).norms(&norms).build());
+        let out = write_temp("normvec.bedGraph", &[]);
+        assert_eq!(dump_norm_vectors(&input, "KR", 100, None, Some(&out), false).unwrap(), (3, 1));
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "chr1\t0\t100\t1.5\nchr1\t100\t200\tnan\nchr1\t200\t250\t0.5\n");
+        assert_eq!(dump_norm_vectors(&input, "KR", 100, None, Some(&out), true).unwrap(), (2, 1));
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "chr1\t0\t100\t1.5\nchr1\t200\t250\t0.5\n");
+
+        let err = dump_norm_vectors(&input, "KR", 100, Some(&["chr2".to_string()]), Some(&out), false).unwrap_err().to_string();
+        assert!(err.contains("no KR normalization vector for chr2"), "{}", err);
+        let err = dump_norm_vectors(&input, "SCALE", 100, None, Some(&out), false).unwrap_err().to_string();
+        assert!(err.ends_with("available there: KR, VC"), "{}", err);
+        for p in [&input, &out] {
+            std::fs::remove_file(p).unwrap();
+        }
+    }
+
+    #[test]
+    fn dump_oe_divides_by_expected_and_matrix_mean() {
+        let chroms = [("All", 1), ("chr1", 500), ("chr2", 200)];
+        let bytes = TestHic::new(&chroms, 100)
+            // chr1: distances 0, 2, 1 and 4 (beyond the vector); chr2: distance 1 hits a zero
+            .matrix(1, 1, &[(0, 0, 8), (1, 3, 6), (3, 2, 3), (0, 4, 5)])
+            .matrix(2, 2, &[(0, 1, 4)])
+            .matrix(1, 2, &[(1, 0, 6), (4, 1, 2)])
+            .norm("VC", 1, &[1.0, 2.0, 1.0, 1.0, 1.0])
+            .expected("NONE", &[4.0, 0.0, 3.0, 2.0], &[(1, 2.0)])
+            .expected("VC", &[2.0, 1.0, 1.0, 1.0], &[])
+            .build();
+        let input = write_temp("dump_oe.hic", &bytes);
+        let mut hic = HicFile::open(&input).unwrap();
+        let exp = hic.expected_values("NONE", "BP", 100).unwrap().unwrap();
+        assert_eq!(exp.at(1, 2), Some(1.5));
+        assert_eq!(exp.at(2, 2), Some(3.0)); // no factor stored for chr2
+        assert_eq!(exp.at(1, 4), None);
+        assert!(hic.expected_values("KR", "BP", 100).unwrap().is_none());
+        assert_eq!(hic.read_expected_vector(1, "BP", 100, "NONE").unwrap().unwrap(), vec![2.0, 0.0, 1.5, 1.0]);
+        assert_eq!(hic.read_expected_vector(2, "BP", 100, "VC").unwrap().unwrap(), vec![2.0, 1.0, 1.0, 1.0]);
+        assert!(hic.read_expected_vector(1, "BP", 100, "KR").unwrap().is_none());
+
+        let dump = |norm: &str| {
+            let out = write_temp(&format!("dump_oe_{}.slc.gz", norm), &[]);
+            let opts = DumpOptions { matrix: MatrixType::Oe, norm: norm.to_string(), sort: SortMode::Global, ..Default::default() };
+            dump_hic_genome_wide(&input, 100, &out, &opts).unwrap();
+            let (_, records) = read_slice_file(&out);
+            std::fs::remove_file(&out).unwrap();
+            records.iter().map(|r| (r.key1, r.key2, r.bin_x, r.bin_y, r.counts)).collect::<Vec<_>>()
+        };
+
+        // chr1 expected = [2, 0, 1.5, 1] after its factor; inter mean = 8 / (5 * 2)
+        assert_eq!(
+            dump("NONE"),
+            vec![(0, 0, 0, 0, 4.0), (0, 0, 1, 3, 4.0), (0, 1, 1, 0, 7.5), (0, 1, 4, 1, 2.5)]
+        );
+        // No KR vectors at all; VC counts are divided by the VC expected vector
+        let err = dump_hic_genome_wide(&input, 100, Path::new("unused.slc.gz"), &DumpOptions { matrix: MatrixType::Oe, norm: "KR".into(), ..Default::default() });
+        assert!(err.unwrap_err().to_string().contains("available: VC"));
+        let opts = DumpOptions { matrix: MatrixType::Oe, norm: "VC".into(), chroms: Some(vec!["chr1".into()]), ..Default::default() };
+        let out = write_temp("dump_oe_vc_chr1.slc.gz", &[]);
+        dump_hic_genome_wide(&input, 100, &out, &opts).unwrap();
+        let (_, records) = read_slice_file(&out);
+        let mut got: Vec<_> = records.iter().map(|r| (r.bin_x, r.bin_y, r.counts)).collect();
+        got.sort_by_key(|r| (r.0, r.1));
+        assert_eq!(got, vec![(0, 0, 4.0), (1, 3, 3.0), (3, 2, 3.0)]);
+        std::fs::remove_file(&out).unwrap();
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn dump_expected_writes_scaled_decay_per_chromosome() {
+        let chroms = [("All", 1), ("chr1", 250), ("chr2", 500)];
+        let bytes = TestHic::new(&chroms, 100).expected("NONE", &[6.0, 3.0, 2.0, 1.0], &[(1, 2.0)]).build();
+        let input = write_temp("dump_expected.hic", &bytes);
+        let out = write_temp("dump_expected.tsv", &[]);
+        dump_expected(&input, 100, &out, &DumpOptions::default()).unwrap();
+        let text = std::fs::read_to_string(&out).unwrap();
+        let err = dump_expected(&input, 100, &out, &DumpOptions { norm: "KR".into(), ..Default::default() });
+        std::fs::remove_file(&out).unwrap();
+        std::fs::remove_file(&input).unwrap();
+
+        // chr1 spans 3 bins and is scaled by 2; chr2 stops at the end of the vector
+        assert_eq!(
+            text.lines().collect::<Vec<_>>(),
+            vec![
+                "chrom\tdistance_bins\texpected",
+                "chr1\t0\t3", "chr1\t1\t1.5", "chr1\t2\t1",
+                "chr2\t0\t6", "chr2\t1\t3", "chr2\t2\t2", "chr2\t3\t1",
+            ]
+        );
+        assert!(err.unwrap_err().to_string().contains("available there: NONE"));
+    }
+
+    #[test]
+    fn dump_region_keeps_records_inside_both_bin_ranges() {
+        let chroms = [("All", 1), ("chr1", 1000), ("chr2", 500)];
+        let bytes = TestHic::new(&chroms, 100)
+            .matrix(1, 1, &[(1, 4, 5), (4, 1, 7), (2, 2, 1), (5, 6, 2)])
+            .matrix(1, 2, &[(3, 0, 4), (3, 4, 6), (8, 1, 3)])
+            .build();
+        let input = write_temp("dump_region.hic", &bytes);
+        let dump = |r1: &str, r2: &str| {
+            let out = write_temp("dump_region.slc.gz", &[]);
+            let opts = DumpOptions { sort: SortMode::PerPair, ..Default::default() };
+            dump_hic_region(&input, &Region::parse(r1, None).unwrap(), &Region::parse(r2, None).unwrap(), 100, &out, &opts).unwrap();
+            let (keys, records) = read_slice_file(&out);
+            std::fs::remove_file(&out).unwrap();
+            assert_eq!(keys.len(), 2);
+            records.iter().map(|r| (r.key1, r.bin_x, r.key2, r.bin_y, r.counts)).collect::<Vec<_>>()
+        };
+
+        // chr1 bins 1-2 vs bins 4-5, matched in either orientation
+        assert_eq!(dump("chr1:100-299", "chr1:400-599"), vec![(0, 1, 0, 4, 5.0), (0, 4, 0, 1, 7.0)]);
+        // Reversed inter request: ranges follow the stored chr1 x chr2 axes
+        assert_eq!(dump("2:0-199", "chr1:300-399"), vec![(0, 3, 1, 0, 4.0)]);
+        assert!(dump("chr2:0-499", "chr2:0-499").is_empty());
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn virtual_4c_collects_contacts_of_the_viewpoint_bin() {
+        let chroms = [("All", 1), ("chr1", 1000), ("chr2", 500)];
+        let bytes = TestHic::new(&chroms, 100)
+            .matrix(1, 1, &[(1, 4, 5), (4, 1, 7), (2, 2, 1), (5, 6, 2), (1, 1, 3)])
+            .matrix(1, 2, &[(1, 0, 4), (3, 4, 6), (1, 3, 2), (8, 1, 3)])
+            .matrix(2, 2, &[(0, 1, 9)])
+            .build();
+        let input = write_temp("virtual_4c.hic", &bytes);
+        let profile = |viewpoint: &str| {
+            let out = write_temp("virtual_4c.tsv", &[]);
+            dump_virtual_4c(&input, viewpoint, 100, Some(&out), &DumpOptions::default()).unwrap();
+            let text = std::fs::read_to_string(&out).unwrap();
+            std::fs::remove_file(&out).unwrap();
+            text
+        };
+
+        // Both orientations of the intra pair are summed; chr2 partners come from the x anchor
+        assert_eq!(profile("chr1:150"), "chr1\t100\t3\nchr1\t400\t12\nchr2\t0\t4\nchr2\t300\t2\n");
+        // A viewpoint on the second stored chromosome matches on the y axis
+        assert_eq!(profile("2:1,99"), "chr1\t800\t3\nchr2\t0\t9\n");
+        assert!(dump_virtual_4c(&input, "chr1:2000", 100, None, &DumpOptions::default()).is_err());
+        assert!(dump_virtual_4c(&input, "chr1", 100, None, &DumpOptions::default()).is_err());
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn slice_containers_hold_the_same_header_and_records() {
+        let chroms = [("All", 1), ("chr1", 1000), ("chr2", 450)];
+        let matrices: [TestMatrix; 2] = [(1, 1, &[(0, 2, 5), (3, 3, 2)]), (1, 2, &[(7, 4, 2)])];
+        let input = write_temp("containers.hic", &TestHic::new(&chroms, 100).matrices(&matrices).build());
+        let dump = |compression| {
+            let out = write_temp("containers.slc", &[]);
+            dump_hic_genome_wide(&input, 100, &out, &DumpOptions { compression, ..Default::default() }).unwrap();
+            let bytes = std::fs::read(&out).unwrap();
+            let slice = read_slice_file(&out);
+            std::fs::remove_file(&out).unwrap();
+            (bytes, slice)
+        };
+        let (gzip, expected) = dump(SliceCompression::Gzip);
+        let (plain, from_plain) = dump(SliceCompression::None);
+        let (bgzf, from_bgzf) = dump(SliceCompression::Bgzf);
+        std::fs::remove_file(&input).unwrap();
+
+        assert!(gzip.starts_with(&[0x1f, 0x8b]));
+        assert!(plain.starts_with(slice::HICSLICE_MAGIC));
+        assert_eq!(&bgzf[12..14], b"BC");
+        assert!(bgzf.ends_with(&crate::bgzf::EOF_BLOCK));
+        assert_eq!(expected.1.len(), 3);
+        assert_eq!(from_plain, expected);
+        assert_eq!(from_bgzf, expected);
+        let prefix = Path::new("out");
+        let opts = DumpOptions { compression: SliceCompression::None, ..Default::default() };
+        assert_eq!(multi_resolution_path(prefix, 100, &opts), Path::new("out.100.slc"));
+        // A full output name has the bin size inserted before its extension
+        assert_eq!(multi_resolution_path(Path::new("dir/out.slc.gz"), 100, &opts), Path::new("dir/out.100.slc"));
+        let short = DumpOptions { format: DumpFormat::Short, ..Default::default() };
+        assert_eq!(multi_resolution_path(Path::new("out.short.gz"), 5000, &short), Path::new("out.5000.short.gz"));
+        assert_eq!(multi_resolution_path(Path::new("out.hic"), 5000, &short), Path::new("out.hic.5000.short.gz"));
+    }
+
+    #[test]
+    fn multi_resolution_dump_sums_coarser_bins_from_the_finest() {
+        let chroms = [("All", 1), ("chr1", 1000), ("chr2", 450)];
+        let matrices: [TestMatrix; 2] = [(1, 1, &[(0, 2, 5), (1, 3, 1), (3, 3, 2)]), (1, 2, &[(7, 4, 2), (6, 5, 3)])];
+        let input = write_temp("multires.hic", &TestHic::new(&chroms, 100).matrices(&matrices).build());
+        let prefix = std::env::temp_dir().join(format!("hickit-test-{}-multires", std::process::id()));
+        let opts = DumpOptions { sort: SortMode::PerPair, ..Default::default() };
+        let paths = dump_hic_multi_resolution(&input, &[200, 100, 200], &prefix, &opts).unwrap();
+        assert_eq!(paths, vec![multi_resolution_path(&prefix, 100, &opts), multi_resolution_path(&prefix, 200, &opts)]);
+        assert!(paths[1].to_string_lossy().ends_with("-multires.200.slc.gz"));
+
+        // The stored resolution matches a single dump; 200 is not stored and is summed
+        let single = write_temp("multires-single.slc.gz", &[]);
+        dump_hic_genome_wide(&input, 100, &single, &opts).unwrap();
+        assert_eq!(read_slice_file(&paths[0]), read_slice_file(&single));
+        assert_eq!(open_slice(&paths[1]).unwrap().0.binsize, 200);
+        let recs: Vec<(i16, i32, i16, i32, f32)> =
+            read_slice_file(&paths[1]).1.iter().map(|r| (r.key1, r.bin_x, r.key2, r.bin_y, r.counts)).collect();
+        assert_eq!(recs, vec![(0, 0, 0, 1, 6.0), (0, 1, 0, 1, 2.0), (0, 3, 1, 2, 5.0)]);
+        for p in paths.iter().chain([&single]) {
+            std::fs::remove_file(p).unwrap();
+        }
+
+        // Normalized values cannot be summed, so an unstored resolution is an error
+        let vc = DumpOptions { norm: "VC".into(), ..opts };
+        let err = dump_hic_multi_resolution(&input, &[100, 200], &prefix, &vc).unwrap_err().to_string();
+        assert!(err.contains("200"), "{}", err);
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn dump_all_resolutions_writes_each_stored_resolution() {
+        let chroms = [("All", 1), ("chr1", 1000), ("chr2", 450)];
+        let matrices: [TestMatrix; 2] = [(1, 1, &[(0, 2, 5), (3, 3, 2)]), (1, 2, &[(7, 4, 2)])];
+        let input = write_temp("allres.hic", &TestHic::new(&chroms, 100).matrices(&matrices).build());
+        let prefix = std::env::temp_dir().join(format!("hickit-test-{}-allres.slc.gz", std::process::id()));
+        let opts = DumpOptions { sort: SortMode::PerPair, ..Default::default() };
+        let paths = dump_hic_all_resolutions(&input, &prefix, &opts).unwrap();
+        assert_eq!(paths, vec![multi_resolution_path(&prefix, 100, &opts)]);
+        assert!(paths[0].to_string_lossy().ends_with("-allres.100.slc.gz"));
+
+        let single = write_temp("allres-single.slc.gz", &[]);
+        dump_hic_genome_wide(&input, 100, &single, &opts).unwrap();
+        assert_eq!(read_slice_file(&paths[0]), read_slice_file(&single));
+        // No fragment resolutions are stored
+        let frag = DumpOptions { unit: "FRAG".into(), ..opts };
+        assert!(dump_hic_all_resolutions(&input, &prefix, &frag).is_err());
+        for p in paths.iter().chain([&single, &input]) {
+            std::fs::remove_file(p).unwrap();
+        }
+    }
+
+    #[test]
+    fn dump_filters_by_min_count_and_contact_scope() {
+        let chroms = [("All", 1), ("chr1", 1000), ("chr2", 450)];
+        let matrices: [TestMatrix; 3] = [(1, 1, &[(0, 2, 5), (1, 3, 1), (3, 3, 2)]), (1, 2, &[(7, 4, 2), (6, 5, 3)]), (2, 2, &[(0, 0, 4)])];
+        let input = write_temp("filters.hic", &TestHic::new(&chroms, 100).matrices(&matrices).build());
+        let dump = |opts: DumpOptions| {
+            let out = write_temp("filters.slc.gz", &[]);
+            dump_hic_genome_wide(&input, 100, &out, &opts).unwrap();
+            let recs: Vec<(i16, i16, f32)> = read_slice_file(&out).1.iter().map(|r| (r.key1, r.key2, r.counts)).collect();
+            std::fs::remove_file(&out).unwrap();
+            recs
+        };
+        assert_eq!(dump(DumpOptions { min_count: Some(3.0), ..Default::default() }), vec![(0, 0, 5.0), (0, 1, 3.0), (1, 1, 4.0)]);
+        assert_eq!(dump(DumpOptions { scope: ContactScope::Cis, ..Default::default() }).len(), 4);
+        assert_eq!(dump(DumpOptions { scope: ContactScope::Trans, ..Default::default() }), vec![(0, 1, 2.0), (0, 1, 3.0)]);
+
+        // A summed resolution applies the minimum to the sums, not to the counts summed
+        let prefix = std::env::temp_dir().join(format!("hickit-test-{}-filters", std::process::id()));
+        let opts = DumpOptions { min_count: Some(6.0), scope: ContactScope::Cis, sort: SortMode::PerPair, ..Default::default() };
+        let paths = dump_hic_multi_resolution(&input, &[100, 200], &prefix, &opts).unwrap();
+        assert!(read_slice_file(&paths[0]).1.is_empty());
+        let recs: Vec<(i32, i32, f32)> = read_slice_file(&paths[1]).1.iter().map(|r| (r.bin_x, r.bin_y, r.counts)).collect();
+        assert_eq!(recs, vec![(0, 1, 6.0)]);
+        for p in paths.iter().chain([&input]) {
+            std::fs::remove_file(p).unwrap();
+        }
+    }
+
+    #[test]
+    fn dump_keeps_selected_chromosomes_with_dense_keys() {
+        let chroms = [("All", 1), ("chr1", 1000), ("chr2", 500), ("chrUn_1", 300)];
+        let matrices: [TestMatrix; 4] =
+            [(1, 1, &[(0, 2, 5)]), (1, 2, &[(7, 4, 2)]), (2, 2, &[(0, 0, 4)]), (2, 3, &[(1, 2, 3)])];
+        let input = write_temp("chrom_subset.hic", &TestHic::new(&chroms, 100).matrices(&matrices).build());
+        let out = write_temp("chrom_subset.slc.gz", &[]);
+        let dump = |opts: DumpOptions| {
+            dump_hic_genome_wide(&input, 100, &out, &opts).unwrap();
+            let (keys, recs) = read_slice_file(&out);
+            (keys, recs.iter().map(|r| (r.key1, r.key2, r.counts)).collect::<Vec<_>>())
+        };
+        let names = |list: &[&str]| Some(list.iter().map(|n| n.to_string()).collect::<Vec<_>>());
+
+        // chr2 and the scaffold only: chr2 takes key 0
+        let (keys, recs) = dump(DumpOptions { chroms: names(&["2", "CHRUN_1"]), ..Default::default() });
+        assert_eq!(keys, vec![("chr2".to_string(), 0), ("chrUn_1".to_string(), 1)]);
+        assert_eq!(recs, vec![(0, 0, 4.0), (0, 1, 3.0)]);
+        let (keys, recs) = dump(DumpOptions { exclude_chroms: names(&["chrUn_1"]), ..Default::default() });
+        assert_eq!(keys.len(), 2);
+        assert_eq!(recs, vec![(0, 0, 5.0), (0, 1, 2.0), (1, 1, 4.0)]);
+        let (keys, recs) = dump(DumpOptions { chroms: names(&["chr1", "chr2"]), exclude_chroms: names(&["chr1"]), ..Default::default() });
+        assert_eq!((keys.len(), recs), (1, vec![(0, 0, 4.0)]));
+
+        let err = dump_hic_genome_wide(&input, 100, &out, &DumpOptions { chroms: names(&["chr9"]), ..Default::default() }).unwrap_err();
+        assert!(err.to_string().contains("Chromosome 'chr9' not found") && err.to_string().contains("chrUn_1"), "{}", err);
+        let opts = DumpOptions { chroms: names(&["chr1"]), exclude_chroms: names(&["1"]), ..Default::default() };
+        assert!(dump_hic_genome_wide(&input, 100, &out, &opts).unwrap_err().to_string().contains("leave no chromosome"));
+        // An empty list selects nothing rather than writing an empty dump
+        let err = dump_hic_genome_wide(&input, 100, &out, &DumpOptions { chroms: names(&[]), ..Default::default() }).unwrap_err();
+        assert!(err.to_string().starts_with("--chroms names no chromosome"), "{}", err);
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&out).unwrap();
+    }
+
+    #[test]
+    fn effres_report_is_formatted_by_the_caller() {
+        let chroms = [("All", 1), ("chr1", 400)];
+        let matrices: [TestMatrix; 1] = [(1, 1, &[(0, 1, 4), (2, 3, 1)])];
+        let input = write_temp("effres.hic", &TestHic::new(&chroms, 100).matrices(&matrices).build());
+
+        let opts = EffresOptions { thr: 4, pct: 0.5, ..EffresOptions::default() };
+        let report = effres_hic(&input, &["1".into()], &opts, false).unwrap();
+        // Bins 0 and 1 reach 4 contacts, bins 2 and 3 only have one
+        let EffresResult::Chromosome { chromosome, rows, effective_resolution } = &report.result else {
+            panic!("expected the single-chromosome mode: {:?}", report.result)
+        };
+        assert_eq!((chromosome.as_str(), *effective_resolution), ("chr1", Some(100)));
+        assert_eq!(rows, &vec![EffresCoverage { resolution: 100, coverage: 0.5, tally: None }]);
+
+        let mut tsv = Vec::new();
+        write_effres_to(&report, EffresFormat::Tsv, &mut tsv).unwrap();
+        assert_eq!(String::from_utf8(tsv).unwrap(), "resolution_bp\tcoverage\n100\t0.500\n");
+        let mut json = Vec::new();
+        write_effres_to(&report, EffresFormat::Json, &mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!((json["mode"].as_str(), json["thr"].as_i64()), (Some("chromosome"), Some(4)));
+        assert_eq!(json["effective_resolution"], 100);
+        assert_eq!(json["rows"][0], serde_json::json!({"resolution": 100, "coverage": 0.5}));
+
+        let err = effres_hic(&input, &["chr9".into()], &opts, false).unwrap_err();
+        assert!(err.to_string().contains("Chromosome 'chr9' not found") && err.to_string().contains("available: chr1"), "{}", err);
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn effres_reports_each_requested_chromosome_and_their_summary() {
+        let chroms = [("All", 1), ("chr1", 400), ("chr2", 200), ("chr3", 300)];
+        let matrices: [TestMatrix; 2] = [(1, 1, &[(0, 1, 4), (2, 3, 1)]), (2, 2, &[(0, 0, 3), (1, 1, 2)])];
+        let input = write_temp("effres-multi.hic", &TestHic::new(&chroms, 100).matrices(&matrices).build());
+        let opts = EffresOptions { thr: 4, pct: 0.5, ..EffresOptions::default() };
+
+        // chr1 bins [4, 4, 1, 1] and chr2 bins [6, 4] at 100 bp; chr3 has no signal
+        let report = effres_hic(&input, &["1".into(), "chr2".into(), "chr3".into()], &opts, false).unwrap();
+        let EffresResult::Chromosomes { chromosomes, summary } = &report.result else { panic!("{:?}", report.result) };
+        let effective: Vec<_> = chromosomes.iter().map(|c| (c.chromosome.as_str(), c.effective_resolution)).collect();
+        assert_eq!(effective, vec![("chr1", Some(100)), ("chr2", Some(100)), ("chr3", None)]);
+        assert_eq!(summary, &vec![EffresSummaryRow { resolution: 100, min: 0.5, mean: 0.75, max: 1.0, full: None }]);
+
+        let mut tsv = Vec::new();
+        write_effres_to(&report, EffresFormat::Tsv, &mut tsv).unwrap();
+        assert_eq!(
+            String::from_utf8(tsv).unwrap(),
+            "chromosome\tresolution_bp\tcoverage\nchr1\t100\t0.500\nchr2\t100\t1.000\nchr3\t100\t0.000\n"
+        );
+        let mut text = Vec::new();
+        write_effres_to(&report, EffresFormat::Text, &mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.contains("# Chromosomes: chr1, chr2, chr3\n") && text.contains("Effective resolution on chr2: 100 bp"), "{}", text);
+        assert!(text.ends_with("resolution_bp\tmin_cov\tmean_cov\tmax_cov\n100\t0.500\t0.750\t1.000\n"), "{}", text);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!((json["mode"].as_str(), json["chromosomes"][1]["chromosome"].as_str()), (Some("chromosomes"), Some("chr2")));
+
+        let err = effres_hic(&input, &["chr1".into(), "chr9".into()], &opts, false).unwrap_err();
+        assert!(err.to_string().contains("Chromosome 'chr9' not found"), "{}", err);
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn include_trans_credits_each_anchor_to_its_own_chromosome() {
+        let chroms = [("All", 1), ("chr1", 400), ("chr2", 300), ("chr3", 200)];
+        let matrices: [TestMatrix; 3] = [(1, 1, &[(0, 1, 4)]), (1, 2, &[(2, 0, 5), (3, 2, 6)]), (2, 3, &[(1, 1, 7)])];
+        let input = write_temp("effres-trans.hic", &TestHic::new(&chroms, 100).matrices(&matrices).build());
+        let mut hic = HicFile::open(&input).unwrap();
+
+        assert_eq!(hic.bin_counts(&[1, 2], "BP", 100, false).unwrap(), vec![Some(vec![4.0, 4.0, 0.0, 0.0]), None]);
+        // bin_x of 1_2 is on chr1, bin_y on chr2; chr3 is not requested but its pair with chr2 counts
+        let trans = hic.bin_counts(&[2, 1], "BP", 100, true).unwrap();
+        assert_eq!(trans, vec![Some(vec![5.0, 7.0, 6.0]), Some(vec![4.0, 4.0, 5.0, 6.0])]);
+
+        let opts = EffresOptions { thr: 4, pct: 0.9, min_length: 0, include: vec!["chr1".into(), "chr2".into()], ..EffresOptions::default() };
+        let per_chrom = |opts: &EffresOptions| -> Vec<Option<(i32, f64)>> {
+            per_chrom_effres_hic(&input, opts).unwrap().into_iter().map(|c| c.effective).collect()
+        };
+        assert_eq!(per_chrom(&opts), vec![None, None]);
+        let trans_opts = EffresOptions { include_trans: true, ..opts.clone() };
+        assert_eq!(per_chrom(&trans_opts), vec![Some((100, 1.0)), Some((100, 1.0))]);
+
+        let report = effres_hic(&input, &[], &trans_opts, false).unwrap();
+        assert!(report.include_trans);
+        assert_eq!(report.result, EffresResult::Summary { rows: vec![EffresSummaryRow { resolution: 100, min: 1.0, mean: 1.0, max: 1.0, full: None }] });
+        let report = effres_hic(&input, &["chr2".into()], &trans_opts, false).unwrap();
+        let EffresResult::Chromosome { effective_resolution, .. } = report.result else { panic!("{:?}", report.result) };
+        assert_eq!(effective_resolution, Some(100));
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn effres_sums_unstored_resolutions_from_a_stored_one() {
+        let chroms = [("All", 1), ("chr1", 400)];
+        let matrices: [TestMatrix; 1] = [(1, 1, &[(0, 1, 4), (2, 3, 1)])];
+        let input = write_temp("effres-agg.hic", &TestHic::new(&chroms, 100).matrices(&matrices).build());
+        let opts = EffresOptions { thr: 2, pct: 0.9, min_length: 0, resolutions: vec![200, 100, 150], ..EffresOptions::default() };
+
+        // Bins at 100 bp: [4, 4, 1, 1]; 200 sums pairs, 150 has no stored divisor and takes bins by their start
+        let report = effres_hic(&input, &["chr1".into()], &opts, false).unwrap();
+        assert_eq!(
+            report.aggregated,
+            vec![AggregatedResolution { resolution: 150, from: 100 }, AggregatedResolution { resolution: 200, from: 100 }]
+        );
+        let mut tsv = Vec::new();
+        write_effres_to(&report, EffresFormat::Tsv, &mut tsv).unwrap();
+        assert_eq!(
+            String::from_utf8(tsv).unwrap(),
+            "resolution_bp\tcoverage\tsource\n100\t0.500\tstored\n150\t0.333\taggregated\n200\t1.000\taggregated\n"
+        );
+        let EffresResult::Chromosome { effective_resolution, .. } = report.result else { panic!("{:?}", report.result) };
+        assert_eq!(effective_resolution, Some(200));
+
+        let per_chrom = per_chrom_effres_hic(&input, &opts).unwrap();
+        assert_eq!(per_chrom[0].effective, Some((200, 1.0)));
+        let report = effres_hic(&input, &[], &opts, false).unwrap();
+        let EffresResult::Summary { rows } = &report.result else { panic!("{:?}", report.result) };
+        assert_eq!(rows.iter().map(|r| r.max).collect::<Vec<_>>(), vec![0.5, 1.0 / 3.0, 1.0]);
+
+        let finer = EffresOptions { resolutions: vec![50], ..opts };
+        assert!(effres_hic(&input, &["chr1".into()], &finer, false).unwrap_err().to_string().contains("finer than the finest stored"));
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn full_stats_add_tallies_and_quartiles_from_the_same_counts() {
+        let chroms = [("All", 1), ("chr1", 400), ("chr2", 200)];
+        let matrices: [TestMatrix; 2] = [(1, 1, &[(0, 1, 4), (2, 3, 1)]), (2, 2, &[(0, 0, 3)])];
+        let input = write_temp("effres-stats.hic", &TestHic::new(&chroms, 100).matrices(&matrices).build());
+        let opts = EffresOptions { thr: 2, pct: 0.6, min_length: 0, stats: EffresStats::Full, ..EffresOptions::default() };
+
+        // chr1 bins [4, 4, 1, 1], chr2 bins [6, 0]: both at 0.5 coverage
+        let report = effres_hic(&input, &[], &opts, false).unwrap();
+        let EffresResult::Summary { rows } = &report.result else { panic!("{:?}", report.result) };
+        let expected = EffresSummaryStats {
+            median: 0.5,
+            chromosomes: 2,
+            passing: 0,
+            tally: BinTally { contacts: 8.0, covered_bins: 3, total_bins: 6 },
+            bin_count_quartiles: [1.0, 2.5, 4.0],
+        };
+        assert_eq!(rows[0].full, Some(expected));
+        let mut tsv = Vec::new();
+        write_effres_to(&report, EffresFormat::Tsv, &mut tsv).unwrap();
+        assert_eq!(
+            String::from_utf8(tsv).unwrap().lines().nth(1),
+            Some("100\t0.500\t0.500\t0.500\t0.500\t2\t0\t8\t3\t6\t1\t2.5\t4")
+        );
+
+        let report = effres_hic(&input, &["chr1".into()], &opts, false).unwrap();
+        let mut tsv = Vec::new();
+        write_effres_to(&report, EffresFormat::Tsv, &mut tsv).unwrap();
+        assert_eq!(String::from_utf8(tsv).unwrap(), "resolution_bp\tcoverage\tcontacts\tcovered_bins\ttotal_bins\n100\t0.500\t5\t2\t4\n");
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["rows"][0], serde_json::json!({"resolution": 100, "coverage": 0.5, "contacts": 5.0, "covered_bins": 2, "total_bins": 4}));
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn effres_filters_include_over_min_length_and_exclude_over_all() {
+        let chroms = [("All", 1), ("chr1", 400), ("chr2", 200), ("chrM", 100)];
+        let input = write_temp("effres-filter.hic", &TestHic::new(&chroms, 100).matrix(1, 1, &[(0, 1, 4)]).build());
+        let hic = HicFile::open(&input).unwrap();
+        let names = |v: &[EffresChrom]| v.iter().map(|c| c.2.clone()).collect::<Vec<_>>();
+
+        let opts = EffresOptions { min_length: 300, ..EffresOptions::default() };
+        let (kept, skipped) = effres_chromosomes(&hic, &opts).unwrap();
+        assert_eq!(names(&kept), vec!["chr1"]);
+        assert_eq!(skipped[0], SkippedChromosome { name: "chr2".into(), reason: "shorter than 300 bp".into() });
+
+        let opts = EffresOptions { include: vec!["chr2".into(), "M".into()], exclude: vec!["chrM".into()], ..opts };
+        let (kept, skipped) = effres_chromosomes(&hic, &opts).unwrap();
+        assert_eq!(names(&kept), vec!["chr2"]);
+        let reasons: Vec<_> = skipped.iter().map(|s| (s.name.as_str(), s.reason.as_str())).collect();
+        assert_eq!(reasons, vec![("chr1", "not included"), ("chrM", "excluded")]);
+        assert_eq!(opts.describe_filter(), "only chr2, M; excluding chrM");
+
+        let opts = EffresOptions { exclude: vec!["chrX".into()], ..EffresOptions::default() };
+        assert!(effres_chromosomes(&hic, &opts).unwrap_err().to_string().contains("chrX"));
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn dense_matrix_mirrors_intra_records_and_writes_npy() {
+        let chroms = [("All", 1), ("chr1", 250), ("chr2", 450)];
+        // (3, 0) lies past ceil(250 / 100) = 3 bins and is dropped
+        let matrices: [TestMatrix; 2] = [(1, 1, &[(0, 2, 5), (1, 1, 2), (0, 3, 9)]), (1, 2, &[(1, 4, 7)])];
+        let input = write_temp("dense.hic", &TestHic::new(&chroms, 100).matrices(&matrices).build());
+        let opts = DumpOptions::default();
+
+        let m = dense_matrix(&input, "1", 100, None, &opts).unwrap();
+        assert_eq!(m.n, 3);
+        assert_eq!(m.values, vec![0.0, 0.0, 5.0, 0.0, 2.0, 0.0, 5.0, 0.0, 0.0]);
+        assert_eq!((m.get(0, 2), m.get(2, 0)), (5.0, 5.0));
+        let err = dense_matrix(&input, "chr1", 100, Some(35), &opts).unwrap_err().to_string();
+        assert!(err.contains("3 × 3 bins") && err.contains("--max-memory"), "{}", err);
+        assert!(dense_matrix(&input, "chr3", 100, None, &opts).unwrap_err().to_string().contains("available: chr1, chr2"));
+        assert!(dense_matrix(&input, "chr1", 50, None, &opts).unwrap_err().to_string().contains("100"));
+
+        let npy = write_temp("dense.npy", &[]);
+        m.write(&npy).unwrap();
+        let bytes = std::fs::read(&npy).unwrap();
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+        assert_eq!((10 + header_len) % 64, 0);
+        let header = std::str::from_utf8(&bytes[10..10 + header_len]).unwrap();
+        assert!(header.starts_with("{'descr': '<f4', 'fortran_order': False, 'shape': (3, 3), }") && header.ends_with('\n'));
+        assert_eq!(bytes.len(), 10 + header_len + 9 * 4);
+        assert_eq!(f32::from_le_bytes(bytes[10 + header_len + 8..10 + header_len + 12].try_into().unwrap()), 5.0);
+
+        let tsv = write_temp("dense.tsv", &[]);
+        m.write(&tsv).unwrap();
+        assert_eq!(std::fs::read_to_string(&tsv).unwrap(), "0\t0\t5\n0\t2\t0\n5\t0\t0\n");
+        for p in [&input, &npy, &tsv] {
+            std::fs::remove_file(p).unwrap();
+        }
+    }
+
+    #[test]
+    fn dump_short_format_writes_juicer_columns() {
+        let chroms = [("All", 1), ("chr1", 1000), ("chr2", 500)];
+        let bytes = TestHic::new(&chroms, 100).matrix(1, 1, &[(0, 2, 5)]).matrix(1, 2, &[(7, 4, 2)]).build();
+        let input = write_temp("dump_short.hic", &bytes);
+        let out = write_temp("dump_short.txt", &[]);
+        let opts = DumpOptions { format: DumpFormat::Short, ..Default::default() };
+        dump_hic_genome_wide(&input, 100, &out, &opts).unwrap();
+        let text = std::fs::read_to_string(&out).unwrap();
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&out).unwrap();
+
+        // str1 chr1 pos1 frag1 str2 chr2 pos2 frag2 score
+        assert_eq!(text, "0 chr1 0 0 0 chr1 200 0 5\n0 chr1 700 0 0 chr2 400 0 2\n");
+    }
+
+    #[test]
+    fn dump_bedpe_writes_clipped_upper_triangle_intervals() {
+        let chroms = [("All", 1), ("chr1", 1000), ("chr2", 450)];
+        let bytes = TestHic::new(&chroms, 100).matrix(1, 1, &[(3, 1, 5), (9, 9, 1)]).matrix(1, 2, &[(7, 4, 2)]).build();
+        let input = write_temp("dump_bedpe.hic", &bytes);
+        let out = write_temp("dump_bedpe.bedpe.gz", &[]);
+        let opts = DumpOptions { format: DumpFormat::Bedpe, ..Default::default() };
+        dump_hic_genome_wide(&input, 100, &out, &opts).unwrap();
+        let mut text = String::new();
+        MultiGzDecoder::new(File::open(&out).unwrap()).read_to_string(&mut text).unwrap();
+        std::fs::remove_file(&out).unwrap();
+
+        // The lower-triangle (3, 1) record is swapped; chr2 bin 4 ends at the chromosome end
+        assert_eq!(
+            text,
+            "chr1\t100\t200\tchr1\t300\t400\t5\n\
+             chr1\t900\t1000\tchr1\t900\t1000\t1\n\
+             chr1\t700\t800\tchr2\t400\t450\t2\n"
+        );
+        let frag = DumpOptions { unit: "FRAG".into(), ..opts };
+        assert!(dump_hic_genome_wide(&input, 100, &out, &frag).unwrap_err().to_string().contains("--format bedpe"));
+        std::fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn pair_text_dump_uses_stored_order_and_bin_starts() {
+        let chroms = [("All", 1), ("chr1", 1000), ("chr2", 500)];
+        let bytes = TestHic::new(&chroms, 100).matrix(1, 2, &[(7, 4, 2), (3, 0, 4)]).build();
+        let input = write_temp("pair_text.hic", &bytes);
+        let out = write_temp("pair_text.txt", &[]);
+        let opts = DumpOptions { sort: SortMode::PerPair, ..Default::default() };
+        // Names given in reverse and loosely: chr1 stays on the x axis
+        dump_hic_pair_text(&input, "2", "CHR1", 100, Some(&out), &opts).unwrap();
+        let text = std::fs::read_to_string(&out).unwrap();
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&out).unwrap();
+        assert_eq!(text, "300\t0\t4\n700\t400\t2\n");
+    }
+
+    #[test]
+    fn all_matrix_dump_places_genome_bins_on_chromosomes() {
+        // Juicer-style: "All" is the genome in kb, and the ALL matrix bin size
+        // 100 is in kb too, so bins are 100 kb; chr1 holds bins 0-19, chr2 20-29
+        let chroms = [("All", 3000), ("chr1", 2_000_000), ("chr2", 1_000_000)];
+        let all: &[(i16, i16, i16)] = &[(5, 0, 3), (19, 25, 4), (22, 22, 1)];
+        let input = write_temp("all_matrix.hic", &TestHic::new(&chroms, 100).matrix(0, 0, all).matrix(1, 1, &[(0, 0, 9)]).build());
+        let out = write_temp("all_matrix.txt", &[]);
+        let opts = DumpOptions { sort: SortMode::PerPair, canonicalize: true, ..Default::default() };
+        dump_hic_all_matrix(&input, 100_000, Some(&out), false, &opts).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "chr1\t0\tchr1\t500000\t3\nchr1\t1900000\tchr2\t500000\t4\nchr2\t200000\tchr2\t200000\t1\n"
+        );
+        // Stored order and orientation, by row
+        dump_hic_all_matrix(&input, 100_000, Some(&out), true, &DumpOptions::default()).unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "5\t0\t3\n22\t22\t1\n19\t25\t4\n");
+
+        // The header's 100 bp resolution is not one of the ALL matrix's
+        let err = dump_hic_all_matrix(&input, 100, Some(&out), false, &DumpOptions::default()).unwrap_err().to_string();
+        assert!(err.ends_with("is not stored at 100 bp; available: [100000]"), "{}", err);
+        let kr = DumpOptions { norm: "KR".into(), ..Default::default() };
+        assert!(dump_hic_all_matrix(&input, 100_000, Some(&out), false, &kr).is_err());
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&out).unwrap();
+    }
+
+    #[test]
+    fn retries_transient_io_errors_only() {
+        let eio = || std::io::Error::from_raw_os_error(5);
+        let mut calls = 0;
+        let r = with_io_retries(2, false, String::new, || { calls += 1; if calls < 3 { Err(eio()) } else { Ok(calls) } });
+        assert_eq!(r.unwrap(), 3);
+
+        // Gives up after the configured retries
+        calls = 0;
+        assert!(with_io_retries(1, false, String::new, || -> std::io::Result<()> { calls += 1; Err(eio()) }).is_err());
+        assert_eq!(calls, 2);
+
+        // Permanent errors fail on the first attempt
+        calls = 0;
+        let r = with_io_retries(2, false, String::new, || -> std::io::Result<()> {
+            calls += 1;
+            Err(std::io::ErrorKind::NotFound.into())
+        });
+        assert!(r.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn region_blocks_follow_grid_and_diagonal_layouts() {
+        let blocks = |version, is_intra, x: RangeInclusive<i32>, y: RangeInclusive<i32>| {
+            region_block_numbers(version, is_intra, 10, 5, &x, &y).into_iter().collect::<Vec<_>>()
+        };
+        // Grid: row from bin_y, column from bin_x
+        assert_eq!(blocks(8, false, 5..=25, 12..=12), vec![5, 6, 7]);
+        // Intra grid is also searched transposed (contact stored in the other triangle)
+        assert_eq!(blocks(8, true, 0..=5, 30..=35), vec![3, 15]);
+        // v9 intra: depth log2(1 + 100 / sqrt(2) / 10) = 3, diagonal position 50 / 10 = 5
+        assert_eq!(blocks(9, true, 0..=0, 100..=100), vec![3 * 5 + 5]);
+        assert_eq!(blocks(9, true, 100..=100, 0..=0), vec![3 * 5 + 5]);
+        // A region on the diagonal starts at depth 0
+        assert_eq!(blocks(9, true, 0..=9, 0..=9), vec![0]);
+        // v9 inter-chromosomal matrices keep the grid
+        assert_eq!(blocks(9, false, 5..=25, 12..=12), vec![5, 6, 7]);
+    }
+
+    fn write_temp(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("hickit-test-{}-{}", std::process::id(), name));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn opens_frag_only_file_with_clear_error() {
+        let bytes = v8_header(&[("All", 1), ("chr1", 1000), ("chr2", 450)], &[], &[1, 5]);
+        let path = write_temp("frag_only.hic", &bytes);
+        let hic = HicFile::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(hic.resolutions.is_empty());
+        assert_eq!(hic.frag_resolutions, vec![1, 5]);
+        assert_eq!(hic.frag_site_counts, vec![0, 10, 4]);
+        let err = hic.require_bp().unwrap_err().to_string();
+        assert!(err.contains("no BP resolutions") && err.contains("FRAG"), "{}", err);
+    }
+
+    #[test]
+    fn decodes_frag_unit_matrices() {
+        let chroms = [("All", 1), ("chr1", 1000), ("chr2", 500)];
+        let bytes = TestHic::new(&chroms, 1).unit("FRAG").matrix(1, 1, &[(0, 2, 5), (3, 3, 1)]).build();
+        let path = write_temp("frag_unit.hic", &bytes);
+        let mut hic = HicFile::open(&path).unwrap();
+
+        assert!(hic.require_resolution("FRAG", 1).is_ok());
+        assert!(hic.require_resolution("FRAG", 5).unwrap_err().to_string().contains("available: [1]"));
+        let err = hic.require_resolution("BP", 1).unwrap_err().to_string();
+        assert!(err.contains("no BP resolutions"), "{}", err);
+        // Bins along FRAG axes are fragments: chr1 has 10 restriction sites
+        assert_eq!(hic.chrom_extent(1, "FRAG"), 10);
+        assert_eq!(hic.chrom_extent(1, "BP"), 1000);
+
+        let mut intra: Vec<(i32, i32, f32)> =
+            hic.query("chr1", "chr1", "FRAG", 1).unwrap().iter().map(|r| (r.bin_x, r.bin_y, r.counts)).collect();
+        intra.sort_by_key(|&(x, y, _)| (x, y));
+        assert_eq!(intra, vec![(0, 2, 5.0), (3, 3, 1.0)]);
+        let counts = hic.intra_bin_counts(1, "FRAG", 1).unwrap().unwrap();
+        assert_eq!(counts[..4], [5.0, 0.0, 5.0, 2.0]);
+        std::fs::remove_file(&path).unwrap();
+
+        // And the reverse: a BP-only file names what it does store
+        let bytes = TestHic::new(&chroms, 100).build();
+        let path = write_temp("bp_unit.hic", &bytes);
+        let hic = HicFile::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let err = hic.require_unit("FRAG").unwrap_err().to_string();
+        assert!(err.contains("no FRAG") && err.contains("100"), "{}", err);
+        assert!(hic.require_unit("MB").unwrap_err().to_string().contains("Unsupported unit"));
+    }
+
+    #[test]
+    fn names_the_matrix_missing_a_unit_resolution_pair() {
+        let chroms = [("All", 1), ("chr1", 1000)];
+        let bytes = TestHic::new(&chroms, 100).matrix(1, 1, &[(0, 2, 5)]).build();
+        let path = write_temp("missing_zoom.hic", &bytes);
+        let mut hic = HicFile::open(&path).unwrap();
+        let err = hic.get_matrix_zoom_data(1, 1, "FRAG", 1).err().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(err.to_string().starts_with("No FRAG 1 matrix for chr1 vs chr1"), "{}", err);
+        assert!(format!("{:#}", err).contains("Resolution 1 at unit FRAG not found"), "{:#}", err);
+    }
+
+    #[test]
+    fn opens_bp_file_without_frag_section() {
+        let bytes = v8_header(&[("All", 1), ("chr1", 1000)], &[1000, 500], &[]);
+        let path = write_temp("bp_only.hic", &bytes);
+        let hic = HicFile::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(hic.resolutions, vec![1000, 500]);
+        assert!(hic.frag_resolutions.is_empty() && hic.frag_site_counts.is_empty());
+        assert!(hic.require_bp().is_ok());
+    }
+
+    #[test]
+    fn rebinning_sums_fine_bins_into_coarser_multiples() {
+        let mut fine = vec![0.0; 11];
+        for (bin, c) in [(0, 1.0), (4, 2.0), (5, 3.0), (9, 4.0), (10, 5.0)] { fine[bin] = c; }
+        assert_eq!(rebin_counts(&fine, 5_000, 25_000), vec![3.0, 7.0, 5.0]);
+        assert_eq!(rebin_counts(&fine, 5_000, 5_000), fine);
+        assert!(rebin_counts(&[], 5_000, 25_000).is_empty());
+    }
+
+    #[test]
+    fn dense_bin_counts_match_the_sparse_map_bit_for_bit() {
+        let mut x: u32 = 7;
+        let mut next = || { x = x.wrapping_mul(1_103_515_245).wrapping_add(12345); x >> 8 };
+        let blocks: Vec<Vec<ContactRecord>> = (0..4)
+            .map(|_| (0..500).map(|_| ContactRecord { bin_x: (next() % 90) as i32, bin_y: (next() % 90) as i32, counts: next() as f32 / 977.0 }).collect())
+            .collect();
+
+        // The former accumulator: a sparse map fed block by block
+        let mut sparse: HashMap<i32, f64> = HashMap::new();
+        let mut dense = Vec::new();
+        for records in &blocks {
+            for rec in records {
+                *sparse.entry(rec.bin_x).or_insert(0.0) += rec.counts as f64;
+                *sparse.entry(rec.bin_y).or_insert(0.0) += rec.counts as f64;
+            }
+            add_bin_counts(&mut dense, records, 80);
+        }
+        assert_eq!(dense.len(), 90); // grew past the 80 expected bins
+        for (&bin, &c) in &sparse {
+            assert_eq!(dense[bin as usize].to_bits(), c.to_bits());
+        }
+        for (thr, mode) in [(1.0, ZeroBins::Uncovered), (30.0, ZeroBins::Excluded), (0.0, ZeroBins::Uncovered)] {
+            assert_eq!(
+                coverage_fraction(dense.iter().copied(), thr, 100, mode),
+                coverage_fraction(sparse.values().copied(), thr, 100, mode)
+            );
+        }
+
+        let mut empty = Vec::new();
+        add_bin_counts(&mut empty, &[], 80);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn num_bins_covers_partial_last_bin() {
+        assert_eq!(num_bins_for(100, 10), 10);
+        assert_eq!(num_bins_for(101, 10), 11);
+        assert_eq!(num_bins_for(0, 10), 0);
     }
-    Ok(())
 }
@@ -1,12 +1,16 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use rustc_hash::FxHashMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::path::Path;
 use std::str;
 
-pub type ChrMap = FxHashMap<String, u8>;
+/// 1-based chromosome code (0 = unknown); wide enough for contig-level assemblies.
+pub type ChrCode = u32;
 
-// A compact, fast open-addressing map from chromosome name -> u8 code.
+pub type ChrMap = FxHashMap<String, ChrCode>;
+
+// A compact, fast open-addressing map from chromosome name -> chromosome code.
 // Built once from provided chrom.size or pairs header; lookups are zero-allocation.
 #[cfg(feature = "fast_chrmap")]
 #[derive(Clone, Debug)]
@@ -14,7 +18,7 @@ pub struct FastChrMap {
     // All keys stored once for byte comparison
     names: Vec<String>,
     // Code per entry in `names`
-    codes: Vec<u8>,
+    codes: Vec<ChrCode>,
     // Open addressing table storing index into `names` (i32: -1 = empty)
     slots: Vec<i32>,
     mask: usize,
@@ -22,7 +26,7 @@ pub struct FastChrMap {
 
 #[cfg(feature = "fast_chrmap")]
 impl FastChrMap {
-    pub fn from_names_codes(names: Vec<String>, codes: Vec<u8>) -> Self {
+    pub fn from_names_codes(names: Vec<String>, codes: Vec<ChrCode>) -> Self {
         let n = names.len().max(1);
         let cap = (n.next_power_of_two()) * 2; // load factor <= 0.5
         let mut slots = vec![-1; cap];
@@ -43,12 +47,12 @@ impl FastChrMap {
     }
 
     #[inline]
-    pub fn get(&self, key: &str) -> Option<u8> {
+    pub fn get(&self, key: &str) -> Option<ChrCode> {
         self.get_bytes(key.as_bytes())
     }
 
     #[inline]
-    pub fn get_bytes(&self, key: &[u8]) -> Option<u8> {
+    pub fn get_bytes(&self, key: &[u8]) -> Option<ChrCode> {
         let mut h = fnv1a64(key) as usize & self.mask;
         loop {
             let s = self.slots[h];
@@ -75,10 +79,12 @@ fn fnv1a64(bytes: &[u8]) -> u64 {
 
 #[derive(Debug, Clone)]
 pub struct Pair {
-    pub chr1: u8,
+    pub chr1: ChrCode,
     pub pos1: u32,
-    pub chr2: u8,
+    pub chr2: ChrCode,
     pub pos2: u32,
+    /// Contacts this pair stands for: 1, or the score of a scored input line
+    pub weight: u32,
 }
 
 pub fn create_chr_map(chrom_size_file: Option<&str>) -> ChrMap {
@@ -116,8 +122,8 @@ pub fn create_fast_chr_map_from_file(filename: &str) -> Result<FastChrMap> {
     let file = File::open(filename)?;
     let reader = BufReader::new(file);
     let mut names: Vec<String> = Vec::new();
-    let mut codes: Vec<u8> = Vec::new();
-    let mut chr_index = 1u8;
+    let mut codes: Vec<ChrCode> = Vec::new();
+    let mut chr_index: ChrCode = 1;
 
     for line in reader.lines() {
         let line = line?;
@@ -138,8 +144,8 @@ pub fn create_fast_chr_map_from_file(filename: &str) -> Result<FastChrMap> {
 fn fast_map_from_default() -> FastChrMap {
     // Provide both bare and chr-prefixed aliases as entries mapping to same code
     let mut names: Vec<String> = Vec::new();
-    let mut codes: Vec<u8> = Vec::new();
-    for i in 1..=22u8 {
+    let mut codes: Vec<ChrCode> = Vec::new();
+    for i in 1..=22 {
         names.push(i.to_string());
         codes.push(i);
         names.push(format!("chr{}", i));
@@ -160,7 +166,7 @@ pub fn create_chr_map_from_file(filename: &str) -> Result<ChrMap> {
     let file = File::open(filename)?;
     let reader = BufReader::new(file);
     let mut map = ChrMap::default();
-    let mut chr_index = 1u8;
+    let mut chr_index: ChrCode = 1;
 
     for line in reader.lines() {
         let line = line?;
@@ -185,7 +191,7 @@ pub fn create_default_chr_map() -> ChrMap {
 
     // Human chromosomes 1-22, X, Y
     for i in 1..=22 {
-        map.insert(i.to_string(), i as u8);
+        map.insert(i.to_string(), i as ChrCode);
     }
     map.insert("X".to_string(), 23);
     map.insert("Y".to_string(), 24);
@@ -194,7 +200,7 @@ pub fn create_default_chr_map() -> ChrMap {
 
     // Add common prefixes
     for i in 1..=22 {
-        map.insert(format!("chr{}", i), i as u8);
+        map.insert(format!("chr{}", i), i as ChrCode);
     }
     map.insert("chrX".to_string(), 23);
     map.insert("chrY".to_string(), 24);
@@ -241,6 +247,11 @@ pub fn get_genome_lengths_from_file(filename: &str) -> Result<Vec<u32>> {
     Ok(lengths)
 }
 
+/// `path` as the `&str` the chromosome sizes readers take.
+pub fn utf8_path(path: &Path) -> Result<&str> {
+    path.to_str().ok_or_else(|| anyhow::anyhow!("Chromosome sizes path {:?} is not valid UTF-8", path))
+}
+
 pub fn read_chrom_sizes_with_names(filename: &str) -> Result<(Vec<String>, Vec<u32>)> {
     let file = File::open(filename)?;
     let reader = BufReader::new(file);
@@ -264,6 +275,77 @@ pub fn read_chrom_sizes_with_names(filename: &str) -> Result<(Vec<String>, Vec<u
     Ok((names, lengths))
 }
 
+/// Names and lengths from a `samtools faidx` index, in file order (the order
+/// of the FASTA and of a BAM's @SQ lines). Every line must have the 5 columns
+/// of a FASTA index (`name length offset linebases linewidth`), or 6 for a
+/// FASTQ one, with numbers after the name, so a two-column chrom.size is
+/// rejected rather than taken for an index.
+pub fn read_fai(path: &Path) -> Result<(Vec<String>, Vec<u32>)> {
+    let file = File::open(path).with_context(|| format!("Open {:?}", path))?;
+    let mut names = Vec::new();
+    let mut lengths = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let parts: Vec<&str> = line.split('\t').collect();
+        let numeric = parts[1..].iter().all(|p| p.parse::<u64>().is_ok());
+        if !matches!(parts.len(), 5 | 6) || !numeric {
+            anyhow::bail!(
+                "{:?} line {} is not a .fai line (name, length, offset, line bases, line width): '{}'",
+                path,
+                i + 1,
+                line
+            );
+        }
+        let length = parts[1]
+            .parse::<u32>()
+            .map_err(|_| anyhow::anyhow!("{:?} line {}: length {} of '{}' is too large", path, i + 1, parts[1], parts[0]))?;
+        names.push(parts[0].to_string());
+        lengths.push(length);
+    }
+    if names.is_empty() {
+        anyhow::bail!("{:?} lists no sequences", path);
+    }
+    Ok((names, lengths))
+}
+
+/// Read one name per line (first whitespace-separated field), skipping blank
+/// and `#` lines, so a chrom.size file works as a list too.
+pub fn read_name_list(path: &Path) -> Result<Vec<String>> {
+    let file = File::open(path).with_context(|| format!("Open {:?}", path))?;
+    let mut names = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.split_whitespace().next() {
+            names.push(name.to_string());
+        }
+    }
+    Ok(names)
+}
+
+/// Names given as `chr1,chr2,...`, or read with [`read_name_list`] when
+/// `spec` is an existing file.
+pub fn parse_name_list(spec: &str) -> Result<Vec<String>> {
+    if Path::new(spec).is_file() {
+        let names = read_name_list(Path::new(spec))?;
+        if names.is_empty() {
+            anyhow::bail!("No chromosome names in {:?}", spec);
+        }
+        return Ok(names);
+    }
+    let names: Vec<String> = spec.split(',').map(str::trim).filter(|n| !n.is_empty()).map(String::from).collect();
+    if names.is_empty() {
+        anyhow::bail!("No chromosome names in '{}'", spec);
+    }
+    Ok(names)
+}
+
 pub fn get_default_genome_lengths() -> Vec<u32> {
     // hg19 chromosome lengths (from UCSC)
     vec![
@@ -301,6 +383,48 @@ pub fn get_default_genome_names() -> Vec<String> {
     names
 }
 
+/// Parse a human-friendly byte size: `1024`, `512K`, `800M`, `16G`, `1T` (binary units; a trailing `B`/`iB` is accepted).
+pub fn parse_byte_size(s: &str) -> Result<u64> {
+    let t = s.trim();
+    let upper = t.to_ascii_uppercase();
+    let stripped = upper.trim_end_matches("IB").trim_end_matches('B');
+    let (num, mult) = match stripped.chars().last() {
+        Some('K') => (&stripped[..stripped.len() - 1], 1u64 << 10),
+        Some('M') => (&stripped[..stripped.len() - 1], 1u64 << 20),
+        Some('G') => (&stripped[..stripped.len() - 1], 1u64 << 30),
+        Some('T') => (&stripped[..stripped.len() - 1], 1u64 << 40),
+        _ => (stripped, 1u64),
+    };
+    let value: f64 = num
+        .trim()
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid size '{}': expected e.g. 512M or 16G", s))?;
+    if !(value.is_finite() && value >= 0.0) {
+        anyhow::bail!("Invalid size '{}'", s);
+    }
+    Ok((value * mult as f64) as u64)
+}
+
+/// Format bytes with a binary unit, e.g. `1.5 GiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut v = bytes as f64;
+    let mut u = 0;
+    while v >= 1024.0 && u < UNITS.len() - 1 {
+        v /= 1024.0;
+        u += 1;
+    }
+    if u == 0 { format!("{} B", bytes) } else { format!("{:.1} {}", v, UNITS[u]) }
+}
+
+/// `MemAvailable` from `/proc/meminfo`; `None` where it cannot be determined.
+pub fn available_memory_bytes() -> Option<u64> {
+    let info = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = info.lines().find(|l| l.starts_with("MemAvailable:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
 #[inline]
 pub fn parse_u32_fast(s: &[u8]) -> Option<u32> {
     if s.is_empty() {
@@ -309,7 +433,7 @@ pub fn parse_u32_fast(s: &[u8]) -> Option<u32> {
 
     let mut result = 0u32;
     for &byte in s {
-        if byte >= b'0' && byte <= b'9' {
+        if byte.is_ascii_digit() {
             result = result * 10 + (byte - b'0') as u32;
         } else {
             return None;
@@ -319,7 +443,7 @@ pub fn parse_u32_fast(s: &[u8]) -> Option<u32> {
 }
 
 #[inline]
-pub fn parse_chr(s: &[u8], chr_map: &ChrMap) -> Option<u8> {
+pub fn parse_chr(s: &[u8], chr_map: &ChrMap) -> Option<ChrCode> {
     let s_str = str::from_utf8(s).ok()?;
     chr_map.get(s_str).copied()
 }
@@ -342,7 +466,7 @@ pub fn create_lookup_map(chrom_size_file: Option<&str>) -> ChrLookup {
 
 #[cfg(feature = "fast_chrmap")]
 pub fn build_lookup_from_names(names: Vec<String>) -> ChrLookup {
-    let codes: Vec<u8> = (0..names.len()).map(|i| (i as u8) + 1).collect();
+    let codes: Vec<ChrCode> = (1..=names.len() as ChrCode).collect();
     FastChrMap::from_names_codes(names, codes)
 }
 
@@ -350,7 +474,7 @@ pub fn build_lookup_from_names(names: Vec<String>) -> ChrLookup {
 pub fn build_lookup_from_names(names: Vec<String>) -> ChrLookup {
     let mut map: ChrMap = ChrMap::default();
     for (i, nm) in names.into_iter().enumerate() {
-        map.insert(nm, (i as u8) + 1);
+        map.insert(nm, i as ChrCode + 1);
     }
     map
 }
@@ -373,9 +497,85 @@ mod tests {
         p.to_string_lossy().into_owned()
     }
 
+    #[test]
+    fn fai_contigs_are_coded_in_index_order() {
+        let path = std::env::temp_dir().join(format!("hickit-test-{}.fa.fai", std::process::id()));
+        let fai = "ctgB\t5000\t6\t60\t61\nctgA\t300\t5094\t60\t61\nchrM\t16569\t5406\t60\t61\n";
+        std::fs::write(&path, fai).unwrap();
+        let (names, lengths) = read_fai(&path).unwrap();
+        assert_eq!(names, vec!["ctgB", "ctgA", "chrM"]);
+        assert_eq!(lengths, vec![5000, 300, 16569]);
+
+        // The chrom.size readers take the same first two columns, so codes follow the index
+        let path_s = path.to_str().unwrap();
+        assert_eq!(read_chrom_sizes_with_names(path_s).unwrap(), (names, lengths));
+        let lookup = create_lookup_map(Some(path_s));
+        #[cfg(feature = "fast_chrmap")]
+        assert_eq!(["ctgB", "ctgA", "chrM"].map(|n| lookup.get(n)), [Some(1), Some(2), Some(3)]);
+        #[cfg(not(feature = "fast_chrmap"))]
+        assert_eq!(["ctgB", "ctgA", "chrM"].map(|n| lookup.get(n).copied()), [Some(1), Some(2), Some(3)]);
+
+        // A chrom.size is not an index
+        std::fs::write(&path, "ctgB\t5000\nctgA\t300\n").unwrap();
+        let err = read_fai(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(err.to_string().contains("line 1 is not a .fai line"), "{}", err);
+    }
+
+    #[test]
+    fn keeps_distinct_codes_past_255_contigs() {
+        let path = std::env::temp_dir().join(format!("hickit-test-{}-500ctg.size", std::process::id()));
+        let sizes: String = (1..=500).map(|i| format!("ctg{}\t{}\n", i, 1000 + i)).collect();
+        std::fs::write(&path, sizes).unwrap();
+        let path_s = path.to_str().unwrap();
+        let map = create_chr_map_from_file(path_s).unwrap();
+        let lookup = create_lookup_map(Some(path_s));
+        let lengths = get_genome_lengths_from_file(path_s).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(map.len(), 500);
+        assert_eq!(map["ctg300"], 300);
+        assert_eq!(map["ctg500"], 500);
+        #[cfg(feature = "fast_chrmap")]
+        assert_eq!(lookup.get("ctg300"), Some(300));
+        #[cfg(not(feature = "fast_chrmap"))]
+        assert_eq!(lookup.get("ctg300"), Some(&300));
+
+        let mut cov = crate::coverage::Coverage::from_lengths(100, lengths);
+        cov.add_pair(&Pair { chr1: map["ctg300"], pos1: 950, chr2: map["ctg255"], pos2: 50, weight: 1 });
+        assert_eq!(cov.chr_bins(299)[9], 1);
+        assert_eq!(cov.chr_bins(254)[0], 1);
+        assert_eq!(cov.get_total_contacts(), 2);
+    }
+
+    #[test]
+    fn parses_byte_sizes() {
+        assert_eq!(parse_byte_size("1024").unwrap(), 1024);
+        assert_eq!(parse_byte_size("512M").unwrap(), 512 << 20);
+        assert_eq!(parse_byte_size("16g").unwrap(), 16 << 30);
+        assert_eq!(parse_byte_size("1.5GiB").unwrap(), 3 << 29);
+        assert!(parse_byte_size("lots").is_err());
+        assert_eq!(format_bytes(3 << 29), "1.5 GiB");
+    }
+
+    #[test]
+    fn name_lists_come_inline_or_from_a_file() {
+        assert_eq!(parse_name_list("chr1, chr2,,chrX").unwrap(), vec!["chr1", "chr2", "chrX"]);
+        assert!(parse_name_list(",").is_err());
+        // An existing path is a list file, e.g. a chrom.size
+        let names = parse_name_list(&repo_file("examples/chrom.size")).unwrap();
+        assert_eq!(names[0], "ptg000001l");
+        // A list file with only comments and blank lines names nothing
+        let empty = std::env::temp_dir().join(format!("hickit-empty-names-{}.txt", std::process::id()));
+        std::fs::write(&empty, "# autosomes\n\n").unwrap();
+        let err = parse_name_list(empty.to_str().unwrap()).unwrap_err().to_string();
+        std::fs::remove_file(&empty).unwrap();
+        assert!(err.starts_with("No chromosome names in"), "{}", err);
+    }
+
     #[test]
     fn reads_chrom_size_lengths_and_map() {
-        let path = repo_file("chrom.size");
+        let path = repo_file("examples/chrom.size");
 
         // Validate lengths parsing
         let lengths = get_genome_lengths_from_file(&path).expect("should read chrom.size lengths");
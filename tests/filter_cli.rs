@@ -0,0 +1,53 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const MERGED_NODUPS: &str = "\
+0 chr1 150 1 16 chr1 900 3 60 50M SEQ 60 50M SEQ r0 r0
+0 chr1 10 1 16 chr2 180 2 60 50M SEQ 60 50M SEQ r1 r1
+0 chr2 500 4 16 chr1 120 1 60 50M SEQ 60 50M SEQ r2 r2
+0 chr1 190 2 16 chr1 195 2 60 50M SEQ 60 50M SEQ r3 r3
+0 chr3 100 1 16 chr1 200 5 0 50M SEQ 60 50M SEQ r4 r4
+0 chr2 120 1 16 chr2 160 2 60 50M SEQ 60 50M SEQ r5 r5
+";
+
+/// Pipe `MERGED_NODUPS` through `hickit filter - <args>` and return stdout.
+fn filter_stdin(args: &[&str]) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_hickit"))
+        .arg("filter")
+        .arg("-")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("run hickit");
+    child.stdin.take().unwrap().write_all(MERGED_NODUPS.as_bytes()).unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    String::from_utf8(out.stdout).unwrap()
+}
+
+fn read_ids(text: &str) -> Vec<&str> {
+    text.lines().map(|l| l.split_whitespace().nth(14).unwrap()).collect()
+}
+
+#[test]
+fn emits_only_lines_overlapping_the_region() {
+    // r3 sits on one fragment and r4 has mapq 0: dropped by the default unique filter
+    let out = filter_stdin(&["chr1:100-200"]);
+    assert_eq!(read_ids(&out), vec!["r0", "r2"]);
+    // Lines come out unmodified
+    assert_eq!(out.lines().next().unwrap(), MERGED_NODUPS.lines().next().unwrap());
+
+    // CHR START-END form, and the unique filter turned off
+    let out = filter_stdin(&["chr1", "100-200", "--no-require-unique"]);
+    assert_eq!(read_ids(&out), vec!["r0", "r2", "r3", "r4"]);
+}
+
+#[test]
+fn bed_file_replaces_the_positional_region() {
+    let bed = std::env::temp_dir().join(format!("hickit-filter-cli-{}.bed", std::process::id()));
+    std::fs::write(&bed, "chr1\t140\t160\nchr2\t149\t160\n").unwrap();
+    let out = filter_stdin(&["--bed", bed.to_str().unwrap()]);
+    std::fs::remove_file(&bed).unwrap();
+    assert_eq!(read_ids(&out), vec!["r0", "r5"]);
+}
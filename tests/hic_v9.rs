@@ -0,0 +1,40 @@
+use std::process::Command;
+
+/// Version 9 file with two chromosomes (chr1 1000 bp, chr2 600 bp) at 50 and
+/// 100 bp, whose VC vectors (all 2.0) were appended after the footer: only the
+/// header's normalization index position finds them.
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/v9_appended_norm.hic");
+
+fn hickit(args: &[&str]) -> String {
+    let out = Command::new(env!("CARGO_BIN_EXE_hickit")).args(args).output().expect("run hickit");
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    String::from_utf8(out.stdout).unwrap()
+}
+
+#[test]
+fn lists_header_and_appended_normalizations() {
+    let json: serde_json::Value = serde_json::from_str(&hickit(&["straw", "list", "--format", "json", FIXTURE])).unwrap();
+    assert_eq!(json["version"], 9);
+    assert_eq!(json["resolutions"], serde_json::json!([50, 100]));
+    assert_eq!(json["chromosomes"][1], serde_json::json!({"name": "chr2", "index": 2, "length": 600}));
+    assert_eq!(json["normalizations"], serde_json::json!(["VC"]));
+}
+
+#[test]
+fn dumps_pairs_and_genome_wide_slices() {
+    let mut intra: Vec<String> = hickit(&["straw", "dump", "observed", "NONE", FIXTURE, "BP", "50", "chr1", "chr1"])
+        .lines()
+        .map(String::from)
+        .collect();
+    intra.sort();
+    assert_eq!(intra, vec!["0\t0\t4", "100\t450\t3", "250\t300\t2", "900\t950\t1"]);
+    // 100 bp inter records summed from 50 bp, divided by 2.0 * 2.0
+    assert_eq!(hickit(&["straw", "dump", "observed", "VC", FIXTURE, "BP", "100", "chr1", "chr2"]), "0\t100\t1.25\n600\t500\t0.5\n");
+
+    let slice = std::env::temp_dir().join(format!("hickit-v9-{}.slc.gz", std::process::id()));
+    let slice = slice.to_str().unwrap();
+    hickit(&["straw", "dump", "observed", "NONE", FIXTURE, "BP", "100", slice]);
+    let stats = hickit(&["straw", "slice", "stats", slice]);
+    std::fs::remove_file(slice).unwrap();
+    assert!(stats.contains("Records: 8"), "{}", stats);
+}
@@ -0,0 +1,100 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const SIZES: &str = "chr1\t1000\nchr2\t500\n";
+
+const PAIRS: &str = "\
+## pairs format v1.0
+#columns: readID chr1 pos1 chr2 pos2 strand1 strand2 pair_type
+r1\tchr1\t150\tchr1\t160\t+\t-\tUU
+r2\tchr1\t150\tchr1\t160\t+\t-\tUU
+r3\tchr1\t150\tchr1\t160\t+\t-\tUU
+r4\tchr1\t450\tchr2\t300\t+\t-\tUU
+r5\tchr2\t310\tchr2\t320\t+\t-\tUU
+";
+
+/// Version 9 file with chr1 (1000 bp) and chr2 (600 bp) at 50 and 100 bp.
+const HIC: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/v9_appended_norm.hic");
+
+/// A scratch directory holding `pairs` as `in.pairs` and `sizes`, unique to this test.
+fn scratch(name: &str, pairs: &str, sizes: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("hickit-resolution-cli-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("in.pairs"), pairs).unwrap();
+    std::fs::write(dir.join("sizes"), sizes).unwrap();
+    dir
+}
+
+/// Run `hickit <cmd>` on the scratch inputs at 50 bp base bins and >= 1
+/// contact per bin, with `args`, and return stdout.
+fn hickit(cmd: &str, dir: &Path, args: &[&str]) -> String {
+    let out = Command::new(env!("CARGO_BIN_EXE_hickit"))
+        .arg(cmd)
+        .arg(dir.join("in.pairs"))
+        .arg("-c")
+        .arg(dir.join("sizes"))
+        .args(["--bin-width", "50", "--count-threshold", "1"])
+        .args(args)
+        .output()
+        .expect("run hickit");
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    String::from_utf8(out.stdout).unwrap()
+}
+
+/// Run `hickit resolution` with `args` and parse the `--json` document.
+fn resolution_json(dir: &Path, args: &[&str]) -> serde_json::Value {
+    let json = dir.join("out.json");
+    hickit("resolution", dir, &[&["--json", json.to_str().unwrap()], args].concat());
+    serde_json::from_str(&std::fs::read_to_string(&json).unwrap()).unwrap()
+}
+
+#[test]
+fn json_lists_the_hotspots_when_requested() {
+    let dir = scratch("hotspots", PAIRS, SIZES);
+    let plain = resolution_json(&dir, &[]);
+    let doc = resolution_json(&dir, &["--hotspots", "2", "--hotspot-bin-size", "100"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(plain.get("hotspots").is_none() && plain.get("hotspot_share").is_none());
+    // 10 pair ends: chr1:100-200 holds 6, then chr2:300-400 holds 3
+    let hotspots = doc["hotspots"].as_array().unwrap();
+    assert_eq!(hotspots.len(), 2);
+    assert_eq!(hotspots[0]["chrom"], "chr1");
+    assert_eq!((hotspots[0]["start"].as_u64(), hotspots[0]["end"].as_u64()), (Some(100), Some(200)));
+    assert_eq!(hotspots[0]["count"], 6);
+    assert!((hotspots[0]["fraction"].as_f64().unwrap() - 0.6).abs() < 1e-9);
+    assert_eq!(hotspots[1]["chrom"], "chr2");
+    assert_eq!((hotspots[1]["start"].as_u64(), hotspots[1]["end"].as_u64()), (Some(300), Some(400)));
+    assert_eq!(hotspots[1]["count"], 3);
+    assert!((doc["hotspot_share"].as_f64().unwrap() - 0.9).abs() < 1e-9);
+}
+
+/// The `method resolution_bp` rows of `compare` and its ratio line.
+fn compare_rows(out: &str) -> Vec<&str> {
+    out.lines().skip_while(|l| *l != "method\tresolution_bp").take(4).collect()
+}
+
+#[test]
+fn compare_reports_both_resolutions_and_their_ratio() {
+    // chr1 and chr2 at the .hic's lengths; the .hic covers 56% of its 100 bp bins
+    let sizes = "chr1\t1000\nchr2\t600\n";
+    let header = "## pairs format v1.0\n#columns: readID chr1 pos1 chr2 pos2 strand1 strand2 pair_type\n";
+    let dense = format!(
+        "{}r1\tchr1\t10\tchr1\t460\t+\t-\tUU\nr2\tchr1\t260\tchr1\t310\t+\t-\tUU\n\
+         r3\tchr1\t910\tchr2\t560\t+\t-\tUU\nr4\tchr2\t110\tchr1\t610\t+\t-\tUU\n",
+        header
+    );
+    let dir = scratch("compare", &dense, sizes);
+    let args = ["--hic", HIC, "--prop", "0.5", "--step-size", "50"];
+    let agree = hickit("compare", &dir, &args);
+    assert!(agree.contains("100\t9\t16\t0.562\n"), "{}", agree);
+    assert_eq!(compare_rows(&agree), vec!["method\tresolution_bp", "pairs\t100", "hic\t100", "ratio (hic/pairs)\t1.000"]);
+    assert!(agree.contains("OK: resolutions agree within 2x"), "{}", agree);
+
+    // One pair leaves the pairs-based resolution far coarser than the .hic one
+    std::fs::write(dir.join("in.pairs"), format!("{}r1\tchr1\t10\tchr2\t460\t+\t-\tUU\n", header)).unwrap();
+    let differ = hickit("compare", &dir, &args);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(compare_rows(&differ), vec!["method\tresolution_bp", "pairs\t350", "hic\t100", "ratio (hic/pairs)\t0.286"]);
+    assert!(differ.contains("WARNING: resolutions differ by more than 2x"), "{}", differ);
+}